@@ -0,0 +1,4 @@
+// Generates the gRPC service of proto/fleet.proto (requires protoc)
+fn main() {
+    tonic_build::compile_protos("proto/fleet.proto").expect("failed to compile proto/fleet.proto");
+}
@@ -0,0 +1,213 @@
+// src/admin.rs
+//
+// Operator routes, protected by the bearer token set as `admin_token` in the chain configuration
+// (disabled when no token is set). They let an operator clean up stuck games without restarting
+// the chain and losing every other game, and hide annotations of archived games.
+
+use axum::{
+    extract::{DefaultBodyLimit, Path, Request},
+    http::header,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use fleetconfig::config;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracing::{info, warn};
+
+use crate::error::ChainError;
+use crate::export::import_handler;
+use crate::{archive_game, now_secs, SharedData};
+
+// Compare tokens through their hashes so the comparison time does not depend on the token
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    let given = Sha256::digest(given.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    given.iter().zip(expected.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn require_admin(request: Request, next: Next) -> Response {
+    let expected = match &config().chain.admin_token {
+        Some(token) if !token.is_empty() => token,
+        _ => return ChainError::NotFound("The admin API is disabled".to_string()).into_response(),
+    };
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match given {
+        Some(given) if token_matches(given, expected) => next.run(request).await,
+        _ => {
+            warn!("Unauthorized admin request to {}", request.uri().path());
+            ChainError::Unauthorized("Invalid admin token".to_string()).into_response()
+        }
+    }
+}
+
+// Action of the operator on a game. Actions are kept in the transaction log, so the games rebuilt
+// from it at startup are the games that were served.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum OperatorAction {
+    // Remove a running game without archiving it
+    Delete { gameid: String },
+    // End a running game now and archive it, optionally declaring a winner
+    ForceEnd { gameid: String, winner: Option<String> },
+    // Hide an annotation of an archived game from the replay viewer, or show it again
+    Moderate { gameid: String, annotation: usize, hidden: bool },
+}
+
+pub fn perform(shared: &SharedData, action: &OperatorAction) -> Result<String, ChainError> {
+    match action {
+        OperatorAction::Delete { gameid } => delete(shared, gameid),
+        OperatorAction::ForceEnd { gameid, winner } => end(shared, gameid, winner.clone()),
+        OperatorAction::Moderate { gameid, annotation, hidden } => moderate(shared, gameid, *annotation, *hidden),
+    }
+}
+
+fn delete(shared: &SharedData, gameid: &str) -> Result<String, ChainError> {
+    match shared.games().remove(gameid) {
+        Some(_) => {
+            shared.identities.forget_game(gameid);
+            shared.events.forget_game(gameid);
+            shared.spectators.forget_game(gameid);
+            shared.webhooks.forget_game(gameid);
+            shared.idempotency.forget_game(gameid);
+            shared.event(format!("Game {} was deleted by an operator", gameid));
+            Ok("OK".to_string())
+        }
+        None => Err(ChainError::NotFound("Game not found".to_string())),
+    }
+}
+
+fn end(shared: &SharedData, gameid: &str, winner: Option<String>) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+
+    let game = match gmap.get(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    if let Some(winner) = &winner {
+        if !game.pmap.contains_key(winner) {
+            return Err(ChainError::NotFound("Winner is not a player of this game".to_string()));
+        }
+    }
+
+    if let Some(game) = gmap.remove(gameid) {
+        match &winner {
+            Some(winner) => shared.game_event(gameid, format!("Game {} was ended by an operator. {} wins!", gameid, winner)),
+            None => shared.game_event(gameid, format!("Game {} was ended by an operator without a winner", gameid)),
+        }
+        archive_game(shared, gameid, game, winner);
+    }
+    Ok("OK".to_string())
+}
+
+fn moderate(shared: &SharedData, gameid: &str, seq: usize, hidden: bool) -> Result<String, ChainError> {
+    let mut archive = shared.archived();
+
+    let game = match archive.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found in archive".to_string())),
+    };
+    let annotation = match game.annotations.iter_mut().find(|annotation| annotation.seq == seq) {
+        Some(annotation) => annotation,
+        None => return Err(ChainError::NotFound("Annotation not found".to_string())),
+    };
+
+    annotation.hidden = hidden;
+    let verb = if hidden { "hidden" } else { "shown again" };
+    info!("Annotation {} of game {} by {} was {} by an operator", seq, gameid, annotation.fleet, verb);
+    Ok("OK".to_string())
+}
+
+async fn delete_game(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<String, ChainError> {
+    shared.replication.operate(&shared, OperatorAction::Delete { gameid })
+}
+
+#[derive(Default, Deserialize)]
+struct ForceEnd {
+    winner: Option<String>,
+}
+
+async fn force_end(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    body: Option<Json<ForceEnd>>,
+) -> Result<String, ChainError> {
+    let winner = body.unwrap_or_default().0.winner;
+    shared.replication.operate(&shared, OperatorAction::ForceEnd { gameid, winner })
+}
+
+async fn hide_annotation(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, annotation)): Path<(String, usize)>,
+) -> Result<String, ChainError> {
+    shared.replication.operate(&shared, OperatorAction::Moderate { gameid, annotation, hidden: true })
+}
+
+async fn unhide_annotation(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, annotation)): Path<(String, usize)>,
+) -> Result<String, ChainError> {
+    shared.replication.operate(&shared, OperatorAction::Moderate { gameid, annotation, hidden: false })
+}
+
+#[derive(Serialize)]
+struct GameSummary {
+    gameid: String,
+    players: usize,
+    started: bool,
+    blinded: bool,
+    spectators: usize,
+    // Seconds since the last transaction of the game
+    idle_seconds: u64,
+}
+
+#[derive(Serialize)]
+struct AdminStats {
+    games: Vec<GameSummary>,
+    archived_games: usize,
+    applied_seq: u64,
+}
+
+async fn stats(Extension(shared): Extension<SharedData>) -> Json<AdminStats> {
+    let now = now_secs();
+    let mut games: Vec<GameSummary> = shared.games()
+        .iter()
+        .map(|(gameid, game)| GameSummary {
+            gameid: gameid.clone(),
+            players: game.pmap.len(),
+            started: game.started,
+            blinded: game.blinded,
+            spectators: shared.spectators.count(gameid),
+            idle_seconds: now.saturating_sub(game.last_activity),
+        })
+        .collect();
+    games.sort_by(|a, b| a.gameid.cmp(&b.gameid));
+
+    let archived_games = shared.archived().len();
+    let applied_seq = *shared.applied.borrow();
+    Json(AdminStats { games, archived_games, applied_seq })
+}
+
+pub fn routes() -> Router {
+    if config().chain.admin_token.is_none() {
+        info!("No admin token configured, the admin API is disabled");
+    }
+    Router::new()
+        .route("/games/:gameid", delete(delete_game))
+        .route("/games/:gameid/force-end", post(force_end))
+        .route("/games/:gameid/annotations/:annotation/hide", post(hide_annotation))
+        .route("/games/:gameid/annotations/:annotation/unhide", post(unhide_annotation))
+        .route("/admin/stats", get(stats))
+        // An export carries every receipt of a game
+        .route("/admin/import", post(import_handler).layer(DefaultBodyLimit::disable()))
+        .route_layer(middleware::from_fn(require_admin))
+}
@@ -0,0 +1,103 @@
+// src/audit.rs
+//
+// End-of-game audits. Once a game is over, a player can submit one succinct receipt attesting
+// that all its reports were consistent with the board it committed to: the audit guest verifies
+// every report receipt of the player as an assumption and follows the board digest from the one
+// committed when joining through each of them. The chain checks the result against its history of
+// the player's board, so a single receipt covers the whole game, away from the chain too.
+//
+// With `audit_required`, new games withhold their certificate until the winner has been audited.
+// Blinded reports are proven by another guest and opened by revealing the board instead (see
+// blind.rs), so only plain games can be audited.
+
+use fleetcore::{AuditJournal, Command, CommunicationData};
+use risc0_zkvm::Digest;
+use tracing::warn;
+
+use crate::challenge::{check_challenge, redeem_challenge};
+use crate::error::ChainError;
+use crate::keys::verify_receipt;
+use crate::{now_secs, record_player, SharedData};
+
+// Audit accepted for a player of an archived game
+#[derive(Clone)]
+pub struct Audit {
+    pub reports: u32,
+    // Board digest the reports led to
+    pub board: Digest,
+}
+
+pub fn handle_audit(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: AuditJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut archive = shared.archived();
+
+    // Check if the game is over
+    let game = match archive.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found in archive", data.gameid);
+            return Err(ChainError::NotFound("Game not found in archive".to_string()));
+        }
+    };
+
+    // Check if the player was in the game
+    let key = match game.players.get(&data.fleet) {
+        Some(key) => *key,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&key, input_data, &data.gameid) {
+        warn!("Invalid signature in audit request");
+        return Err(err);
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Blinded reports are checked by revealing the board
+    if !game.blind.is_empty() {
+        warn!("Game {} used blinded reports, audit rejected", data.gameid);
+        return Err(ChainError::Rejected("This game used blinded reports".to_string()));
+    }
+
+    if game.audits.contains_key(&data.fleet) {
+        warn!("Player {} has already been audited in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Already audited".to_string()));
+    }
+
+    // Check if the audited reports were proven by an accepted report guest
+    if !shared.guests.accepts(Command::Report, &data.report_id, now_secs()) {
+        warn!("Audit from {} in game {} covers an unknown report guest", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Unknown report guest".to_string()));
+    }
+
+    // Check if the audit covers every report the chain applied to the player's board
+    let history = match game.boards.get(&data.fleet) {
+        Some(history) => history,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+    let last = history.moves().last().map_or(history.initial(), |step| step.digest);
+    if data.initial != history.initial() || data.reports as usize != history.moves().len() || data.board != last {
+        warn!("Audit from {} does not match its board history in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Audit does not match the board history".to_string()));
+    }
+
+    // Every check passed: use up the challenge of the receipt
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    game.audits.insert(data.fleet.clone(), Audit { reports: data.reports, board: data.board });
+
+    shared.game_event(&data.gameid, format!(
+        "{} proved its {} reports consistent with its board in game {}",
+        data.fleet,
+        data.reports,
+        data.gameid
+    ));
+    Ok("OK".to_string())
+}
@@ -0,0 +1,389 @@
+// src/blind.rs
+//
+// Turn engine of blinded games. Reports only publish a commitment to their outcome, so the chain
+// (and every spectator) learns who was shot at but not whether the shot hit. Each player's hits
+// are kept in a private ledger: the chain only stores the ledger digest proven by the player's
+// last report, and a victory claim must prove that the player's board still has ships that are
+// not in that ledger. After the game, players reveal their boards and the chain checks every
+// outcome against the commitment made when reporting it.
+//
+// Plain games can be revealed too: the board is then replayed through the reports recorded in its
+// history (see history.rs).
+
+use axum::{extract::Path, http::HeaderMap, Extension, Json};
+use fleetcore::{
+    outcome_commitment, verify_canonical, BlindReportJournal, BlindWinJournal, CommunicationData, Coord,
+    RevealData, RevealStatement, ShotOutcome, REVEAL_DOMAIN,
+};
+use risc0_zkvm::Digest;
+use serde::Serialize;
+use tracing::{info_span, warn};
+
+use crate::challenge::{check_challenge, redeem_challenge};
+use crate::correlation;
+use crate::error::ChainError;
+use crate::history::BLINDED;
+use crate::ids::check_fleet;
+use crate::keys::verify_receipt;
+use crate::replication::Transaction;
+use crate::turns;
+use crate::{charge_cycles, claim_victory, next_request_id, now_secs, record_player, ArchivedGame, SharedData};
+
+// A blinded report as recorded by the chain
+#[derive(Clone)]
+pub struct BlindReport {
+    pos: Coord,
+    turn: u32,
+    outcome: Digest,
+}
+
+// What the chain knows about a player's private ledger
+#[derive(Default)]
+pub struct BlindLedger {
+    // Ledger digest after the last report, None before the first one
+    ledger: Option<Digest>,
+    reports: Vec<BlindReport>,
+}
+
+impl BlindLedger {
+    // Number of reports the player made, which is the turn of its next report
+    pub fn turn(&self) -> u32 {
+        self.reports.len() as u32
+    }
+
+    // Check that a journal continues this ledger. The guests prove that the ledger of turn 0 is
+    // empty, so only later turns have to match the stored digest.
+    fn check(&self, turn: u32, ledger: &Digest) -> Result<(), ChainError> {
+        if turn != self.turn() {
+            return Err(ChainError::Rejected(format!("Expected report turn {}, got {}", self.turn(), turn)));
+        }
+        match &self.ledger {
+            Some(expected) if expected != ledger => Err(ChainError::Rejected("Ledger mismatch".to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Outcome of a report, known once the board has been revealed
+#[derive(Clone, Serialize)]
+pub struct RevealedReport {
+    pos: String,
+    turn: u32,
+    hit: bool,
+}
+
+// Commitments of a player kept in the archive of a blinded game
+pub struct BlindRecord {
+    reports: Vec<BlindReport>,
+}
+
+impl BlindRecord {
+    pub fn new(ledger: &BlindLedger) -> Self {
+        BlindRecord { reports: ledger.reports.clone() }
+    }
+}
+
+pub fn handle_blind_report(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: BlindReportJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+
+    // Check if the game uses blinded reports
+    if !game.blinded {
+        warn!("Game {} does not use blinded reports", data.gameid);
+        return Err(ChainError::Rejected("This game does not use blinded reports".to_string()));
+    }
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&data.fleet) {
+        Some(player) => player,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&shared.identities.player_key(&data.gameid, player), input_data, &data.gameid) {
+        warn!("Invalid signature in blinded report request");
+        return Err(err);
+    }
+
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if someone has claimed victory and timeout is active
+    if let Some((claimant, claim_time)) = &game.first_victory_claim {
+        let current_time = now_secs();
+
+        if current_time.saturating_sub(*claim_time) < game.victory_timeout_seconds {
+            let remaining_time = game.victory_timeout_seconds - current_time.saturating_sub(*claim_time);
+            warn!("Cannot report during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Win' button.", claimant, remaining_time);
+            return Err(ChainError::Rejected("Cannot report during victory claim period".to_string()));
+        }
+    }
+
+    // Check if it's the player's turn to report
+    if game.next_report.as_ref() != Some(&data.fleet) {
+        warn!("Not {}'s turn to report in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Not your turn to report".to_string()));
+    }
+
+    // The board never changes in a blinded game, hits only go to the ledger
+    if player.current_state != data.board {
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    // Check if the report continues the player's ledger
+    if let Err(err) = player.blind.check(data.turn, &data.ledger) {
+        warn!("Player {}'s report does not continue its ledger in game {}: {}", data.fleet, data.gameid, err);
+        return Err(err);
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford reporting in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    player.cycles_spent += cycles;
+    player.blind.ledger = Some(data.next_ledger);
+    player.blind.reports.push(BlindReport { pos: data.pos, turn: data.turn, outcome: data.outcome });
+    player.history.record(&data.board, BLINDED, data.pos, game.last_shooter.as_deref());
+
+    // Update the next player according to the game's turn policy
+    let next_player = turns::after_report(game, game.last_shooter.as_deref(), &data.fleet, &mut *shared.rng());
+    game.next_player = Some(next_player);
+    game.next_report = None;
+    game.last_shooter = None;
+    game.pending_shot = None;
+    game.turn_started_at = now_secs();
+    game.last_activity = game.turn_started_at;
+
+    shared.game_event(&data.gameid, format!(
+        "{} reported a blinded outcome at position {} in game {}",
+        data.fleet,
+        data.pos,
+        data.gameid
+    ));
+
+    Ok("OK".to_string())
+}
+
+pub fn handle_blind_win(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: BlindWinJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+
+    // Check if the game uses blinded reports
+    if !game.blinded {
+        warn!("Game {} does not use blinded reports", data.gameid);
+        return Err(ChainError::Rejected("This game does not use blinded reports".to_string()));
+    }
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&data.fleet) {
+        Some(player) => player,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&shared.identities.player_key(&data.gameid, player), input_data, &data.gameid) {
+        warn!("Invalid signature in blinded win request");
+        return Err(err);
+    }
+
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if player's board hash matches the one committed when joining
+    if player.current_state != data.board {
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    // Check if the claim was proven against the player's latest ledger
+    if let Err(err) = player.blind.check(data.turn, &data.ledger) {
+        warn!("Player {}'s victory claim does not use its latest ledger in game {}: {}", data.fleet, data.gameid, err);
+        return Err(err);
+    }
+
+    // Check if player has already claimed victory
+    if player.has_claimed_victory {
+        warn!("Player {} has already claimed victory in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Already claimed victory".to_string()));
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford claiming victory in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    claim_victory(shared, &mut gmap, &data.gameid, &data.fleet, cycles, input_data.nonce, &data.challenge)
+}
+
+pub fn handle_reveal(shared: &SharedData, gameid: &str, input_data: &RevealData) -> Result<String, ChainError> {
+    let mut archive = shared.archived();
+
+    // Check if the game has been archived
+    let game = match archive.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found in archive".to_string())),
+    };
+
+    // Check if the player took part in a blinded game
+    let verifying_key = match game.players.get(&input_data.fleet) {
+        Some(key) => key,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+    let history = match game.boards.get(&input_data.fleet) {
+        Some(history) => history,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Verify the signature over the revealed board
+    let statement = RevealStatement {
+        gameid,
+        fleet: &input_data.fleet,
+        board: input_data.board,
+        random: &input_data.random,
+    };
+    if !verify_canonical(verifying_key, REVEAL_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in reveal for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    if game.revealed.contains_key(&input_data.fleet) {
+        return Err(ChainError::Rejected("Board already revealed".to_string()));
+    }
+
+    // Check if the revealed board is the one committed when joining, and led to every board
+    // digest the player's reports proved
+    if let Err(err) = history.replay(input_data.board, &input_data.random) {
+        warn!("{} revealed a board that does not match its history in game {}: {}", input_data.fleet, gameid, err);
+        return Err(ChainError::Rejected(err));
+    }
+
+    // In a plain game the outcomes were public all along
+    let record = match game.blind.get(&input_data.fleet) {
+        Some(record) => record,
+        None => {
+            let revealed: Vec<RevealedReport> = history
+                .moves()
+                .iter()
+                .map(|step| RevealedReport {
+                    pos: step.pos.to_string(),
+                    turn: step.index,
+                    hit: step.outcome().is_some_and(ShotOutcome::is_hit),
+                })
+                .collect();
+            return Ok(publish_reveal(shared, gameid, &input_data.fleet, game, revealed));
+        }
+    };
+
+    // Open every outcome commitment with the revealed board
+    let mut revealed = Vec::with_capacity(record.reports.len());
+    for report in &record.reports {
+        let hit = input_data.board.contains(report.pos);
+        let expected = outcome_commitment(gameid, &input_data.fleet, report.pos, hit, &input_data.random, report.turn);
+        if expected != report.outcome {
+            warn!(
+                "Outcome commitment of {} at position {} does not match the revealed board in game {}",
+                input_data.fleet,
+                report.pos,
+                gameid
+            );
+            return Err(ChainError::Rejected("Outcome commitment mismatch".to_string()));
+        }
+        revealed.push(RevealedReport { pos: report.pos.to_string(), turn: report.turn, hit });
+    }
+
+    Ok(publish_reveal(shared, gameid, &input_data.fleet, game, revealed))
+}
+
+// Keep the outcomes of a revealed board in the archive and announce them
+fn publish_reveal(
+    shared: &SharedData,
+    gameid: &str,
+    fleet: &str,
+    game: &mut ArchivedGame,
+    revealed: Vec<RevealedReport>,
+) -> String {
+    let hits = revealed.iter().filter(|r| r.hit).count();
+    shared.game_event(gameid, format!(
+        "{} revealed its board in game {}: {} of {} shots hit",
+        fleet,
+        gameid,
+        hits,
+        revealed.len()
+    ));
+    game.revealed.insert(fleet.to_string(), revealed);
+    "OK".to_string()
+}
+
+pub async fn reveal_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    Json(input_data): Json<RevealData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "reveal",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Reveal { gameid, data: input_data }).map(|(_, response)| response)
+}
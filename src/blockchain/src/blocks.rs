@@ -0,0 +1,189 @@
+// src/blocks.rs
+//
+// Block production. Every transaction the chain applies, whether it accepted it or a peer did, is
+// given the next index and added to the open block, in the order of the replication log. The
+// block is sealed `block_interval_seconds` after its first transaction, or as soon as it holds
+// `block_max_transactions`, and its header links to the hash of the previous block (see
+// fleetcore::Block). Replicas seal their own blocks, so block numbers and hashes are only
+// meaningful on the node that served them.
+
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use fleetconfig::config;
+use fleetcore::{
+    canonical_json, journal_digest, transactions_digest, Block, BlockHeader, BlockTransaction,
+};
+use risc0_zkvm::Digest;
+use serde::Deserialize;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::error::ChainError;
+use crate::limits::JournalHead;
+use crate::replication::Transaction;
+use crate::{now_secs, SharedData};
+
+// Headers served per /blocks request
+const HEADERS_PAGE: usize = 100;
+
+#[derive(Default)]
+struct BlockState {
+    sealed: Vec<Block>,
+    open: Vec<BlockTransaction>,
+    // Time the first transaction of the open block was applied
+    opened_at: u64,
+    next_index: u64,
+}
+
+#[derive(Default)]
+pub struct Blocks {
+    state: Mutex<BlockState>,
+}
+
+impl Blocks {
+    fn state(&self) -> MutexGuard<'_, BlockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Headers of up to `limit` sealed blocks, from block `since` on
+    pub fn headers(&self, since: u64, limit: usize) -> Vec<BlockHeader> {
+        self.state()
+            .sealed
+            .iter()
+            .skip(since.try_into().unwrap_or(usize::MAX))
+            .take(limit)
+            .map(|block| block.header.clone())
+            .collect()
+    }
+
+    pub fn latest(&self) -> Option<BlockHeader> {
+        self.state().sealed.last().map(|block| block.header.clone())
+    }
+
+    pub fn block(&self, number: u64) -> Option<Block> {
+        let state = self.state();
+        usize::try_from(number).ok().and_then(|number| state.sealed.get(number)).cloned()
+    }
+
+    // Number of the sealed block holding a transaction, None while its block is still open
+    pub fn block_of(&self, index: u64) -> Option<u64> {
+        let state = self.state();
+        let position = state.sealed.partition_point(|block| block.header.first_index + block.header.count as u64 <= index);
+        state.sealed.get(position).map(|block| block.header.number)
+    }
+}
+
+impl BlockState {
+    fn seal(&mut self, now: u64) -> BlockHeader {
+        let transactions = std::mem::take(&mut self.open);
+        let prev = self.sealed.last().map(|block| &block.header);
+        let mut header = BlockHeader {
+            number: prev.map_or(0, |prev| prev.number + 1),
+            prev_hash: prev.map_or(Digest::ZERO, |prev| prev.hash),
+            timestamp: now,
+            first_index: transactions.first().map_or(self.next_index, |tx| tx.index),
+            count: transactions.len() as u32,
+            transactions: transactions_digest(&transactions),
+            hash: Digest::ZERO,
+        };
+        header.hash = header.compute_hash();
+        self.sealed.push(Block { header: header.clone(), transactions });
+        header
+    }
+}
+
+// Kind, game, fleet and digest of a transaction, as recorded in its block
+pub fn describe(tx: &Transaction) -> (String, String, String, Digest) {
+    // Messages are identified by the digest of their canonical encoding, signature included
+    fn message_digest<T: serde::Serialize>(data: &T) -> Digest {
+        journal_digest(&canonical_json(data).unwrap_or_default())
+    }
+    match tx {
+        Transaction::Chain(input_data) => {
+            let head = input_data.receipt.journal.decode::<JournalHead>().ok();
+            let (gameid, fleet) = head.map_or_else(Default::default, |head| (head.gameid, head.fleet));
+            (format!("{:?}", input_data.cmd), gameid, fleet, journal_digest(&input_data.receipt.journal.bytes))
+        }
+        Transaction::Ready { gameid, data } => ("Ready".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::Annotation { gameid, data } => ("Annotation".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::Chat { gameid, data } => ("Chat".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::Reveal { gameid, data } => ("Reveal".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::Pause { gameid, data } => ("Pause".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::Rematch { gameid, data } => ("Rematch".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+        Transaction::TeamMessage { gameid, data } => ("TeamMessage".to_string(), gameid.clone(), data.fleet.clone(), message_digest(data)),
+    }
+}
+
+fn sealed_event(shared: &SharedData, header: &BlockHeader) {
+    shared.event(format!(
+        "Block {} sealed with {} transactions ({} to {})",
+        header.number,
+        header.count,
+        header.first_index,
+        header.first_index + header.count as u64 - 1
+    ));
+}
+
+// Add an applied transaction to the open block and return its index
+pub fn record_transaction(shared: &SharedData, tx: &Transaction) -> u64 {
+    let (kind, gameid, fleet, digest) = describe(tx);
+    let now = now_secs();
+    let mut state = shared.blocks.state();
+    let index = state.next_index;
+    state.next_index += 1;
+    if state.open.is_empty() {
+        state.opened_at = now;
+    }
+    state.open.push(BlockTransaction { index, kind, gameid, fleet, digest });
+
+    if state.open.len() >= config().chain.block_max_transactions.max(1) {
+        let header = state.seal(now);
+        drop(state);
+        sealed_event(shared, &header);
+    }
+    index
+}
+
+// Seal the open block if its interval has elapsed
+pub fn seal_due(shared: &SharedData) {
+    let now = now_secs();
+    let mut state = shared.blocks.state();
+    if state.open.is_empty() || now < state.opened_at + config().chain.block_interval_seconds {
+        return;
+    }
+    let header = state.seal(now);
+    drop(state);
+    sealed_event(shared, &header);
+}
+
+#[derive(Deserialize)]
+pub struct HeadersQuery {
+    #[serde(default)]
+    since: u64,
+}
+
+// Headers of the sealed blocks, from block `since` on
+pub async fn headers_handler(
+    Extension(shared): Extension<SharedData>,
+    Query(query): Query<HeadersQuery>,
+) -> Json<Vec<BlockHeader>> {
+    Json(shared.blocks.headers(query.since, HEADERS_PAGE))
+}
+
+pub async fn latest_handler(Extension(shared): Extension<SharedData>) -> Result<Json<BlockHeader>, ChainError> {
+    match shared.blocks.latest() {
+        Some(header) => Ok(Json(header)),
+        None => Err(ChainError::NotFound("No block has been sealed yet".to_string())),
+    }
+}
+
+pub async fn block_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(number): Path<u64>,
+) -> Result<Json<Block>, ChainError> {
+    match shared.blocks.block(number) {
+        Some(block) => Ok(Json(block)),
+        None => Err(ChainError::NotFound("Block not found".to_string())),
+    }
+}
@@ -0,0 +1,69 @@
+// src/certificate.rs
+//
+// Certificates the chain issues when a game ends, signed with its genesis key so they can be
+// verified offline (see fleetcore::SignedCertificate::verify).
+
+use ed25519_dalek::SigningKey;
+use fleetconfig::TurnPolicy;
+use fleetcore::{canonical_json, sign_canonical, CertifiedPlayer, GameCertificate, SignedCertificate, CERTIFICATE_DOMAIN};
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::Game;
+
+// Settings a game was played with, hashed into its certificate
+#[derive(Serialize)]
+struct GameSettings {
+    victory_timeout_seconds: u64,
+    cycle_budget: Option<u64>,
+    blinded: bool,
+    min_players: usize,
+    max_players: usize,
+    turn_policy: TurnPolicy,
+}
+
+fn config_hash(game: &Game) -> String {
+    let settings = GameSettings {
+        victory_timeout_seconds: game.victory_timeout_seconds,
+        cycle_budget: game.cycle_budget,
+        blinded: game.blinded,
+        min_players: game.min_players,
+        max_players: game.max_players,
+        turn_policy: game.turn_policy,
+    };
+    // The settings only hold integers, booleans and a plain string, which always have a
+    // canonical encoding
+    let encoded = canonical_json(&settings).unwrap_or_default();
+    hex::encode(Sha256::digest(encoded))
+}
+
+// Assemble and sign the certificate of a game that just ended
+pub fn issue(
+    key: &SigningKey,
+    gameid: &str,
+    game: &Game,
+    winner: Option<String>,
+    seq: u64,
+    ended_at: u64,
+) -> Result<SignedCertificate, fleetcore::CanonicalError> {
+    let mut players: Vec<CertifiedPlayer> = game.pmap
+        .iter()
+        .map(|(fleet, player)| CertifiedPlayer {
+            fleet: fleet.clone(),
+            public_key: hex::encode(player.verifying_key.as_bytes()),
+            final_board: player.current_state.to_string(),
+        })
+        .collect();
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+
+    let certificate = GameCertificate {
+        gameid: gameid.to_string(),
+        config_hash: config_hash(game),
+        players,
+        winner,
+        seq,
+        ended_at,
+    };
+    let signature = sign_canonical(key, CERTIFICATE_DOMAIN, &certificate)?;
+    Ok(SignedCertificate { certificate, signature })
+}
@@ -0,0 +1,113 @@
+// src/challenge.rs
+//
+// Challenges binding receipts to a live session. Before proving, a host asks
+// `/challenge/{gameid}/{fleet}` for a fresh random nonce, which the guest commits to the journal.
+// The chain only accepts a receipt carrying a challenge it issued to that player, that has not
+// expired and was not used before, so proofs can neither be computed in advance nor replayed.
+// A handler checks the challenge along with the rest of the receipt, and only uses it up once the
+// receipt passed every check, so a rejected receipt leaves its challenge for a corrected retry.
+
+use axum::{extract::Path, Extension, Json};
+use fleetcore::Challenge;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use tracing::warn;
+
+use crate::error::ChainError;
+use crate::replication::replaying;
+use crate::{now_secs, SharedData};
+
+// Challenges a player may hold at once, e.g. a report proven while a fire is still being proven
+const MAX_PENDING: usize = 8;
+// Length of a challenge, in bytes
+const CHALLENGE_LENGTH: usize = 16;
+
+pub struct Challenges {
+    ttl: u64,
+    // Expiry of the challenges issued to each (game, fleet) and not used yet
+    issued: Mutex<HashMap<(String, String), HashMap<String, u64>>>,
+}
+
+impl Challenges {
+    pub fn new(ttl: u64) -> Self {
+        Challenges { ttl, issued: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn issue(&self, gameid: &str, fleet: &str, now: u64) -> Challenge {
+        let mut bytes = [0u8; CHALLENGE_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let challenge = Challenge { challenge: hex::encode(bytes), expires_at: now + self.ttl };
+
+        let mut issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        let pending = issued.entry((gameid.to_string(), fleet.to_string())).or_default();
+        pending.retain(|_, expires_at| *expires_at > now);
+        // Forget the oldest challenge rather than letting a player pile them up
+        if pending.len() >= MAX_PENDING {
+            if let Some(oldest) = pending.iter().min_by_key(|(_, expires_at)| **expires_at).map(|(c, _)| c.clone()) {
+                pending.remove(&oldest);
+            }
+        }
+        pending.insert(challenge.challenge.clone(), challenge.expires_at);
+        challenge
+    }
+
+    // Check the challenge of a receipt, which must have been issued to this player and not expired
+    pub fn check(&self, gameid: &str, fleet: &str, challenge: &str, now: u64) -> Result<(), ChainError> {
+        let issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        let expires_at = issued.get(&(gameid.to_string(), fleet.to_string())).and_then(|pending| pending.get(challenge));
+        match expires_at {
+            Some(expires_at) if *expires_at > now => Ok(()),
+            Some(_) => Err(ChainError::Rejected("Challenge expired, please try again".to_string())),
+            None => Err(ChainError::Unauthorized("Unknown challenge".to_string())),
+        }
+    }
+
+    // Use up the challenge of a receipt that was applied
+    pub fn redeem(&self, gameid: &str, fleet: &str, challenge: &str) {
+        let mut issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (gameid.to_string(), fleet.to_string());
+        if let Some(pending) = issued.get_mut(&key) {
+            pending.remove(challenge);
+            if pending.is_empty() {
+                issued.remove(&key);
+            }
+        }
+    }
+
+    // Drop the challenges nobody used in time
+    pub fn expire(&self, now: u64) {
+        let mut issued = self.issued.lock().unwrap_or_else(PoisonError::into_inner);
+        issued.retain(|_, pending| {
+            pending.retain(|_, expires_at| *expires_at > now);
+            !pending.is_empty()
+        });
+    }
+}
+
+// Check the challenge committed to a receipt, logging why it was refused
+pub fn check_challenge(shared: &SharedData, gameid: &str, fleet: &str, challenge: &str) -> Result<(), ChainError> {
+    // The node that accepted the transaction issued the challenge and redeemed it
+    if replaying() {
+        return Ok(());
+    }
+    shared.challenges.check(gameid, fleet, challenge, now_secs()).map_err(|err| {
+        warn!("Refused challenge of {} in game {}: {}", fleet, gameid, err);
+        err
+    })
+}
+
+// Use up the challenge of a receipt once it passed every check
+pub fn redeem_challenge(shared: &SharedData, gameid: &str, fleet: &str, challenge: &str) {
+    if !replaying() {
+        shared.challenges.redeem(gameid, fleet, challenge);
+    }
+}
+
+// Fresh challenge for the next proof of a player
+pub async fn challenge_handler(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, fleet)): Path<(String, String)>,
+) -> Json<Challenge> {
+    Json(shared.challenges.issue(&gameid, &fleet, now_secs()))
+}
@@ -0,0 +1,110 @@
+// src/clocks.rs
+//
+// Chess clocks. With `clock_seconds` set, every player of a new game gets that much time to play
+// in total. The clock of the player the game waits for, to fire, wave or report, runs down while
+// the game waits; all clocks stop in the lobby, while the game is paused and while a victory claim
+// runs. The clocks are charged every second and right before each transaction, so a turn is
+// charged to the player who had it. Warnings go out on the game's event stream when a clock runs
+// low, and a player whose clock runs out forfeits, through the transaction log when the clock ran
+// out between two transactions (see timeouts.rs).
+
+use std::collections::HashMap;
+
+use crate::error::ChainError;
+use crate::timeouts::{self, Timeout};
+use crate::{concede, now_secs, Game, SharedData};
+
+// Seconds left on a clock at which its player is warned
+const WARNINGS: [u64; 2] = [60, 10];
+
+#[derive(Clone, Copy)]
+pub struct Clock {
+    pub left: u64,
+    // Warnings already sent
+    warned: usize,
+}
+
+impl Clock {
+    pub fn new(seconds: u64) -> Self {
+        Clock { left: seconds, warned: 0 }
+    }
+}
+
+// Player whose clock runs
+fn running(game: &Game) -> Option<String> {
+    if !game.started || game.paused || game.first_victory_claim.is_some() {
+        return None;
+    }
+    game.next_report.clone().or_else(|| game.next_player.clone())
+}
+
+// Charge the time elapsed since the game's clocks were last charged to the running clock, and
+// return its player if the clock ran out
+fn charge(shared: &SharedData, gameid: &str, game: &mut Game, now: u64) -> Option<String> {
+    let elapsed = now.saturating_sub(game.clock_at);
+    game.clock_at = now;
+    let fleet = running(game)?;
+    let player = game.pmap.get_mut(&fleet).filter(|player| !player.forfeited)?;
+    let clock = player.clock.as_mut()?;
+    clock.left = clock.left.saturating_sub(elapsed);
+
+    let warned = clock.warned;
+    while clock.warned < WARNINGS.len() && clock.left <= WARNINGS[clock.warned] {
+        clock.warned += 1;
+    }
+    if clock.left == 0 {
+        return Some(fleet);
+    }
+    if clock.warned > warned {
+        shared.game_event(gameid, format!("{} has {} seconds left on its clock in game {}", fleet, clock.left, gameid));
+    }
+    None
+}
+
+// Forfeit the players whose clock ran out
+fn time_out(shared: &SharedData, gmap: &mut HashMap<String, Game>, out: Vec<(String, String)>) {
+    for (gameid, fleet) in out {
+        shared.game_event(&gameid, format!("{} ran out of time in game {} and forfeits", fleet, gameid));
+        concede(shared, gmap, &gameid, &fleet);
+    }
+}
+
+// Charge the clocks of a game, before a transaction is applied to it
+pub fn settle(shared: &SharedData, gameid: &str) {
+    let now = now_secs();
+    let mut gmap = shared.games();
+    let out = match gmap.get_mut(gameid) {
+        Some(game) => charge(shared, gameid, game, now).map(|fleet| (gameid.to_string(), fleet)),
+        None => None,
+    };
+    time_out(shared, &mut gmap, out.into_iter().collect());
+}
+
+// Charge the clocks of every game, run every second
+pub fn tick(shared: &SharedData) {
+    let now = now_secs();
+    let out: Vec<String> = shared.games()
+        .iter_mut()
+        .filter_map(|(gameid, game)| charge(shared, gameid, game, now).map(|_| gameid.clone()))
+        .collect();
+    for gameid in out {
+        timeouts::commit(shared, Timeout::Clock { gameid });
+    }
+}
+
+// Forfeit the player of a game whose clock ran out
+pub fn run_out(shared: &SharedData, gameid: &str) -> Result<String, ChainError> {
+    let now = now_secs();
+    let mut gmap = shared.games();
+    let fleet = match gmap.get_mut(gameid) {
+        Some(game) => charge(shared, gameid, game, now),
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    match fleet {
+        Some(fleet) => {
+            time_out(shared, &mut gmap, vec![(gameid.to_string(), fleet)]);
+            Ok("OK".to_string())
+        }
+        None => Err(ChainError::Rejected("No clock ran out".to_string())),
+    }
+}
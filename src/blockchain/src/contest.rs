@@ -0,0 +1,121 @@
+// src/contest.rs
+//
+// Contested victory claims. While a claim's timeout runs, an opponent of the claimant can contest
+// it with the Contest transaction, whose `contest` guest proves that the board the player committed
+// to still has at least one ship square. A contest stands against the claim like a claim of the
+// contester's side: when the timeout expires the claims conflict, nobody wins and the game goes on.
+//
+// Blinded games never remove hits from the board, so a contest would prove nothing there: they are
+// contested with a blinded victory claim, which proves ships left against the player's ledger.
+
+use fleetcore::{BaseJournal, CommunicationData};
+use tracing::warn;
+
+use crate::challenge::{check_challenge, redeem_challenge};
+use crate::error::ChainError;
+use crate::keys::verify_receipt;
+use crate::{charge_cycles, now_secs, record_player, SharedData};
+
+pub fn handle_contest(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: BaseJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+
+    // Blinded games keep every square on the board
+    if game.blinded {
+        warn!("Game {} uses blinded reports, contest rejected", data.gameid);
+        return Err(ChainError::Rejected("This game uses blinded reports, contest with a victory claim".to_string()));
+    }
+
+    // Check if there is a running claim of the other side to contest
+    let current_time = now_secs();
+    let (claimant, deadline) = match &game.first_victory_claim {
+        Some((claimant, claim_time)) if current_time.saturating_sub(*claim_time) < game.victory_timeout_seconds => {
+            (claimant.clone(), claim_time + game.victory_timeout_seconds)
+        }
+        _ => {
+            warn!("No victory claim to contest in game {}", data.gameid);
+            return Err(ChainError::Rejected("No victory claim to contest".to_string()));
+        }
+    };
+    if !game.opponents(&data.fleet, &claimant) {
+        warn!("Player {} cannot contest the claim of its own side in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Cannot contest the victory claim of your own side".to_string()));
+    }
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&data.fleet) {
+        Some(player) => player,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&shared.identities.player_key(&data.gameid, player), input_data, &data.gameid) {
+        warn!("Invalid signature in contest request");
+        return Err(err);
+    }
+
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if the ships proven left are on the player's current board
+    if player.current_state != data.board {
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    if player.forfeited {
+        warn!("Player {} has forfeited game {} and cannot contest", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Cannot contest after forfeiting".to_string()));
+    }
+    if player.has_claimed_victory {
+        warn!("Player {} already stands against the claim in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Already contested or claimed victory".to_string()));
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford contesting in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    player.cycles_spent += cycles;
+    player.has_claimed_victory = true;
+    game.last_activity = current_time;
+
+    shared.game_event(&data.gameid, format!(
+        "{} proves ships left and contests victory of player {} in game {}! Game will resume after {} seconds (at {}).",
+        data.fleet,
+        claimant,
+        data.gameid,
+        deadline - current_time,
+        deadline
+    ));
+    Ok("Victory contested. Game continues.".to_string())
+}
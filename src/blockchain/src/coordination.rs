@@ -0,0 +1,180 @@
+// src/coordination.rs
+//
+// Coordination channel of the teams of a team game. Players send their teammates messages
+// encrypted on their host to the X25519 form of each member's key (see
+// `fleetcore::TeamMessageData`). The chain only sees ciphertexts: it checks that a message is
+// signed by its sender, not replayed, and sealed for exactly the members of the sender's team, then
+// keeps it with the game for the members to fetch. The messages are dropped with the game.
+
+use axum::{
+    extract::{Path, Query},
+    http::HeaderMap,
+    Extension, Json,
+};
+use fleetcore::{verify_canonical, TeamChannel, TeamMember, TeamMessage, TeamMessageData, TeamMessageStatement, TEAM_MESSAGE_DOMAIN};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use tracing::{info, info_span, warn};
+
+use crate::correlation;
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::keys::parse_signature;
+use crate::replication::{self, Transaction};
+use crate::{next_request_id, now_secs, SharedData};
+
+// Sizes of an X25519 public key, of a ChaCha20-Poly1305 nonce, and of a content key once sealed
+// (the key and its tag)
+const EPHEMERAL_KEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+const SEALED_KEY_LEN: usize = 48;
+// Largest encrypted text, room for a chat message of 280 characters of up to 4 bytes, and its tag
+const CIPHERTEXT_MAX_LEN: usize = 280 * 4 + 16;
+// Messages kept for each game, the oldest are dropped first
+const MESSAGES_KEPT: usize = 200;
+
+pub fn handle_team_message(shared: &SharedData, gameid: &str, input_data: &TeamMessageData) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+
+    // Check if the player is in the game
+    let player = match game.pmap.get(&input_data.fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Only the players of a team game have teammates
+    let team = match player.team.clone().filter(|_| game.teams) {
+        Some(team) => team,
+        None => return Err(ChainError::Rejected("Team messages can only be sent in team games".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the message
+    let statement = TeamMessageStatement {
+        gameid,
+        fleet: &input_data.fleet,
+        ephemeral_key: &input_data.ephemeral_key,
+        iv: &input_data.iv,
+        ciphertext: &input_data.ciphertext,
+        keys: &input_data.keys,
+        nonce: input_data.nonce,
+    };
+    if !verify_canonical(&shared.identities.player_key(gameid, player), TEAM_MESSAGE_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in team message for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // Check if the message is not a replay of an earlier one
+    if input_data.nonce <= player.team_nonce {
+        warn!("Replayed team message from {} in game {}", input_data.fleet, gameid);
+        return Err(ChainError::Rejected("Team message nonce must increase".to_string()));
+    }
+
+    // Check the sizes of the encrypted parts
+    if input_data.ephemeral_key.len() != EPHEMERAL_KEY_LEN || input_data.iv.len() != IV_LEN {
+        return Err(ChainError::Malformed("Invalid ephemeral key or nonce in team message".to_string()));
+    }
+    if input_data.keys.iter().any(|sealed| sealed.key.len() != SEALED_KEY_LEN) {
+        return Err(ChainError::Malformed("Invalid sealed key in team message".to_string()));
+    }
+    if input_data.ciphertext.len() <= 16 || input_data.ciphertext.len() > CIPHERTEXT_MAX_LEN {
+        return Err(ChainError::Rejected(format!("Message must be at most {} bytes once encrypted", CIPHERTEXT_MAX_LEN)));
+    }
+
+    // Every member of the team, and nobody else, must be able to read the message
+    let recipients: BTreeSet<&str> = input_data.keys.iter().map(|sealed| sealed.fleet.as_str()).collect();
+    if recipients.len() != input_data.keys.len() || recipients != game.members(&team) {
+        warn!("Team message from {} in game {} not sealed for its team", input_data.fleet, gameid);
+        return Err(ChainError::Rejected(format!("Message must be sealed for every member of team {}", team)));
+    }
+
+    // Team messages share the fleet's transaction budget, on the node they were sent to
+    if !replication::replaying() {
+        if let Err(retry_after) = shared.limits.per_fleet.check((gameid.to_string(), input_data.fleet.clone())) {
+            warn!("Rate limited team messages of fleet {}", input_data.fleet);
+            return Err(ChainError::RateLimited("Too many messages, slow down".to_string(), retry_after));
+        }
+    }
+
+    if let Some(player) = game.pmap.get_mut(&input_data.fleet) {
+        player.team_nonce = input_data.nonce;
+    }
+    let seq = game.team_messages.back().map_or(1, |message| message.seq + 1);
+    if game.team_messages.len() >= MESSAGES_KEPT {
+        game.team_messages.pop_front();
+    }
+    game.team_messages.push_back(TeamMessage { seq, sent_at: now_secs(), data: input_data.clone() });
+
+    // Only the operator's log tells that a message was sent, the events are public
+    info!("{} sent message {} to team {} in game {}", input_data.fleet, seq, team, gameid);
+    Ok("OK".to_string())
+}
+
+pub async fn team_message_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    Json(input_data): Json<TeamMessageData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "team_message",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::TeamMessage { gameid, data: input_data }).map(|(_, response)| response)
+}
+
+#[derive(Deserialize)]
+pub struct ChannelQuery {
+    // Only the messages numbered after this one
+    #[serde(default)]
+    after: u64,
+}
+
+// Members of the team of a player, with their keys, and the messages sealed for the player
+pub async fn team_channel_handler(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, fleet)): Path<(String, String)>,
+    Query(query): Query<ChannelQuery>,
+) -> Result<Json<TeamChannel>, ChainError> {
+    let gmap = shared.games();
+    let game = match gmap.get(&gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    let team = match game.team_of(&fleet).filter(|_| game.teams) {
+        Some(team) => team,
+        None => return Err(ChainError::NotFound("Player not found in a team".to_string())),
+    };
+
+    let members = game
+        .members(team)
+        .into_iter()
+        .filter_map(|name| game.pmap.get(name))
+        .map(|player| TeamMember {
+            fleet: player.name.clone(),
+            public_key: shared.identities.player_key(&gameid, player).to_bytes().to_vec(),
+        })
+        .collect();
+    let messages = game
+        .team_messages
+        .iter()
+        .filter(|message| message.seq > query.after)
+        .filter(|message| message.data.keys.iter().any(|sealed| sealed.fleet == fleet))
+        .cloned()
+        .collect();
+    Ok(Json(TeamChannel { team: team.to_string(), members, messages }))
+}
@@ -0,0 +1,91 @@
+// src/correlation.rs
+//
+// Correlation IDs. A host gives every action of a player an ID, sent in the `x-correlation-id`
+// header of the requests the action makes (and in the body of its receipt transaction), so the
+// action can be followed from the host's proving through the chain's verification to the events
+// it published. The chain checks the ID like a fleet name, echoes it on the response, records it
+// in the logs of the transaction, on `/tx/{id}` and on the events the transaction publishes, and
+// passes it to its replicas along with the transaction.
+//
+// The events and the replication log are written deep inside the handlers of the transactions,
+// which run on one thread without awaiting, so the ID of the transaction being applied is kept in
+// a thread local for the time it is applied, like the clock of the replication.
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use fleetcore::{check_correlation_id, CORRELATION_HEADER};
+use std::cell::RefCell;
+use tracing::Span;
+
+use crate::error::ChainError;
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Restores the previous correlation ID once the transaction has been applied
+pub struct Scope(Option<String>);
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+// Tag what the current thread does with `id` until the scope is dropped
+pub fn enter(id: Option<String>) -> Scope {
+    Scope(CURRENT.with(|current| current.replace(id)))
+}
+
+// Correlation ID of the transaction being applied on this thread
+pub fn current() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+// Correlation ID of a request, checked by `echo` before it reaches a handler
+pub fn from_headers(headers: &HeaderMap) -> Option<String> {
+    headers.get(CORRELATION_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string)
+}
+
+// Correlation ID of a signed game message: recorded on the span of its handler, and entered for the
+// time the message is applied
+pub fn enter_request(headers: &HeaderMap, span: &Span) -> Scope {
+    let id = from_headers(headers);
+    if let Some(id) = &id {
+        span.record("correlation_id", id.as_str());
+    }
+    enter(id)
+}
+
+// Correlation ID sent in the body of a receipt transaction, or else in its header (or its gRPC
+// metadata, which `echo` does not see)
+pub fn of_transaction(body: Option<&str>, headers: &HeaderMap) -> Result<Option<String>, ChainError> {
+    match body.map(str::to_string).or_else(|| from_headers(headers)) {
+        Some(id) => check_correlation_id(&id).map(|_| Some(id)).map_err(ChainError::InvalidId),
+        None => Ok(None),
+    }
+}
+
+// Middleware refusing a malformed correlation ID and echoing a valid one on the response
+pub async fn echo(request: Request, next: Next) -> Response {
+    let id = match request.headers().get(CORRELATION_HEADER) {
+        Some(value) => {
+            let id = value.to_str().unwrap_or("\u{fffd}");
+            if let Err(err) = check_correlation_id(id) {
+                return ChainError::InvalidId(err).into_response();
+            }
+            HeaderValue::from_str(id).ok()
+        }
+        None => None,
+    };
+    let mut response = next.run(request).await;
+    if let Some(id) = id {
+        response.headers_mut().insert(CORRELATION_HEADER, id);
+    }
+    response
+}
@@ -0,0 +1,96 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum::Json;
+use fleetcore::{InputError, InvalidInput, UnsupportedVersion, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use std::fmt;
+
+// Error returned by the chain handlers. The message is sent back to the host as the response
+// body, and the variant selects the HTTP status code.
+#[derive(Debug)]
+pub enum ChainError {
+    // The request could not be decoded (journal, key or signature bytes)
+    Malformed(String),
+    // The receipt or the signature did not verify
+    Unauthorized(String),
+    // The game, player or target does not exist
+    NotFound(String),
+    // The action is not allowed in the current state of the game
+    Rejected(String),
+    // The same receipt was already applied to the game
+    DuplicateTransaction(String),
+    // The game is paused until its players vote to resume it
+    Paused(String),
+    // The client sent too many requests and can retry after the given number of seconds
+    RateLimited(String, u64),
+    // The request body is larger than the configured cap
+    TooLarge(String),
+    // The transaction or its journal is in a protocol version the chain does not accept
+    UnsupportedVersion(u32),
+    // The chain could not write the transaction to its log, and did not apply it
+    Unavailable(String),
+    // A game ID or fleet name is outside the limits of fleetcore's inputs module
+    InvalidId(InputError),
+}
+
+impl ChainError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ChainError::Malformed(_) => StatusCode::BAD_REQUEST,
+            ChainError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ChainError::NotFound(_) => StatusCode::NOT_FOUND,
+            ChainError::Rejected(_) | ChainError::DuplicateTransaction(_) => StatusCode::CONFLICT,
+            ChainError::Paused(_) => StatusCode::LOCKED,
+            ChainError::RateLimited(..) => StatusCode::TOO_MANY_REQUESTS,
+            ChainError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ChainError::UnsupportedVersion(_) | ChainError::InvalidId(_) => StatusCode::BAD_REQUEST,
+            ChainError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Malformed(msg)
+            | ChainError::Unauthorized(msg)
+            | ChainError::NotFound(msg)
+            | ChainError::Rejected(msg)
+            | ChainError::DuplicateTransaction(msg)
+            | ChainError::Paused(msg)
+            | ChainError::RateLimited(msg, _)
+            | ChainError::TooLarge(msg)
+            | ChainError::Unavailable(msg) => write!(f, "{}", msg),
+            ChainError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported protocol version {}, the chain accepts versions {} to {}",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+            ChainError::InvalidId(err) => write!(f, "Invalid identifier: {}", err),
+        }
+    }
+}
+
+impl IntoResponse for ChainError {
+    fn into_response(self) -> Response {
+        if let ChainError::RateLimited(_, retry_after) = &self {
+            let retry_after = [(header::RETRY_AFTER, retry_after.to_string())];
+            return (self.status(), retry_after, self.to_string()).into_response();
+        }
+        // Hosts read the supported range from the body
+        if let ChainError::UnsupportedVersion(version) = &self {
+            let body = UnsupportedVersion {
+                version: *version,
+                min_supported: MIN_PROTOCOL_VERSION,
+                max_supported: PROTOCOL_VERSION,
+            };
+            return (self.status(), Json(body)).into_response();
+        }
+        // and the field that was refused
+        if let ChainError::InvalidId(err) = &self {
+            return (self.status(), Json(InvalidInput::from(err))).into_response();
+        }
+        (self.status(), self.to_string()).into_response()
+    }
+}
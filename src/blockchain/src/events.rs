@@ -0,0 +1,213 @@
+// src/events.rs
+//
+// Event stream of the chain (the messages shown on the dashboard). Every event is numbered and
+// the most recent ones are kept in a history buffer, so a client that reconnects with the number
+// of the last event it saw (the SSE `Last-Event-ID` header, or `?last_seq=`) receives exactly the
+// events it missed. Numbers start again from 1 when the chain restarts, so a token past the latest
+// event is from a previous run, and replays the whole buffer with a gap notice.
+//
+// The events of each game are also kept in a longer history of their own, for as long as the game
+// or its archive is kept, so spectators joining late catch up on the whole game: page through it
+// with `GET /games/{id}/events?since=`, or follow `/logs?gameid=` which resumes from it.
+//
+// Live events go through a bounded channel. A client too slow to keep up loses its place in it:
+// it is sent a `resync` event (see Resync) saying where it stopped and where the stream is, and
+// its stream ends, so it catches up from the history instead of silently missing events.
+//
+// Each event is signed with the node's response key over its number and message, so a client
+// holding the key can check that an event comes from the chain.
+
+use ed25519_dalek::SigningKey;
+use fleetcore::{sign_canonical, EventStatement, EVENT_DOMAIN};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use crate::correlation;
+
+// Number of past events kept for reconnecting clients
+const HISTORY_LEN: usize = 1024;
+// Number of past events kept for each game
+const GAME_HISTORY_LEN: usize = 4096;
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct ChainEvent {
+    pub seq: u64,
+    // Game the event is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gameid: Option<String>,
+    pub msg: String,
+    // Hex-encoded signature of the event by the node's response key
+    pub signature: String,
+    // Correlation ID of the transaction that published the event, if its host sent one. It is not
+    // signed: it helps following an action, not proving anything about it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Default)]
+struct GameHistory {
+    events: VecDeque<ChainEvent>,
+    // Number of the last event dropped from the buffer, 0 if none was
+    dropped: u64,
+}
+
+struct History {
+    events: VecDeque<ChainEvent>,
+    games: HashMap<String, GameHistory>,
+    next_seq: u64,
+}
+
+pub struct EventLog {
+    tx: broadcast::Sender<ChainEvent>,
+    history: Mutex<History>,
+    key: SigningKey,
+}
+
+// What a client receives when it subscribes
+pub struct Subscription {
+    // Events published after the client's resume token
+    pub missed: Vec<ChainEvent>,
+    // Some of the missed events are no longer in the history buffer
+    pub gap: bool,
+    // Number of the event the live events follow: the last one replayed, or the latest one
+    // published when nothing was
+    pub last_seq: u64,
+    pub live: broadcast::Receiver<ChainEvent>,
+}
+
+// Sent to a client that fell behind the live events, before its stream ends
+#[derive(Serialize, ToSchema)]
+pub struct Resync {
+    // Number of live events the client lost
+    pub missed: u64,
+    // Number of the last event the client received, to resume after
+    pub last_seq: u64,
+    // Number of the latest event published
+    pub latest_seq: u64,
+    // Where to catch up from: the game's history, or the stream itself, which replays the
+    // missed events while they are in its buffer
+    pub resume: String,
+}
+
+// Page of the history of a game
+#[derive(Serialize, ToSchema)]
+pub struct EventPage {
+    pub events: Vec<ChainEvent>,
+    // Some events after `since` are no longer in the history buffer
+    pub gap: bool,
+    // Resume token of the next page, if there are more events
+    pub next: Option<u64>,
+}
+
+impl EventLog {
+    pub fn new(key: SigningKey) -> Self {
+        let (tx, _rx) = broadcast::channel(100);
+        EventLog {
+            tx,
+            history: Mutex::new(History {
+                events: VecDeque::with_capacity(HISTORY_LEN),
+                games: HashMap::new(),
+                next_seq: 1,
+            }),
+            key,
+        }
+    }
+
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Number and record an event, then send it to the connected clients
+    pub fn publish(&self, gameid: Option<&str>, msg: String) {
+        let mut history = self.history();
+        let statement = EventStatement { seq: history.next_seq, msg };
+        let signature = sign_canonical(&self.key, EVENT_DOMAIN, &statement).map(hex::encode).unwrap_or_default();
+        let event = ChainEvent {
+            seq: statement.seq,
+            gameid: gameid.map(str::to_string),
+            msg: statement.msg,
+            signature,
+            correlation_id: correlation::current(),
+        };
+        history.next_seq += 1;
+        if history.events.len() == HISTORY_LEN {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        if let Some(gameid) = gameid {
+            let game = history.games.entry(gameid.to_string()).or_default();
+            if game.events.len() == GAME_HISTORY_LEN {
+                if let Some(dropped) = game.events.pop_front() {
+                    game.dropped = dropped.seq;
+                }
+            }
+            game.events.push_back(event.clone());
+        }
+        // Sent under the history lock, so subscribers never see an event both replayed and live;
+        // having no subscriber connected is not an error
+        let _ = self.tx.send(event);
+    }
+
+    // Subscribe to the live events, replaying the ones published after `last_seq`. New clients
+    // (no resume token) only get live events. Clients following a single game are replayed from
+    // the game's own history, and should drop the live events of other games.
+    pub fn subscribe(&self, last_seq: Option<u64>, gameid: Option<&str>) -> Subscription {
+        let history = self.history();
+        let live = self.tx.subscribe();
+        let (last_seq, restarted) = match last_seq {
+            // A token numbered past the latest event was given by a previous run of the chain, which
+            // numbered its events from 1 as well: replay everything there is, as a gap
+            Some(last_seq) if last_seq >= history.next_seq => (0, true),
+            Some(last_seq) => (last_seq, false),
+            None => return Subscription { missed: Vec::new(), gap: false, last_seq: history.next_seq - 1, live },
+        };
+
+        let (missed, gap) = match gameid {
+            Some(gameid) => match history.games.get(gameid) {
+                Some(game) => (after(&game.events, last_seq, usize::MAX), last_seq < game.dropped),
+                None => (Vec::new(), false),
+            },
+            None => {
+                let oldest = history.events.front().map_or(history.next_seq, |event| event.seq);
+                (after(&history.events, last_seq, usize::MAX), last_seq + 1 < oldest)
+            }
+        };
+        let last_seq = missed.last().map_or(last_seq, |event| event.seq);
+        Subscription { missed, gap: gap || restarted, last_seq, live }
+    }
+
+    // Tell a client that lost `missed` live events after `last_seq` how to catch up
+    pub fn resync(&self, missed: u64, last_seq: u64, gameid: Option<&str>) -> Resync {
+        let latest_seq = self.history().next_seq - 1;
+        let resume = match gameid {
+            Some(gameid) => format!("/games/{}/events?since={}", gameid, last_seq),
+            None => format!("/logs?last_seq={}", last_seq),
+        };
+        Resync { missed, last_seq, latest_seq, resume }
+    }
+
+    // Up to `limit` events of a game published after `since`, None if the game has no history
+    pub fn page(&self, gameid: &str, since: u64, limit: usize) -> Option<EventPage> {
+        let history = self.history();
+        let game = history.games.get(gameid)?;
+        let events = after(&game.events, since, limit);
+        let next = match events.last() {
+            Some(last) if game.events.back().is_some_and(|newest| newest.seq > last.seq) => Some(last.seq),
+            _ => None,
+        };
+        Some(EventPage { events, gap: since < game.dropped, next })
+    }
+
+    // Drop the history of a game that is no longer kept
+    pub fn forget_game(&self, gameid: &str) {
+        self.history().games.remove(gameid);
+    }
+}
+
+// Events of a buffer published after `seq`, oldest first
+fn after(events: &VecDeque<ChainEvent>, seq: u64, limit: usize) -> Vec<ChainEvent> {
+    events.iter().filter(|event| event.seq > seq).take(limit).cloned().collect()
+}
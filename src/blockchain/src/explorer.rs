@@ -0,0 +1,476 @@
+// src/explorer.rs
+//
+// Block explorer. The index page shows the latest blocks and the games the chain knows about next
+// to the live dashboard, and links to a page per block and per game. A game page lists the
+// players with their public keys and every transaction of the game in the chain's order, with its
+// block, the fields of its journal and whether its receipt verifies, and the annotations the players
+// attached to it once it ended. Pages are rendered on the server from the blocks and the
+// replication log, and only show what the chain already publishes: annotations hidden by an
+// operator are left out.
+//
+// When the fleetwasm package is served (`wasm_pkg`), game pages can also verify the receipts in
+// the browser, so visitors do not have to trust the chain's own verification.
+
+use axum::{
+    extract::Path,
+    http::header,
+    response::{Html, IntoResponse},
+    Extension,
+};
+use fleetconfig::config;
+use fleetcore::{
+    AuditJournal, BaseJournal, BlindReportJournal, BlindWinJournal, Command, CommunicationData, FireJournal,
+    RegisterJournal, ReportJournal, StatusJournal, WinJournal,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::blocks::describe;
+use crate::error::ChainError;
+use crate::heatmap;
+use crate::replication::{Replicated, Transaction};
+use crate::SharedData;
+
+// Blocks listed on the index page
+const LATEST_BLOCKS: u64 = 20;
+
+// Escape text written into the pages, game and fleet names are chosen by players
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escape a path segment of a link
+fn segment(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// JSON written into an inline script, which must not close the script element
+fn script_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default().replace("</", "<\\/")
+}
+
+fn game_link(gameid: &str) -> String {
+    format!(r#"<a href="/explorer/games/{}">{}</a>"#, segment(gameid), escape(gameid))
+}
+
+fn block_link(number: Option<u64>) -> String {
+    match number {
+        Some(number) => format!(r#"<a href="/explorer/blocks/{}">{}</a>"#, number, number),
+        None => "pending".to_string(),
+    }
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>{title}</title>
+    <style>
+        table {{ border-collapse: collapse; }}
+        td, th {{ border: 1px solid #ccc; padding: 2px 6px; text-align: left; vertical-align: top; }}
+        .digest {{ font-family: monospace; }}
+    </style>
+    <script src="/timeline.js"></script>
+</head>
+<body>
+<p><a href="/">Explorer</a></p>
+{body}
+</body>
+</html>"#,
+        title = escape(title),
+        body = body
+    ))
+}
+
+// Digests serialize as their eight words, shown as the usual hex string
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(words) if words.len() == 8 && words.iter().all(|w| w.as_u64().is_some_and(|w| w <= u32::MAX as u64)) => {
+            let bytes: Vec<u8> = words.iter().flat_map(|w| (w.as_u64().unwrap_or_default() as u32).to_le_bytes()).collect();
+            hex::encode(bytes)
+        }
+        value => value.to_string(),
+    }
+}
+
+fn fields<T: DeserializeOwned + Serialize>(input_data: &CommunicationData) -> Vec<(String, String)> {
+    let journal = input_data.receipt.journal.decode::<T>().ok().and_then(|journal| serde_json::to_value(journal).ok());
+    match journal {
+        Some(Value::Object(fields)) => fields.iter().map(|(name, value)| (name.clone(), value_text(value))).collect(),
+        _ => vec![("journal".to_string(), "could not be decoded".to_string())],
+    }
+}
+
+// Fields of the journal of a receipt
+fn journal_fields(input_data: &CommunicationData) -> Vec<(String, String)> {
+    match input_data.cmd {
+        Command::Join | Command::Wave | Command::Forfeit | Command::Contest => fields::<BaseJournal>(input_data),
+        Command::Fire => fields::<FireJournal>(input_data),
+        Command::Report => fields::<ReportJournal>(input_data),
+        Command::BlindReport => fields::<BlindReportJournal>(input_data),
+        Command::Win => fields::<WinJournal>(input_data),
+        Command::BlindWin => fields::<BlindWinJournal>(input_data),
+        Command::Register => fields::<RegisterJournal>(input_data),
+        Command::Status => fields::<StatusJournal>(input_data),
+        Command::Audit => fields::<AuditJournal>(input_data),
+    }
+}
+
+// Fields and verification status of a transaction
+fn details(shared: &SharedData, entry: &Replicated) -> (Vec<(String, String)>, String) {
+    match &entry.tx {
+        Transaction::Chain(input_data) => {
+            // Checked against the guests accepted when the transaction was applied
+            let status = match shared.guests.verify(input_data.cmd, &input_data.receipt, entry.at) {
+                Ok(accepted) => format!("verified (guest version {})", escape(accepted.version())),
+                Err(err) => format!("<b>not verified</b>: {}", escape(&err.to_string())),
+            };
+            (journal_fields(input_data), status)
+        }
+        Transaction::Ready { data, .. } => (vec![("start".to_string(), data.start.to_string())], "signed message".to_string()),
+        Transaction::Annotation { gameid, data } => {
+            let hidden = shared.archived().get(gameid).is_some_and(|game| {
+                game.annotations.iter().any(|a| a.hidden && a.fleet == data.fleet && a.text == data.text.trim())
+            });
+            let text = if hidden { "(hidden by an operator)".to_string() } else { data.text.clone() };
+            (vec![("text".to_string(), text)], "signed message".to_string())
+        }
+        Transaction::Chat { data, .. } => (vec![("text".to_string(), data.text.clone())], "signed message".to_string()),
+        Transaction::Reveal { data, .. } => (vec![("random".to_string(), data.random.clone())], "signed message".to_string()),
+        Transaction::Pause { data, .. } => (vec![("pause".to_string(), data.pause.to_string())], "signed message".to_string()),
+        Transaction::Rematch { .. } => (Vec::new(), "signed message".to_string()),
+        Transaction::TeamMessage { data, .. } => {
+            (vec![("recipients".to_string(), data.keys.len().to_string())], "signed message, encrypted".to_string())
+        }
+    }
+}
+
+// Dashboard with the latest blocks and every game
+pub async fn index(Extension(shared): Extension<SharedData>) -> Html<String> {
+    let mut blocks = String::new();
+    let first = shared.blocks.latest().map_or(0, |latest| (latest.number + 1).saturating_sub(LATEST_BLOCKS));
+    for header in shared.blocks.headers(first, LATEST_BLOCKS as usize).iter().rev() {
+        blocks.push_str(&format!(
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class="digest">{}</td></tr>"#,
+            block_link(Some(header.number)),
+            header.timestamp,
+            header.count,
+            header.first_index,
+            header.hash
+        ));
+    }
+
+    let mut running: Vec<(String, usize, bool)> = shared.games()
+        .iter()
+        .map(|(gameid, game)| (gameid.clone(), game.pmap.len(), game.started))
+        .collect();
+    running.sort();
+    let mut archived: Vec<(String, usize, Option<String>)> = shared.archived()
+        .iter()
+        .map(|(gameid, game)| (gameid.clone(), game.players.len(), game.winner.clone()))
+        .collect();
+    archived.sort();
+
+    let mut games = String::new();
+    for (gameid, players, started) in running {
+        let state = if started { "running" } else { "lobby" };
+        games.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            game_link(&gameid),
+            players,
+            state,
+            shared.spectators.count(&gameid)
+        ));
+    }
+    for (gameid, players, winner) in archived {
+        let state = match winner {
+            Some(winner) => format!("won by {}", escape(&winner)),
+            None => "ended".to_string(),
+        };
+        games.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            game_link(&gameid),
+            players,
+            state,
+            shared.spectators.count(&gameid)
+        ));
+    }
+
+    page(
+        "Blockchain Emulator",
+        &format!(
+            r#"
+<h1>Blocks</h1>
+<table>
+<tr><th>Height</th><th>Sealed at</th><th>Transactions</th><th>First index</th><th>Hash</th></tr>
+{blocks}
+</table>
+<h1>Games</h1>
+<table>
+<tr><th>Game</th><th>Players</th><th>State</th><th>Spectators</th></tr>
+{games}
+</table>
+<h1>Turns</h1>
+<div id="games"></div>
+<h1>Registered Transactions</h1>
+<ul id="logs"></ul>
+<script>
+    followTimelines(document.getElementById('games'), '/timelines');
+    const eventSource = new EventSource('/logs');
+    eventSource.onmessage = function(event) {{
+        const logs = document.getElementById('logs');
+        const log = document.createElement('li');
+        log.textContent = event.data;
+        logs.appendChild(log);
+    }};
+    // The browser reconnects on its own and resumes after the last event it received
+    eventSource.addEventListener('gap', function(event) {{
+        const log = document.createElement('li');
+        log.textContent = '(' + event.data + ')';
+        document.getElementById('logs').appendChild(log);
+    }});
+    // Sent before the stream ends when the page fell behind: the reconnection catches up
+    eventSource.addEventListener('resync', function(event) {{
+        const resync = JSON.parse(event.data);
+        const log = document.createElement('li');
+        log.textContent = '(Fell behind by ' + resync.missed + ' events, catching up)';
+        document.getElementById('logs').appendChild(log);
+    }});
+</script>
+"#
+        ),
+    )
+}
+
+// A sealed block with its transactions
+pub async fn block_page(
+    Extension(shared): Extension<SharedData>,
+    Path(number): Path<u64>,
+) -> Result<Html<String>, ChainError> {
+    let block = shared.blocks.block(number).ok_or_else(|| ChainError::NotFound("Block not found".to_string()))?;
+    let header = &block.header;
+
+    let prev = match header.number {
+        0 => "none".to_string(),
+        number => format!(r#"<a href="/explorer/blocks/{}">{}</a>"#, number - 1, header.prev_hash),
+    };
+    let mut transactions = String::new();
+    for tx in &block.transactions {
+        transactions.push_str(&format!(
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class="digest">{}</td></tr>"#,
+            tx.index,
+            escape(&tx.kind),
+            game_link(&tx.gameid),
+            escape(&tx.fleet),
+            tx.digest
+        ));
+    }
+
+    Ok(page(
+        &format!("Block {}", header.number),
+        &format!(
+            r#"
+<h1>Block {number}</h1>
+<table>
+<tr><th>Hash</th><td class="digest">{hash}</td></tr>
+<tr><th>Previous</th><td class="digest">{prev}</td></tr>
+<tr><th>Sealed at</th><td>{timestamp}</td></tr>
+<tr><th>Transactions digest</th><td class="digest">{digest}</td></tr>
+</table>
+<h2>Transactions</h2>
+<table>
+<tr><th>Index</th><th>Kind</th><th>Game</th><th>Fleet</th><th>Digest</th></tr>
+{transactions}
+</table>
+"#,
+            number = header.number,
+            hash = header.hash,
+            timestamp = header.timestamp,
+            digest = header.transactions,
+        ),
+    ))
+}
+
+// Players of a game, the public view of their boards and the game's transactions in the chain's
+// order
+pub async fn game_page(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Html<String>, ChainError> {
+    // Players and their keys, from the running game or from the archive
+    let mut players: Vec<(String, String)> = Vec::new();
+    let mut state = String::new();
+    let mut annotations = String::new();
+    if let Some(game) = shared.games().get(&gameid) {
+        players = game.pmap
+            .values()
+            .map(|player| (player.name.clone(), hex::encode(shared.identities.player_key(&gameid, player).as_bytes())))
+            .collect();
+        state = match (&game.next_player, game.started) {
+            (_, false) => "In the lobby".to_string(),
+            (Some(next), true) => format!("Running, {} shots fired, waiting for {}", game.shots, escape(next)),
+            (None, true) => format!("Running, {} shots fired", game.shots),
+        };
+    }
+    if state.is_empty() {
+        if let Some(game) = shared.archived().get(&gameid) {
+            players = game.players.iter().map(|(fleet, key)| (fleet.clone(), hex::encode(key.as_bytes()))).collect();
+            state = match &game.winner {
+                Some(winner) => format!("Ended at {}, won by {}", game.ended_at, escape(winner)),
+                None => format!("Ended at {} without a winner", game.ended_at),
+            };
+            for annotation in game.annotations.iter().filter(|a| !a.hidden) {
+                annotations.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{:?}</td><td>{}</td></tr>",
+                    annotation.timestamp,
+                    escape(&annotation.fleet),
+                    annotation.kind,
+                    escape(&annotation.text)
+                ));
+            }
+        }
+    }
+    let transactions = shared.replication.game_transactions(&gameid);
+    if state.is_empty() && transactions.is_empty() {
+        return Err(ChainError::NotFound("Game not found".to_string()));
+    }
+    let boards = match heatmap::boards(&shared, &gameid) {
+        Ok(view) => format!("<h2>Boards</h2>\n<pre>{}</pre>", escape(&heatmap::render_text(&view))),
+        Err(_) => String::new(),
+    };
+    players.sort();
+    if !annotations.is_empty() {
+        annotations = format!(
+            "<h2>Annotations</h2>\n<table>\n<tr><th>Time</th><th>Fleet</th><th>Kind</th><th>Text</th></tr>\n{}\n</table>",
+            annotations
+        );
+    }
+
+    let mut keys = String::new();
+    for (fleet, key) in &players {
+        keys.push_str(&format!(r#"<tr><td>{}</td><td class="digest">{}</td></tr>"#, escape(fleet), key));
+    }
+
+    let mut moves = String::new();
+    for (index, entry) in &transactions {
+        let (fields, status) = details(&shared, entry);
+        let fields: String = fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}<br>", escape(name), escape(value)))
+            .collect();
+        let (kind, _, fleet, _) = describe(&entry.tx);
+        moves.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            index,
+            block_link(shared.blocks.block_of(*index)),
+            entry.at,
+            escape(&kind),
+            escape(&fleet),
+            fields,
+            status
+        ));
+    }
+
+    // Verified again in the browser with the players' keys shown above
+    let verify = match config().wasm_pkg {
+        Some(_) => format!(
+            r#"
+<h2>Verify in your browser</h2>
+<p><button id="verify">Verify receipts</button> <span id="verify-status"></span></p>
+<ul id="verify-results"></ul>
+<script type="module">
+import init, {{ verifyAppliedReceipt, verifyCertificate }} from "/wasm/fleetwasm.js";
+const gameid = {gameid_js};
+const keys = {keys_js};
+document.getElementById("verify").onclick = async () => {{
+    const status = document.getElementById("verify-status");
+    const results = document.getElementById("verify-results");
+    const show = (text) => {{ const li = document.createElement("li"); li.textContent = text; results.appendChild(li); }};
+    results.innerHTML = "";
+    status.textContent = "Verifying...";
+    await init();
+    const game = "/games/" + encodeURIComponent(gameid);
+    const methods = await (await fetch("/methods")).text();
+    const receipts = await (await fetch(game + "/receipts")).json();
+    let failed = 0;
+    for (const receipt of receipts) {{
+        try {{
+            const result = JSON.parse(verifyAppliedReceipt(JSON.stringify(receipt), methods, JSON.stringify(keys)));
+            const ok = result.image_id !== null && result.signed !== false;
+            if (!ok) failed++;
+            show(`#${{result.index}} ${{result.cmd}} by ${{result.journal.fleet}}: `
+                + (result.image_id ? `verified against ${{result.image_id}}` : "NOT VERIFIED")
+                + (result.signed === null ? ", signature not checked" : result.signed ? ", signed" : ", BAD SIGNATURE"));
+        }} catch (e) {{
+            failed++;
+            show(`#${{receipt.index}}: ${{e}}`);
+        }}
+    }}
+    const certificate = await fetch(game + "/certificate");
+    if (certificate.ok) {{
+        const genesis = await (await fetch("/genesis")).text();
+        const ok = verifyCertificate(await certificate.text(), genesis);
+        if (!ok) failed++;
+        show(ok ? "Certificate signed by the genesis key" : "CERTIFICATE NOT SIGNED BY THE GENESIS KEY");
+    }}
+    status.textContent = failed === 0 ? `${{receipts.length}} receipts verified` : `${{failed}} checks failed`;
+}};
+</script>
+"#,
+            gameid_js = script_json(&gameid),
+            keys_js = script_json(&players.iter().cloned().collect::<HashMap<String, String>>()),
+        ),
+        None => String::new(),
+    };
+
+    Ok(page(
+        &format!("Game {}", gameid),
+        &format!(
+            r#"
+<h1>Game {gameid}</h1>
+<p>{state}</p>
+<h2>Players</h2>
+<table>
+<tr><th>Fleet</th><th>Public key</th></tr>
+{keys}
+</table>
+{boards}
+<h2>Transactions</h2>
+<table>
+<tr><th>Index</th><th>Block</th><th>Time</th><th>Kind</th><th>Fleet</th><th>Journal</th><th>Receipt</th></tr>
+{moves}
+</table>
+{annotations}
+{verify}
+"#,
+            gameid = escape(&gameid),
+        ),
+    ))
+}
+
+// Files of the fleetwasm package, loaded by the game pages
+pub async fn wasm_asset(Path(file): Path<String>) -> Result<impl IntoResponse, ChainError> {
+    let not_found = || ChainError::NotFound("File not found".to_string());
+    let (path, content_type) = config().wasm_file(&file).ok_or_else(not_found)?;
+    let bytes = tokio::fs::read(path).await.map_err(|_| not_found())?;
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes))
+}
@@ -0,0 +1,208 @@
+// src/export.rs
+//
+// Game exports. `GET /games/{gameid}/export` answers with a self-contained JSON document of a game,
+// running or over: its players and their keys, its parameters, and every transaction applied to
+// it in order, each with the digest of its journal or message. The transactions are the entries of
+// the replication log, with the clock and the seed of the node that accepted them.
+//
+// An operator imports an export into another chain with `POST /admin/import` (or the
+// `fleet-import` tool of the host). The chain replays the transactions as a replica would, so
+// every receipt is verified again and every transition checked by the usual handlers, and the
+// import stops at the first transaction that does not apply. The game must not exist on the chain
+// importing it, which should be a fresh one: as for replicas, timeouts act on each node separately.
+
+use axum::{extract::Path, Extension, Json};
+use fleetconfig::TurnPolicy;
+use fleetcore::PROTOCOL_VERSION;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::blocks::describe;
+use crate::error::ChainError;
+use crate::keys::parse_verifying_key;
+use crate::replication::Replicated;
+use crate::{now_secs, Game, SharedData};
+
+// Version of the export document
+const EXPORT_FORMAT: u32 = 1;
+
+// Rules a game is played with
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GameParameters {
+    blinded: bool,
+    min_players: usize,
+    max_players: usize,
+    cycle_budget: Option<u64>,
+    turn_policy: TurnPolicy,
+    status_every: u32,
+    audit_required: bool,
+    private: bool,
+    teams: bool,
+    victory_timeout_seconds: u64,
+}
+
+impl GameParameters {
+    pub fn of(game: &Game) -> Self {
+        GameParameters {
+            blinded: game.blinded,
+            min_players: game.min_players,
+            max_players: game.max_players,
+            cycle_budget: game.cycle_budget,
+            turn_policy: game.turn_policy,
+            status_every: game.status_every,
+            audit_required: game.audit_required,
+            private: game.private,
+            teams: game.teams,
+            victory_timeout_seconds: game.victory_timeout_seconds,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct ExportedPlayer {
+    fleet: String,
+    // Hex of the key the player signs with
+    verifying_key: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ExportedTransaction {
+    // Position of the transaction in the log of the exporting node
+    index: u64,
+    command: String,
+    fleet: String,
+    // Digest of the journal of a receipt, or of the canonical form of a signed message
+    digest: String,
+    #[serde(flatten)]
+    entry: Replicated,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GameExport {
+    format: u32,
+    protocol_version: u32,
+    gameid: String,
+    exported_at: u64,
+    over: bool,
+    winner: Option<String>,
+    players: Vec<ExportedPlayer>,
+    parameters: GameParameters,
+    transactions: Vec<ExportedTransaction>,
+}
+
+// Export a game, running or over
+fn export(shared: &SharedData, gameid: &str) -> Result<GameExport, ChainError> {
+    // Read the state under the game lock, then the transactions: the log has at least those
+    let (over, winner, mut players, parameters) = {
+        let gmap = shared.games();
+        let archive = shared.archived();
+        if let Some(game) = gmap.get(gameid) {
+            let players: Vec<ExportedPlayer> = game.pmap
+                .values()
+                .map(|player| ExportedPlayer {
+                    fleet: player.name.clone(),
+                    verifying_key: hex::encode(shared.identities.player_key(gameid, player).as_bytes()),
+                })
+                .collect();
+            (false, None, players, GameParameters::of(game))
+        } else if let Some(game) = archive.get(gameid) {
+            let players: Vec<ExportedPlayer> = game.players
+                .iter()
+                .map(|(fleet, key)| ExportedPlayer { fleet: fleet.clone(), verifying_key: hex::encode(key.as_bytes()) })
+                .collect();
+            (true, game.winner.clone(), players, game.parameters.clone())
+        } else {
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+
+    let transactions = shared.replication
+        .game_transactions(gameid)
+        .into_iter()
+        .map(|(index, entry)| {
+            let (command, _, fleet, digest) = describe(&entry.tx);
+            ExportedTransaction { index, command, fleet, digest: digest.to_string(), entry }
+        })
+        .collect();
+
+    Ok(GameExport {
+        format: EXPORT_FORMAT,
+        protocol_version: PROTOCOL_VERSION,
+        gameid: gameid.to_string(),
+        exported_at: now_secs(),
+        over,
+        winner,
+        players,
+        parameters,
+        transactions,
+    })
+}
+
+// Replay an exported game, and check that it reaches the state the export describes
+fn import(shared: &SharedData, export: GameExport) -> Result<String, ChainError> {
+    if export.format != EXPORT_FORMAT {
+        return Err(ChainError::Malformed(format!("Unsupported export format {}", export.format)));
+    }
+    let gameid = export.gameid;
+    if shared.games().contains_key(&gameid) || shared.archived().contains_key(&gameid) {
+        return Err(ChainError::Rejected(format!("Game {} already exists on this chain", gameid)));
+    }
+
+    // Check that the document is consistent before applying anything
+    let mut entries = Vec::with_capacity(export.transactions.len());
+    for (position, exported) in export.transactions.into_iter().enumerate() {
+        let (command, tx_gameid, fleet, digest) = describe(&exported.entry.tx);
+        let described = command == exported.command && fleet == exported.fleet && digest.to_string() == exported.digest;
+        if tx_gameid != gameid || !described {
+            return Err(ChainError::Malformed(format!("Transaction {} does not match its description", position)));
+        }
+        entries.push(exported.entry);
+    }
+
+    // Players who joined with a key registered for every game did not send it along
+    for player in &export.players {
+        let key = hex::decode(&player.verifying_key)
+            .map_err(|_| ChainError::Malformed(format!("Malformed key of {}", player.fleet)))?;
+        shared.identities.carry(&gameid, &player.fleet, parse_verifying_key(&key)?);
+    }
+
+    let count = shared.replication.import(shared, entries)?;
+
+    // Check that the replay ends where the exporting chain was
+    let parameters = {
+        let gmap = shared.games();
+        let archive = shared.archived();
+        match (gmap.get(&gameid), archive.get(&gameid)) {
+            (Some(game), _) if !export.over => GameParameters::of(game),
+            // A victory claim ends the game once its timeout expires on this node's clock
+            (Some(game), _) if game.first_victory_claim.is_some() => GameParameters::of(game),
+            (None, Some(game)) if export.over && game.winner == export.winner => game.parameters.clone(),
+            _ => return Err(ChainError::Rejected(format!("Game {} does not end as exported", gameid))),
+        }
+    };
+    if parameters != export.parameters {
+        return Err(ChainError::Rejected(format!(
+            "Game {} was replayed with other parameters than exported: {:?}",
+            gameid, parameters
+        )));
+    }
+
+    info!("Imported game {} ({} transactions)", gameid, count);
+    shared.game_event(&gameid, format!("Game {} was imported with {} transactions", gameid, count));
+    Ok(format!("Imported {} transactions", count))
+}
+
+pub async fn export_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<GameExport>, ChainError> {
+    export(&shared, &gameid).map(Json)
+}
+
+pub async fn import_handler(
+    Extension(shared): Extension<SharedData>,
+    Json(export): Json<GameExport>,
+) -> Result<String, ChainError> {
+    import(&shared, export)
+}
@@ -0,0 +1,329 @@
+// src/graphql.rs
+//
+// GraphQL API, on `/graphql` (queries over POST or GET, subscriptions over a WebSocket on
+// `/graphql/ws`, GraphiQL on `/graphiql`). It exposes what the REST routes serve in one schema:
+// running and archived games with their players, every transaction of a game (moves) and the
+// receipts among them, and a subscription to the live events of a game, which first replays the
+// game's history like the spectator stream. Like the explorer, it only shows what the chain
+// already publishes.
+//
+// The API is only built with the `graphql` feature. Requests are passed to async-graphql by the
+// handlers below, subscriptions speaking either the graphql-ws or the graphql-transport-ws
+// protocol.
+
+use async_graphql::http::{
+    parse_query_string, GraphiQLSource, WebSocket as GraphQLWebSocket, WebSocketProtocols, WsMessage,
+    ALL_WEBSOCKET_PROTOCOLS,
+};
+use async_graphql::{ComplexObject, Context, EmptyMutation, Object, Schema, SimpleObject, Subscription};
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        RawQuery,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use futures::stream::{self, Stream, StreamExt};
+use futures::SinkExt;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use crate::blocks::describe;
+use crate::events::ChainEvent;
+use crate::monitoring::{self, SseClient};
+use crate::replication::Transaction;
+use crate::SharedData;
+
+#[derive(SimpleObject)]
+struct GamePlayer {
+    fleet: String,
+    // Hex-encoded key the player signs its transactions with
+    public_key: String,
+    team: Option<String>,
+    // Only known while the game is running
+    forfeited: Option<bool>,
+    shots_fired: Option<u32>,
+    hits: Option<u32>,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct GameView {
+    gameid: String,
+    // Running, or kept in the archive
+    running: bool,
+    started: bool,
+    // Only known while the game is running
+    private: Option<bool>,
+    teams: Option<bool>,
+    shots: Option<u32>,
+    next_player: Option<String>,
+    next_report: Option<String>,
+    winner: Option<String>,
+    ended_at: Option<u64>,
+    players: Vec<GamePlayer>,
+}
+
+// A transaction of a game: a receipt, or a signed message (ready, chat, annotation, reveal)
+#[derive(SimpleObject)]
+struct GameMove {
+    index: u64,
+    // Sealed block holding the transaction, None while its block is open
+    block: Option<u64>,
+    at: u64,
+    kind: String,
+    fleet: String,
+    // Journal digest of a receipt, digest of the canonical message otherwise
+    digest: String,
+}
+
+#[derive(SimpleObject)]
+struct GameReceipt {
+    index: u64,
+    block: Option<u64>,
+    at: u64,
+    command: String,
+    fleet: String,
+    nonce: u64,
+    cycles: Option<u64>,
+    journal_digest: String,
+    // Hex-encoded journal, for clients that decode and verify it themselves
+    journal: String,
+}
+
+#[derive(SimpleObject)]
+struct GameEvent {
+    seq: u64,
+    gameid: Option<String>,
+    msg: String,
+    // Hex-encoded signature of seq and msg by the node's response key
+    signature: String,
+    // Correlation ID of the transaction that published the event
+    correlation_id: Option<String>,
+}
+
+fn running_view(shared: &SharedData, gameid: &str) -> Option<GameView> {
+    let gmap = shared.games();
+    let game = gmap.get(gameid)?;
+    let mut players: Vec<GamePlayer> = game.pmap
+        .values()
+        .map(|player| GamePlayer {
+            fleet: player.name.clone(),
+            public_key: hex::encode(shared.identities.player_key(gameid, player).as_bytes()),
+            team: player.team.clone(),
+            forfeited: Some(player.forfeited),
+            shots_fired: Some(player.shots_fired),
+            hits: Some(player.hits),
+        })
+        .collect();
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+    Some(GameView {
+        gameid: gameid.to_string(),
+        running: true,
+        started: game.started,
+        private: Some(game.private),
+        teams: Some(game.teams),
+        shots: Some(game.shots),
+        next_player: game.next_player.clone(),
+        next_report: game.next_report.clone(),
+        winner: None,
+        ended_at: None,
+        players,
+    })
+}
+
+fn archived_view(shared: &SharedData, gameid: &str) -> Option<GameView> {
+    let archive = shared.archived();
+    let game = archive.get(gameid)?;
+    let mut players: Vec<GamePlayer> = game.players
+        .iter()
+        .map(|(fleet, key)| GamePlayer {
+            fleet: fleet.clone(),
+            public_key: hex::encode(key.as_bytes()),
+            team: None,
+            forfeited: None,
+            shots_fired: None,
+            hits: None,
+        })
+        .collect();
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+    Some(GameView {
+        gameid: gameid.to_string(),
+        running: false,
+        started: true,
+        private: None,
+        teams: None,
+        shots: None,
+        next_player: None,
+        next_report: None,
+        winner: game.winner.clone(),
+        ended_at: Some(game.ended_at),
+        players,
+    })
+}
+
+#[ComplexObject]
+impl GameView {
+    // Every transaction of the game, in the chain's order
+    async fn moves(&self, ctx: &Context<'_>) -> Vec<GameMove> {
+        let shared = ctx.data_unchecked::<SharedData>();
+        shared.replication
+            .game_transactions(&self.gameid)
+            .into_iter()
+            .map(|(index, entry)| {
+                let (kind, _, fleet, digest) = describe(&entry.tx);
+                GameMove { index, block: shared.blocks.block_of(index), at: entry.at, kind, fleet, digest: digest.to_string() }
+            })
+            .collect()
+    }
+
+    // Receipts applied to the game, in the chain's order
+    async fn receipts(&self, ctx: &Context<'_>) -> Vec<GameReceipt> {
+        let shared = ctx.data_unchecked::<SharedData>();
+        shared.replication
+            .game_transactions(&self.gameid)
+            .into_iter()
+            .filter_map(|(index, entry)| {
+                let (_, _, fleet, digest) = describe(&entry.tx);
+                match entry.tx {
+                    Transaction::Chain(data) => Some(GameReceipt {
+                        index,
+                        block: shared.blocks.block_of(index),
+                        at: entry.at,
+                        command: format!("{:?}", data.cmd),
+                        fleet,
+                        nonce: data.nonce,
+                        cycles: data.cycles,
+                        journal_digest: digest.to_string(),
+                        journal: hex::encode(&data.receipt.journal.bytes),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // Running games, then archived ones, by game ID
+    async fn games(&self, ctx: &Context<'_>) -> Vec<GameView> {
+        let shared = ctx.data_unchecked::<SharedData>();
+        let mut running: Vec<String> = shared.games().keys().cloned().collect();
+        let mut archived: Vec<String> = shared.archived().keys().cloned().collect();
+        running.sort();
+        archived.sort();
+        running
+            .iter()
+            .filter_map(|gameid| running_view(shared, gameid))
+            .chain(archived.iter().filter_map(|gameid| archived_view(shared, gameid)))
+            .collect()
+    }
+
+    async fn game(&self, ctx: &Context<'_>, gameid: String) -> Option<GameView> {
+        let shared = ctx.data_unchecked::<SharedData>();
+        running_view(shared, &gameid).or_else(|| archived_view(shared, &gameid))
+    }
+}
+
+struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    // Events of a game from its start, or after `last_seq`, then live
+    async fn game_events(&self, ctx: &Context<'_>, gameid: String, last_seq: Option<u64>) -> impl Stream<Item = GameEvent> {
+        let shared = ctx.data_unchecked::<SharedData>();
+        let subscription = shared.events.subscribe(Some(last_seq.unwrap_or(0)), Some(&gameid));
+        let event = |event: ChainEvent| GameEvent {
+            seq: event.seq,
+            gameid: event.gameid,
+            msg: event.msg,
+            signature: event.signature,
+            correlation_id: event.correlation_id,
+        };
+        let missed: Vec<GameEvent> = subscription.missed.into_iter().map(event).collect();
+        // Counted with the SSE clients until the subscription ends
+        let client = SseClient::connect();
+        let gameid = Some(gameid);
+        let live = BroadcastStream::new(subscription.live)
+            // The subscriber fell behind: end the subscription so it resumes after its last event
+            .take_while(|result| {
+                if let Err(BroadcastStreamRecvError::Lagged(count)) = result {
+                    monitoring::events_lagged(*count);
+                }
+                futures::future::ready(result.is_ok())
+            })
+            .filter_map(move |result| {
+                let _client = &client;
+                futures::future::ready(result.ok().filter(|e| e.gameid == gameid).map(event))
+            });
+        stream::iter(missed).chain(live)
+    }
+}
+
+pub type FleetSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").subscription_endpoint("/graphql/ws").finish())
+}
+
+// Query sent as the JSON body of a POST
+async fn post_query(Extension(schema): Extension<FleetSchema>, Json(request): Json<async_graphql::Request>) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+// Query sent in the query string of a GET
+async fn get_query(Extension(schema): Extension<FleetSchema>, RawQuery(query): RawQuery) -> Response {
+    match parse_query_string(query.as_deref().unwrap_or_default()) {
+        Ok(request) => Json(schema.execute(request).await).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn subscribe(Extension(schema): Extension<FleetSchema>, headers: HeaderMap, upgrade: WebSocketUpgrade) -> Response {
+    let protocol = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| protocols.split(',').find_map(|protocol| protocol.trim().parse::<WebSocketProtocols>().ok()));
+    let Some(protocol) = protocol else {
+        return (StatusCode::BAD_REQUEST, "Unsupported WebSocket protocol").into_response();
+    };
+    upgrade.protocols(ALL_WEBSOCKET_PROTOCOLS).on_upgrade(move |socket| serve_subscriptions(schema, socket, protocol))
+}
+
+// Pass the messages of a WebSocket to async-graphql and its answers back, until either side closes
+async fn serve_subscriptions(schema: FleetSchema, socket: WebSocket, protocol: WebSocketProtocols) {
+    let (mut sink, stream) = socket.split();
+    let input = stream
+        .take_while(|message| futures::future::ready(message.is_ok()))
+        .filter_map(|message| {
+            futures::future::ready(match message {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            })
+        });
+    let mut output = GraphQLWebSocket::new(schema, input, protocol);
+    while let Some(message) = output.next().await {
+        let message = match message {
+            WsMessage::Text(text) => Message::Text(text),
+            WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame { code, reason: reason.into() })),
+        };
+        if sink.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
+pub fn routes(shared: SharedData) -> Router {
+    let schema: FleetSchema = Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot).data(shared).finish();
+    Router::new()
+        .route("/graphql", get(get_query).post(post_query))
+        .route("/graphql/ws", get(subscribe))
+        .route("/graphiql", get(graphiql))
+        .layer(Extension(schema))
+}
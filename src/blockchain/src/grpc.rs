@@ -0,0 +1,229 @@
+// src/grpc.rs
+//
+// gRPC interface of the chain (proto/fleet.proto), for clients that prefer streaming RPC over SSE
+// and JSON. It mirrors the HTTP API: SubmitReceipt goes through the same checks as POST /chain
+// (the per-IP rate limit, body cap, host token, idempotency key and correlation ID included, the
+// latter three sent as metadata), GetGameState answers like /gamestate, and StreamEvents follows the event log like
+// /logs, resuming after `last_seq`. Receipts are sent encoded with bincode. The service listens on
+// `grpc_port`, and is disabled when it is unset.
+
+use axum::{
+    extract::{Path, Query},
+    http::{HeaderMap, HeaderValue},
+    Extension, Json,
+};
+use fleetconfig::config;
+use fleetcore::{Command, CommunicationData, CORRELATION_HEADER};
+use futures::stream::{self, Stream, StreamExt};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tonic::{Request, Response, Status};
+use tracing::{error, warn};
+
+use crate::error::ChainError;
+use crate::events::ChainEvent;
+use crate::hosts;
+use crate::idempotency::IDEMPOTENCY_HEADER;
+use crate::submissions::TX_ID_HEADER;
+use crate::monitoring::{self, SseClient};
+use crate::{game_state_handler, submit_transaction, GameStateQuery, SharedData};
+
+pub mod proto {
+    tonic::include_proto!("fleet.v1");
+}
+
+use proto::fleet_chain_server::{FleetChain, FleetChainServer};
+
+impl From<ChainError> for Status {
+    fn from(err: ChainError) -> Status {
+        let msg = err.to_string();
+        match err {
+            ChainError::Malformed(_) | ChainError::UnsupportedVersion(_) | ChainError::InvalidId(_) => {
+                Status::invalid_argument(msg)
+            }
+            ChainError::Unauthorized(_) => Status::unauthenticated(msg),
+            ChainError::NotFound(_) => Status::not_found(msg),
+            ChainError::Rejected(_) | ChainError::Paused(_) => Status::failed_precondition(msg),
+            ChainError::DuplicateTransaction(_) => Status::already_exists(msg),
+            ChainError::RateLimited(..) | ChainError::TooLarge(_) => Status::resource_exhausted(msg),
+            ChainError::Unavailable(_) => Status::unavailable(msg),
+        }
+    }
+}
+
+impl From<proto::Command> for Command {
+    fn from(cmd: proto::Command) -> Command {
+        match cmd {
+            proto::Command::Join => Command::Join,
+            proto::Command::Fire => Command::Fire,
+            proto::Command::Report => Command::Report,
+            proto::Command::Wave => Command::Wave,
+            proto::Command::Win => Command::Win,
+            proto::Command::BlindReport => Command::BlindReport,
+            proto::Command::BlindWin => Command::BlindWin,
+            proto::Command::Forfeit => Command::Forfeit,
+            proto::Command::Register => Command::Register,
+            proto::Command::Status => Command::Status,
+            proto::Command::Audit => Command::Audit,
+            proto::Command::Contest => Command::Contest,
+        }
+    }
+}
+
+impl TryFrom<proto::SubmitReceiptRequest> for CommunicationData {
+    type Error = ChainError;
+
+    fn try_from(request: proto::SubmitReceiptRequest) -> Result<Self, ChainError> {
+        let cmd = proto::Command::try_from(request.cmd)
+            .map_err(|_| ChainError::Malformed(format!("Unknown command {}", request.cmd)))?;
+        let receipt = bincode::deserialize(&request.receipt)
+            .map_err(|e| ChainError::Malformed(format!("Cannot decode receipt: {}", e)))?;
+        Ok(CommunicationData {
+            version: request.version,
+            cmd: cmd.into(),
+            receipt,
+            signature: request.signature,
+            public_key: request.public_key,
+            nonce: request.nonce,
+            cycles: request.cycles,
+            private: request.private,
+            invite: request.invite,
+            team: request.team,
+            victory_timeout: request.victory_timeout,
+            correlation_id: request.correlation_id,
+        })
+    }
+}
+
+fn proto_event(event: &ChainEvent) -> proto::ChainEvent {
+    proto::ChainEvent {
+        seq: event.seq,
+        gameid: event.gameid.clone(),
+        msg: event.msg.clone(),
+        gap: false,
+        signature: event.signature.clone(),
+        correlation_id: event.correlation_id.clone(),
+    }
+}
+
+struct FleetChainService {
+    shared: SharedData,
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::ChainEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl FleetChain for FleetChainService {
+    async fn submit_receipt(
+        &self,
+        request: Request<proto::SubmitReceiptRequest>,
+    ) -> Result<Response<proto::SubmitReceiptReply>, Status> {
+        if let Some(addr) = request.remote_addr() {
+            if let Err(retry_after) = self.shared.limits.per_ip.check(addr.ip()) {
+                warn!("Rate limited gRPC transactions from {}", addr.ip());
+                return Err(ChainError::RateLimited("Too many transactions, slow down".to_string(), retry_after).into());
+            }
+        }
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        hosts::check_token(token)?;
+        let mut request_headers = HeaderMap::new();
+        for name in [IDEMPOTENCY_HEADER, CORRELATION_HEADER] {
+            if let Some(value) = request.metadata().get(name).and_then(|v| v.to_str().ok()) {
+                if let Ok(value) = HeaderValue::from_str(value) {
+                    request_headers.insert(name, value);
+                }
+            }
+        }
+        let input_data = CommunicationData::try_from(request.into_inner())?;
+        let (headers, message) = submit_transaction(&self.shared, &request_headers, input_data)
+            .map_err(|(headers, err)| {
+                // Rejected transactions keep their ID, to look them up on /tx
+                let mut status = Status::from(err);
+                if let Some(id) = headers.get(TX_ID_HEADER).and_then(|v| v.to_str().ok()?.parse().ok()) {
+                    status.metadata_mut().insert(TX_ID_HEADER, id);
+                }
+                status
+            })?;
+        let request_id = headers
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .unwrap_or_default();
+        let tx_id = headers
+            .get(TX_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Response::new(proto::SubmitReceiptReply { message, request_id, tx_id }))
+    }
+
+    async fn get_game_state(
+        &self,
+        request: Request<proto::GetGameStateRequest>,
+    ) -> Result<Response<proto::GameState>, Status> {
+        let request = request.into_inner();
+        let Json(state) = game_state_handler(
+            Extension(self.shared.clone()),
+            Path((request.gameid, request.fleet)),
+            Query(GameStateQuery { min_seq: request.min_seq }),
+        )
+        .await?;
+        Ok(Response::new(proto::GameState {
+            next_player: state.next_player,
+            next_report: state.next_report,
+            started: state.started,
+            seq: state.seq,
+            blinded: state.blinded,
+            report_turn: state.report_turn,
+            status_due: state.status_due,
+            victory_timeout_seconds: state.victory_timeout_seconds,
+            victory_deadline: state.victory_deadline,
+            clock_left: state.clock_left,
+            paused: state.paused,
+        }))
+    }
+
+    type StreamEventsStream = EventStream;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<EventStream>, Status> {
+        let request = request.into_inner();
+        let subscription = self.shared.events.subscribe(request.last_seq, request.gameid.as_deref());
+
+        // Tell the client when it was away for longer than the history buffer covers
+        let gap = subscription.gap.then(|| Ok(proto::ChainEvent { gap: true, ..Default::default() }));
+        let missed = subscription.missed.into_iter().map(|event| Ok(proto_event(&event)));
+        // Counted with the SSE clients until the stream is dropped
+        let client = SseClient::connect();
+        let gameid = request.gameid;
+        let live = BroadcastStream::new(subscription.live).filter_map(move |result| {
+            let _client = &client;
+            let item = match result {
+                Ok(event) if gameid.is_some() && event.gameid != gameid => None,
+                Ok(event) => Some(Ok(proto_event(&event))),
+                // The client fell behind: end the stream so it resumes from the history
+                Err(BroadcastStreamRecvError::Lagged(count)) => {
+                    monitoring::events_lagged(count);
+                    Some(Err(Status::aborted("Lagged, resume after the last event received")))
+                }
+            };
+            futures::future::ready(item)
+        });
+
+        Ok(Response::new(Box::pin(stream::iter(gap).chain(stream::iter(missed)).chain(live))))
+    }
+}
+
+pub async fn serve(shared: SharedData, addr: SocketAddr) {
+    let service = FleetChainServer::new(FleetChainService { shared })
+        .max_decoding_message_size(config().chain.max_body_bytes);
+    if let Err(e) = tonic::transport::Server::builder().add_service(service).serve(addr).await {
+        error!("gRPC service stopped: {}", e);
+    }
+}
@@ -0,0 +1,147 @@
+// src/heatmap.rs
+//
+// Public view of the players' boards, for the explorer and for spectators in a terminal:
+// `/games/{gameid}/board` as JSON, `/games/{gameid}/board.txt` as a grid per player. It is built
+// from the reports applied to each board, so it only shows what the players already published:
+// the squares shot at and the outcome their owner reported. A blinded report only shows that the
+// square was shot at. Running and archived games are both served.
+
+use axum::{extract::Path, http::header, response::IntoResponse, Extension, Json};
+use fleetcore::{ShotOutcome, BOARD_SIZE};
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::error::ChainError;
+use crate::history::BoardHistory;
+use crate::SharedData;
+
+// What is known of a square. A square shot at more than once shows the most telling report: a hit
+// square is reported a miss when shot again, as it is no longer on the board.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Square {
+    #[default]
+    Unknown,
+    Miss,
+    Blinded,
+    Hit,
+    // The hit sank a ship
+    Sunk,
+}
+
+impl Square {
+    fn symbol(self) -> char {
+        match self {
+            Square::Unknown => '.',
+            Square::Miss => 'o',
+            Square::Blinded => '?',
+            Square::Hit => 'X',
+            Square::Sunk => '#',
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlayerBoard {
+    fleet: String,
+    reports: usize,
+    hits: usize,
+    misses: usize,
+    // Sizes of the ships sunk, in the order they went down
+    sunk: Vec<u8>,
+    // Rows of the board, top first
+    grid: Vec<Vec<Square>>,
+}
+
+#[derive(Serialize)]
+pub struct BoardsView {
+    gameid: String,
+    players: Vec<PlayerBoard>,
+}
+
+fn player_board(fleet: &str, history: &BoardHistory) -> PlayerBoard {
+    let mut grid = vec![vec![Square::Unknown; BOARD_SIZE as usize]; BOARD_SIZE as usize];
+    let mut board = PlayerBoard { fleet: fleet.to_string(), reports: 0, hits: 0, misses: 0, sunk: Vec::new(), grid: Vec::new() };
+    for step in history.moves() {
+        let square = match step.outcome() {
+            Some(ShotOutcome::Miss) => Square::Miss,
+            Some(ShotOutcome::Hit) => Square::Hit,
+            Some(ShotOutcome::Sunk { size }) => {
+                board.sunk.push(size);
+                Square::Sunk
+            }
+            None => Square::Blinded,
+        };
+        match square {
+            Square::Miss => board.misses += 1,
+            Square::Hit | Square::Sunk => board.hits += 1,
+            _ => {}
+        }
+        board.reports += 1;
+        let cell = &mut grid[step.pos.y() as usize][step.pos.x() as usize];
+        *cell = (*cell).max(square);
+    }
+    board.grid = grid;
+    board
+}
+
+// Boards of a running game, or of an archived one, by fleet name
+pub fn boards(shared: &SharedData, gameid: &str) -> Result<BoardsView, ChainError> {
+    let running: Option<Vec<PlayerBoard>> = shared
+        .games()
+        .get(gameid)
+        .map(|game| game.pmap.values().map(|player| player_board(&player.name, &player.history)).collect());
+    let mut players = match running {
+        Some(players) => players,
+        None => shared
+            .archived()
+            .get(gameid)
+            .map(|game| game.boards.iter().map(|(fleet, history)| player_board(fleet, history)).collect())
+            .ok_or_else(|| ChainError::NotFound("Game not found".to_string()))?,
+    };
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+    Ok(BoardsView { gameid: gameid.to_string(), players })
+}
+
+// The boards as text: a grid per player, columns A-J and rows 0-9, as on the page
+pub fn render_text(view: &BoardsView) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Game {}", view.gameid);
+    let _ = writeln!(out, "Legend: . not shot, o miss, X hit, # hit that sank a ship, ? blinded report");
+    for player in &view.players {
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{}: {} reports, {} hits, {} misses, {} ships sunk",
+            player.fleet,
+            player.reports,
+            player.hits,
+            player.misses,
+            player.sunk.len()
+        );
+        let columns: Vec<String> = (0..BOARD_SIZE).map(|x| ((b'A' + x) as char).to_string()).collect();
+        let _ = writeln!(out, "  {}", columns.join(" "));
+        for (y, row) in player.grid.iter().enumerate() {
+            let squares: Vec<String> = row.iter().map(|square| square.symbol().to_string()).collect();
+            let _ = writeln!(out, "{} {}", y, squares.join(" "));
+        }
+    }
+    out
+}
+
+// Handler returning the public view of the players' boards
+pub async fn board_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<BoardsView>, ChainError> {
+    boards(&shared, &gameid).map(Json)
+}
+
+// Same view as text, for terminals
+pub async fn board_text_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<impl IntoResponse, ChainError> {
+    let view = boards(&shared, &gameid)?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], render_text(&view)))
+}
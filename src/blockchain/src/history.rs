@@ -0,0 +1,126 @@
+// src/history.rs
+//
+// Evolution of each player's board. The chain only needs the latest board digest to check the next
+// report, but it also keeps every move that changed (or could have changed) the board, so that a
+// board revealed after the game can be replayed through the whole game and not only checked
+// against its final state.
+
+use fleetcore::{board_digest, Board, Coord, ShotOutcome};
+use std::collections::HashSet;
+use risc0_zkvm::Digest;
+use serde::Serialize;
+
+// Report of a blinded game, whose outcome is only committed to
+pub const BLINDED: &str = "Blinded";
+
+// A report applied to a player's board
+#[derive(Clone)]
+pub struct BoardMove {
+    // Number of reports the player made before this one
+    pub index: u32,
+    // Board digest once the report was applied
+    pub digest: Digest,
+    // "Hit", "Miss", "Sunk(size)" or BLINDED
+    pub report: String,
+    pub pos: Coord,
+    // Fleet whose shot the report answers
+    pub shooter: Option<String>,
+}
+
+impl BoardMove {
+    // Outcome of the report, None if it was blinded
+    pub fn outcome(&self) -> Option<ShotOutcome> {
+        self.report.parse().ok()
+    }
+}
+
+// A move as published in the archive
+#[derive(Clone, Serialize)]
+pub struct MoveView {
+    index: u32,
+    digest: String,
+    report: String,
+    pos: String,
+}
+
+impl From<&BoardMove> for MoveView {
+    fn from(step: &BoardMove) -> Self {
+        MoveView {
+            index: step.index,
+            digest: step.digest.to_string(),
+            report: step.report.clone(),
+            pos: step.pos.to_string(),
+        }
+    }
+}
+
+// Append-only history of a player's board
+#[derive(Clone)]
+pub struct BoardHistory {
+    // Board digest committed when joining
+    initial: Digest,
+    moves: Vec<BoardMove>,
+}
+
+impl BoardHistory {
+    pub fn new(initial: Digest) -> Self {
+        BoardHistory { initial, moves: Vec::new() }
+    }
+
+    // Record a report and the board digest it led to
+    pub fn record(&mut self, digest: &Digest, report: &str, pos: Coord, shooter: Option<&str>) {
+        self.moves.push(BoardMove {
+            index: self.moves.len() as u32,
+            digest: *digest,
+            report: report.to_string(),
+            pos,
+            shooter: shooter.map(String::from),
+        });
+    }
+
+    pub fn initial(&self) -> Digest {
+        self.initial
+    }
+
+    pub fn moves(&self) -> &[BoardMove] {
+        &self.moves
+    }
+
+    // Squares hit by the plain reports so far, only by the shots of `shooter` if given
+    pub fn hit_squares(&self, shooter: Option<&str>) -> HashSet<Coord> {
+        self.moves
+            .iter()
+            .filter(|step| step.outcome().is_some_and(ShotOutcome::is_hit))
+            .filter(|step| shooter.is_none() || step.shooter.as_deref() == shooter)
+            .map(|step| step.pos)
+            .collect()
+    }
+
+    // Check if `hits` are the squares hit by the plain reports so far
+    pub fn same_hits(&self, hits: &[Coord]) -> bool {
+        let recorded = self.hit_squares(None);
+        hits.len() == recorded.len() && hits.iter().all(|hit| recorded.contains(hit))
+    }
+
+    // Replay a revealed board through every recorded move, checking each board digest and each
+    // plain report along the way. Blinded reports never change the board.
+    pub fn replay(&self, mut board: Board, random: &str) -> Result<(), String> {
+        if board_digest(board, random) != self.initial {
+            return Err("Board does not match the committed board".to_string());
+        }
+
+        for step in &self.moves {
+            let hit = board.contains(step.pos);
+            match step.outcome().map(ShotOutcome::is_hit) {
+                Some(true) if !hit => return Err(format!("Report {} at {} was a miss", step.index, step.pos)),
+                Some(false) if hit => return Err(format!("Report {} at {} was a hit", step.index, step.pos)),
+                Some(true) => board = board.without(step.pos),
+                _ => {}
+            }
+            if board_digest(board, random) != step.digest {
+                return Err(format!("Board does not match its digest after report {}", step.index));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+// src/hosts.rs
+//
+// Authentication of the hosts submitting transactions. When `host_tokens` is set in the chain
+// configuration, /chain and the routes of the signed game messages (ready, chat, annotations,
+// reveal) only accept requests carrying the bearer token of one of the listed hosts, and so does
+// SubmitReceipt on the gRPC service. Reading the chain stays open to anyone.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use fleetconfig::config;
+use tracing::warn;
+
+use crate::admin::token_matches;
+use crate::error::ChainError;
+
+// Name of the host whose token was presented, if any. Every token is compared, so the time taken
+// does not tell which host a token is close to.
+fn host_of(token: &str) -> Option<&'static str> {
+    config()
+        .chain
+        .host_tokens
+        .iter()
+        .fold(None, |found, (name, expected)| {
+            if token_matches(token, expected) {
+                Some(name.as_str())
+            } else {
+                found
+            }
+        })
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+// Check a token presented to submit transactions, when hosts have to authenticate
+pub fn check_token(token: Option<&str>) -> Result<(), ChainError> {
+    if config().chain.host_tokens.is_empty() {
+        return Ok(());
+    }
+    match token.and_then(host_of) {
+        Some(_) => Ok(()),
+        None => Err(ChainError::Unauthorized("Invalid host token".to_string())),
+    }
+}
+
+// Middleware of the routes adding transactions to the chain
+pub async fn require_host(request: Request, next: Next) -> Response {
+    match check_token(bearer(request.headers())) {
+        Ok(()) => next.run(request).await,
+        Err(e) => {
+            warn!("Unauthorized host request to {}", request.uri().path());
+            e.into_response()
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// src/idempotency.rs
+//
+// Idempotency keys for /chain. A host that lost the answer to a transaction cannot tell whether
+// the chain applied it, and resending it would be refused as a replay once it was. Sent with an
+// `Idempotency-Key` header, a transaction is remembered per game for ten minutes with the response
+// it got, and a retry with the same key gets that response again instead of being applied twice.
+// A key is bound to the journal it was first sent with; reusing it for another transaction is
+// refused, and so is a retry arriving while the first attempt is still being applied. Keys are only
+// taken for receipts that verified, and failed transactions are not remembered, as they changed
+// nothing and can simply be retried; a game is forgotten with its last key.
+
+use axum::http::{HeaderMap, HeaderValue};
+use fleetcore::Digest;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::error::ChainError;
+
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+// Seconds a key is remembered
+const KEY_TTL_SECONDS: u64 = 10 * 60;
+// Keys remembered for a game, the oldest is dropped beyond
+const MAX_KEYS_PER_GAME: usize = 1024;
+const MAX_KEY_LEN: usize = 128;
+
+enum Outcome {
+    // Still being applied
+    Pending,
+    Done { headers: HeaderMap, body: String },
+}
+
+struct Entry {
+    // Journal digest of the transaction the key was sent with
+    digest: Digest,
+    at: u64,
+    outcome: Outcome,
+}
+
+#[derive(Default)]
+pub struct Idempotency {
+    games: Mutex<HashMap<String, HashMap<String, Entry>>>,
+}
+
+// What to do with a transaction sent with a key
+pub enum Begin<'a> {
+    // First time the key is seen: apply the transaction, then complete the key
+    Fresh(Pending<'a>),
+    // The transaction was already applied: answer with its response
+    Replay(HeaderMap, String),
+}
+
+// Key of a transaction being applied, forgotten if the transaction fails
+pub struct Pending<'a> {
+    idempotency: &'a Idempotency,
+    gameid: String,
+    key: String,
+    completed: bool,
+}
+
+impl Pending<'_> {
+    // Remember the response of the applied transaction
+    pub fn complete(mut self, headers: &HeaderMap, body: &str) {
+        if let Some(entry) = self.idempotency.games().get_mut(&self.gameid).and_then(|keys| keys.get_mut(&self.key)) {
+            entry.outcome = Outcome::Done { headers: headers.clone(), body: body.to_string() };
+        }
+        self.completed = true;
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            let mut games = self.idempotency.games();
+            if let Some(keys) = games.get_mut(&self.gameid) {
+                keys.remove(&self.key);
+                if keys.is_empty() {
+                    games.remove(&self.gameid);
+                }
+            }
+        }
+    }
+}
+
+impl Idempotency {
+    fn games(&self) -> MutexGuard<'_, HashMap<String, HashMap<String, Entry>>> {
+        self.games.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn begin(&self, gameid: &str, key: &str, digest: Digest, now: u64) -> Result<Begin<'_>, ChainError> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(ChainError::Malformed(format!("Idempotency keys must be 1 to {} characters", MAX_KEY_LEN)));
+        }
+        let mut games = self.games();
+        let keys = games.entry(gameid.to_string()).or_default();
+        keys.retain(|_, entry| matches!(entry.outcome, Outcome::Pending) || now.saturating_sub(entry.at) < KEY_TTL_SECONDS);
+
+        if let Some(entry) = keys.get(key) {
+            if entry.digest != digest {
+                return Err(ChainError::Rejected("Idempotency key already used for another transaction".to_string()));
+            }
+            return match &entry.outcome {
+                Outcome::Pending => Err(ChainError::Rejected(
+                    "A transaction with this idempotency key is being applied, retry later".to_string(),
+                )),
+                Outcome::Done { headers, body } => {
+                    let mut headers = headers.clone();
+                    headers.insert("idempotent-replayed", HeaderValue::from_static("true"));
+                    Ok(Begin::Replay(headers, body.clone()))
+                }
+            };
+        }
+
+        if keys.len() >= MAX_KEYS_PER_GAME {
+            let oldest = keys
+                .iter()
+                .filter(|(_, entry)| matches!(entry.outcome, Outcome::Done { .. }))
+                .min_by_key(|(_, entry)| entry.at)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => {
+                    keys.remove(&oldest);
+                }
+                None => return Err(ChainError::Rejected("Too many transactions being applied to this game".to_string())),
+            }
+        }
+        keys.insert(key.to_string(), Entry { digest, at: now, outcome: Outcome::Pending });
+        Ok(Begin::Fresh(Pending {
+            idempotency: self,
+            gameid: gameid.to_string(),
+            key: key.to_string(),
+            completed: false,
+        }))
+    }
+
+    pub fn forget_game(&self, gameid: &str) {
+        self.games().remove(gameid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "game";
+
+    fn digest(n: u8) -> Digest {
+        Digest::from([n; 32])
+    }
+
+    // Apply a transaction under `key` and remember `body` as its response
+    fn apply(idempotency: &Idempotency, key: &str, now: u64, body: &str) {
+        match idempotency.begin(GAME, key, digest(1), now).unwrap() {
+            Begin::Fresh(pending) => pending.complete(&HeaderMap::new(), body),
+            Begin::Replay(..) => panic!("{} was already applied", key),
+        }
+    }
+
+    fn replayed(idempotency: &Idempotency, key: &str, now: u64) -> Option<String> {
+        match idempotency.begin(GAME, key, digest(1), now).unwrap() {
+            Begin::Replay(headers, body) => {
+                assert_eq!(headers["idempotent-replayed"], "true");
+                Some(body)
+            }
+            Begin::Fresh(_) => None,
+        }
+    }
+
+    #[test]
+    fn a_retry_gets_the_first_response_again() {
+        let idempotency = Idempotency::default();
+        apply(&idempotency, "k", 0, "applied");
+        assert_eq!(replayed(&idempotency, "k", 1).as_deref(), Some("applied"));
+        assert_eq!(replayed(&idempotency, "k", 2).as_deref(), Some("applied"));
+    }
+
+    #[test]
+    fn a_key_is_bound_to_its_journal() {
+        let idempotency = Idempotency::default();
+        apply(&idempotency, "k", 0, "applied");
+        let result = idempotency.begin(GAME, "k", digest(2), 1);
+        assert!(matches!(result, Err(ChainError::Rejected(message)) if message.contains("another transaction")));
+    }
+
+    #[test]
+    fn a_retry_waits_for_the_first_attempt() {
+        let idempotency = Idempotency::default();
+        let first = idempotency.begin(GAME, "k", digest(1), 0).unwrap();
+        let result = idempotency.begin(GAME, "k", digest(1), 1);
+        assert!(matches!(result, Err(ChainError::Rejected(message)) if message.contains("being applied")));
+        drop(first);
+        // The first attempt failed, so the retry is applied
+        assert_eq!(replayed(&idempotency, "k", 2), None);
+    }
+
+    #[test]
+    fn a_game_is_forgotten_with_its_last_failed_key() {
+        let idempotency = Idempotency::default();
+        drop(idempotency.begin(GAME, "k", digest(1), 0).unwrap());
+        assert!(idempotency.games().is_empty());
+    }
+
+    #[test]
+    fn keys_expire_after_their_ttl() {
+        let idempotency = Idempotency::default();
+        apply(&idempotency, "k", 0, "applied");
+        assert!(replayed(&idempotency, "k", KEY_TTL_SECONDS - 1).is_some());
+        assert_eq!(replayed(&idempotency, "k", KEY_TTL_SECONDS), None);
+    }
+
+    #[test]
+    fn the_oldest_key_makes_room_for_a_new_one() {
+        let idempotency = Idempotency::default();
+        apply(&idempotency, "0", 0, "applied");
+        for n in 1..MAX_KEYS_PER_GAME {
+            apply(&idempotency, &n.to_string(), 1, "applied");
+        }
+        apply(&idempotency, "new", 2, "applied");
+        assert_eq!(idempotency.games()[GAME].len(), MAX_KEYS_PER_GAME);
+        assert!(replayed(&idempotency, "1", 3).is_some());
+        assert_eq!(replayed(&idempotency, "0", 3), None);
+    }
+
+    #[test]
+    fn keys_being_applied_are_never_dropped() {
+        let idempotency = Idempotency::default();
+        let pending: Vec<Begin> =
+            (0..MAX_KEYS_PER_GAME).map(|n| idempotency.begin(GAME, &n.to_string(), digest(1), 0).unwrap()).collect();
+        let result = idempotency.begin(GAME, "new", digest(1), 1);
+        assert!(matches!(result, Err(ChainError::Rejected(message)) if message.contains("Too many")));
+        drop(pending);
+        assert!(idempotency.games().is_empty());
+    }
+
+    #[test]
+    fn keys_must_be_short_and_not_empty() {
+        let idempotency = Idempotency::default();
+        assert!(matches!(idempotency.begin(GAME, "", digest(1), 0), Err(ChainError::Malformed(_))));
+        let long = "k".repeat(MAX_KEY_LEN + 1);
+        assert!(matches!(idempotency.begin(GAME, &long, digest(1), 0), Err(ChainError::Malformed(_))));
+    }
+}
@@ -0,0 +1,119 @@
+// src/identity.rs
+//
+// Registry of the players' verifying keys. A player registers a long-lived key with a Register
+// transaction, either for one game or for every game (`fleetcore::ANY_GAME`), and can then join
+// without sending a key. Every handler checks signatures against the registered key, falling back
+// to the key a player sent when joining without a registration (hosts that predate Register).
+//
+// A registration is never replaced, and never contradicts the key a player already plays with, so
+// the key checked for a player stays the same for the whole game.
+
+use ed25519_dalek::VerifyingKey;
+use fleetcore::{CommunicationData, RegisterJournal, ANY_GAME};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use tracing::warn;
+
+use crate::challenge::{check_challenge, redeem_challenge};
+use crate::error::ChainError;
+use crate::keys::{parse_verifying_key, verify_receipt};
+use crate::{record_player, Player, SharedData};
+
+#[derive(Default)]
+pub struct Identities {
+    // Registered key of each (game or ANY_GAME, fleet)
+    keys: Mutex<HashMap<(String, String), VerifyingKey>>,
+}
+
+impl Identities {
+    // Key registered for a player in a game, or for every game
+    pub fn key(&self, gameid: &str, fleet: &str) -> Option<VerifyingKey> {
+        let keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.get(&(gameid.to_string(), fleet.to_string()))
+            .or_else(|| keys.get(&(ANY_GAME.to_string(), fleet.to_string())))
+            .copied()
+    }
+
+    // Key the messages of a player already in a game must be signed with
+    pub fn player_key(&self, gameid: &str, player: &Player) -> VerifyingKey {
+        self.key(gameid, &player.name).unwrap_or(player.verifying_key)
+    }
+
+    // Register a player's key for a single game, unless the player already registered one for it
+    pub fn carry(&self, gameid: &str, fleet: &str, key: VerifyingKey) {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.entry((gameid.to_string(), fleet.to_string())).or_insert(key);
+    }
+
+    // Drop the registrations made for a single game once it is over
+    pub fn forget_game(&self, gameid: &str) {
+        let mut keys = self.keys.lock().unwrap_or_else(PoisonError::into_inner);
+        keys.retain(|(game, _), _| game != gameid);
+    }
+}
+
+pub fn handle_register(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: RegisterJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+
+    let key = match parse_verifying_key(&data.public_key) {
+        Ok(key) => key,
+        Err(err) => {
+            warn!("Invalid verifying key in register request: {}", err);
+            return Err(err);
+        }
+    };
+
+    // The signature shows that the player holds the registered key
+    if let Err(err) = verify_receipt(&key, input_data, &data.gameid) {
+        warn!("Invalid signature in register request");
+        return Err(err);
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Lock the games before the registry, as the handlers do
+    let gmap = shared.games();
+    let mut keys = shared.identities.keys.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // Check if the fleet already registered a key for this scope
+    let scope = (data.gameid.clone(), data.fleet.clone());
+    if let Some(registered) = keys.get(&scope) {
+        if *registered == key {
+            redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+            return Ok("Already registered".to_string());
+        }
+        warn!("Fleet {} already registered another key for {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Fleet already registered with another key".to_string()));
+    }
+
+    // Check if the fleet does not play a game covered by the registration with another key
+    let conflict = gmap.iter().any(|(gameid, game)| {
+        let covered = if data.gameid == ANY_GAME {
+            !keys.contains_key(&(gameid.clone(), data.fleet.clone()))
+        } else {
+            *gameid == data.gameid
+        };
+        covered && game.pmap.get(&data.fleet).is_some_and(|player| player.verifying_key != key)
+    });
+    if conflict {
+        warn!("Fleet {} already plays with another key", data.fleet);
+        return Err(ChainError::Rejected("Fleet already plays with another key".to_string()));
+    }
+
+    // Every check passed: use up the challenge of the receipt
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    keys.insert(scope, key);
+    drop(keys);
+    drop(gmap);
+
+    if data.gameid == ANY_GAME {
+        shared.event(format!("{} registered a key for all games", data.fleet));
+    } else {
+        shared.event(format!("{} registered a key for game {}", data.fleet, data.gameid));
+    }
+    Ok("OK".to_string())
+}
@@ -0,0 +1,38 @@
+// src/ids.rs
+//
+// Identifier policy of the chain. Game IDs and fleet names end up in pages, logs and event streams,
+// so the chain only takes the ones the guests accept (see fleetcore's inputs module): in the path
+// of every route, through a route layer, in the journals of the transactions, and in the fleet of
+// the signed game messages, checked by their handlers. A refused identifier is answered with
+// `400 Bad Request` and a JSON body naming the field.
+
+use axum::{
+    extract::{RawPathParams, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use fleetcore::check_id;
+use tracing::warn;
+
+use crate::error::ChainError;
+
+// Route layer refusing a `:gameid` or `:fleet` path parameter outside the limits
+pub async fn check_path_ids(params: RawPathParams, request: Request, next: Next) -> Response {
+    for (name, value) in &params {
+        let field = match name {
+            "gameid" => "game ID",
+            "fleet" => "fleet",
+            _ => continue,
+        };
+        if let Err(err) = check_id(field, value) {
+            warn!("Refused a request with an invalid {}: {}", field, err);
+            return ChainError::InvalidId(err).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+// The fleet named in the body of a signed game message
+pub fn check_fleet(fleet: &str) -> Result<(), ChainError> {
+    check_id("fleet", fleet).map_err(ChainError::InvalidId)
+}
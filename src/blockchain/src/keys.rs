@@ -0,0 +1,82 @@
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH, SIGNATURE_LENGTH};
+use rand::RngCore;
+use std::io::Write;
+use std::path::Path;
+
+use fleetcore::{verify_canonical, CommunicationData, ReceiptStatement, RECEIPT_DOMAIN};
+
+use crate::error::ChainError;
+
+// Parse a verifying key sent by a client. Keys of the wrong length, bytes that are not a valid
+// curve point, and weak (small-order) keys that would accept forged signatures are rejected.
+pub fn parse_verifying_key(bytes: &[u8]) -> Result<VerifyingKey, ChainError> {
+    let bytes: &[u8; PUBLIC_KEY_LENGTH] = bytes.try_into().map_err(|_| {
+        ChainError::Malformed(format!(
+            "Verifying key must be {} bytes, got {}",
+            PUBLIC_KEY_LENGTH,
+            bytes.len()
+        ))
+    })?;
+
+    let key = VerifyingKey::from_bytes(bytes)
+        .map_err(|_| ChainError::Malformed("Verifying key is not a valid Ed25519 point".to_string()))?;
+
+    if key.is_weak() {
+        return Err(ChainError::Malformed("Verifying key is a weak key".to_string()));
+    }
+    Ok(key)
+}
+
+// Parse a signature sent by a client, checking its length before conversion
+pub fn parse_signature(bytes: &[u8]) -> Result<Signature, ChainError> {
+    let bytes: &[u8; SIGNATURE_LENGTH] = bytes.try_into().map_err(|_| {
+        ChainError::Malformed(format!(
+            "Signature must be {} bytes, got {}",
+            SIGNATURE_LENGTH,
+            bytes.len()
+        ))
+    })?;
+    Ok(Signature::from_bytes(bytes))
+}
+
+// Check the signature of a receipt sent for `gameid`. It covers the command and the nonce along
+// with the journal, so a journal signed for one command or game is rejected in another.
+pub fn verify_receipt(key: &VerifyingKey, input_data: &CommunicationData, gameid: &str) -> Result<(), ChainError> {
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+    let statement = ReceiptStatement::new(input_data.cmd, gameid, input_data.nonce, &input_data.receipt.journal.bytes);
+    if !verify_canonical(key, RECEIPT_DOMAIN, &statement, &input_data.signature) {
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+    Ok(())
+}
+
+// Load a signing key of the chain (a hex-encoded seed), creating it on first start. The genesis key
+// signs game certificates and the response key the node's responses, so both must stay the same
+// for the lifetime of the chain.
+pub fn load_signing_key(path: &Path) -> std::io::Result<SigningKey> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            let seed: [u8; SECRET_KEY_LENGTH] = hex::decode(text.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "signing key must be 32 hex-encoded bytes"))?;
+            Ok(SigningKey::from_bytes(&seed))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut seed = [0u8; SECRET_KEY_LENGTH];
+            rand::rngs::OsRng.fill_bytes(&mut seed);
+
+            // Only the chain may read the key
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(path)?;
+            file.write_all(hex::encode(seed).as_bytes())?;
+            file.sync_all()?;
+            Ok(SigningKey::from_bytes(&seed))
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,167 @@
+// src/leaderboard.rs
+//
+// Statistics of each fleet across games. When a game that started ends, every player's result is
+// added to the fleet's totals: games, wins and losses, shots fired and how many of them hit, and
+// time played. The totals are kept in `leaderboard_file` in the storage directory, so they survive
+// restarts of the chain, and served on /leaderboard and /players/{fleet}/stats.
+//
+// Fleets are identified by name, like everywhere else on the chain. Blinded reports do not tell
+// the chain whether a shot hit, so hits only count plain reports.
+
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tracing::{error, warn};
+
+use crate::error::ChainError;
+use crate::SharedData;
+
+// Fleets listed by default on /leaderboard
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct PlayerStats {
+    games: u32,
+    wins: u32,
+    losses: u32,
+    shots_fired: u32,
+    hits: u32,
+    // Time from the start to the end of the fleet's games, summed
+    seconds_played: u64,
+}
+
+// Result of a fleet in a game that ended
+pub struct GameResult {
+    pub fleet: String,
+    // None when the game ended without a winner
+    pub won: Option<bool>,
+    pub shots_fired: u32,
+    pub hits: u32,
+    pub seconds: u64,
+}
+
+#[derive(Serialize)]
+pub struct StatsView {
+    fleet: String,
+    games: u32,
+    wins: u32,
+    losses: u32,
+    shots_fired: u32,
+    hits: u32,
+    // Hits per shot fired, 0 before the first shot
+    hit_rate: f64,
+    average_game_seconds: u64,
+}
+
+impl StatsView {
+    fn new(fleet: &str, stats: &PlayerStats) -> Self {
+        StatsView {
+            fleet: fleet.to_string(),
+            games: stats.games,
+            wins: stats.wins,
+            losses: stats.losses,
+            shots_fired: stats.shots_fired,
+            hits: stats.hits,
+            hit_rate: if stats.shots_fired == 0 { 0.0 } else { stats.hits as f64 / stats.shots_fired as f64 },
+            average_game_seconds: stats.seconds_played / stats.games.max(1) as u64,
+        }
+    }
+}
+
+pub struct Leaderboard {
+    path: PathBuf,
+    stats: Mutex<HashMap<String, PlayerStats>>,
+}
+
+impl Leaderboard {
+    // Read the totals kept by a previous run. Unreadable totals are reported and started over.
+    pub fn load(path: PathBuf) -> Self {
+        let stats = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring leaderboard {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Could not read leaderboard {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+        Leaderboard { path, stats: Mutex::new(stats) }
+    }
+
+    fn stats(&self) -> MutexGuard<'_, HashMap<String, PlayerStats>> {
+        self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn save(&self, stats: &HashMap<String, PlayerStats>) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(stats)?)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+
+    // Games won by a fleet, 0 before its first game
+    pub fn wins(&self, fleet: &str) -> u32 {
+        self.stats().get(fleet).map_or(0, |stats| stats.wins)
+    }
+
+    // Add the results of a game to the totals of its fleets
+    pub fn record_game(&self, results: Vec<GameResult>) {
+        let mut stats = self.stats();
+        for result in results {
+            let totals = stats.entry(result.fleet).or_default();
+            totals.games += 1;
+            match result.won {
+                Some(true) => totals.wins += 1,
+                Some(false) => totals.losses += 1,
+                None => {}
+            }
+            totals.shots_fired += result.shots_fired;
+            totals.hits += result.hits;
+            totals.seconds_played += result.seconds;
+        }
+        if let Err(e) = self.save(&stats) {
+            error!("Could not save leaderboard {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    limit: Option<usize>,
+}
+
+// Fleets ranked by wins, then by hit rate
+pub async fn leaderboard_handler(
+    Extension(shared): Extension<SharedData>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Json<Vec<StatsView>> {
+    let mut ranking: Vec<StatsView> = shared.leaderboard
+        .stats()
+        .iter()
+        .map(|(fleet, stats)| StatsView::new(fleet, stats))
+        .collect();
+    ranking.sort_by(|a, b| {
+        b.wins
+            .cmp(&a.wins)
+            .then(b.hit_rate.total_cmp(&a.hit_rate))
+            .then(a.fleet.cmp(&b.fleet))
+    });
+    ranking.truncate(query.limit.unwrap_or(DEFAULT_LIMIT));
+    Json(ranking)
+}
+
+pub async fn player_stats_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(fleet): Path<String>,
+) -> Result<Json<StatsView>, ChainError> {
+    match shared.leaderboard.stats().get(&fleet) {
+        Some(stats) => Ok(Json(StatsView::new(&fleet, stats))),
+        None => Err(ChainError::NotFound("No finished game for this fleet".to_string())),
+    }
+}
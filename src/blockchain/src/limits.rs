@@ -0,0 +1,122 @@
+// src/limits.rs
+//
+// Flood protection for /chain. Requests are rate limited per client IP before their body is read
+// and per fleet once the receipt has been verified, with token buckets refilled continuously, and
+// bodies larger than the configured cap are refused without being read. Journals naming a game or
+// fleet outside the limits of fleetcore's inputs module are refused before their receipt is verified.
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use fleetcore::{check_id, check_register_gameid, Command, InputError};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+use tracing::warn;
+
+use crate::error::ChainError;
+use crate::SharedData;
+
+// Number of clients tracked before the buckets that are full again are dropped
+const MAX_TRACKED: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter<K> {
+    per_minute: u32,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    // Allow `per_minute` requests per key, in bursts of up to as many (0 for no limit)
+    pub fn new(per_minute: u32) -> Self {
+        RateLimiter { per_minute, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    // Take a token for `key`, or return the number of seconds until one is available
+    pub fn check(&self, key: K) -> Result<(), u64> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() >= MAX_TRACKED {
+            buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second < capacity);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(((1.0 - bucket.tokens) / per_second).ceil() as u64);
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+pub struct Limits {
+    pub max_body_bytes: usize,
+    pub per_ip: RateLimiter<IpAddr>,
+    pub per_fleet: RateLimiter<(String, String)>,
+}
+
+// Leading fields shared by every journal, enough to know who a transaction is for
+#[derive(Deserialize)]
+pub struct JournalHead {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+}
+
+impl JournalHead {
+    // The limits the guests check their inputs against, for journals proven by a guest that did
+    // not check them. Registrations can be for every game.
+    pub fn check(&self, cmd: Command) -> Result<(), InputError> {
+        match cmd {
+            Command::Register => check_register_gameid(&self.gameid)?,
+            _ => check_id("game ID", &self.gameid)?,
+        }
+        check_id("fleet", &self.fleet)
+    }
+}
+
+// Middleware of /chain and /challenge: per-IP rate limit and body size cap, checked before the
+// body is read
+pub async fn limit_chain_requests(
+    Extension(shared): Extension<SharedData>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(retry_after) = shared.limits.per_ip.check(addr.ip()) {
+        warn!("Rate limited transactions from {}", addr.ip());
+        return ChainError::RateLimited("Too many transactions, slow down".to_string(), retry_after).into_response();
+    }
+
+    // Bodies without a length are cut off at the cap while they are read
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+    if let Some(length) = length.filter(|length| *length > shared.limits.max_body_bytes) {
+        warn!("Refused a {} byte transaction from {}", length, addr.ip());
+        return ChainError::TooLarge(format!("Transactions are limited to {} bytes", shared.limits.max_body_bytes))
+            .into_response();
+    }
+
+    next.run(request).await
+}
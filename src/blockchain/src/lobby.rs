@@ -0,0 +1,184 @@
+// src/lobby.rs
+//
+// Lobby of a game. Players can join until the game starts, which happens once every player that
+// joined has sent a signed Ready, or when the creator of the game starts it, provided the game has
+// its minimum number of players. Nobody can fire before that.
+//
+// When the game starts, the turn order is derived from the players' join journals (see
+// fleetcore::TurnOrder) and published on /games/{gameid}/turn-order so clients can check it.
+//
+// A game created by a private join only lets in the fleets that present an invite signed by the
+// creator's key (see fleetcore::SignedInvite).
+
+use axum::{extract::Path, http::HeaderMap, Extension, Json};
+use fleetcore::{
+    verify_canonical, JoinDigest, ReadyData, ReadyStatement, SignedInvite, TurnOrder, READY_DOMAIN,
+};
+use tracing::{info_span, warn};
+
+use crate::correlation;
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::keys::parse_signature;
+use crate::replication::Transaction;
+use crate::{next_request_id, now_secs, Game, SharedData};
+
+// Check the invite a fleet joining a private game presents
+pub fn check_invite(
+    shared: &SharedData,
+    gameid: &str,
+    game: &Game,
+    fleet: &str,
+    code: Option<&str>,
+) -> Result<(), ChainError> {
+    let invite = match code.map(SignedInvite::from_code) {
+        Some(Some(invite)) => invite,
+        Some(None) => return Err(ChainError::Malformed("Invalid invite code".to_string())),
+        None => {
+            warn!("{} tried to join private game {} without an invite", fleet, gameid);
+            return Err(ChainError::Unauthorized("This game is private, an invite is needed to join".to_string()));
+        }
+    };
+
+    // Invites are signed with the key the creator plays with
+    let creator_key = match game.pmap.get(&game.creator) {
+        Some(creator) => Some(shared.identities.player_key(gameid, creator)),
+        None => shared.identities.key(gameid, &game.creator),
+    };
+    parse_signature(&invite.signature)?;
+    if !creator_key.is_some_and(|key| invite.verify(&key)) {
+        warn!("Invite of {} to game {} is not signed by the creator", fleet, gameid);
+        return Err(ChainError::Unauthorized("Invite is not signed by the creator of the game".to_string()));
+    }
+
+    if !invite.admits(gameid, fleet, now_secs()) {
+        warn!("Invite of {} to game {} does not admit it", fleet, gameid);
+        return Err(ChainError::Unauthorized("Invite is expired or for another game or fleet".to_string()));
+    }
+    Ok(())
+}
+
+pub fn handle_ready(shared: &SharedData, gameid: &str, input_data: &ReadyData) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&input_data.fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the statement
+    let statement = ReadyStatement { gameid, fleet: &input_data.fleet, start: input_data.start };
+    if !verify_canonical(&shared.identities.player_key(gameid, player), READY_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in ready request for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // Check if the game is still in its lobby
+    if game.started {
+        return Err(ChainError::Rejected("The game has already started".to_string()));
+    }
+
+    // Only the creator can start the game without waiting for everyone
+    if input_data.start && game.creator != input_data.fleet {
+        warn!("{} tried to start game {} created by {}", input_data.fleet, gameid, game.creator);
+        return Err(ChainError::Rejected("Only the creator of the game can start it".to_string()));
+    }
+
+    if !player.ready {
+        player.ready = true;
+        let ready = game.pmap.values().filter(|player| player.ready).count();
+        shared.game_event(gameid, format!(
+            "{} is ready in game {} ({} of {} players ready)",
+            input_data.fleet,
+            gameid,
+            ready,
+            game.pmap.len()
+        ));
+    }
+
+    // Start the game once everyone is ready, or when the creator says so, if enough players joined
+    if !(input_data.start || game.pmap.values().all(|player| player.ready)) {
+        game.last_activity = now_secs();
+        return Ok("OK".to_string());
+    }
+    if game.pmap.len() < game.min_players {
+        shared.game_event(gameid, format!(
+            "Game {} cannot start yet: {} players are needed, {} have joined",
+            gameid,
+            game.min_players,
+            game.pmap.len()
+        ));
+        game.last_activity = now_secs();
+        return Ok("Waiting for more players".to_string());
+    }
+    if game.teams && game.sides_left().len() < 2 {
+        shared.game_event(gameid, format!("Game {} cannot start yet: every player is in the same team", gameid));
+        game.last_activity = now_secs();
+        return Ok("Waiting for another team".to_string());
+    }
+
+    // Draw the turn order from the join journals, which no single party controls
+    let joins = game.pmap
+        .iter()
+        .map(|(fleet, player)| JoinDigest { fleet: fleet.clone(), digest: player.join_digest })
+        .collect();
+    let turn_order = TurnOrder::derive(gameid, joins);
+    game.order = turn_order.order.clone();
+    game.next_player = turn_order.order.first().cloned();
+
+    game.started = true;
+    game.started_at = now_secs();
+    game.turn_started_at = game.started_at;
+    game.last_activity = game.turn_started_at;
+    shared.game_event(gameid, format!(
+        "Game {} started with {} players, turn order {} (seed {})",
+        gameid,
+        game.pmap.len(),
+        turn_order.order.join(", "),
+        turn_order.seed
+    ));
+    game.turn_order = Some(turn_order);
+    Ok("Game started".to_string())
+}
+
+// Turn order of a started game, with the join digests it was derived from
+pub async fn turn_order_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<TurnOrder>, ChainError> {
+    let gmap = shared.games();
+    match gmap.get(&gameid).map(|game| &game.turn_order) {
+        Some(Some(turn_order)) => Ok(Json(turn_order.clone())),
+        Some(None) => Err(ChainError::NotFound("The game has not started yet".to_string())),
+        None => Err(ChainError::NotFound("Game not found".to_string())),
+    }
+}
+
+pub async fn ready_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    Json(input_data): Json<ReadyData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "ready",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Ready { gameid, data: input_data }).map(|(_, response)| response)
+}
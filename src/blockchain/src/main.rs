@@ -1,75 +1,606 @@
 use axum::{
-    extract::{Extension, Path},
-    response::{sse::Event, Html, IntoResponse},
+    extract::{DefaultBodyLimit, Extension, Path, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{sse::Event, Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use serde::Serialize;
-use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use futures::future;
+use futures::stream::{self, StreamExt};
 use rand::SeedableRng;
-use risc0_zkvm::Digest;
+use risc0_zkvm::{Digest, Receipt};
+use clap::Parser;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
-    net::SocketAddr,
-    sync::{Arc, Mutex},
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::sync::watch;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use metrics_exporter_prometheus::PrometheusHandle;
+use tracing::{error, info, info_span, warn, Span};
+use utoipa::{IntoParams, ToSchema};
+
+use fleetconfig::{config, TurnPolicy};
+use fleetcore::{
+    fleet_squares, journal_digest, verify_canonical, AnnotationData, AnnotationKind, AnnotationStatement, AppliedReceipt,
+    BaseJournal, ChatData, ChatStatement, Command, CommunicationData, FireJournal, ReportJournal, SignedCertificate,
+    TeamMessage, TurnOrder, UnsupportedVersion, WinBasis, WinJournal, ANNOTATION_DOMAIN, CHAT_DOMAIN, CORRELATION_HEADER,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
-use tokio::sync::broadcast;
-use tokio_stream::wrappers::BroadcastStream;
-use ed25519_dalek::{VerifyingKey, Verifier, Signature};
 
-use fleetcore::{BaseJournal, Command, FireJournal, CommunicationData, ReportJournal};
-use methods::{FIRE_ID, JOIN_ID, REPORT_ID, WAVE_ID, WIN_ID};
+mod admin;
+mod audit;
+mod blind;
+mod blocks;
+mod certificate;
+mod challenge;
+mod clocks;
+mod contest;
+mod coordination;
+mod correlation;
+mod error;
+mod events;
+mod export;
+mod explorer;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod grpc;
+mod heatmap;
+mod history;
+mod idempotency;
+mod hosts;
+mod identity;
+mod ids;
+mod keys;
+mod leaderboard;
+mod limits;
+mod lobby;
+mod matchmaking;
+mod monitoring;
+mod openapi;
+mod pause;
+mod rematch;
+mod replication;
+mod signing;
+mod spectators;
+mod state_root;
+mod status;
+mod storage;
+mod submissions;
+mod teams;
+mod timeline;
+mod timeouts;
+mod turns;
+mod versions;
+mod webhooks;
+use audit::{handle_audit, Audit};
+use blind::{handle_blind_report, handle_blind_win, handle_reveal, reveal_handler, BlindLedger, BlindRecord, RevealedReport};
+use blocks::{block_handler, describe, headers_handler, latest_handler, Blocks};
+use challenge::{challenge_handler, check_challenge, redeem_challenge, Challenges};
+use clocks::Clock;
+use contest::handle_contest;
+use coordination::{handle_team_message, team_channel_handler, team_message_handler};
+use error::ChainError;
+use events::{ChainEvent, EventLog, EventPage, Resync};
+use export::{export_handler, GameParameters};
+use heatmap::{board_handler, board_text_handler};
+use history::{BoardHistory, MoveView};
+use hosts::require_host;
+use idempotency::{Begin, Idempotency, IDEMPOTENCY_HEADER};
+use identity::{handle_register, Identities};
+use ids::{check_fleet, check_path_ids};
+use keys::{load_signing_key, parse_signature, parse_verifying_key, verify_receipt};
+use leaderboard::{leaderboard_handler, player_stats_handler, GameResult, Leaderboard};
+use limits::{limit_chain_requests, JournalHead, Limits, RateLimiter};
+use lobby::{check_invite, handle_ready, ready_handler, turn_order_handler};
+use matchmaking::{matchmake_handler, Matchmaking};
+use monitoring::{Rejection, SseClient};
+use pause::{check_paused, handle_pause, pause_handler};
+use rematch::{handle_rematch, rematch_handler, Rematches};
+use replication::{Replication, Transaction};
+use signing::{pubkey_handler, sign_responses};
+use spectators::{spectate_handler, spectator_stream, Spectators};
+use state_root::state_root_handler;
+use status::{handle_status, status_due, FleetStatus};
+use submissions::{tx_handler, Submissions, TxStatus, TX_ID_HEADER};
+use timeline::{timeline, Timeline, TIMELINE_JS};
+use timeouts::Timeout;
+use versions::{methods_handler, Accepted, GuestVersions};
+use webhooks::{delete_hook, hook_status, register_hook, Webhooks};
 
 struct Player {
     name: String,
     current_state: Digest,
+    history: BoardHistory, // every report applied to the board, for the reveal
     last_turn_timestamp: u64,
     has_claimed_victory: bool,
     verifying_key: VerifyingKey,
+    cycles_spent: u64,
+    blind: BlindLedger, // reports of a blinded game
+    forfeited: bool, // conceded, out of the turn rotation
+    ready: bool, // ready to start, in the lobby
+    chat_nonce: u64, // nonce of the last chat message
+    pause_nonce: u64, // nonce of the last pause or resume vote
+    team_nonce: u64, // nonce of the last team message
+    receipt_nonce: u64, // nonce of the last receipt
+    join_digest: Digest, // digest of the join journal, seeds the turn order
+    status: Option<FleetStatus>, // remaining fleet last proved by the player
+    clock: Option<Clock>, // time left to play, in games with clocks
+    shots_fired: u32,
+    hits: u32, // shots of the player reported as hits
+    team: Option<String>, // team of the player in a team game
+}
+
+impl Player {
+    // Player who just joined with the given board, with the chain's defaults
+    fn new(name: &str, board: Digest, verifying_key: VerifyingKey, now: u64) -> Player {
+        Player {
+            name: name.to_string(),
+            current_state: board,
+            history: BoardHistory::new(board),
+            last_turn_timestamp: now,
+            has_claimed_victory: false,
+            verifying_key,
+            cycles_spent: 0,
+            blind: BlindLedger::default(),
+            forfeited: false,
+            ready: false,
+            chat_nonce: 0,
+            pause_nonce: 0,
+            team_nonce: 0,
+            receipt_nonce: 0,
+            join_digest: Digest::default(),
+            status: None,
+            clock: (config().chain.clock_seconds > 0).then(|| Clock::new(config().chain.clock_seconds)),
+            shots_fired: 0,
+            hits: 0,
+            team: None,
+        }
+    }
 }
+
 struct Game {
     pmap: HashMap<String, Player>,
     next_player: Option<String>,
     next_report: Option<String>,
     first_victory_claim: Option<(String, u64)>, // (player_name, timestamp)
     victory_timeout_seconds: u64,
-    first_shot_fired: bool,
+    creator: String, // player who created the game by joining first
+    started: bool, // all players were ready or the creator started the game
+    turn_started_at: u64, // when the game started waiting for the current fire, report or wave
+    last_activity: u64, // when a transaction was last applied to the game
+    last_shooter: Option<String>,
+    cycle_budget: Option<u64>, // maximum cycles each player can spend in the game
+    blinded: bool, // reports only publish commitments to their outcome
+    min_players: usize, // players needed to fire
+    max_players: usize, // players the game accepts
+    turn_policy: TurnPolicy,
+    order: Vec<String>, // players in the order they joined, then in the turn order once started
+    turn_order: Option<TurnOrder>, // derivation of the turn order, set when the game starts
+    shots: u32, // shots fired so far
+    status_every: u32, // shots after which players must prove their remaining fleet (0 for never)
+    pending_shot: Option<Receipt>, // fire receipt awaiting its report, the assumption of that report
+    audit_required: bool, // the certificate is withheld until the winner audits its reports
+    started_at: u64, // when the game left the lobby
+    private: bool, // only fleets invited by the creator can join
+    teams: bool, // players play in teams
+    recent_journals: VecDeque<Digest>, // digests of the last receipts applied, the oldest first
+    clock_at: u64, // when the running clock was last charged
+    paused: bool, // paused by all its players, moves are refused
+    pause_votes: HashSet<String>, // players who voted to pause, or to resume a paused game
+    team_messages: VecDeque<TeamMessage>, // encrypted messages of the teams, the oldest first
+}
+
+// Maximum length (in characters) of a post-game annotation
+const ANNOTATION_MAX_LEN: usize = 280;
+// Maximum number of annotations a single player can attach to a game
+const ANNOTATION_MAX_PER_PLAYER: usize = 3;
+// Seconds after the end of a game during which players can still annotate it
+const ANNOTATION_WINDOW_SECONDS: u64 = 600;
+// Maximum length (in characters) of a chat message
+const CHAT_MAX_LEN: usize = 280;
+
+// Smallest cycle count any receipt can report (one minimal segment)
+const MIN_CYCLES: u64 = 1 << 13;
+
+// Largest cycle count a receipt for the given command can plausibly report
+fn max_cycles(cmd: Command) -> u64 {
+    match cmd {
+        Command::Join
+        | Command::Wave
+        | Command::Win
+        | Command::BlindWin
+        | Command::Forfeit
+        | Command::Register
+        | Command::Status
+        | Command::Contest => 1 << 21,
+        Command::Fire | Command::Report | Command::BlindReport | Command::Audit => 1 << 22,
+    }
+}
+
+// Bounds of the victory timeout a game can be created with
+const MIN_VICTORY_TIMEOUT_SECONDS: u64 = 10;
+const MAX_VICTORY_TIMEOUT_SECONDS: u64 = 600;
+
+// Victory timeout of a new game: the one asked for by its creator, within bounds, or the default
+fn victory_timeout(requested: Option<u64>) -> u64 {
+    requested
+        .unwrap_or(config().chain.victory_timeout_seconds)
+        .clamp(MIN_VICTORY_TIMEOUT_SECONDS, MAX_VICTORY_TIMEOUT_SECONDS)
+}
+
+impl Game {
+    // Game created by `creator` joining it, in the lobby, with the chain's defaults
+    fn new(creator: &str, now: u64, victory_timeout_seconds: u64, cycle_budget: Option<u64>) -> Game {
+        let chain = &config().chain;
+        Game {
+            pmap: HashMap::new(),
+            next_player: Some(creator.to_string()),
+            next_report: None,
+            first_victory_claim: None,
+            victory_timeout_seconds,
+            creator: creator.to_string(),
+            started: false,
+            turn_started_at: now,
+            last_activity: now,
+            last_shooter: None,
+            cycle_budget,
+            blinded: chain.blind_reports,
+            min_players: chain.min_players,
+            max_players: chain.max_players,
+            turn_policy: chain.turn_policy,
+            order: Vec::new(),
+            turn_order: None,
+            shots: 0,
+            status_every: chain.status_every_shots,
+            pending_shot: None,
+            audit_required: chain.audit_required,
+            started_at: 0,
+            private: false,
+            teams: false,
+            recent_journals: VecDeque::new(),
+            clock_at: now,
+            paused: false,
+            pause_votes: HashSet::new(),
+            team_messages: VecDeque::new(),
+        }
+    }
+
+    // Time the running victory claim's timeout expires
+    fn victory_deadline(&self) -> Option<u64> {
+        self.first_victory_claim.as_ref().map(|(_, claim_time)| claim_time + self.victory_timeout_seconds)
+    }
+}
+
+// Per-player cycle budget of new games, unlimited when the chain's `cycle_budget` is 0
+fn default_cycle_budget() -> Option<u64> {
+    Some(config().chain.cycle_budget).filter(|&budget| budget > 0)
+}
+
+#[derive(Clone, Serialize)]
+struct Annotation {
+    seq: usize, // number of the annotation in its game, from 1, used to moderate it
+    fleet: String,
+    kind: AnnotationKind,
+    text: String,
+    timestamp: u64,
+    hidden: bool,
+}
+
+// A finished game kept around so players can attach annotations and others can replay it
+struct ArchivedGame {
+    players: HashMap<String, VerifyingKey>,
+    winner: Option<String>,
+    ended_at: u64,
+    annotations: Vec<Annotation>,
+    annotation_nonces: HashMap<String, u64>, // nonce of the last annotation, per player
+    boards: HashMap<String, BoardHistory>, // per player
+    blind: HashMap<String, BlindRecord>, // per player, in blinded games
+    revealed: HashMap<String, Vec<RevealedReport>>, // outcomes of the boards revealed after the game
+    audits: HashMap<String, Audit>, // players who proved their reports after the game
+    audit_required: bool,
+    certificate: Option<SignedCertificate>,
+    parameters: GameParameters, // rules the game was played with
+    rematch: Option<String>, // game reserved for a rematch of this one
 }
 
 #[derive(Clone)]
 struct SharedData {
-    tx: broadcast::Sender<String>,
+    events: Arc<EventLog>,
     gmap: Arc<Mutex<HashMap<String, Game>>>,
-    _rng: Arc<Mutex<rand::rngs::StdRng>>,
+    archive: Arc<Mutex<HashMap<String, ArchivedGame>>>,
+    guests: Arc<GuestVersions>,
+    applied: Arc<watch::Sender<u64>>, // sequence number of the last applied transaction
+    metrics: PrometheusHandle,
+    genesis: Arc<SigningKey>, // signs game certificates
+    responses: Arc<SigningKey>, // signs the responses and events of this node
+    limits: Arc<Limits>,
+    rng: Arc<Mutex<rand::rngs::StdRng>>, // draws turns in games with the random turn policy, seeded by each transaction
+    challenges: Arc<Challenges>, // issued to players before proving, redeemed by their receipts
+    identities: Arc<Identities>, // keys registered by the players
+    replication: Arc<Replication>, // log of the applied transactions, shared with the peers
+    blocks: Arc<Blocks>, // numbers the applied transactions and batches them into blocks
+    leaderboard: Arc<Leaderboard>, // statistics of each fleet across games
+    matchmaking: Arc<Matchmaking>, // games opened by matchmaking and the seats assigned in them
+    spectators: Arc<Spectators>, // spectator tokens, and the spectators connected to each game
+    webhooks: Arc<Webhooks>, // callback URLs registered for each game
+    idempotency: Arc<Idempotency>, // responses of the recent transactions sent with an idempotency key
+    submissions: Arc<Submissions>, // status of the latest transactions submitted to this node
+    rematches: Arc<Rematches>, // games reserved for the players of an ended game
+}
+
+impl SharedData {
+    // State of a node that has not applied any transaction yet, with the limits of its config
+    fn new(genesis: SigningKey, responses: SigningKey, metrics: PrometheusHandle) -> SharedData {
+        let chain = &config().chain;
+        SharedData {
+            events: Arc::new(EventLog::new(responses.clone())),
+            gmap: Arc::new(Mutex::new(HashMap::new())),
+            archive: Arc::new(Mutex::new(HashMap::new())),
+            guests: Arc::new(GuestVersions::load()),
+            applied: Arc::new(watch::Sender::new(0)),
+            metrics,
+            genesis: Arc::new(genesis),
+            responses: Arc::new(responses),
+            limits: Arc::new(Limits {
+                max_body_bytes: chain.max_body_bytes,
+                per_ip: RateLimiter::new(chain.ip_requests_per_minute),
+                per_fleet: RateLimiter::new(chain.fleet_requests_per_minute),
+            }),
+            rng: Arc::new(Mutex::new(rand::rngs::StdRng::from_entropy())),
+            challenges: Arc::new(Challenges::new(chain.challenge_ttl_seconds)),
+            identities: Arc::new(Identities::default()),
+            replication: Arc::new(Replication::start()),
+            blocks: Arc::new(Blocks::default()),
+            leaderboard: Arc::new(Leaderboard::load(config().storage_file(&chain.leaderboard_file))),
+            matchmaking: Arc::new(Matchmaking::default()),
+            spectators: Arc::new(Spectators::default()),
+            webhooks: Arc::new(Webhooks::default()),
+            idempotency: Arc::new(Idempotency::default()),
+            submissions: Arc::new(Submissions::default()),
+            rematches: Arc::new(Rematches::default()),
+        }
+    }
+
+    // Publish an event to the players' event stream, and to the operator's log
+    fn event(&self, msg: String) {
+        info!("{}", msg);
+        self.events.publish(None, msg);
+    }
+
+    // Publish an event about a game, also kept in the game's event history
+    fn game_event(&self, gameid: &str, msg: String) {
+        info!("{}", msg);
+        self.events.publish(Some(gameid), msg);
+    }
+
+    // Lock the game map, recovering the data if a previous holder panicked
+    fn games(&self) -> MutexGuard<'_, HashMap<String, Game>> {
+        self.gmap.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn archived(&self) -> MutexGuard<'_, HashMap<String, ArchivedGame>> {
+        self.archive.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn rng(&self) -> MutexGuard<'_, rand::rngs::StdRng> {
+        self.rng.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Current UNIX time in seconds (0 if the clock is set before the epoch). While a transaction is
+// applied, the time the chain accepted it, so every replica applies it at the same time.
+fn now_secs() -> u64 {
+    if let Some(at) = replication::pinned_time() {
+        return at;
+    }
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Parser)]
+#[command(about = "Fleet blockchain emulator: verifies receipts and runs the games")]
+struct Args {
+    #[arg(long, help = "Port to listen on [default: 3001]")]
+    port: Option<u16>,
+    #[arg(long, help = "Port of the gRPC service [default: disabled]")]
+    grpc_port: Option<u16>,
+    #[arg(long, help = "Address to listen on [default: 0.0.0.0]")]
+    bind: Option<IpAddr>,
+    #[arg(long, help = "Accept fake receipts from hosts running in dev mode (RISC0_DEV_MODE)")]
+    dev_mode: bool,
+    #[arg(long, help = "Directory for the chain's data")]
+    storage_path: Option<PathBuf>,
+    #[arg(long, help = "Seconds other players have to contest a victory claim in new games [default: 30]")]
+    victory_timeout: Option<u64>,
+    #[arg(long, help = "Start new games with blinded reports (hits and misses hidden from the chain)")]
+    blind_reports: bool,
+    #[arg(long, help = "Name of this node among the replicas of the chain [default: chain0]")]
+    node_id: Option<String>,
+    #[arg(long, value_delimiter = ',', help = "Base URLs of the other replicas, comma-separated")]
+    peers: Vec<String>,
+    #[arg(long, help = "Replica to download the existing games from before serving")]
+    sync_from: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
-    // Create a broadcast channel for log messages
-    let (tx, _rx) = broadcast::channel::<String>(100);
-    let shared = SharedData {
-        tx: tx,
-        gmap: Arc::new(Mutex::new(HashMap::new())),
-        _rng: Arc::new(Mutex::new(rand::rngs::StdRng::from_entropy())),
+    // Command-line flags take precedence over fleet.toml and the environment
+    let args = Args::parse();
+    let config = fleetconfig::init(|config| {
+        if let Some(port) = args.port {
+            config.chain.port = port;
+        }
+        if let Some(grpc_port) = args.grpc_port {
+            config.chain.grpc_port = Some(grpc_port);
+        }
+        if let Some(bind) = args.bind {
+            config.chain.bind = bind;
+        }
+        if args.dev_mode {
+            config.dev_mode = true;
+        }
+        if let Some(storage_path) = args.storage_path {
+            config.storage_path = Some(storage_path);
+        }
+        if let Some(victory_timeout) = args.victory_timeout {
+            config.chain.victory_timeout_seconds = victory_timeout;
+        }
+        if args.blind_reports {
+            config.chain.blind_reports = true;
+        }
+        if let Some(node_id) = args.node_id {
+            config.chain.node_id = node_id;
+        }
+        if !args.peers.is_empty() {
+            config.chain.peers = args.peers;
+        }
+        if let Some(sync_from) = args.sync_from {
+            config.chain.sync_from = Some(sync_from);
+        }
+    });
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&config.log_level))
+        .init();
+
+    // The genesis key signs game certificates and the response key every response, so the chain
+    // cannot run without them
+    let genesis_path = config.storage_file(&config.chain.genesis_key);
+    let genesis = match load_signing_key(&genesis_path) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("Could not load genesis key {}: {}", genesis_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    info!("Genesis key: {}", hex::encode(genesis.verifying_key().as_bytes()));
+    let responses_path = config.storage_file(&config.chain.response_key);
+    let responses = match load_signing_key(&responses_path) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("Could not load response key {}: {}", responses_path.display(), e);
+            std::process::exit(1);
+        }
     };
+    info!("Response key: {}", hex::encode(responses.verifying_key().as_bytes()));
+
+    let shared = SharedData::new(genesis, responses, monitoring::install());
+
+    // Rebuild the games from the transactions this node applied before it stopped
+    match shared.replication.restore(&shared) {
+        Ok(count) => info!("Restored {} transactions from the transaction log", count),
+        Err(e) => {
+            error!("Could not restore the games: {}", e);
+            std::process::exit(1);
+        }
+    }
 
-    // Clone shared data for the timeout checker before moving it to the extension
+    // A fresh replica starts with the games of the node it syncs from
+    if let Some(from) = &config.chain.sync_from {
+        match shared.replication.sync(&shared, from).await {
+            Ok(count) => info!("Synced {} transactions from {}", count, from),
+            Err(e) => {
+                error!("Could not sync from {}: {}", from, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Clone shared data for the timeout checker and the gRPC service before moving it to the extension
     let timeout_checker = shared.clone();
+    let grpc_shared = shared.clone();
 
     // Build our application with a route
     let app = Router::new()
-        .route("/", get(index))
+        .route("/", get(explorer::index))
+        .route("/explorer/blocks/:number", get(explorer::block_page))
+        .route("/explorer/games/:gameid", get(explorer::game_page))
+        .route("/wasm/:file", get(explorer::wasm_asset))
         .route("/logs", get(logs))
-        .route("/chain", post(smart_contract))
+        .route(
+            "/chain",
+            post(smart_contract)
+                .layer(DefaultBodyLimit::max(config.chain.max_body_bytes))
+                .layer(middleware::from_fn(limit_chain_requests))
+                .layer(middleware::from_fn(require_host)),
+        )
+        .route(
+            "/challenge/:gameid/:fleet",
+            get(challenge_handler).layer(middleware::from_fn(limit_chain_requests)),
+        )
+        .route(
+            "/matchmake",
+            post(matchmake_handler).layer(middleware::from_fn(limit_chain_requests)),
+        )
+        .route("/tx/:id", get(tx_handler))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics_handler))
+        .route("/genesis", get(genesis_handler))
+        .route("/pubkey", get(pubkey_handler))
+        .route("/methods", get(methods_handler))
+        .route("/blocks", get(headers_handler))
+        .route("/blocks/latest", get(latest_handler))
+        .route("/blocks/:number", get(block_handler))
         .route("/gamestate/:gameid/:fleet", get(game_state_handler))
+        .route("/games/:gameid/archive", get(archive_handler))
+        .route("/games/:gameid/annotations", post(annotation_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/reveal", post(reveal_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/chat", post(chat_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/ready", post(ready_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/pause", post(pause_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/rematch", post(rematch_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/team-messages", post(team_message_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/team-messages/:fleet", get(team_channel_handler).layer(middleware::from_fn(require_host)))
+        .route("/games/:gameid/turn-order", get(turn_order_handler))
+        .route("/games/:gameid/certificate", get(certificate_handler))
+        .route("/games/:gameid/receipts", get(receipts_handler))
+        .route("/games/:gameid/export", get(export_handler))
+        .route("/games/:gameid/state-root", get(state_root_handler))
+        .route("/games/:gameid/timeline", get(timeline_handler))
+        .route("/games/:gameid/board", get(board_handler))
+        .route("/games/:gameid/board.txt", get(board_text_handler))
+        .route("/games/:gameid/stats", get(stats_handler))
+        .route("/games/:gameid/shot", get(shot_handler))
+        .route("/games/:gameid/watch", get(watch))
+        .route("/games/:gameid/events", get(events_handler))
+        .route(
+            "/games/:gameid/spectate",
+            post(spectate_handler).layer(middleware::from_fn(limit_chain_requests)),
+        )
+        .route("/games/:gameid/spectate/stream", get(spectator_stream))
+        .route(
+            "/games/:gameid/hooks",
+            post(register_hook).layer(middleware::from_fn(limit_chain_requests)),
+        )
+        .route("/games/:gameid/hooks/:id", get(hook_status).delete(delete_hook))
+        .route("/timelines", get(timelines_handler))
+        .route("/leaderboard", get(leaderboard_handler))
+        .route("/players/:fleet/stats", get(player_stats_handler))
+        .route("/timeline.js", get(timeline_js))
+        .merge(admin::routes())
+        .merge(replication::routes())
+        .merge(openapi::routes());
+    #[cfg(feature = "graphql")]
+    let app = app.merge(graphql::routes(shared.clone()));
+    let app = app
+        .route_layer(middleware::from_fn(check_path_ids))
+        .layer(middleware::from_fn(correlation::echo))
+        .layer(middleware::from_fn(sign_responses))
         .layer(Extension(shared));
 
     // Run our app with hyper
-    //let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3001));
-    println!("Listening on http://{}", addr);
+    let addr = config.chain.addr();
+    info!("Listening on http://{}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     
     // Start the timeout checker task
@@ -77,269 +608,810 @@ async fn main() {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
         loop {
             interval.tick().await;
-            check_victory_timeouts(&timeout_checker).await;
+            check_victory_timeouts(&timeout_checker);
+            clocks::tick(&timeout_checker);
+            expire_stale_games(&timeout_checker);
+            timeout_checker.webhooks.deliver_ends();
+            timeout_checker.challenges.expire(now_secs());
+            rematch::expire(&timeout_checker);
+            blocks::seal_due(&timeout_checker);
         }
     });
 
-    axum::serve(listener, app).await.unwrap();
+    if let Some(addr) = config.chain.grpc_addr() {
+        info!("gRPC service listening on {}", addr);
+        tokio::spawn(grpc::serve(grpc_shared, addr));
+    }
+
+    // Peer addresses are needed for per-IP rate limiting
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
 
-// Handler to serve the HTML page
-async fn index() -> Html<&'static str> {
-    Html(
-        r#"
-        <!DOCTYPE html>
-        <html>
-        <head>
-            <title>Blockchain Emulator</title>
-        </head>
-        <body>
-            <h1>Registered Transactions</h1>          
-            <ul id="logs"></ul>
-            <script>
-                const eventSource = new EventSource('/logs');
-                eventSource.onmessage = function(event) {
-                    const logs = document.getElementById('logs');
-                    const log = document.createElement('li');
-                    log.textContent = event.data;
-                    logs.appendChild(log);
-                };
-            </script>
-        </body>
-        </html>
-        "#,
-    )
+// Answer of the liveness endpoint
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    games: usize,
+    archived_games: usize,
 }
 
-// Handler to manage SSE connections
-#[axum::debug_handler]
-async fn logs(Extension(shared): Extension<SharedData>) -> impl IntoResponse {
-    let rx = BroadcastStream::new(shared.tx.subscribe());
-    let stream = rx.filter_map(|result| async move {
-        match result {
-            Ok(msg) => Some(Ok(Event::default().data(msg))),
-            Err(_) => Some(Err(Box::<dyn Error + Send + Sync>::from("Error"))),
-        }
-    });
+// Liveness endpoint: answers as long as the game state can be locked
+async fn healthz(Extension(shared): Extension<SharedData>) -> Json<Health> {
+    let games = shared.games().len();
+    let archived_games = shared.archived().len();
+    Json(Health { status: "ok", games, archived_games })
+}
+
+// Readiness endpoint: the chain is ready when its storage directory is writable
+async fn readyz() -> impl IntoResponse {
+    match config().check_storage() {
+        Ok(()) => (StatusCode::OK, "ok".to_string()),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, format!("storage unavailable: {}", e)),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LogsQuery {
+    last_seq: Option<u64>,
+    // Only follow the events of this game
+    gameid: Option<String>,
+}
+
+// Largest page of game events served at once
+const MAX_EVENTS_PAGE: usize = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventsQuery {
+    #[serde(default)]
+    since: u64,
+    limit: Option<usize>,
+}
+
+// The signature of the event goes in a comment, which EventSource clients ignore, and so does the
+// correlation ID of the transaction that published it
+fn chain_event(event: &ChainEvent) -> Event {
+    let mut sse = Event::default().id(event.seq.to_string()).comment(format!("sig {}", event.signature));
+    if let Some(id) = &event.correlation_id {
+        sse = sse.comment(format!("correlation {}", id));
+    }
+    sse.data(&event.msg)
+}
+
+// `resync` event sent to a client that fell behind, unnumbered so its resume token is kept
+fn resync_event(resync: &Resync) -> Event {
+    Event::default().event("resync").data(serde_json::to_string(resync).unwrap_or_default())
+}
+
+// Prometheus metrics of the chain
+async fn metrics_handler(Extension(shared): Extension<SharedData>) -> String {
+    monitoring::set_active_games(shared.games().len());
+    shared.metrics.render()
+}
 
-    axum::response::sse::Sse::new(stream)
+// Handler returning the events of a game published after `since`, oldest first. Clients page
+// through the history by passing the `next` token of each page as the next `since`.
+#[utoipa::path(
+    get,
+    path = "/games/{gameid}/events",
+    tag = "games",
+    params(("gameid" = String, Path, description = "Game ID"), EventsQuery),
+    responses(
+        (status = 200, description = "Page of the game's events", body = EventPage),
+        (status = 404, description = "Game not found", body = String),
+    ),
+)]
+async fn events_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<EventPage>, ChainError> {
+    let limit = query.limit.unwrap_or(MAX_EVENTS_PAGE).clamp(1, MAX_EVENTS_PAGE);
+    match shared.events.page(&gameid, query.since, limit) {
+        Some(page) => Ok(Json(page)),
+        None => Err(ChainError::NotFound("No events for this game".to_string())),
+    }
 }
 
-fn xy_pos(pos: u8) -> String {
-    let x = pos % 10;
-    let y = pos / 10;
-    format!("{}{}", (x + 65) as char, y)
+// Handler to manage SSE connections. Clients resume after the last event they saw, given by the
+// browser's Last-Event-ID header when it reconnects or by `?last_seq=`. With `?gameid=`, only the
+// events of that game are sent, resumed from the game's own history.
+#[utoipa::path(
+    get,
+    path = "/logs",
+    tag = "events",
+    params(LogsQuery, ("Last-Event-ID" = Option<u64>, Header, description = "Number of the last event received")),
+    responses((status = 200, description = "Server-sent events, one per chain event", content_type = "text/event-stream", body = String)),
+)]
+#[axum::debug_handler]
+async fn logs(
+    Extension(shared): Extension<SharedData>,
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let last_seq = query.last_seq.or_else(|| {
+        headers.get("last-event-id").and_then(|v| v.to_str().ok()?.parse().ok())
+    });
+    let subscription = shared.events.subscribe(last_seq, query.gameid.as_deref());
+
+    // Tell the client when it was away for longer than the history buffer covers
+    let gap: Option<Result<Event, Box<dyn Error + Send + Sync>>> = subscription
+        .gap
+        .then(|| Ok(Event::default().event("gap").data("Some events were missed")));
+    let missed = subscription.missed.into_iter().map(|event| Ok(chain_event(&event)));
+    // Counted as connected until the stream is dropped
+    let client = SseClient::connect();
+    let gameid = query.gameid;
+    let live = BroadcastStream::new(subscription.live)
+        .scan(Some(subscription.last_seq), move |position, result| {
+            let _client = &client;
+            // The stream ends after asking the client to resync
+            let Some(last_seq) = *position else { return future::ready(None) };
+            let item = match result {
+                Ok(event) if gameid.is_some() && event.gameid != gameid => None,
+                Ok(event) => {
+                    *position = Some(event.seq);
+                    Some(chain_event(&event))
+                }
+                // The client fell behind: tell it where it stopped, so it catches up from the history
+                Err(BroadcastStreamRecvError::Lagged(count)) => {
+                    monitoring::events_lagged(count);
+                    *position = None;
+                    Some(resync_event(&shared.events.resync(count, last_seq, gameid.as_deref())))
+                }
+            };
+            future::ready(Some(item))
+        })
+        .filter_map(|item| future::ready(item.map(Ok)));
+
+    axum::response::sse::Sse::new(stream::iter(gap).chain(stream::iter(missed)).chain(live))
 }
 
+// Submit a signed receipt, answering once it is applied, or right away with `Prefer: respond-async`
+#[utoipa::path(
+    post,
+    path = "/chain",
+    tag = "chain",
+    request_body = CommunicationData,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Key a retry of the transaction is sent with again"),
+        ("Prefer" = Option<String>, Header, description = "`respond-async` to be answered before the receipt is verified"),
+        ("X-Correlation-Id" = Option<String>, Header, description = "ID of the host's action, echoed on the response and recorded on the transaction's events"),
+    ),
+    responses(
+        (status = 200, description = "Transaction applied; its ID is returned in `x-tx-id` (also on errors) and the request ID in `x-request-id`", body = String),
+        (status = 202, description = "Transaction accepted for verification; follow it on `/tx/{id}` with the ID returned in `x-tx-id`", body = String),
+        (status = 400, description = "Malformed transaction, or a protocol version the chain does not accept", body = UnsupportedVersion),
+        (status = 401, description = "The receipt or its signature did not verify", body = String),
+        (status = 404, description = "Unknown game, player or target", body = String),
+        (status = 409, description = "Not allowed in the current state of the game", body = String),
+        (status = 413, description = "Transaction too large", body = String),
+        (status = 429, description = "Too many requests, retry after `Retry-After` seconds", body = String),
+    ),
+)]
 async fn smart_contract(
     Extension(shared): Extension<SharedData>,
+    request_headers: HeaderMap,
     Json(input_data): Json<CommunicationData>,
-) -> String {
-    match input_data.cmd {
-        Command::Join => handle_join(&shared, &input_data),
-        Command::Fire => handle_fire(&shared, &input_data),
-        Command::Report => handle_report(&shared, &input_data),
-        Command::Wave => handle_wave(&shared, &input_data),
-        Command::Win => handle_win(&shared, &input_data),
+) -> Response {
+    let respond_async = request_headers
+        .get(header::PREFER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|pref| pref.trim().eq_ignore_ascii_case("respond-async")));
+    if !respond_async {
+        return submit_transaction(&shared, &request_headers, input_data).into_response();
     }
+
+    // Verify and apply the transaction in a worker, and tell the game's event stream how it went
+    let tx_id = shared.submissions.open(input_data.cmd, now_secs());
+    let gameid = input_data.receipt.journal.decode::<JournalHead>().ok().map(|head| head.gameid);
+    let correlation_id = correlation::of_transaction(input_data.correlation_id.as_deref(), &request_headers).ok().flatten();
+    let id = tx_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let _correlation = correlation::enter(correlation_id);
+        let msg = match settle(&shared, &id, submit(&shared, &request_headers, input_data, &id)) {
+            Ok((headers, _)) => {
+                let index = headers.get("x-tx-index").and_then(|v| v.to_str().ok()).unwrap_or("?");
+                format!("Transaction {} applied as transaction {}", id, index)
+            }
+            Err((_, err)) => format!("Transaction {} rejected: {}", id, err),
+        };
+        match gameid {
+            Some(gameid) => shared.game_event(&gameid, msg),
+            None => shared.event(msg),
+        }
+    });
+    let headers = [(TX_ID_HEADER, tx_id.clone())];
+    (StatusCode::ACCEPTED, headers, format!("Transaction {} accepted, follow it on /tx/{}", tx_id, tx_id)).into_response()
+}
+
+// Submit a transaction and answer once it is applied
+fn submit_transaction(
+    shared: &SharedData,
+    request_headers: &HeaderMap,
+    input_data: CommunicationData,
+) -> Result<(HeaderMap, String), (HeaderMap, ChainError)> {
+    let tx_id = shared.submissions.open(input_data.cmd, now_secs());
+    settle(shared, &tx_id, submit(shared, request_headers, input_data, &tx_id))
 }
 
-fn handle_join(shared: &SharedData, input_data: &CommunicationData) -> String {
-    // Verify the receipt first
-    if input_data.receipt.verify(JOIN_ID).is_err() {
-        shared.tx.send("Attempting to join game with invalid receipt".to_string()).unwrap();
-        return "Could not verify receipt".to_string();
+// Record the outcome of a submission, and add its ID to a rejection
+fn settle(
+    shared: &SharedData,
+    tx_id: &str,
+    result: Result<(HeaderMap, String), ChainError>,
+) -> Result<(HeaderMap, String), (HeaderMap, ChainError)> {
+    match result {
+        // Answered with the response of an earlier submission, and its ID
+        Ok((headers, response)) if headers.contains_key("idempotent-replayed") => {
+            match headers.get("x-tx-index").and_then(|v| v.to_str().ok()?.parse().ok()) {
+                Some(index) => shared.submissions.update(tx_id, TxStatus::Applied { index, block: None }),
+                None => shared.submissions.discard(tx_id),
+            }
+            Ok((headers, response))
+        }
+        Ok(answer) => Ok(answer),
+        Err(err) => {
+            shared.submissions.update(tx_id, TxStatus::Rejected { reason: err.to_string() });
+            let mut headers = HeaderMap::new();
+            if let Ok(value) = HeaderValue::from_str(tx_id) {
+                headers.insert(TX_ID_HEADER, value);
+            }
+            Err((headers, err))
+        }
     }
-    
-    // Decode the journal
-    let data: BaseJournal = input_data.receipt.journal.decode().unwrap();
+}
 
-    // Get verifying key from the communication data
-    let verifying_key_bytes = match input_data.public_key.as_ref() {
-        Some(pk) => pk,
-        None => {
-            shared.tx.send("Verifying key is missing in join request".to_string()).unwrap();
-            return "Missing verifying key".to_string();
+fn submit(
+    shared: &SharedData,
+    request_headers: &HeaderMap,
+    mut input_data: CommunicationData,
+    tx_id: &str,
+) -> Result<(HeaderMap, String), ChainError> {
+    // Every log line of the transaction carries its request ID, correlation ID, command, game and
+    // fleet
+    let request_id = next_request_id();
+    let span = info_span!(
+        "transaction",
+        request_id,
+        correlation_id = tracing::field::Empty,
+        command = ?input_data.cmd,
+        gameid = tracing::field::Empty,
+        fleet = tracing::field::Empty,
+    );
+    let _entered = span.enter();
+    monitoring::receipt_received(input_data.cmd);
+
+    // The events of the transaction, and its replicas, carry the ID of the host's action
+    input_data.correlation_id = correlation::of_transaction(input_data.correlation_id.as_deref(), request_headers)?;
+    if let Some(id) = &input_data.correlation_id {
+        span.record("correlation_id", id.as_str());
+        shared.submissions.correlate(tx_id, id);
+    }
+    let _correlation = correlation::enter(input_data.correlation_id.clone());
+
+    // Check if the transaction and its journal are in a version the chain can decode
+    let journal_version = input_data.receipt.journal.decode::<u32>().unwrap_or_default();
+    for version in [input_data.version, journal_version] {
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            monitoring::receipt_rejected(input_data.cmd, Rejection::Version);
+            warn!("Rejected {:?} transaction in protocol version {}", input_data.cmd, version);
+            return Err(ChainError::UnsupportedVersion(version));
+        }
+    }
+
+    let head = input_data.receipt.journal.decode::<JournalHead>().ok();
+    if let Some(head) = &head {
+        // IDs outside the limits the guests enforce were never proven by an accepted guest
+        if let Err(err) = head.check(input_data.cmd) {
+            monitoring::receipt_rejected(input_data.cmd, Rejection::Inputs);
+            warn!("Rejected {:?} transaction: {}", input_data.cmd, err);
+            return Err(ChainError::InvalidId(err));
+        }
+        shared.submissions.identify(tx_id, &head.gameid, &head.fleet);
+
+        // Check if the fleet is within its rate limit, before spending time on its receipt
+        if let Err(retry_after) = shared.limits.per_fleet.check((head.gameid.clone(), head.fleet.clone())) {
+            monitoring::receipt_rejected(input_data.cmd, Rejection::RateLimit);
+            warn!("Rate limited transactions of fleet {}", head.fleet);
+            return Err(ChainError::RateLimited("Too many transactions for this fleet, slow down".to_string(), retry_after));
+        }
+    }
+
+    // Verify the receipt first, against every guest version still accepted
+    let started = std::time::Instant::now();
+    let accepted = match shared.guests.verify(input_data.cmd, &input_data.receipt, now_secs()) {
+        Ok(accepted) => accepted,
+        Err(err) => {
+            monitoring::receipt_rejected(input_data.cmd, Rejection::Receipt);
+            warn!("Rejected {:?} transaction: {}", input_data.cmd, err);
+            return Err(err);
+        }
+    };
+    monitoring::receipt_verified(input_data.cmd, started.elapsed());
+    shared.submissions.update(tx_id, TxStatus::Verified);
+
+    // A retry of a transaction already applied gets its response again. Only a verified receipt
+    // takes a key, so an unproven journal cannot hold one.
+    let idempotency_key = request_headers.get(IDEMPOTENCY_HEADER).and_then(|v| v.to_str().ok());
+    let pending = match (idempotency_key, &head) {
+        (Some(key), Some(head)) => {
+            let digest = journal_digest(&input_data.receipt.journal.bytes);
+            match shared.idempotency.begin(&head.gameid, key, digest, now_secs())? {
+                Begin::Replay(headers, response) => {
+                    info!("Answered a retry of transaction {} of fleet {}", key, head.fleet);
+                    return Ok((headers, response));
+                }
+                Begin::Fresh(pending) => Some(pending),
+            }
+        }
+        _ => None,
+    };
+
+    // Check if the reported cycle count is plausible for the command
+    if let Some(cycles) = input_data.cycles {
+        if !(MIN_CYCLES..=max_cycles(input_data.cmd)).contains(&cycles) {
+            monitoring::receipt_rejected(input_data.cmd, Rejection::Cycles);
+            warn!("Rejected {:?} transaction reporting {} cycles", input_data.cmd, cycles);
+            return Err(ChainError::Malformed("Implausible cycle count".to_string()));
+        }
+    }
+
+    let (cmd, cycles, correlation_id) = (input_data.cmd, input_data.cycles, input_data.correlation_id.clone());
+    let (index, response) = match shared.replication.commit(shared, Transaction::Chain(input_data)) {
+        Ok(committed) => committed,
+        Err(err) => {
+            monitoring::receipt_rejected(cmd, Rejection::Contract);
+            return Err(err);
+        }
+    };
+    shared.submissions.update(tx_id, TxStatus::Applied { index, block: None });
+
+    // Number the applied transaction so the host can wait for its state to be visible
+    let mut seq = 0;
+    shared.applied.send_modify(|applied| {
+        *applied += 1;
+        seq = *applied;
+    });
+
+    // Tag the transaction with the guest version that proved it
+    let cycles = cycles.map_or("unknown".to_string(), |c| c.to_string());
+    shared.event(format!(
+        "{:?} transaction proven by guest version {} in {} cycles",
+        cmd,
+        accepted.version(),
+        cycles
+    ));
+
+    let mut headers = guest_headers(&accepted);
+    headers.insert("x-applied-seq", HeaderValue::from(seq));
+    headers.insert("x-tx-index", HeaderValue::from(index));
+    headers.insert("x-request-id", HeaderValue::from(request_id));
+    if let Ok(value) = HeaderValue::from_str(tx_id) {
+        headers.insert(TX_ID_HEADER, value);
+    }
+    if let Some(value) = correlation_id.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        headers.insert(CORRELATION_HEADER, value);
+    }
+    if let Some(pending) = pending {
+        pending.complete(&headers, &response);
+    }
+    Ok((headers, response))
+}
+
+// Apply a transaction to the games, on the node that accepted it and on its replicas, and notify
+// the game's webhooks of it
+fn apply_transaction(shared: &SharedData, tx: &Transaction) -> Result<String, ChainError> {
+    let (_, gameid, _, digest) = describe(tx);
+    let receipt = matches!(tx, Transaction::Chain(_));
+    if receipt {
+        check_duplicate(shared, &gameid, &digest)?;
+    }
+    check_paused(shared, &gameid, tx)?;
+    // The turn the transaction ends is charged to the clock of the player who had it
+    clocks::settle(shared, &gameid);
+    let watched = shared.webhooks.watching(&gameid);
+    let before = if watched { webhooks::turn(shared, &gameid) } else { None };
+    let result = apply_to_games(shared, tx);
+    if receipt && result.is_ok() {
+        record_journal(shared, &gameid, digest);
+    }
+    if watched && result.is_ok() {
+        webhooks::applied(shared, &gameid, tx, before);
+    }
+    shared.webhooks.deliver_ends();
+    result
+}
+
+// Receipts remembered per game to refuse their resubmission, the oldest is forgotten beyond
+const RECENT_JOURNALS: usize = 256;
+
+// Refuse a receipt already applied to the game, whatever its nonce. A host resending the same
+// receipt (a double click in the UI, a retry without an idempotency key) must not move the game.
+fn check_duplicate(shared: &SharedData, gameid: &str, digest: &Digest) -> Result<(), ChainError> {
+    let gmap = shared.games();
+    if gmap.get(gameid).is_some_and(|game| game.recent_journals.contains(digest)) {
+        warn!("Duplicate receipt {} in game {}", digest, gameid);
+        return Err(ChainError::DuplicateTransaction("This receipt was already applied to the game".to_string()));
+    }
+    Ok(())
+}
+
+fn record_journal(shared: &SharedData, gameid: &str, digest: Digest) {
+    if let Some(game) = shared.games().get_mut(gameid) {
+        if game.recent_journals.len() == RECENT_JOURNALS {
+            game.recent_journals.pop_front();
         }
+        game.recent_journals.push_back(digest);
+    }
+}
+
+fn apply_to_games(shared: &SharedData, tx: &Transaction) -> Result<String, ChainError> {
+    match tx {
+        Transaction::Chain(input_data) => match input_data.cmd {
+            Command::Join => handle_join(shared, input_data),
+            Command::Fire => handle_fire(shared, input_data),
+            Command::Report => handle_report(shared, input_data),
+            Command::Wave => handle_wave(shared, input_data),
+            Command::Win => handle_win(shared, input_data),
+            Command::BlindReport => handle_blind_report(shared, input_data),
+            Command::BlindWin => handle_blind_win(shared, input_data),
+            Command::Forfeit => handle_forfeit(shared, input_data),
+            Command::Register => handle_register(shared, input_data),
+            Command::Status => handle_status(shared, input_data),
+            Command::Audit => handle_audit(shared, input_data),
+            Command::Contest => handle_contest(shared, input_data),
+        },
+        Transaction::Ready { gameid, data } => handle_ready(shared, gameid, data),
+        Transaction::Annotation { gameid, data } => handle_annotation(shared, gameid, data),
+        Transaction::Chat { gameid, data } => handle_chat(shared, gameid, data),
+        Transaction::Reveal { gameid, data } => handle_reveal(shared, gameid, data),
+        Transaction::Pause { gameid, data } => handle_pause(shared, gameid, data),
+        Transaction::Rematch { gameid, data } => handle_rematch(shared, gameid, data),
+        Transaction::TeamMessage { gameid, data } => handle_team_message(shared, gameid, data),
+    }
+}
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// Number identifying a request in the chain's logs
+fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// Add the game and fleet of a decoded journal to the current transaction's span
+fn record_player(gameid: &str, fleet: &str) {
+    let span = Span::current();
+    span.record("gameid", gameid);
+    span.record("fleet", fleet);
+}
+
+// Check that a transaction fits in a player's remaining cycle budget and return the cycles to
+// charge. Games without a budget accept transactions from hosts that do not report cycles.
+fn charge_cycles(budget: Option<u64>, spent: u64, cycles: Option<u64>) -> Result<u64, ChainError> {
+    let budget = match budget {
+        Some(budget) => budget,
+        None => return Ok(cycles.unwrap_or(0)),
     };
+    let cycles = match cycles {
+        Some(cycles) => cycles,
+        None => return Err(ChainError::Rejected("This game has a cycle budget, the cycle count is required".to_string())),
+    };
+    if spent.saturating_add(cycles) > budget {
+        return Err(ChainError::Rejected(format!("Cycle budget exceeded ({} of {} cycles spent)", spent, budget)));
+    }
+    Ok(cycles)
+}
+
+// Response metadata telling the host which guest version was accepted, and warning hosts still
+// running the previous version
+fn guest_headers(accepted: &Accepted) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(version) = HeaderValue::from_str(accepted.version()) {
+        headers.insert("x-guest-version", version);
+    }
+    if let Some(warning) = accepted.deprecation() {
+        if let Ok(warning) = HeaderValue::from_str(&warning) {
+            headers.insert("x-guest-deprecated", warning);
+        }
+    }
+    headers
+}
+
+fn handle_join(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: BaseJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
 
-    // Convert bytes to VerifyingKey
-    let verifying_key = match VerifyingKey::from_bytes(verifying_key_bytes.as_slice().try_into().unwrap()) {
+    // Convert the verifying key sent along, if any
+    let sent_key = match input_data.public_key.as_deref().map(parse_verifying_key).transpose() {
         Ok(key) => key,
-        Err(_) => {
-            shared.tx.send("Invalid verifying key in join request".to_string()).unwrap();
-            return "Invalid verifying key".to_string();
+        Err(err) => {
+            warn!("Invalid verifying key in join request: {}", err);
+            return Err(err);
         }
     };
 
-    // Convert signature bytes to Signature
-    let signature = Signature::from_bytes(input_data.signature.as_slice().try_into().unwrap());
+    // Use the key registered by the player, or the one sent when the player has not registered
+    let verifying_key = match (shared.identities.key(&data.gameid, &data.fleet), sent_key) {
+        (Some(registered), Some(sent)) if registered != sent => {
+            warn!("Verifying key of {} does not match its registered key", data.fleet);
+            return Err(ChainError::Unauthorized("Verifying key does not match the registered key".to_string()));
+        }
+        (Some(key), _) | (None, Some(key)) => key,
+        (None, None) => {
+            warn!("Verifying key is missing in join request");
+            return Err(ChainError::Malformed("Missing verifying key, register one or send it along".to_string()));
+        }
+    };
 
-    // Verify the signature against the receipt data
-    if verifying_key.verify(&input_data.receipt.journal.bytes.as_slice(), &signature).is_err() {
-        shared.tx.send("Invalid signature in join request".to_string()).unwrap();
-        return "Invalid signature".to_string();
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&verifying_key, input_data, &data.gameid) {
+        warn!("Invalid signature in join request");
+        return Err(err);
     }
 
-    let mut gmap = shared.gmap.lock().unwrap();
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if the game is not a rematch of a game the player did not play
+    shared.rematches.check(&data.gameid, &data.fleet)?;
+
+    let mut gmap = shared.games();
     
     // Get current timestamp for initializing player
-    let current_time = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let current_time = now_secs();
     
-    // Check if game exists and if the first shot has been fired
+    // Check if game exists and if it has started
     if let Some(existing_game) = gmap.get(&data.gameid) {
-        // Check if the first shot has been fired
-        if existing_game.first_shot_fired {
-            shared.tx.send(format!("Cannot join game {} - game has already started (first shot fired)", data.gameid)).unwrap();
-            return "Cannot join - game has already started".to_string();
+        // Check if the game is still in its lobby
+        if existing_game.started {
+            warn!("Cannot join game {} - game has already started", data.gameid);
+            return Err(ChainError::Rejected("Cannot join - game has already started".to_string()));
         }
         
         // Check if player is already in the game
         if existing_game.pmap.contains_key(&data.fleet) {
-            shared.tx.send(format!("Player {} already in game {}", data.fleet, data.gameid)).unwrap();
-            return "Player already in game".to_string();
+            warn!("Player {} already in game {}", data.fleet, data.gameid);
+            return Err(ChainError::Rejected("Player already in game".to_string()));
+        }
+
+        // Check if the player picked a team exactly when the game is played in teams
+        if existing_game.teams != input_data.team.is_some() {
+            warn!("Player {} joined game {} with the wrong team setting", data.fleet, data.gameid);
+            let msg = if existing_game.teams { "This game is played in teams, pick a team" } else { "This game is not played in teams" };
+            return Err(ChainError::Rejected(msg.to_string()));
+        }
+
+        // Check if the player was invited to a private game
+        if existing_game.private {
+            check_invite(shared, &data.gameid, existing_game, &data.fleet, input_data.invite.as_deref())?;
+        }
+
+        // Check if the game has room for another player
+        if existing_game.pmap.len() >= existing_game.max_players {
+            shared.game_event(&data.gameid, format!(
+                "{} cannot join game {}: the game is full ({} players)",
+                data.fleet, data.gameid, existing_game.max_players
+            ));
+            return Err(ChainError::Rejected("Cannot join - game is full".to_string()));
         }
     }
+
+    // Check if the team has a name
+    if input_data.team.as_ref().is_some_and(|team| team.trim().is_empty()) {
+        return Err(ChainError::Malformed("Team cannot be an empty string".to_string()));
+    }
+
+    // Check if the join fits in the game's cycle budget
+    let cycle_budget = gmap.get(&data.gameid).map_or_else(default_cycle_budget, |game| game.cycle_budget);
+    let cycles = match charge_cycles(cycle_budget, 0, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford joining game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the challenge of the receipt
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
     
     // Create or get the game entry
-    let game = gmap.entry(data.gameid.clone()).or_insert(Game {
-        pmap: HashMap::new(),
-        next_player: Some(data.fleet.clone()),
-        next_report: None,
-        first_victory_claim: None,
-        victory_timeout_seconds: 30,
-        first_shot_fired: false,
+    let game = gmap.entry(data.gameid.clone()).or_insert_with(|| Game {
+        private: input_data.private,
+        teams: input_data.team.is_some(),
+        ..Game::new(&data.fleet, current_time, victory_timeout(input_data.victory_timeout), cycle_budget)
     });
     
     // Insert the player into the game
     let player_inserted = game.pmap.entry(data.fleet.clone()).or_insert_with(|| Player {
-        name: data.fleet.clone(),
-        current_state: data.board.clone(),
-        last_turn_timestamp: current_time,
-        has_claimed_victory: false,
-        verifying_key: verifying_key,
+        cycles_spent: cycles,
+        receipt_nonce: input_data.nonce,
+        join_digest: journal_digest(&input_data.receipt.journal.bytes),
+        team: input_data.team.clone(),
+        ..Player::new(&data.fleet, data.board, verifying_key, current_time)
     }).name == data.fleet;
+    if player_inserted {
+        game.order.push(data.fleet.clone());
+    }
+    game.last_activity = current_time;
     
     let mesg = if player_inserted {
-        format!("{} joined game {}", data.fleet, data.gameid)
+        match &input_data.team {
+            Some(team) => format!("{} joined game {} in team {}", data.fleet, data.gameid, team),
+            None => format!("{} joined game {}", data.fleet, data.gameid),
+        }
     } else {
         format!("Player already in game {}", data.gameid)
     };
-    shared.tx.send(mesg).unwrap();
-    "OK".to_string()
+    shared.game_event(&data.gameid, mesg);
+    Ok("OK".to_string())
 }
 
-fn handle_fire(shared: &SharedData, input_data: &CommunicationData) -> String {
-    // Verify the receipt first
-    if input_data.receipt.verify(FIRE_ID).is_err() {
-        shared.tx.send("Attempting to fire with invalid receipt".to_string()).unwrap();
-        return "Could not verify receipt".to_string();
-    }
-
+fn handle_fire(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
     // Decode the journal
-    let data: FireJournal = input_data.receipt.journal.decode().unwrap();
-    let mut gmap = shared.gmap.lock().unwrap();
+    let data: FireJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
 
     // Check if the game exists
     let game = match gmap.get_mut(&data.gameid) {
         Some(game) => game,
         None => {
-            shared.tx.send(format!("Game {} not found", data.gameid)).unwrap();
-            return "Game not found".to_string();
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
         }
     };
 
     // Check if the target is in the game
     if !game.pmap.contains_key(&data.target) {
-        shared.tx.send(format!("Target {} not found in game {}", data.target, data.gameid)).unwrap();
-        return "Target not found".to_string();
+        warn!("Target {} not found in game {}", data.target, data.gameid);
+        return Err(ChainError::NotFound("Target not found".to_string()));
+    }
+
+    // Check if the target has not conceded
+    if game.pmap.get(&data.target).is_some_and(|target| target.forfeited) {
+        warn!("Target {} has forfeited game {}", data.target, data.gameid);
+        return Err(ChainError::Rejected("Target has forfeited".to_string()));
     }
 
     // Check if the target is not the player itself
     if data.fleet == data.target {
-        shared.tx.send(format!("Cannot fire at yourself in game {}", data.gameid)).unwrap();
-        return "Cannot fire at yourself".to_string();
+        warn!("Cannot fire at yourself in game {}", data.gameid);
+        return Err(ChainError::Rejected("Cannot fire at yourself".to_string()));
     }
 
+    // Check if the target is not a teammate
+    if !game.opponents(&data.fleet, &data.target) {
+        warn!("{} cannot fire at teammate {} in game {}", data.fleet, data.target, data.gameid);
+        return Err(ChainError::Rejected("Cannot fire at a teammate".to_string()));
+    }
+
+    // Players still in the game, counted before the shooter is borrowed
+    let players = game.pmap.values().filter(|player| !player.forfeited).count();
+    let due = game.pmap.get(&data.fleet).is_some_and(|player| status_due(game, player));
+
     // Check if the player is in the game
     let player = match game.pmap.get_mut(&data.fleet) {
         Some(player) => player,
         None => {
-            shared.tx.send(format!("Player {} not found in game {}", data.fleet, data.gameid)).unwrap();
-            return "Player not found".to_string();
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
         }
     };
 
     // Get verifying key from player
-    let verifying_key = &player.verifying_key;
+    let verifying_key = &shared.identities.player_key(&data.gameid, player);
 
-    // Convert signature bytes to Signature
-    let signature = Signature::from_bytes(input_data.signature.as_slice().try_into().unwrap());
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(verifying_key, input_data, &data.gameid) {
+        warn!("Invalid signature in fire request");
+        return Err(err);
+    }
 
-    // Verify the signature against the receipt data
-    if verifying_key.verify(&input_data.receipt.journal.bytes.as_slice(), &signature).is_err() {
-        shared.tx.send("Invalid signature in fire request".to_string()).unwrap();
-        return "Invalid signature".to_string();
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
     }
 
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
     // Check if someone has claimed victory and timeout is active
     if let Some((claimant, claim_time)) = &game.first_victory_claim {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let current_time = now_secs();
         
-        if current_time - claim_time < game.victory_timeout_seconds {
-            let remaining_time = game.victory_timeout_seconds - (current_time - claim_time);
-            shared.tx.send(format!("Cannot fire during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Win' button.", claimant, remaining_time)).unwrap();
-            return "Cannot fire during victory claim period".to_string();
+        if current_time.saturating_sub(*claim_time) < game.victory_timeout_seconds {
+            let remaining_time = game.victory_timeout_seconds - current_time.saturating_sub(*claim_time);
+            warn!("Cannot fire during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Contest' button.", claimant, remaining_time);
+            return Err(ChainError::Rejected("Cannot fire during victory claim period".to_string()));
         }
     }
 
     // Check if player's board hash matches the current state (current saved board hash)
     if player.current_state != data.board {
-        shared.tx.send(format!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid)).unwrap();
-        return "Board hash mismatch".to_string();
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    // Check if the shot was proven against the player's own join receipt
+    if !shared.guests.accepts(Command::Join, &data.join_id, now_secs()) {
+        warn!("Fire from {} in game {} composed with an unknown join guest", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Unknown join guest".to_string()));
+    }
+    if data.join_journal != player.join_digest {
+        warn!("Fire from {} in game {} composed with another join receipt", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Join receipt mismatch".to_string()));
+    }
+
+    // Check if the game has left its lobby
+    if !game.started {
+        warn!("Cannot fire before game {} has started", data.gameid);
+        return Err(ChainError::Rejected("The game has not started yet".to_string()));
+    }
+
+    // Check if enough players are left to play
+    if players < game.min_players {
+        shared.game_event(&data.gameid, format!(
+            "{} cannot fire in game {}: {} players are needed, {} are in the game",
+            data.fleet, data.gameid, game.min_players, players
+        ));
+        return Err(ChainError::Rejected("Not enough players in the game".to_string()));
     }
 
     // Check if it's the player's turn
     if game.next_player.as_ref() != Some(&data.fleet) {
-        shared.tx.send(format!("Not {}'s turn in game {}", data.fleet, data.gameid)).unwrap();
-        return "Not your turn".to_string();
+        warn!("Not {}'s turn in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Not your turn".to_string()));
     }
 
     // Check if someone has yet to report, including the player
-    if game.next_report.is_some() {
-        shared.tx.send(format!("Cannot fire until player {} has reported in game {}", game.next_report.as_ref().unwrap(), data.gameid)).unwrap();
-        return format!("Cannot fire until player {} has reported", game.next_report.as_ref().unwrap()).to_string();
+    if let Some(reporter) = &game.next_report {
+        warn!("Cannot fire until player {} has reported in game {}", reporter, data.gameid);
+        return Err(ChainError::Rejected(format!("Cannot fire until player {} has reported", reporter)));
     }
 
-    // Check if the target position is valid
-    if data.pos > 99 {
-        shared.tx.send(format!("Invalid target position {} in game {}", xy_pos(data.pos), data.gameid)).unwrap();
-        return "Invalid target position".to_string();
+    // Check if the player has proved its remaining fleet recently enough
+    if due {
+        warn!("Player {} must prove its remaining fleet before firing in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Prove your remaining fleet with Status before firing".to_string()));
     }
 
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford firing in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+
     // Get current timestamp
-    let current_time = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let current_time = now_secs();
     
     // Update the timestamp for the player who just reported
     player.last_turn_timestamp = current_time;
-
-    // Mark that the first shot has been fired
-    game.first_shot_fired = true;
+    player.cycles_spent += cycles;
+    player.shots_fired += 1;
 
     // Update who needs to report to the player that was just fired at
     game.next_report = Some(data.target.clone());
     
     // Update the next player (next_player will be attributed to the player that was just fired at after they report)
     game.next_player = None;
+    game.last_shooter = Some(data.fleet.clone());
+    game.pending_shot = Some(input_data.receipt.clone());
+    game.turn_started_at = current_time;
+    game.last_activity = current_time;
+    game.shots += 1;
     
     // Send a message about the successful shot
     let msg = format!(
@@ -347,135 +1419,160 @@ fn handle_fire(shared: &SharedData, input_data: &CommunicationData) -> String {
         data.fleet,
         data.target,
         data.gameid,
-        xy_pos(data.pos)
+        data.pos
     );
-    shared.tx.send(msg).unwrap();
+    shared.game_event(&data.gameid, msg);
     
-    "OK".to_string()
+    Ok("OK".to_string())
 }
 
-fn handle_report(shared: &SharedData, input_data: &CommunicationData) -> String {
-    // Verify the receipt first
-    if input_data.receipt.verify(REPORT_ID).is_err() {
-        shared.tx.send("Attempting to report with invalid receipt".to_string()).unwrap();
-        return "Could not verify receipt".to_string();
-    }
-
+fn handle_report(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
     // Decode the journal
-    let data: ReportJournal = input_data.receipt.journal.decode().unwrap();
-    let mut gmap = shared.gmap.lock().unwrap();
+    let data: ReportJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
 
     // Check if the game exists
     let game = match gmap.get_mut(&data.gameid) {
         Some(game) => game,
         None => {
-            shared.tx.send(format!("Game {} not found", data.gameid)).unwrap();
-            return "Game not found".to_string();
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
         }
     };
 
+    // Blinded games only accept blinded reports
+    if game.blinded {
+        warn!("Game {} uses blinded reports, plain report rejected", data.gameid);
+        return Err(ChainError::Rejected("This game uses blinded reports".to_string()));
+    }
+
     // Check if the player is in the game
     let player = match game.pmap.get_mut(&data.fleet) {
         Some(player) => player,
         None => {
-            shared.tx.send(format!("Player {} not found in game {}", data.fleet, data.gameid)).unwrap();
-            return "Player not found".to_string();
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
         }
     };
 
     // Get verifying key from player
-    let verifying_key = &player.verifying_key;
+    let verifying_key = &shared.identities.player_key(&data.gameid, player);
 
-    // Convert signature bytes to Signature
-    let signature = Signature::from_bytes(input_data.signature.as_slice().try_into().unwrap());
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(verifying_key, input_data, &data.gameid) {
+        warn!("Invalid signature in report request");
+        return Err(err);
+    }
 
-    // Verify the signature against the receipt data
-    if verifying_key.verify(&input_data.receipt.journal.bytes.as_slice(), &signature).is_err() {
-        shared.tx.send("Invalid signature in report request".to_string()).unwrap();
-        return "Invalid signature".to_string();
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
     }
 
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
     // Check if someone has claimed victory and timeout is active
     if let Some((claimant, claim_time)) = &game.first_victory_claim {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let current_time = now_secs();
         
-        if current_time - claim_time < game.victory_timeout_seconds {
-            let remaining_time = game.victory_timeout_seconds - (current_time - claim_time);
-            shared.tx.send(format!("Cannot report during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Win' button.", claimant, remaining_time)).unwrap();
-            return "Cannot report during victory claim period".to_string();
+        if current_time.saturating_sub(*claim_time) < game.victory_timeout_seconds {
+            let remaining_time = game.victory_timeout_seconds - current_time.saturating_sub(*claim_time);
+            warn!("Cannot report during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Contest' button.", claimant, remaining_time);
+            return Err(ChainError::Rejected("Cannot report during victory claim period".to_string()));
         }
     }
 
     // Check if it's the player's turn to report
     if game.next_report.as_ref() != Some(&data.fleet) {
-        shared.tx.send(format!("Not {}'s turn to report in game {}", data.fleet, data.gameid)).unwrap();
-        return "Not your turn to report".to_string();
+        warn!("Not {}'s turn to report in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Not your turn to report".to_string()));
     }
 
     // Check if player's board hash matches the current state (current saved board hash)
     if player.current_state != data.board {
-        shared.tx.send(format!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid)).unwrap();
-        return "Board hash mismatch".to_string();
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
     }
 
-    // Check if position is valid
-    if data.pos > 99 {
-        shared.tx.send(format!("Invalid position {} in game {}", xy_pos(data.pos), data.gameid)).unwrap();
-        return "Invalid position".to_string();
+    // Check if the outcome was computed with the hits the player reported so far
+    if !player.history.same_hits(&data.hits) {
+        warn!("Player {}'s report does not use its earlier hits in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Earlier hits mismatch".to_string()));
     }
 
-    // Check if the report is valid ("Hit" or "Miss")
-    if data.report != "Hit" && data.report != "Miss" {
-        shared.tx.send(format!("Invalid report {} in game {}", data.report, data.gameid)).unwrap();
-        return "Invalid report".to_string();
+    // Check if the report was proven against the shot it answers
+    let answers_shot = game.pending_shot.as_ref()
+        .is_some_and(|shot| journal_digest(&shot.journal.bytes) == data.fire_journal);
+    if !shared.guests.accepts(Command::Fire, &data.fire_id, now_secs()) || !answers_shot {
+        warn!("Report from {} in game {} does not answer the pending shot", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Report does not answer the pending shot".to_string()));
     }
 
-    // Update the player's board state
-    if data.report == "Hit" {
-        // Remove the position from the player's board
-        player.current_state = data.next_board.clone();
-    } else {
-        // Update the player's board state to the next board
-        player.current_state = data.next_board.clone();
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford reporting in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    player.cycles_spent += cycles;
+
+    // Update the player's board state, a hit removes the position from the board
+    player.current_state = data.next_board.clone();
+    player.history.record(&data.next_board, &data.report.to_string(), data.pos, game.last_shooter.as_deref());
+
+    // Credit the hit to the player who fired
+    if data.report.is_hit() {
+        if let Some(shooter) = game.last_shooter.as_ref().and_then(|shooter| game.pmap.get_mut(shooter)) {
+            shooter.hits += 1;
+        }
     }
 
-    // Update the next player to the player that was just reported
-    game.next_player = Some(data.fleet.clone());
+    // Update the next player according to the game's turn policy
+    let next_player = turns::after_report(game, game.last_shooter.as_deref(), &data.fleet, &mut *shared.rng());
+    game.next_player = Some(next_player.clone());
     game.next_report = None;
+    game.last_shooter = None;
+    game.pending_shot = None;
+    game.turn_started_at = now_secs();
+    game.last_activity = game.turn_started_at;
     
     // Send a message about the successful report
     let msg = format!(
-        "{} reported {} at position {} in game {}",
+        "{} reported {} at position {} in game {}, {} plays next",
         data.fleet,
         data.report,
-        xy_pos(data.pos),
-        data.gameid
+        data.pos,
+        data.gameid,
+        next_player
     );
-    shared.tx.send(msg).unwrap();
+    shared.game_event(&data.gameid, msg);
 
-    "OK".to_string()
+    Ok("OK".to_string())
 }
 
-fn handle_wave(shared: &SharedData, input_data: &CommunicationData) -> String {
-    // Verify the receipt first
-    if input_data.receipt.verify(WAVE_ID).is_err() {
-        shared.tx.send("Attempting to wave with invalid receipt".to_string()).unwrap();
-        return "Could not verify receipt".to_string();
-    }
-
+fn handle_wave(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
     // Decode the journal
-    let data: BaseJournal = input_data.receipt.journal.decode().unwrap();
-    let mut gmap = shared.gmap.lock().unwrap();
+    let data: BaseJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
 
     // Check if the game exists
     let game = match gmap.get_mut(&data.gameid) {
         Some(game) => game,
         None => {
-            shared.tx.send(format!("Game {} not found", data.gameid)).unwrap();
-            return "Game not found".to_string();
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
         }
     };
 
@@ -483,104 +1580,111 @@ fn handle_wave(shared: &SharedData, input_data: &CommunicationData) -> String {
     let player = match game.pmap.get_mut(&data.fleet) {
         Some(player) => player,
         None => {
-            shared.tx.send(format!("Player {} not found in game {}", data.fleet, data.gameid)).unwrap();
-            return "Player not found".to_string();
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
         }
     };
 
     // Get verifying key from player
-    let verifying_key = &player.verifying_key;
+    let verifying_key = &shared.identities.player_key(&data.gameid, player);
 
-    // Convert signature bytes to Signature
-    let signature = Signature::from_bytes(input_data.signature.as_slice().try_into().unwrap());
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(verifying_key, input_data, &data.gameid) {
+        warn!("Invalid signature in wave request");
+        return Err(err);
+    }
 
-    // Verify the signature against the receipt data
-    if verifying_key.verify(&input_data.receipt.journal.bytes.as_slice(), &signature).is_err() {
-        shared.tx.send("Invalid signature in wave request".to_string()).unwrap();
-        return "Invalid signature".to_string();
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
     }
 
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
     // Check if someone has claimed victory and timeout is active
     if let Some((claimant, claim_time)) = &game.first_victory_claim {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let current_time = now_secs();
         
-        if current_time - claim_time < game.victory_timeout_seconds {
-            let remaining_time = game.victory_timeout_seconds - (current_time - claim_time);
-            shared.tx.send(format!("Cannot wave during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Win' button.", claimant, remaining_time)).unwrap();
-            return "Cannot wave during victory claim period".to_string();
+        if current_time.saturating_sub(*claim_time) < game.victory_timeout_seconds {
+            let remaining_time = game.victory_timeout_seconds - current_time.saturating_sub(*claim_time);
+            warn!("Cannot wave during victory claim period. {} claimed victory. {} seconds remaining to contest by clicking on 'Contest' button.", claimant, remaining_time);
+            return Err(ChainError::Rejected("Cannot wave during victory claim period".to_string()));
         }
     }
 
     // Check if player's board hash matches the current state (current saved board hash)
     if player.current_state != data.board {
-        shared.tx.send(format!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid)).unwrap();
-        return "Board hash mismatch".to_string();
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
     }
 
     // check if the player does not have to report
-    if game.next_report.is_some() {
-        shared.tx.send(format!("Cannot wave until player {} has reported in game {}", game.next_report.as_ref().unwrap(), data.gameid)).unwrap();
-        return format!("Cannot wave until player {} has reported", game.next_report.as_ref().unwrap()).to_string();
+    if let Some(reporter) = &game.next_report {
+        warn!("Cannot wave until player {} has reported in game {}", reporter, data.gameid);
+        return Err(ChainError::Rejected(format!("Cannot wave until player {} has reported", reporter)));
     }
 
     // Check if it's the player's turn to wave
     if game.next_player.as_ref() != Some(&data.fleet) {
-        shared.tx.send(format!("Not {}'s turn to wave in game {}", data.fleet, data.gameid)).unwrap();
-        return "Not your turn to wave".to_string();
+        warn!("Not {}'s turn to wave in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Not your turn to wave".to_string()));
     }
 
-    // Find the player who hasn't had a turn in the longest time
-    let mut oldest_timestamp = u64::MAX;
-    let mut next_player_name = String::new();
-    
-    for (player_name, player_data) in &game.pmap {
-        if player_name != &data.fleet && player_data.last_turn_timestamp < oldest_timestamp {
-            oldest_timestamp = player_data.last_turn_timestamp;
-            next_player_name = player_name.clone();
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford waving in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
         }
-    }
-    
-    if next_player_name.is_empty() {
-        shared.tx.send(format!("Player {} has no other players to pass turn to in game {}", data.fleet, data.gameid)).unwrap();
-        return "No other players to pass turn to".to_string();
-    }
+    };
+
+    // Find the next player according to the game's turn policy
+    let next_player_name = match turns::after_pass(game, &data.fleet, &mut *shared.rng()) {
+        Some(next) => next,
+        None => {
+            warn!("Player {} has no other players to pass turn to in game {}", data.fleet, data.gameid);
+            return Err(ChainError::Rejected("No other players to pass turn to".to_string()));
+        }
+    };
     
-    // Update the next player to the one who hasn't played the longest
+    // Every check passed: use up the nonce and the challenge of the receipt
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
     game.next_player = Some(next_player_name.clone());
+    if let Some(player) = game.pmap.get_mut(&data.fleet) {
+        player.receipt_nonce = input_data.nonce;
+        player.cycles_spent += cycles;
+    }
+    game.turn_started_at = now_secs();
+    game.last_activity = game.turn_started_at;
     
     // Send a message about the successful wave
     let msg = format!(
-        "{} waved in game {} and passed turn to {} (who hasn't played since timestamp {})",
+        "{} waved in game {} and passed turn to {}",
         data.fleet,
         data.gameid,
-        next_player_name,
-        oldest_timestamp
+        next_player_name
     );
-    shared.tx.send(msg).unwrap();
+    shared.game_event(&data.gameid, msg);
 
-    "OK".to_string()
+    Ok("OK".to_string())
 }
 
-fn handle_win(shared: &SharedData, input_data: &CommunicationData) -> String {
-    // Verify the receipt first
-    if input_data.receipt.verify(WIN_ID).is_err() {
-        shared.tx.send("Attempting to win with invalid receipt".to_string()).unwrap();
-        return "Could not verify receipt".to_string();
-    }
-
+fn handle_forfeit(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
     // Decode the journal
-    let data: BaseJournal = input_data.receipt.journal.decode().unwrap();
-    let mut gmap = shared.gmap.lock().unwrap();
+    let data: BaseJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
 
     // Check if the game exists
     let game = match gmap.get_mut(&data.gameid) {
         Some(game) => game,
         None => {
-            shared.tx.send(format!("Game {} not found", data.gameid)).unwrap();
-            return "Game not found".to_string();
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
         }
     };
 
@@ -588,183 +1692,958 @@ fn handle_win(shared: &SharedData, input_data: &CommunicationData) -> String {
     let player = match game.pmap.get_mut(&data.fleet) {
         Some(player) => player,
         None => {
-            shared.tx.send(format!("Player {} not found in game {}", data.fleet, data.gameid)).unwrap();
-            return "Player not found".to_string();
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&shared.identities.player_key(&data.gameid, player), input_data, &data.gameid) {
+        warn!("Invalid signature in forfeit request");
+        return Err(err);
+    }
+
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if player's board hash matches the current state (current saved board hash)
+    if player.current_state != data.board {
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    // Check if the player has not already conceded
+    if player.forfeited {
+        warn!("Player {} has already forfeited game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Already forfeited".to_string()));
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford forfeiting game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    player.cycles_spent += cycles;
+    shared.game_event(&data.gameid, format!("{} forfeited game {}", data.fleet, data.gameid));
+    Ok(concede(shared, &mut gmap, &data.gameid, &data.fleet))
+}
+
+// Take a player out of a game, which ends when a single player, or a single team, is left
+fn concede(shared: &SharedData, gmap: &mut HashMap<String, Game>, gameid: &str, fleet: &str) -> String {
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return "Game not found".to_string(),
+    };
+    if let Some(player) = game.pmap.get_mut(fleet) {
+        player.forfeited = true;
+        player.has_claimed_victory = false;
+    }
+    game.last_activity = now_secs();
+
+    // Drop a pending victory claim if nobody else made one
+    if !game.pmap.values().any(|player| player.has_claimed_victory) {
+        game.first_victory_claim = None;
+    }
+
+    // The game ends when a single player, or a single team, is left
+    if game.sides_left().len() <= 1 {
+        let winner = game.pmap
+            .iter()
+            .filter(|(_, player)| !player.forfeited)
+            .map(|(name, _)| name.clone())
+            .min();
+        match &winner {
+            Some(winner) => shared.game_event(gameid, format!(
+                "{} wins game {} by forfeit! Game ended.",
+                game.winner_label(winner),
+                gameid
+            )),
+            None => shared.game_event(gameid, format!("Game {} ended without players", gameid)),
+        }
+        if let Some(game) = gmap.remove(gameid) {
+            archive_game(shared, gameid, game, winner);
+        }
+        return "Forfeited. Game ended.".to_string();
+    }
+
+    // Pass the turn on if the game was waiting for the player. A pending report of the player
+    // is dropped along with the shot.
+    let waiting_on_player = game.next_player.as_deref() == Some(fleet) || game.next_report.as_deref() == Some(fleet);
+    if waiting_on_player {
+        if let Some(next_player) = turns::after_pass(game, fleet, &mut *shared.rng()) {
+            shared.game_event(gameid, format!("{} takes the turn in game {}", next_player, gameid));
+            game.next_player = Some(next_player);
+            game.next_report = None;
+            game.last_shooter = None;
+            game.pending_shot = None;
+            game.turn_started_at = now_secs();
+        }
+    }
+
+    "Forfeited.".to_string()
+}
+
+fn handle_win(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: WinJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+
+    // Blinded games only accept blinded victory claims
+    if game.blinded {
+        warn!("Game {} uses blinded reports, plain victory claim rejected", data.gameid);
+        return Err(ChainError::Rejected("This game uses blinded reports".to_string()));
+    }
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&data.fleet) {
+        Some(player) => player,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
         }
     };
 
     // Get verifying key from player
-    let verifying_key = &player.verifying_key;
+    let verifying_key = &shared.identities.player_key(&data.gameid, player);
 
-    // Convert signature bytes to Signature
-    let signature = Signature::from_bytes(input_data.signature.as_slice().try_into().unwrap());
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(verifying_key, input_data, &data.gameid) {
+        warn!("Invalid signature in win request");
+        return Err(err);
+    }
 
-    // Verify the signature against the receipt data
-    if verifying_key.verify(&input_data.receipt.journal.bytes.as_slice(), &signature).is_err() {
-        shared.tx.send("Invalid signature in win request".to_string()).unwrap();
-        return "Invalid signature".to_string();
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
     }
 
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
     // Check if player's board hash matches the current state (current saved board hash)
     if player.current_state != data.board {
-        shared.tx.send(format!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid)).unwrap();
-        return "Board hash mismatch".to_string();
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
     }
 
-    // Get current timestamp
-    let current_time = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-
     // Check if player has already claimed victory
     if player.has_claimed_victory {
-        shared.tx.send(format!("Player {} has already claimed victory in game {}", data.fleet, data.gameid)).unwrap();
-        return "Already claimed victory".to_string();
+        warn!("Player {} has already claimed victory in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Already claimed victory".to_string()));
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford claiming victory in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Check if the claim rests on the game's record, so it cannot just stall the game
+    if let Err(err) = check_win_basis(game, &data.fleet, &data.basis) {
+        warn!("Unfounded victory claim from {} in game {}: {}", data.fleet, data.gameid, err);
+        return Err(err);
+    }
+
+    claim_victory(shared, &mut gmap, &data.gameid, &data.fleet, cycles, input_data.nonce, &data.challenge)
+}
+
+// Check the basis of a plain victory claim against the reports applied to the game: the opponent
+// it names reported its whole fleet hit, or the claimant hit the whole fleet of every opponent
+// still playing
+fn check_win_basis(game: &Game, fleet: &str, basis: &WinBasis) -> Result<(), ChainError> {
+    match basis {
+        WinBasis::Sunk { opponent } => {
+            let player = match game.pmap.get(opponent) {
+                Some(player) if game.opponents(fleet, opponent) => player,
+                _ => return Err(ChainError::Rejected(format!("{} is not an opponent in this game", opponent))),
+            };
+            if player.history.hit_squares(None).len() != fleet_squares() {
+                return Err(ChainError::Rejected(format!("The fleet of {} is not destroyed", opponent)));
+            }
+        }
+        WinBasis::Hits { opponents } => {
+            for player in game.pmap.values().filter(|p| !p.forfeited && game.opponents(fleet, &p.name)) {
+                let recorded = player.history.hit_squares(Some(fleet));
+                let hits = opponents.iter().find(|(opponent, _)| *opponent == player.name).map(|(_, hits)| hits);
+                match hits {
+                    Some(hits) if hits.len() == fleet_squares() && hits.iter().all(|hit| recorded.contains(hit)) => {}
+                    _ => return Err(ChainError::Rejected(format!("The fleet of {} was not hit in full", player.name))),
+                }
+            }
+        }
     }
+    Ok(())
+}
+
+// Register a proven victory claim: the first claim starts the timeout, later ones contest it. The
+// nonce and challenge of the claim's receipt are used up once the claim is accepted.
+fn claim_victory(
+    shared: &SharedData,
+    gmap: &mut HashMap<String, Game>,
+    gameid: &str,
+    fleet: &str,
+    cycles: u64,
+    nonce: u64,
+    challenge: &str,
+) -> Result<String, ChainError> {
+    let current_time = now_secs();
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    let player = match game.pmap.get_mut(fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Check if the player has not conceded
+    if player.forfeited {
+        warn!("Player {} has forfeited game {} and cannot claim victory", fleet, gameid);
+        return Err(ChainError::Rejected("Cannot claim victory after forfeiting".to_string()));
+    }
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = nonce;
+    redeem_challenge(shared, gameid, fleet, challenge);
 
     // Save that the player has declared victory
     player.has_claimed_victory = true;
+    player.cycles_spent += cycles;
+    game.last_activity = current_time;
 
     // Check if this is the first victory claim
-    if game.first_victory_claim.is_none() {
-        game.first_victory_claim = Some((data.fleet.clone(), current_time));
-        let msg = format!("{} claims victory in game {}. Other players have {} seconds to contest by clicking on 'Win' button.", 
-                         data.fleet, data.gameid, game.victory_timeout_seconds);
-        shared.tx.send(msg).unwrap();
-        return "Victory claimed - timeout started.".to_string();
-    }
+    let (first_claimant, first_claim_time) = match &game.first_victory_claim {
+        Some(claim) => claim,
+        None => {
+            game.first_victory_claim = Some((fleet.to_string(), current_time));
+            // Blinded games are contested with a blinded victory claim, which proves ships left too
+            let button = if game.blinded { "Win" } else { "Contest" };
+            let msg = format!("{} claims victory in game {}. Other players have {} seconds (until {}) to contest by clicking on '{}' button.", 
+                             fleet, gameid, game.victory_timeout_seconds, current_time + game.victory_timeout_seconds, button);
+            shared.game_event(gameid, msg);
+            return Ok("Victory claimed - timeout started.".to_string());
+        }
+    };
 
     // Check if we're still within the timeout period
-    let (first_claimant, first_claim_time) = game.first_victory_claim.as_ref().unwrap();
-    if current_time - first_claim_time < game.victory_timeout_seconds {
-        let remaining_time = game.victory_timeout_seconds - (current_time - first_claim_time);
-        let msg = format!("{} contests victory of player {} in game {}! Game will resume after {} seconds.", 
-                         data.fleet, first_claimant, data.gameid, remaining_time);
-        shared.tx.send(msg).unwrap();
-        return "Victory contested. Game continues.".to_string();
+    if current_time.saturating_sub(*first_claim_time) < game.victory_timeout_seconds {
+        // A teammate of the first claimant backs the claim instead of contesting it
+        if !game.opponents(fleet, first_claimant) {
+            shared.game_event(gameid, format!("{} backs the victory claim of its team in game {}", fleet, gameid));
+            return Ok("Victory claimed by your team - timeout running.".to_string());
+        }
+        let remaining_time = game.victory_timeout_seconds - current_time.saturating_sub(*first_claim_time);
+        let msg = format!("{} contests victory of player {} in game {}! Game will resume after {} seconds (at {}).", 
+                         fleet, first_claimant, gameid, remaining_time, first_claim_time + game.victory_timeout_seconds);
+        shared.game_event(gameid, msg);
+        return Ok("Victory contested. Game continues.".to_string());
     }
 
     // Timeout period has passed, check who won
-    let all_victors: Vec<String> = game.pmap
-        .iter()
-        .filter(|(_, player)| player.has_claimed_victory)
-        .map(|(name, _)| name.clone())
-        .collect();
+    match game.settle_claims() {
+        Ok(winner) => {
+            let msg = format!("Victory timeout expired. {} wins game {}! Game ended.", game.winner_label(&winner), gameid);
+            shared.game_event(gameid, msg);
+
+            // Clean everything, end the game and keep it in the archive
+            if let Some(game) = gmap.remove(gameid) {
+                archive_game(shared, gameid, game, Some(winner.clone()));
+            }
 
-    if all_victors.len() == 1 {
-        let winner = &all_victors[0];
-        let msg = format!("Victory timeout expired. {} wins game {}! Game ended.", winner, data.gameid);
-        shared.tx.send(msg).unwrap();
-        
-        // Clean everything and end the game
-        gmap.remove(&data.gameid);
-        
-        return format!("{} wins - Game ended", winner);
-    } else {
-        let conflict_msg = format!(
-            "Victory timeout expired in game {} with multiple claimants: {}. No winner declared. Game continues as normal.",
-            data.gameid,
-            all_victors.join(", ")
-        );
-        shared.tx.send(conflict_msg).unwrap();
-        
-        // Reset victory claims and continue the game
-        for (_, player) in &mut game.pmap {
-            player.has_claimed_victory = false;
+            Ok(format!("{} wins - Game ended", winner))
+        }
+        Err(all_victors) => {
+            let conflict_msg = format!(
+                "Victory timeout expired in game {} with multiple claimants: {}. No winner declared. Game continues as normal.",
+                gameid,
+                all_victors.join(", ")
+            );
+            shared.game_event(gameid, conflict_msg);
+
+            // Reset victory claims and continue the game
+            for (_, player) in &mut game.pmap {
+                player.has_claimed_victory = false;
+            }
+            game.first_victory_claim = None;
+
+            Ok("Multiple victory claims - no winner. Game continues as normal.".to_string())
         }
-        game.first_victory_claim = None;
-        
-        return "Multiple victory claims - no winner. Game continues as normal.".to_string();
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct GameState {
     next_player: Option<String>,
     next_report: Option<String>,
-    first_shot_fired: bool,
+    started: bool,
+    seq: u64,
+    blinded: bool,
+    report_turn: u32,
+    status_due: bool,
+    victory_timeout_seconds: u64,
+    victory_deadline: Option<u64>, // when the running victory claim's timeout expires
+    clock_left: Option<u64>, // seconds left on the player's clock, in games with clocks
+    paused: bool, // paused by its players, moves are refused until they resume it
+}
+
+// Longest time a game state request waits for the transaction it asked for with `min_seq`
+const MIN_SEQ_WAIT_MILLIS: u64 = 2000;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GameStateQuery {
+    min_seq: Option<u64>,
 }
 
 // Add new handler
-fn handle_game_state(shared: &SharedData, gameid: &str, fleet: &str) -> Result<GameState, String> {
-    let gmap = shared.gmap.lock().unwrap();
+fn handle_game_state(shared: &SharedData, gameid: &str, fleet: &str) -> Result<GameState, ChainError> {
+    let gmap = shared.games();
+    // Read under the game lock: every transaction up to this number is reflected in the state
+    let seq = *shared.applied.borrow();
     
     let game = match gmap.get(gameid) {
         Some(game) => game,
-        None => return Err("Game not found".to_string()),
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
     };
     
     // Verify player is in the game
-    if !game.pmap.contains_key(fleet) {
-        return Err("Player not in game".to_string());
-    }
+    let player = match game.pmap.get(fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not in game".to_string())),
+    };
     
     Ok(GameState {
         next_player: game.next_player.clone(),
         next_report: game.next_report.clone(),
-        first_shot_fired: game.first_shot_fired,
+        started: game.started,
+        seq,
+        blinded: game.blinded,
+        report_turn: player.blind.turn(),
+        status_due: status_due(game, player),
+        victory_timeout_seconds: game.victory_timeout_seconds,
+        victory_deadline: game.victory_deadline(),
+        clock_left: player.clock.map(|clock| clock.left),
+        paused: game.paused,
     })
 }
 
-async fn check_victory_timeouts(shared: &SharedData) {
-    let mut gmap = shared.gmap.lock().unwrap();
-    let mut games_to_remove = Vec::new();
-    
-    for (gameid, game) in gmap.iter_mut() {
-        if let Some((_first_claimant, first_claim_time)) = &game.first_victory_claim {
-            let current_time = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            
-            if current_time - first_claim_time >= game.victory_timeout_seconds {
-                // Handle timeout expiration logic here
-                let all_victors: Vec<String> = game.pmap
-                    .iter()
-                    .filter(|(_, player)| player.has_claimed_victory)
-                    .map(|(name, _)| name.clone())
-                    .collect();
-
-                if all_victors.len() == 1 {
-                    let winner = &all_victors[0];
-                    let msg = format!("Victory timeout expired. {} wins game {}! Game ended.", winner, gameid);
-                    shared.tx.send(msg).unwrap();
-                    games_to_remove.push(gameid.clone());
-                } else {
-                    let conflict_msg = format!(
-                        "Victory timeout expired in game {} with multiple claimants: {}. No winner declared. Game continues as normal.",
-                        gameid,
-                        all_victors.join(", ")
-                    );
-                    shared.tx.send(conflict_msg).unwrap();
-                    
-                    // Reset victory claims
-                    for (_, player) in &mut game.pmap {
-                        player.has_claimed_victory = false;
-                    }
-                    game.first_victory_claim = None;
-                }
+// Settle the victory claims of the games whose claim timed out, through the transaction log
+fn check_victory_timeouts(shared: &SharedData) {
+    let current_time = now_secs();
+    let due: Vec<String> = shared.games()
+        .iter()
+        .filter(|(_, game)| game.victory_deadline().is_some_and(|deadline| current_time >= deadline))
+        .map(|(gameid, _)| gameid.clone())
+        .collect();
+    for gameid in due {
+        timeouts::commit(shared, Timeout::VictoryClaims { gameid });
+    }
+}
+
+// Settle the victory claims of a game once their timeout expired: the single claimant (or team)
+// wins, several claimants go back to playing
+fn time_out_claims(shared: &SharedData, gameid: &str) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+    let current_time = now_secs();
+
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    if !game.victory_deadline().is_some_and(|deadline| current_time >= deadline) {
+        return Err(ChainError::Rejected("No victory claim timed out".to_string()));
+    }
+
+    match game.settle_claims() {
+        Ok(winner) => {
+            let msg = format!("Victory timeout expired. {} wins game {}! Game ended.", game.winner_label(&winner), gameid);
+            shared.game_event(gameid, msg);
+            if let Some(game) = gmap.remove(gameid) {
+                archive_game(shared, gameid, game, Some(winner));
             }
         }
+        Err(all_victors) => {
+            let conflict_msg = format!(
+                "Victory timeout expired in game {} with multiple claimants: {}. No winner declared. Game continues as normal.",
+                gameid,
+                all_victors.join(", ")
+            );
+            shared.game_event(gameid, conflict_msg);
+
+            // Reset victory claims
+            for (_, player) in &mut game.pmap {
+                player.has_claimed_victory = false;
+            }
+            game.first_victory_claim = None;
+        }
     }
-    
-    // Remove ended games
-    for gameid in games_to_remove {
-        gmap.remove(&gameid);
+    Ok("OK".to_string())
+}
+
+// End the games nobody has played in for longer than the configured TTL, through the transaction
+// log
+fn expire_stale_games(shared: &SharedData) {
+    let ttl = config().chain.game_ttl_seconds;
+    if ttl == 0 {
+        return;
+    }
+    let current_time = now_secs();
+    let expired: Vec<String> = shared.games()
+        .iter()
+        .filter(|(_, game)| !game.paused && current_time.saturating_sub(game.last_activity) >= ttl)
+        .map(|(gameid, _)| gameid.clone())
+        .collect();
+    for gameid in expired {
+        timeouts::commit(shared, Timeout::Expiry { gameid, ttl });
+    }
+}
+
+// End a game nobody has played in for `ttl` seconds, without a winner
+fn expire_game(shared: &SharedData, gameid: &str, ttl: u64) -> Result<String, ChainError> {
+    let current_time = now_secs();
+    let mut gmap = shared.games();
+
+    let idle = match gmap.get(gameid) {
+        Some(game) if !game.paused => current_time.saturating_sub(game.last_activity),
+        Some(_) => return Err(ChainError::Rejected("The game is paused".to_string())),
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    if idle < ttl {
+        return Err(ChainError::Rejected("The game is still played".to_string()));
+    }
+
+    if let Some(game) = gmap.remove(gameid) {
+        shared.game_event(gameid, format!("GameExpired: game {} ended after {} seconds without activity", gameid, idle));
+        archive_game(shared, gameid, game, None);
+    }
+    Ok("OK".to_string())
+}
+
+// Move a finished game into the archive, keeping only what is needed to verify annotations
+fn archive_game(shared: &SharedData, gameid: &str, game: Game, winner: Option<String>) {
+    let ended_at = now_secs();
+
+    // Keep the board histories and the commitments of a blinded game, so the boards can be
+    // revealed against them
+    let boards = game.pmap
+        .iter()
+        .map(|(name, player)| (name.clone(), player.history.clone()))
+        .collect();
+    let blind = if game.blinded {
+        game.pmap
+            .iter()
+            .map(|(name, player)| (name.clone(), BlindRecord::new(&player.blind)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    // Certify the outcome so players can prove it away from the chain
+    let seq = *shared.applied.borrow();
+    let certificate = match certificate::issue(&shared.genesis, gameid, &game, winner.clone(), seq, ended_at) {
+        Ok(certificate) => {
+            shared.game_event(gameid, format!("Certificate issued for game {}", gameid));
+            Some(certificate)
+        }
+        Err(e) => {
+            error!("Could not issue a certificate for game {}: {}", gameid, e);
+            None
+        }
+    };
+
+    // Add the game to the statistics of its players, unless it never left the lobby or the
+    // statistics already have it
+    if game.started && !replication::restoring() {
+        let results = game.pmap
+            .values()
+            .map(|player| GameResult {
+                fleet: player.name.clone(),
+                won: winner.as_ref().map(|winner| !game.opponents(winner, &player.name)),
+                shots_fired: player.shots_fired,
+                hits: player.hits,
+                seconds: ended_at.saturating_sub(game.started_at),
+            })
+            .collect();
+        shared.leaderboard.record_game(results);
+    }
+
+    let audit_required = game.audit_required;
+    let parameters = GameParameters::of(&game);
+    let players = game.pmap
+        .into_iter()
+        .map(|(name, player)| (name, player.verifying_key))
+        .collect();
+
+    shared.identities.forget_game(gameid);
+    shared.webhooks.game_ended(gameid, winner.clone());
+    shared.archived().insert(gameid.to_string(), ArchivedGame {
+        players,
+        winner,
+        ended_at,
+        annotations: Vec::new(),
+        annotation_nonces: HashMap::new(),
+        boards,
+        blind,
+        revealed: HashMap::new(),
+        audits: HashMap::new(),
+        audit_required,
+        certificate,
+        parameters,
+        rematch: None,
+    });
+}
+
+fn handle_annotation(shared: &SharedData, gameid: &str, input_data: &AnnotationData) -> Result<String, ChainError> {
+    let mut archive = shared.archived();
+
+    // Check if the game has been archived
+    let game = match archive.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found in archive".to_string())),
+    };
+
+    // Check if the player took part in the game
+    let verifying_key = match game.players.get(&input_data.fleet) {
+        Some(key) => key,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the annotation
+    let statement = AnnotationStatement {
+        gameid,
+        fleet: &input_data.fleet,
+        kind: input_data.kind,
+        text: &input_data.text,
+        nonce: input_data.nonce,
+    };
+    if !verify_canonical(verifying_key, ANNOTATION_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in annotation for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // Check if the annotation is not a replay of an earlier one
+    if input_data.nonce <= game.annotation_nonces.get(&input_data.fleet).copied().unwrap_or_default() {
+        warn!("Replayed annotation from {} in game {}", input_data.fleet, gameid);
+        return Err(ChainError::Rejected("Annotation nonce must increase".to_string()));
+    }
+
+    let current_time = now_secs();
+
+    // Check if the annotation window is still open
+    if current_time.saturating_sub(game.ended_at) > ANNOTATION_WINDOW_SECONDS {
+        return Err(ChainError::Rejected("Annotation window has closed".to_string()));
+    }
+
+    // Check the annotation size limits
+    let text = input_data.text.trim();
+    if text.is_empty() {
+        return Err(ChainError::Rejected("Annotation cannot be empty".to_string()));
+    }
+    if text.chars().count() > ANNOTATION_MAX_LEN || text.chars().any(|c| c.is_control()) {
+        return Err(ChainError::Rejected(format!("Annotation must be at most {} printable characters", ANNOTATION_MAX_LEN)));
+    }
+
+    let count = game.annotations.iter().filter(|a| a.fleet == input_data.fleet).count();
+    if count >= ANNOTATION_MAX_PER_PLAYER {
+        return Err(ChainError::Rejected("Too many annotations for this game".to_string()));
+    }
+
+    game.annotation_nonces.insert(input_data.fleet.clone(), input_data.nonce);
+    let seq = game.annotations.len() + 1;
+    game.annotations.push(Annotation {
+        seq,
+        fleet: input_data.fleet.clone(),
+        kind: input_data.kind,
+        text: text.to_string(),
+        timestamp: current_time,
+        hidden: false,
+    });
+
+    shared.game_event(gameid, format!("{} annotated game {}: {}", input_data.fleet, gameid, text));
+    Ok("OK".to_string())
+}
+
+async fn annotation_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    Json(input_data): Json<AnnotationData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "annotation",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Annotation { gameid, data: input_data }).map(|(_, response)| response)
+}
+
+fn handle_chat(shared: &SharedData, gameid: &str, input_data: &ChatData) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+
+    // Check if the game is running
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&input_data.fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the message
+    let statement = ChatStatement {
+        gameid,
+        fleet: &input_data.fleet,
+        text: &input_data.text,
+        nonce: input_data.nonce,
+    };
+    if !verify_canonical(&shared.identities.player_key(gameid, player), CHAT_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in chat message for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // Check if the message is not a replay of an earlier one
+    if input_data.nonce <= player.chat_nonce {
+        warn!("Replayed chat message from {} in game {}", input_data.fleet, gameid);
+        return Err(ChainError::Rejected("Chat nonce must increase".to_string()));
+    }
+
+    // Check the message size limits
+    let text = input_data.text.trim();
+    if text.is_empty() {
+        return Err(ChainError::Rejected("Message cannot be empty".to_string()));
+    }
+    if text.chars().count() > CHAT_MAX_LEN || text.chars().any(|c| c.is_control()) {
+        return Err(ChainError::Rejected(format!("Message must be at most {} printable characters", CHAT_MAX_LEN)));
+    }
+
+    // Chat shares the fleet's transaction budget, on the node it was sent to
+    if !replication::replaying() {
+        if let Err(retry_after) = shared.limits.per_fleet.check((gameid.to_string(), input_data.fleet.clone())) {
+            warn!("Rate limited chat messages of fleet {}", input_data.fleet);
+            return Err(ChainError::RateLimited("Too many messages, slow down".to_string(), retry_after));
+        }
+    }
+
+    player.chat_nonce = input_data.nonce;
+    shared.game_event(gameid, format!("{} says in game {}: {}", input_data.fleet, gameid, text));
+    Ok("OK".to_string())
+}
+
+async fn chat_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    Json(input_data): Json<ChatData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "chat",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Chat { gameid, data: input_data }).map(|(_, response)| response)
+}
+
+#[derive(Serialize)]
+struct ArchiveView {
+    players: Vec<String>,
+    winner: Option<String>,
+    ended_at: u64,
+    annotations: Vec<Annotation>,
+    revealed: HashMap<String, Vec<RevealedReport>>, // outcomes of the boards revealed after the game
+    boards: HashMap<String, Vec<MoveView>>, // reports applied to each board
+    audited: Vec<String>, // players who proved their reports consistent with their board
+}
+
+// Handler returning an archived game with its visible annotations, used by the replay viewer
+async fn archive_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> impl IntoResponse {
+    let archive = shared.archived();
+    match archive.get(&gameid) {
+        Some(game) => Json(ArchiveView {
+            players: game.players.keys().cloned().collect(),
+            winner: game.winner.clone(),
+            ended_at: game.ended_at,
+            annotations: game.annotations.iter().filter(|a| !a.hidden).cloned().collect(),
+            revealed: game.revealed.clone(),
+            boards: game.boards
+                .iter()
+                .map(|(fleet, history)| (fleet.clone(), history.moves().iter().map(MoveView::from).collect()))
+                .collect(),
+            audited: {
+                let mut audited: Vec<String> = game.audits.keys().cloned().collect();
+                audited.sort();
+                audited
+            },
+        }).into_response(),
+        None => (StatusCode::NOT_FOUND, "Game not found in archive".to_string()).into_response(),
+    }
+}
+
+// Handler returning the receipts applied to a game, running or archived, in the order they were
+// applied, so auditors can verify them and replay the game
+#[utoipa::path(
+    get,
+    path = "/games/{gameid}/receipts",
+    tag = "games",
+    params(("gameid" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Receipts applied to the game, in order", body = Vec<AppliedReceipt>),
+        (status = 404, description = "No receipts for this game", body = String),
+    ),
+)]
+async fn receipts_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<Vec<AppliedReceipt>>, ChainError> {
+    let receipts: Vec<AppliedReceipt> = shared.replication
+        .game_transactions(&gameid)
+        .into_iter()
+        .filter_map(|(index, entry)| match entry.tx {
+            Transaction::Chain(data) => Some(AppliedReceipt { index, block: shared.blocks.block_of(index), at: entry.at, data }),
+            _ => None,
+        })
+        .collect();
+    if receipts.is_empty() {
+        return Err(ChainError::NotFound("No receipts for this game".to_string()));
     }
+    Ok(Json(receipts))
 }
 
-// Add this handler function after the other handlers
+// Handler returning the signed certificate of a finished game
+#[utoipa::path(
+    get,
+    path = "/games/{gameid}/certificate",
+    tag = "games",
+    params(("gameid" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Certificate signed with the genesis key", body = SignedCertificate),
+        (status = 404, description = "No certificate for this game, or it awaits the winner's audit", body = String),
+    ),
+)]
+async fn certificate_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<SignedCertificate>, ChainError> {
+    let archive = shared.archived();
+    let game = match archive.get(&gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("No certificate for this game".to_string())),
+    };
+
+    // Check if the winner has been audited, when the game requires it
+    let awaiting_audit = game.winner.as_ref().is_some_and(|winner| !game.audits.contains_key(winner));
+    if game.audit_required && awaiting_audit {
+        return Err(ChainError::NotFound("The certificate is withheld until the winner audits its reports".to_string()));
+    }
+    match game.certificate.clone() {
+        Some(certificate) => Ok(Json(certificate)),
+        None => Err(ChainError::NotFound("No certificate for this game".to_string())),
+    }
+}
+
+// Handler returning the hex-encoded public genesis key that certificates are verified with
+async fn genesis_handler(Extension(shared): Extension<SharedData>) -> String {
+    hex::encode(shared.genesis.verifying_key().as_bytes())
+}
+
+#[utoipa::path(
+    get,
+    path = "/gamestate/{gameid}/{fleet}",
+    tag = "games",
+    params(
+        ("gameid" = String, Path, description = "Game ID"),
+        ("fleet" = String, Path, description = "Fleet ID"),
+        GameStateQuery,
+    ),
+    responses(
+        (status = 200, description = "Turn state of the game for the fleet", body = GameState),
+        (status = 404, description = "Game or player not found", body = String),
+        (status = 409, description = "The state has not reached `min_seq` yet", body = String),
+    ),
+)]
 async fn game_state_handler(
     Extension(shared): Extension<SharedData>,
     Path((gameid, fleet)): Path<(String, String)>,
-) -> impl IntoResponse {
-    match handle_game_state(&shared, &gameid, &fleet) {
-        Ok(game_state) => Json(game_state).into_response(),
-        Err(error) => (
-            axum::http::StatusCode::BAD_REQUEST,
-            error
-        ).into_response(),
+    Query(query): Query<GameStateQuery>,
+) -> Result<Json<GameState>, ChainError> {
+    // Wait briefly for the transaction the caller just submitted to be applied
+    if let Some(min_seq) = query.min_seq {
+        let mut applied = shared.applied.subscribe();
+        let wait = applied.wait_for(|seq| *seq >= min_seq);
+        let timeout = tokio::time::Duration::from_millis(MIN_SEQ_WAIT_MILLIS);
+        let reached = tokio::time::timeout(timeout, wait).await.is_ok_and(|result| result.is_ok());
+        if !reached {
+            return Err(ChainError::Rejected(format!("State has not reached sequence {} yet", min_seq)));
+        }
+    }
+    handle_game_state(&shared, &gameid, &fleet).map(Json)
+}
+
+// Handler returning the turn timelines of all running games, used by the dashboard
+async fn timelines_handler(Extension(shared): Extension<SharedData>) -> Json<Vec<Timeline>> {
+    let gmap = shared.games();
+    let now = now_secs();
+    let mut games: Vec<_> = gmap.iter().collect();
+    games.sort_by(|a, b| a.0.cmp(b.0));
+    Json(games.into_iter().map(|(gameid, game)| timeline(gameid, game, now)).collect())
+}
+
+async fn timeline_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<Timeline>, ChainError> {
+    let gmap = shared.games();
+    match gmap.get(&gameid) {
+        Some(game) => Ok(Json(timeline(&gameid, game, now_secs()))),
+        None => Err(ChainError::NotFound("Game not found".to_string())),
+    }
+}
+
+async fn timeline_js() -> impl IntoResponse {
+    ([(axum::http::header::CONTENT_TYPE, "application/javascript")], TIMELINE_JS)
+}
+
+// Spectator page following a single game
+async fn watch(Path(gameid): Path<String>) -> Html<String> {
+    // The route layer only lets through game IDs made of letters, digits, '-' and '_', which
+    // cannot break out of the JS strings, but the page escapes it anyway
+    let gameid = explorer::escape(&gameid);
+    Html(format!(
+        r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Watching game {gameid}</title>
+            <script src="/timeline.js"></script>
+        </head>
+        <body>
+            <div id="game"></div>
+            <ul id="events"></ul>
+            <script>
+                followTimelines(document.getElementById('game'), '/games/{gameid}/timeline');
+                // Register as a spectator and follow the public events of the game
+                fetch('/games/{gameid}/spectate', {{ method: 'POST' }})
+                    .then(response => response.ok ? response.json() : Promise.reject(response.status))
+                    .then(spectator => {{
+                        const events = new EventSource(spectator.stream);
+                        events.onmessage = function(event) {{
+                            const item = document.createElement('li');
+                            item.textContent = event.data;
+                            document.getElementById('events').appendChild(item);
+                        }};
+                    }})
+                    .catch(() => {{}});
+            </script>
+        </body>
+        </html>
+        "#
+    ))
+}
+
+#[derive(Serialize)]
+struct PlayerStats {
+    fleet: String,
+    cycles_spent: u64,
+    cycles_left: Option<u64>,
+    remaining_squares: Option<u32>, // ship squares left, as last proved with Status
+    status_shot: Option<u32>, // shots fired in the game when it was proved
+}
+
+#[derive(Serialize)]
+struct GameStats {
+    cycle_budget: Option<u64>,
+    shots: u32,
+    players: Vec<PlayerStats>,
+}
+
+// Handler returning the compute spent and the remaining fleet of each player of a running game
+async fn stats_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<GameStats>, ChainError> {
+    let gmap = shared.games();
+    let game = match gmap.get(&gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+
+    let mut players: Vec<PlayerStats> = game.pmap
+        .values()
+        .map(|player| PlayerStats {
+            fleet: player.name.clone(),
+            cycles_spent: player.cycles_spent,
+            cycles_left: game.cycle_budget.map(|budget| budget.saturating_sub(player.cycles_spent)),
+            remaining_squares: player.status.map(|status| status.remaining),
+            status_shot: player.status.map(|status| status.shot),
+        })
+        .collect();
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+
+    Ok(Json(GameStats {
+        cycle_budget: game.cycle_budget,
+        shots: game.shots,
+        players,
+    }))
+}
+
+// Handler returning the fire receipt awaiting its report, which the target's report is proven against
+async fn shot_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<Receipt>, ChainError> {
+    let gmap = shared.games();
+    match gmap.get(&gameid).map(|game| &game.pending_shot) {
+        Some(Some(shot)) => Ok(Json(shot.clone())),
+        Some(None) => Err(ChainError::NotFound("No shot is awaiting a report".to_string())),
+        None => Err(ChainError::NotFound("Game not found".to_string())),
     }
 }
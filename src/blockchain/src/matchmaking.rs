@@ -0,0 +1,129 @@
+// src/matchmaking.rs
+//
+// Matchmaking. Instead of agreeing on a game ID beforehand, a player can ask the chain to place
+// it in any open game: the chain assigns it to a game opened by matchmaking that still has room,
+// or opens a new one, and returns its ID. The player then joins that game as usual, and the game
+// starts like any other once its players are ready. Rated requests are only matched with fleets
+// of the same rating band, derived from their wins on the leaderboard.
+//
+// An assignment holds a seat in the game until the fleet joins it, or for MATCH_TTL_SECONDS.
+// Matchmaking is not a transaction, so each replica assigns seats on its own. A game made private
+// or played in teams by its first join is left out of matchmaking.
+
+use axum::{Extension, Json};
+use fleetconfig::config;
+use fleetcore::{MatchAssignment, MatchRequest};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::{now_secs, SharedData};
+
+// Seconds an assigned fleet has to join its game
+const MATCH_TTL_SECONDS: u64 = 600;
+
+// Wins between two rating bands
+const BAND_WINS: u32 = 5;
+
+// A game opened by matchmaking that has not started yet
+struct OpenMatch {
+    band: Option<u32>,
+    opened_at: u64,
+    // Fleets assigned to the game that have not joined it yet, with the time of their assignment
+    seats: HashMap<String, u64>,
+}
+
+#[derive(Default)]
+pub struct Matchmaking {
+    open: Mutex<HashMap<String, OpenMatch>>,
+}
+
+impl Matchmaking {
+    fn open(&self) -> MutexGuard<'_, HashMap<String, OpenMatch>> {
+        self.open.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn matchmake(shared: &SharedData, request: &MatchRequest) -> Result<MatchAssignment, ChainError> {
+    if request.fleet.is_empty() {
+        return Err(ChainError::Malformed("Fleet ID cannot be an empty string".to_string()));
+    }
+    let now = now_secs();
+    let band = request.rated.then(|| shared.leaderboard.wins(&request.fleet) / BAND_WINS);
+
+    let gmap = shared.games();
+    let mut open = shared.matchmaking.open();
+
+    // Forget the games that started, and the seats that were taken or never were
+    open.retain(|gameid, game| {
+        let joined = gmap.get(gameid);
+        game.seats.retain(|fleet, at| {
+            now < at + MATCH_TTL_SECONDS && !joined.is_some_and(|joined| joined.pmap.contains_key(fleet))
+        });
+        match joined {
+            Some(joined) => !joined.started && !joined.private && !joined.teams,
+            None => !game.seats.is_empty(),
+        }
+    });
+
+    // Fleets assigned to an open game, and how many it accepts
+    let fleets = |gameid: &str, game: &OpenMatch| {
+        gmap.get(gameid).map_or(0, |joined| joined.pmap.len()) + game.seats.len()
+    };
+    let capacity = |gameid: &str| {
+        gmap.get(gameid).map_or(config().chain.max_players, |joined| joined.max_players)
+    };
+
+    // A fleet asking again keeps its game
+    let assigned = open.iter().find(|(gameid, game)| {
+        game.seats.contains_key(&request.fleet)
+            || gmap.get(*gameid).is_some_and(|joined| joined.pmap.contains_key(&request.fleet))
+    });
+    if let Some((gameid, game)) = assigned {
+        return Ok(MatchAssignment { gameid: gameid.clone(), created: false, fleets: fleets(gameid, game), band: game.band });
+    }
+
+    // Oldest game of the same band with a free seat
+    let mut candidates: Vec<(&String, &OpenMatch)> = open
+        .iter()
+        .filter(|(gameid, game)| game.band == band && fleets(gameid, game) < capacity(gameid))
+        .collect();
+    candidates.sort_by(|a, b| a.1.opened_at.cmp(&b.1.opened_at).then(a.0.cmp(b.0)));
+    let gameid = candidates.first().map(|(gameid, _)| (*gameid).clone());
+
+    let (gameid, created) = match gameid {
+        Some(gameid) => (gameid, false),
+        None => {
+            let mut bytes = [0u8; 8];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let gameid = format!("match-{}", hex::encode(bytes));
+            open.insert(gameid.clone(), OpenMatch { band, opened_at: now, seats: HashMap::new() });
+            (gameid, true)
+        }
+    };
+
+    let game = match open.get_mut(&gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    game.seats.insert(request.fleet.clone(), now);
+    let assignment = MatchAssignment { gameid: gameid.clone(), created, fleets: fleets(&gameid, game), band };
+
+    shared.game_event(&gameid, format!(
+        "{} was matched into game {} ({} fleets assigned)",
+        request.fleet,
+        gameid,
+        assignment.fleets
+    ));
+    Ok(assignment)
+}
+
+pub async fn matchmake_handler(
+    Extension(shared): Extension<SharedData>,
+    Json(request): Json<MatchRequest>,
+) -> Result<Json<MatchAssignment>, ChainError> {
+    check_fleet(&request.fleet)?;
+    matchmake(&shared, &request).map(Json)
+}
@@ -0,0 +1,92 @@
+// src/monitoring.rs
+//
+// Prometheus metrics served on /metrics for capacity planning: receipts received, verified and
+// rejected per command, receipt verification latency, running games, connected event stream
+// clients and events dropped for clients that fell behind.
+
+use fleetcore::Command;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::Duration;
+
+// Histogram buckets (in seconds) of the receipt verification latency
+const VERIFICATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+// Why a transaction was rejected
+#[derive(Clone, Copy)]
+pub enum Rejection {
+    // The transaction or its journal is in an unsupported protocol version
+    Version,
+    // The receipt did not verify against an accepted guest
+    Receipt,
+    // The journal names a game or fleet outside the limits the guests enforce
+    Inputs,
+    // The reported cycle count is implausible
+    Cycles,
+    // The fleet sent too many transactions
+    RateLimit,
+    // The game rules refused the action
+    Contract,
+}
+
+impl Rejection {
+    fn label(self) -> &'static str {
+        match self {
+            Rejection::Version => "version",
+            Rejection::Receipt => "receipt",
+            Rejection::Inputs => "inputs",
+            Rejection::Cycles => "cycles",
+            Rejection::RateLimit => "rate_limit",
+            Rejection::Contract => "contract",
+        }
+    }
+}
+
+// Install the global metrics recorder and return the handle rendering the /metrics page
+pub fn install() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full("chain_receipt_verification_seconds".to_string()), VERIFICATION_BUCKETS)
+        .and_then(PrometheusBuilder::install_recorder)
+        .expect("failed to install the metrics recorder")
+}
+
+fn command(cmd: Command) -> String {
+    format!("{:?}", cmd)
+}
+
+pub fn receipt_received(cmd: Command) {
+    counter!("chain_receipts_received_total", "command" => command(cmd)).increment(1);
+}
+
+pub fn receipt_verified(cmd: Command, elapsed: Duration) {
+    counter!("chain_receipts_verified_total", "command" => command(cmd)).increment(1);
+    histogram!("chain_receipt_verification_seconds", "command" => command(cmd)).record(elapsed.as_secs_f64());
+}
+
+pub fn receipt_rejected(cmd: Command, reason: Rejection) {
+    counter!("chain_receipts_rejected_total", "command" => command(cmd), "reason" => reason.label()).increment(1);
+}
+
+pub fn set_active_games(games: usize) {
+    gauge!("chain_active_games").set(games as f64);
+}
+
+pub fn events_lagged(count: u64) {
+    counter!("chain_events_lagged_total").increment(count);
+}
+
+// Counts a connected event stream client for as long as it is alive
+pub struct SseClient;
+
+impl SseClient {
+    pub fn connect() -> Self {
+        gauge!("chain_sse_clients").increment(1.0);
+        SseClient
+    }
+}
+
+impl Drop for SseClient {
+    fn drop(&mut self) {
+        gauge!("chain_sse_clients").decrement(1.0);
+    }
+}
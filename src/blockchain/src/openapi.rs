@@ -0,0 +1,59 @@
+// src/openapi.rs
+//
+// OpenAPI description of the chain's API, served on `/openapi.json` with a Swagger UI on
+// `/swagger-ui`, so clients in other languages can be generated from it. It covers what players
+// and their tools use: submitting receipts on /chain and following them on /tx, reading the turn
+// state, and following a game's events, receipts and certificate. Receipts are described as
+// opaque objects, in the serde format of `risc0_zkvm::Receipt`.
+
+use axum::Router;
+use fleetcore::{
+    AppliedReceipt, CertifiedPlayer, Command, CommunicationData, GameCertificate, InvalidInput, SignedCertificate,
+    UnsupportedVersion,
+};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::events::{ChainEvent, EventPage, Resync};
+use crate::submissions::{Submission, TxStatus};
+use crate::GameState;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Fleet chain API"),
+    paths(
+        crate::smart_contract,
+        crate::game_state_handler,
+        crate::events_handler,
+        crate::receipts_handler,
+        crate::certificate_handler,
+        crate::logs,
+        crate::submissions::tx_handler,
+    ),
+    components(schemas(
+        AppliedReceipt,
+        CertifiedPlayer,
+        ChainEvent,
+        Command,
+        CommunicationData,
+        EventPage,
+        GameCertificate,
+        GameState,
+        InvalidInput,
+        Resync,
+        SignedCertificate,
+        Submission,
+        TxStatus,
+        UnsupportedVersion,
+    )),
+    tags(
+        (name = "chain", description = "Transactions"),
+        (name = "games", description = "State and history of games"),
+        (name = "events", description = "Live event stream"),
+    ),
+)]
+struct ApiDoc;
+
+pub fn routes() -> Router {
+    SwaggerUi::new("/swagger-ui").url("/openapi.json", ApiDoc::openapi()).into()
+}
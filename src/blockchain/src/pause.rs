@@ -0,0 +1,143 @@
+// src/pause.rs
+//
+// Pausing a game by mutual consent, for long games played over several sessions. A player of a
+// running game votes with a signed Pause, and the game is paused once every player still in it
+// voted so; resuming it takes the same unanimous vote. While a game is paused its clocks stop, it
+// does not expire, and moves (shots, reports, waves, victory claims and contests) are refused
+// with `423 Locked`. A game cannot be paused while a victory claim runs.
+
+use axum::{extract::Path, http::HeaderMap, Extension};
+use fleetcore::{verify_canonical, Command, PauseData, PauseStatement, PAUSE_DOMAIN};
+use tracing::{info_span, warn};
+
+use crate::correlation;
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::keys::parse_signature;
+use crate::replication::Transaction;
+use crate::{next_request_id, now_secs, SharedData};
+
+// Check if a transaction can be applied to the game, refusing the moves of a paused game
+pub fn check_paused(shared: &SharedData, gameid: &str, tx: &Transaction) -> Result<(), ChainError> {
+    let moves = match tx {
+        Transaction::Chain(input_data) => matches!(
+            input_data.cmd,
+            Command::Fire
+                | Command::Report
+                | Command::BlindReport
+                | Command::Wave
+                | Command::Win
+                | Command::BlindWin
+                | Command::Contest
+        ),
+        _ => false,
+    };
+    if moves && shared.games().get(gameid).is_some_and(|game| game.paused) {
+        warn!("Rejected a move in paused game {}", gameid);
+        return Err(ChainError::Paused("The game is paused until its players resume it".to_string()));
+    }
+    Ok(())
+}
+
+pub fn handle_pause(shared: &SharedData, gameid: &str, input_data: &PauseData) -> Result<String, ChainError> {
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&input_data.fleet) {
+        Some(player) => player,
+        None => return Err(ChainError::NotFound("Player not found".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the statement
+    let statement = PauseStatement {
+        gameid,
+        fleet: &input_data.fleet,
+        pause: input_data.pause,
+        nonce: input_data.nonce,
+    };
+    if !verify_canonical(&shared.identities.player_key(gameid, player), PAUSE_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in pause request for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // Check if the vote is not a replay of an earlier one
+    if input_data.nonce <= player.pause_nonce {
+        warn!("Stale pause nonce from {} in game {}", input_data.fleet, gameid);
+        return Err(ChainError::Rejected("Pause nonce must increase".to_string()));
+    }
+    player.pause_nonce = input_data.nonce;
+
+    if player.forfeited {
+        return Err(ChainError::Rejected("Players who forfeited cannot vote".to_string()));
+    }
+    if !game.started {
+        return Err(ChainError::Rejected("The game has not started yet".to_string()));
+    }
+    if input_data.pause == game.paused {
+        let state = if game.paused { "paused" } else { "running" };
+        return Err(ChainError::Rejected(format!("The game is already {}", state)));
+    }
+    if game.first_victory_claim.is_some() {
+        return Err(ChainError::Rejected("Cannot pause while a victory claim runs".to_string()));
+    }
+
+    game.pause_votes.insert(input_data.fleet.clone());
+    let voters = game.pmap.values().filter(|player| !player.forfeited).count();
+    // Players who voted, then forfeited, do not count
+    let votes = game.pause_votes.iter().filter(|fleet| game.pmap.get(*fleet).is_some_and(|player| !player.forfeited)).count();
+    let action = if input_data.pause { "pause" } else { "resume" };
+    shared.game_event(gameid, format!(
+        "{} votes to {} game {} ({} of {} players)",
+        input_data.fleet,
+        action,
+        gameid,
+        votes,
+        voters
+    ));
+    let now = now_secs();
+    game.last_activity = now;
+    if votes < voters {
+        return Ok("OK".to_string());
+    }
+
+    // Everyone still playing agreed
+    game.paused = input_data.pause;
+    game.pause_votes.clear();
+    if game.paused {
+        shared.game_event(gameid, format!("Game {} is paused", gameid));
+        Ok("Game paused".to_string())
+    } else {
+        // The player the game waits for gets a fresh turn
+        game.turn_started_at = now;
+        shared.game_event(gameid, format!("Game {} is resumed", gameid));
+        Ok("Game resumed".to_string())
+    }
+}
+
+pub async fn pause_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    axum::Json(input_data): axum::Json<PauseData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "pause",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Pause { gameid, data: input_data }).map(|(_, response)| response)
+}
@@ -0,0 +1,201 @@
+// src/rematch.rs
+//
+// Rematches. Once a game is over, any of its players can ask for a rematch with a signed Rematch
+// on /games/{gameid}/rematch. The chain reserves a new game for the same fleets, carries the keys
+// they played with over to it, so they join it without registering again, and announces it on the
+// ended game's event stream. The players then join the new game as usual, with a new board.
+//
+// A game has a single rematch: asking again answers with the same game ID, as long as the rematch
+// is reserved. Nobody else can join a rematch, and its reservation lapses after
+// REMATCH_TTL_SECONDS if nobody joined it, through the transaction log (see timeouts.rs). A game whose rematch ID would be longer than a game ID
+can be (see fleetcore's inputs module) has no rematch.
+
+use axum::{extract::Path, http::HeaderMap, Extension};
+use ed25519_dalek::VerifyingKey;
+use fleetcore::{check_id, verify_canonical, RematchData, RematchStatement, REMATCH_DOMAIN};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tracing::{info_span, warn};
+
+use crate::correlation;
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::keys::parse_signature;
+use crate::replication::Transaction;
+use crate::timeouts::{self, Timeout};
+use crate::{next_request_id, now_secs, Game, SharedData};
+
+// Seconds the players of a rematch have to start joining it
+const REMATCH_TTL_SECONDS: u64 = 3600;
+
+// A game reserved for the players of an ended game
+struct Rematch {
+    from: String,
+    fleets: HashSet<String>,
+    opened_at: u64,
+}
+
+#[derive(Default)]
+pub struct Rematches {
+    reserved: Mutex<HashMap<String, Rematch>>,
+}
+
+impl Rematches {
+    fn reserved(&self) -> MutexGuard<'_, HashMap<String, Rematch>> {
+        self.reserved.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Check if a fleet may join a game, which only the players of a rematch may
+    pub fn check(&self, gameid: &str, fleet: &str) -> Result<(), ChainError> {
+        match self.reserved().get(gameid) {
+            Some(rematch) if !rematch.fleets.contains(fleet) => {
+                warn!("{} tried to join rematch {} of game {}", fleet, gameid, rematch.from);
+                Err(ChainError::Unauthorized(format!("This game is a rematch for the players of game {}", rematch.from)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// ID of the rematch of a game: "g-rematch", then "g-rematch-2", "g-rematch-3"...
+fn rematch_id(gameid: &str) -> String {
+    if let Some(base) = gameid.strip_suffix("-rematch") {
+        return format!("{}-rematch-2", base);
+    }
+    if let Some((base, count)) = gameid.rsplit_once("-rematch-") {
+        if let Ok(count) = count.parse::<u32>() {
+            return format!("{}-rematch-{}", base, count + 1);
+        }
+    }
+    format!("{}-rematch", gameid)
+}
+
+pub fn handle_rematch(shared: &SharedData, gameid: &str, input_data: &RematchData) -> Result<String, ChainError> {
+    // Lock the games before the archive, as archiving a game does
+    let gmap = shared.games();
+    let mut archive = shared.archived();
+
+    // Check if the game is over
+    let game = match archive.get(gameid) {
+        Some(game) => game,
+        None => {
+            let msg = if gmap.contains_key(gameid) { "The game is not over yet" } else { "Game not found" };
+            return Err(ChainError::NotFound(msg.to_string()));
+        }
+    };
+
+    // Check if the player played the game
+    let verifying_key = match game.players.get(&input_data.fleet) {
+        Some(key) => *key,
+        None => return Err(ChainError::NotFound("Player not found in archived game".to_string())),
+    };
+
+    // Check the signature length before verifying it
+    parse_signature(&input_data.signature)?;
+
+    // Verify the signature against the canonical encoding of the statement
+    let statement = RematchStatement { gameid, fleet: &input_data.fleet };
+    if !verify_canonical(&verifying_key, REMATCH_DOMAIN, &statement, &input_data.signature) {
+        warn!("Invalid signature in rematch request for game {}", gameid);
+        return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    // A rematch already asked for is shared by all the players, until it lapses
+    if let Some(rematch) = game.rematch.as_ref().filter(|rematch| shared.rematches.reserved().contains_key(*rematch)) {
+        return Ok(rematch.clone());
+    }
+
+    let rematch = rematch_id(gameid);
+    // The players could not prove joining a game with a longer ID than the guests accept
+    if let Err(err) = check_id("game ID", &rematch) {
+        warn!("Rematch {} of game {} is not a valid game ID: {}", rematch, gameid, err);
+        return Err(ChainError::Rejected(format!("No rematch for game {}: {}", gameid, err)));
+    }
+    if gmap.contains_key(&rematch) || archive.contains_key(&rematch) {
+        warn!("Rematch {} of game {} is already taken", rematch, gameid);
+        return Err(ChainError::Rejected(format!("Game {} already exists", rematch)));
+    }
+    let game = match archive.get_mut(gameid) {
+        Some(game) => game,
+        None => return Err(ChainError::NotFound("Game not found".to_string())),
+    };
+    game.rematch = Some(rematch.clone());
+    let players: Vec<(String, VerifyingKey)> = game.players.iter().map(|(fleet, key)| (fleet.clone(), *key)).collect();
+    drop(archive);
+    drop(gmap);
+
+    // The players keep their keys in the rematch
+    for (fleet, key) in &players {
+        shared.identities.carry(&rematch, fleet, *key);
+    }
+    let mut fleets: Vec<&str> = players.iter().map(|(fleet, _)| fleet.as_str()).collect();
+    fleets.sort();
+    shared.rematches.reserved().insert(rematch.clone(), Rematch {
+        from: gameid.to_string(),
+        fleets: players.iter().map(|(fleet, _)| fleet.clone()).collect(),
+        opened_at: now_secs(),
+    });
+
+    shared.game_event(gameid, format!(
+        "{} asks for a rematch of game {}: {} join game {} with a new board",
+        input_data.fleet,
+        gameid,
+        fleets.join(", "),
+        rematch
+    ));
+    Ok(rematch)
+}
+
+// Check if nobody joined a rematch in time
+fn lapsed(gmap: &HashMap<String, Game>, gameid: &str, rematch: &Rematch, now: u64) -> bool {
+    !gmap.contains_key(gameid) && now >= rematch.opened_at + REMATCH_TTL_SECONDS
+}
+
+// Drop the rematches nobody joined in time, through the transaction log
+pub fn expire(shared: &SharedData) {
+    let now = now_secs();
+    let due: Vec<String> = {
+        let gmap = shared.games();
+        let reserved = shared.rematches.reserved();
+        reserved
+            .iter()
+            .filter(|(gameid, rematch)| lapsed(&gmap, gameid, rematch, now))
+            .map(|(gameid, _)| gameid.clone())
+            .collect()
+    };
+    for gameid in due {
+        timeouts::commit(shared, Timeout::Rematch { gameid });
+    }
+}
+
+// Drop a rematch nobody joined in time, with the keys carried over to it
+pub fn lapse(shared: &SharedData, gameid: &str) -> Result<String, ChainError> {
+    let now = now_secs();
+    let gmap = shared.games();
+    let mut reserved = shared.rematches.reserved();
+    if !reserved.get(gameid).is_some_and(|rematch| lapsed(&gmap, gameid, rematch, now)) {
+        return Err(ChainError::Rejected("The rematch has not lapsed".to_string()));
+    }
+    reserved.remove(gameid);
+    shared.identities.forget_game(gameid);
+    Ok("OK".to_string())
+}
+
+pub async fn rematch_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+    axum::Json(input_data): axum::Json<RematchData>,
+) -> Result<String, ChainError> {
+    check_fleet(&input_data.fleet)?;
+    let span = info_span!(
+        "rematch",
+        request_id = next_request_id(),
+        correlation_id = tracing::field::Empty,
+        gameid = %gameid,
+        fleet = %input_data.fleet,
+    );
+    let _entered = span.enter();
+    let _correlation = correlation::enter_request(&headers, &span);
+    shared.replication.commit(&shared, Transaction::Rematch { gameid, data: input_data }).map(|(_, response)| response)
+}
@@ -0,0 +1,603 @@
+// src/replication.rs
+//
+// Replication between several instances of the chain. Every transaction a node accepts is applied
+// and appended to its log under one lock, then gossiped to the peers listed in its configuration,
+// which verify the receipt again and apply the transaction as if they had accepted it themselves.
+// A fresh node set to `sync_from` another one downloads that node's log from /sync and replays it
+// before serving, so it starts with the games already running.
+//
+// Replicas must reach the same state, so a transaction is applied with the clock of the node that
+// accepted it and with the seed that node drew for its turns. Challenges are issued and redeemed
+// by the node a player talks to, and so are rate limits, so replicas skip both. A peer receives the
+// transactions of a node in the order they were accepted, but two nodes accepting transactions of
+// the same game at the same time could reach their peers in different orders: each game should be
+// played through one node, the others serving its state and events and taking over if it fails.
+// Timeouts and the admin API act on each node separately, in the order of its own log.
+
+use axum::{
+    extract::{DefaultBodyLimit, Query, Request},
+    http::header,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use fleetconfig::config;
+use fleetcore::{
+    AnnotationData, ChatData, CommunicationData, PauseData, ReadyData, RematchData, RevealData, TeamMessageData,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::admin::{perform, token_matches, OperatorAction};
+use crate::blocks::{describe, record_transaction};
+use crate::correlation;
+use crate::error::ChainError;
+use crate::storage::{Storage, Stored};
+use crate::timeouts::{self, Timeout};
+use crate::{apply_transaction, now_secs, SharedData};
+
+// Entries served per /sync request
+const SYNC_PAGE: usize = 64;
+
+// Longest wait before sending a transaction to an unreachable peer again
+const MAX_RETRY_SECONDS: u64 = 60;
+
+// A state change accepted by one of the nodes
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transaction {
+    Chain(CommunicationData),
+    Ready { gameid: String, data: ReadyData },
+    Annotation { gameid: String, data: AnnotationData },
+    Chat { gameid: String, data: ChatData },
+    Reveal { gameid: String, data: RevealData },
+    Pause { gameid: String, data: PauseData },
+    Rematch { gameid: String, data: RematchData },
+    TeamMessage { gameid: String, data: TeamMessageData },
+}
+
+// A transaction as committed to the log of the node that accepted it
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Replicated {
+    // Node that accepted the transaction, and its number there (from 1)
+    pub origin: String,
+    pub id: u64,
+    // Clock of the origin when it accepted the transaction
+    pub at: u64,
+    // Seed of the turns drawn by the transaction
+    pub seed: [u8; 32],
+    pub tx: Transaction,
+    // Correlation ID the host sent with the transaction, for the events it publishes on replicas
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SyncPage {
+    pub entries: Vec<Replicated>,
+    // Position to ask for the next page from
+    pub next: usize,
+}
+
+#[derive(Deserialize)]
+struct SyncQuery {
+    #[serde(default)]
+    since: usize,
+}
+
+#[derive(Default)]
+struct Log {
+    // Transactions applied by this node, in order
+    entries: Vec<Replicated>,
+    // Last transaction applied from each origin
+    last: HashMap<String, u64>,
+    // Log on disk, once restored
+    storage: Option<Storage>,
+}
+
+impl Log {
+    // Put a transaction or an operator action on disk before it is applied. Nothing is applied,
+    // nor answered, when it cannot be written.
+    fn write_ahead(&mut self, stored: &Stored) -> Result<(), ChainError> {
+        if let Some(storage) = &mut self.storage {
+            if let Err(e) = storage.append(stored) {
+                error!("Could not write the transaction log {}: {}", storage.path().display(), e);
+                return Err(ChainError::Unavailable(format!("Could not write the transaction log: {}", e)));
+            }
+        }
+        Ok(())
+    }
+
+    // Mark the entry written last as not applied, so it is not replayed
+    fn reject(&mut self) {
+        if let Some(storage) = &mut self.storage {
+            if let Err(e) = storage.append(&Stored::Rejected) {
+                error!("Could not write the transaction log {}: {}", storage.path().display(), e);
+            }
+        }
+    }
+}
+
+pub struct Replication {
+    // Name of this node in the log, unique to each start so a restarted node is not mistaken for
+    // its previous run
+    origin: String,
+    // Held while a transaction is applied, so every node applies them in the order of its log
+    log: Mutex<Log>,
+    peers: Vec<mpsc::UnboundedSender<Replicated>>,
+}
+
+// Transaction being applied on this thread
+#[derive(Clone, Copy)]
+struct Pinned {
+    at: u64,
+    replaying: bool,
+}
+
+thread_local! {
+    static PINNED: Cell<Option<Pinned>> = const { Cell::new(None) };
+}
+
+// Set while the transaction log is replayed at startup
+static RESTORING: AtomicBool = AtomicBool::new(false);
+
+// Unpins the clock once the transaction has been applied, even if a handler panicked
+struct PinGuard;
+
+impl Drop for PinGuard {
+    fn drop(&mut self) {
+        PINNED.with(|pinned| pinned.set(None));
+    }
+}
+
+// Clock of the node that accepted the transaction being applied, if any
+pub fn pinned_time() -> Option<u64> {
+    PINNED.with(|pinned| pinned.get().map(|pinned| pinned.at))
+}
+
+// Check if the transaction being applied was accepted by another node
+pub fn replaying() -> bool {
+    PINNED.with(|pinned| pinned.get().is_some_and(|pinned| pinned.replaying))
+}
+
+// Check if the chain is rebuilding its games from the transaction log, whose effects outside the
+// games (the leaderboard) were already kept
+pub fn restoring() -> bool {
+    RESTORING.load(Ordering::Relaxed)
+}
+
+fn apply(shared: &SharedData, entry: &Replicated, replaying: bool) -> Result<String, ChainError> {
+    PINNED.with(|pinned| pinned.set(Some(Pinned { at: entry.at, replaying })));
+    let _guard = PinGuard;
+    let _correlation = correlation::enter(entry.correlation_id.clone());
+    *shared.rng() = rand::rngs::StdRng::from_seed(entry.seed);
+    apply_transaction(shared, &entry.tx)
+}
+
+impl Replication {
+    // Start sending our transactions to the configured peers
+    pub fn start() -> Self {
+        let chain = &config().chain;
+        let origin = format!("{}-{:08x}", chain.node_id, rand::rngs::OsRng.next_u32());
+        let peers = match peer_token() {
+            Some(_) => chain.peers.clone(),
+            None => {
+                if !chain.peers.is_empty() {
+                    warn!("No peer token configured, transactions are not replicated");
+                }
+                Vec::new()
+            }
+        };
+
+        let peers = peers
+            .into_iter()
+            .map(|peer| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                info!("Replicating transactions to {}", peer);
+                tokio::spawn(forward(peer, receiver));
+                sender
+            })
+            .collect();
+        Replication { origin, log: Mutex::new(Log::default()), peers }
+    }
+
+    fn log(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Apply a transaction accepted by this node, then send it to the peers. Returns the index of
+    // the transaction in the blocks of this node, and the response of its handler.
+    pub fn commit(&self, shared: &SharedData, tx: Transaction) -> Result<(u64, String), ChainError> {
+        let mut log = self.log();
+        let mut seed = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        let entry = Replicated {
+            origin: self.origin.clone(),
+            id: log.last.get(&self.origin).map_or(1, |last| last + 1),
+            at: now_secs(),
+            seed,
+            tx,
+            correlation_id: correlation::current(),
+        };
+
+        log.write_ahead(&Stored::Transaction(entry.clone()))?;
+        let response = match apply(shared, &entry, false) {
+            Ok(response) => response,
+            Err(err) => {
+                log.reject();
+                return Err(err);
+            }
+        };
+        let index = record_transaction(shared, &entry.tx);
+        log.last.insert(entry.origin.clone(), entry.id);
+        for peer in &self.peers {
+            let _ = peer.send(entry.clone());
+        }
+        log.entries.push(entry);
+        Ok((index, response))
+    }
+
+    // Apply a transaction accepted by another node, unless it was already applied
+    pub fn receive(&self, shared: &SharedData, entry: Replicated) -> Result<(), ChainError> {
+        let mut log = self.log();
+        if entry.origin == self.origin || log.last.get(&entry.origin).is_some_and(|last| entry.id <= *last) {
+            return Ok(());
+        }
+
+        // Peers are trusted to relay transactions, not to prove them
+        if let Transaction::Chain(input_data) = &entry.tx {
+            if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&input_data.version) {
+                return Err(ChainError::UnsupportedVersion(input_data.version));
+            }
+            shared.guests.verify(input_data.cmd, &input_data.receipt, entry.at)?;
+        }
+
+        log.write_ahead(&Stored::Transaction(entry.clone()))?;
+        log.last.insert(entry.origin.clone(), entry.id);
+        match apply(shared, &entry, true) {
+            Ok(_) => {
+                record_transaction(shared, &entry.tx);
+                if matches!(entry.tx, Transaction::Chain(_)) {
+                    shared.applied.send_modify(|applied| *applied += 1);
+                }
+                log.entries.push(entry);
+            }
+            // The origin applied it to a state this node does not have (see the top of the file)
+            Err(err) => {
+                log.reject();
+                warn!("Could not apply transaction {} of {}: {}", entry.id, entry.origin, err);
+            }
+        }
+        Ok(())
+    }
+
+    // Apply the transactions of an exported game as if they came from a peer, stopping at the first
+    // one that does not apply. Returns the number of transactions applied.
+    pub fn import(&self, shared: &SharedData, entries: Vec<Replicated>) -> Result<usize, ChainError> {
+        let mut log = self.log();
+        let count = entries.len();
+        for (position, entry) in entries.into_iter().enumerate() {
+            if let Transaction::Chain(input_data) = &entry.tx {
+                if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&input_data.version) {
+                    return Err(ChainError::UnsupportedVersion(input_data.version));
+                }
+                shared.guests.verify(input_data.cmd, &input_data.receipt, entry.at)
+                    .map_err(|err| ChainError::Rejected(format!("Transaction {} does not verify: {}", position, err)))?;
+            }
+            log.write_ahead(&Stored::Transaction(entry.clone()))?;
+            if let Err(err) = apply(shared, &entry, true) {
+                log.reject();
+                return Err(ChainError::Rejected(format!("Transaction {} does not apply: {}", position, err)));
+            }
+            record_transaction(shared, &entry.tx);
+            if matches!(entry.tx, Transaction::Chain(_)) {
+                shared.applied.send_modify(|applied| *applied += 1);
+            }
+            log.entries.push(entry);
+        }
+        Ok(count)
+    }
+
+    // Take an action of the operator on a game, in the order of the log
+    pub fn operate(&self, shared: &SharedData, action: OperatorAction) -> Result<String, ChainError> {
+        let mut log = self.log();
+        log.write_ahead(&Stored::Operator { at: now_secs(), action: action.clone() })?;
+        perform(shared, &action).inspect_err(|_| log.reject())
+    }
+
+    // Act on a deadline that passed, in the order of the log and at the time it was written with
+    pub fn time_out(&self, shared: &SharedData, timeout: Timeout) -> Result<String, ChainError> {
+        let mut log = self.log();
+        let at = now_secs();
+        log.write_ahead(&Stored::Timeout { at, timeout: timeout.clone() })?;
+        PINNED.with(|pinned| pinned.set(Some(Pinned { at, replaying: false })));
+        let _guard = PinGuard;
+        timeouts::act(shared, &timeout).inspect_err(|_| log.reject())
+    }
+
+    // Rebuild the games from the transaction log kept by the previous runs, then keep appending to
+    // it. Entries marked as rejected are skipped; the last entry may have been written by a run
+    // that crashed before applying it, and is dropped if it no longer applies. Returns the number
+    // of transactions replayed.
+    pub fn restore(&self, shared: &SharedData) -> Result<usize, String> {
+        let name = &config().chain.transaction_log;
+        if name.is_empty() {
+            info!("No transaction log configured, the games are kept in memory only");
+            return Ok(0);
+        }
+        let (storage, stored) = Storage::open(config().storage_file(name))?;
+
+        let mut log = self.log();
+        RESTORING.store(true, Ordering::Relaxed);
+        let mut count = 0;
+        let mut result = Ok(());
+        let mut uncommitted = false;
+        let last = stored.len();
+        let mut entries = stored.into_iter().enumerate().peekable();
+        while let Some((position, stored)) = entries.next() {
+            if matches!(entries.peek(), Some((_, Stored::Rejected))) {
+                continue;
+            }
+            // The receipts were verified when they were applied the first time
+            let applied = match &stored {
+                Stored::Transaction(entry) => apply(shared, entry, true),
+                Stored::Operator { at, action } => {
+                    PINNED.with(|pinned| pinned.set(Some(Pinned { at: *at, replaying: true })));
+                    let _guard = PinGuard;
+                    perform(shared, action)
+                }
+                Stored::Timeout { at, timeout } => {
+                    PINNED.with(|pinned| pinned.set(Some(Pinned { at: *at, replaying: true })));
+                    let _guard = PinGuard;
+                    timeouts::act(shared, timeout)
+                }
+                Stored::Rejected => continue,
+            };
+            if let Err(err) = applied {
+                if position + 1 == last {
+                    warn!("Dropping the last entry of {}, it does not apply: {}", storage.path().display(), err);
+                    uncommitted = true;
+                } else {
+                    result = Err(format!("Entry {} of {} does not replay: {}", position + 1, storage.path().display(), err));
+                }
+                break;
+            }
+            if let Stored::Transaction(entry) = stored {
+                record_transaction(shared, &entry.tx);
+                if matches!(entry.tx, Transaction::Chain(_)) {
+                    shared.applied.send_modify(|applied| *applied += 1);
+                }
+                log.last.insert(entry.origin.clone(), entry.id);
+                log.entries.push(entry);
+                count += 1;
+            }
+        }
+        RESTORING.store(false, Ordering::Relaxed);
+        result?;
+        log.storage = Some(storage);
+        if uncommitted {
+            log.reject();
+        }
+        Ok(count)
+    }
+
+    // Transactions of a game with their index, which is their position in the log: both are
+    // assigned under the log lock
+    pub fn game_transactions(&self, gameid: &str) -> Vec<(u64, Replicated)> {
+        self.log()
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| describe(&entry.tx).1 == gameid)
+            .map(|(index, entry)| (index as u64, entry.clone()))
+            .collect()
+    }
+
+    fn page(&self, since: usize) -> SyncPage {
+        let log = self.log();
+        let entries: Vec<Replicated> = log.entries.iter().skip(since).take(SYNC_PAGE).cloned().collect();
+        let next = since + entries.len();
+        SyncPage { entries, next }
+    }
+
+    // Download and apply the log of another node, returning the number of transactions read
+    pub async fn sync(&self, shared: &SharedData, from: &str) -> Result<usize, String> {
+        let client = reqwest::Client::new();
+        let mut since = 0;
+        loop {
+            let mut request = client
+                .get(format!("{}/sync", from))
+                .query(&[("since", since)])
+                .timeout(Duration::from_secs(30));
+            if let Some(token) = peer_token() {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().await.map_err(|e| format!("Failed to reach {}: {}", from, e))?;
+            if !response.status().is_success() {
+                return Err(format!("{} refused to sync: {}", from, response.status()));
+            }
+            let page: SyncPage = response.json().await.map_err(|e| format!("Failed to parse sync page: {}", e))?;
+            if page.entries.is_empty() {
+                return Ok(since);
+            }
+            since = page.next;
+            for entry in page.entries {
+                if let Err(err) = self.receive(shared, entry) {
+                    warn!("Skipped a transaction from {}: {}", from, err);
+                }
+            }
+        }
+    }
+}
+
+fn peer_token() -> Option<&'static str> {
+    config().chain.peer_token.as_deref().filter(|token| !token.is_empty())
+}
+
+// Send the transactions of this node to a peer, in order, until it has them all
+async fn forward(peer: String, mut entries: mpsc::UnboundedReceiver<Replicated>) {
+    let client = reqwest::Client::new();
+    while let Some(entry) = entries.recv().await {
+        let mut delay = 1;
+        loop {
+            let mut request = client
+                .post(format!("{}/gossip", peer))
+                .json(&entry)
+                .timeout(Duration::from_secs(30));
+            if let Some(token) = peer_token() {
+                request = request.bearer_auth(token);
+            }
+            match request.send().await {
+                Ok(response) if response.status().is_success() => break,
+                // The peer is up but refused the transaction, sending it again would not help
+                Ok(response) if response.status().is_client_error() => {
+                    warn!("{} refused transaction {}: {}", peer, entry.id, response.status());
+                    break;
+                }
+                Ok(response) => warn!("{} failed to take transaction {}: {}", peer, entry.id, response.status()),
+                Err(e) => warn!("Could not reach {}: {}", peer, e),
+            }
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            delay = (delay * 2).min(MAX_RETRY_SECONDS);
+        }
+    }
+}
+
+async fn require_peer(request: Request, next: Next) -> Response {
+    let expected = match peer_token() {
+        Some(token) => token,
+        None => return ChainError::NotFound("Replication is disabled".to_string()).into_response(),
+    };
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match given {
+        Some(given) if token_matches(given, expected) => next.run(request).await,
+        _ => {
+            warn!("Unauthorized peer request to {}", request.uri().path());
+            ChainError::Unauthorized("Invalid peer token".to_string()).into_response()
+        }
+    }
+}
+
+async fn gossip_handler(
+    Extension(shared): Extension<SharedData>,
+    Json(entry): Json<Replicated>,
+) -> Result<String, ChainError> {
+    shared.replication.receive(&shared, entry)?;
+    Ok("OK".to_string())
+}
+
+async fn sync_handler(
+    Extension(shared): Extension<SharedData>,
+    Query(query): Query<SyncQuery>,
+) -> Json<SyncPage> {
+    Json(shared.replication.page(query.since))
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        // An entry wraps a transaction accepted on /chain
+        .route("/gossip", post(gossip_handler).layer(DefaultBodyLimit::max(config().chain.max_body_bytes + 4096)))
+        .route("/sync", get(sync_handler))
+        .route_layer(middleware::from_fn(require_peer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use fleetcore::{sign_canonical, AnnotationKind, AnnotationStatement, ANNOTATION_DOMAIN};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use risc0_zkvm::Digest;
+
+    use crate::{Game, Player};
+
+    const GAMEID: &str = "restored";
+    const CLAIMED_AT: u64 = 1_000;
+    const VICTORY_TIMEOUT: u64 = 60;
+
+    // Chain state restoring its games from a transaction log of its own
+    fn shared() -> SharedData {
+        let dir = std::env::temp_dir().join(format!("fleet-restore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        fleetconfig::init(|config| config.storage_path = Some(dir));
+        let key = SigningKey::from_bytes(&[9; 32]);
+        SharedData::new(key.clone(), key, PrometheusBuilder::new().build_recorder().handle())
+    }
+
+    // A running game in which alice claimed victory at CLAIMED_AT and bob never contested
+    fn claimed_game(alice: &SigningKey, bob: &SigningKey) -> Game {
+        let mut game = Game::new("alice", CLAIMED_AT, VICTORY_TIMEOUT, None);
+        for (name, key) in [("alice", alice), ("bob", bob)] {
+            let player = Player { ready: true, ..Player::new(name, Digest::default(), key.verifying_key(), CLAIMED_AT) };
+            game.pmap.insert(name.to_string(), player);
+            game.order.push(name.to_string());
+        }
+        if let Some(alice) = game.pmap.get_mut("alice") {
+            alice.has_claimed_victory = true;
+        }
+        game.started = true;
+        game.started_at = CLAIMED_AT;
+        game.next_player = Some("bob".to_string());
+        game.first_victory_claim = Some(("alice".to_string(), CLAIMED_AT));
+        game
+    }
+
+    #[test]
+    fn a_timed_out_game_is_archived_again_before_its_annotations() {
+        let shared = shared();
+        let alice = SigningKey::from_bytes(&[1; 32]);
+        let bob = SigningKey::from_bytes(&[2; 32]);
+        shared.games().insert(GAMEID.to_string(), claimed_game(&alice, &bob));
+
+        // The claim timed out, then the winner annotated the archived game
+        let text = "gg";
+        let statement = AnnotationStatement { gameid: GAMEID, fleet: "alice", kind: AnnotationKind::Gg, text, nonce: 1 };
+        let annotation = Replicated {
+            origin: "node-1".to_string(),
+            id: 1,
+            at: CLAIMED_AT + VICTORY_TIMEOUT + 30,
+            seed: [0; 32],
+            tx: Transaction::Annotation {
+                gameid: GAMEID.to_string(),
+                data: fleetcore::AnnotationData {
+                    fleet: "alice".to_string(),
+                    kind: AnnotationKind::Gg,
+                    text: text.to_string(),
+                    nonce: 1,
+                    signature: sign_canonical(&alice, ANNOTATION_DOMAIN, &statement).unwrap(),
+                },
+            },
+            correlation_id: None,
+        };
+        let path = config().storage_file(&config().chain.transaction_log);
+        let (mut storage, stored) = Storage::open(path).unwrap();
+        assert!(stored.is_empty());
+        let timeout = Timeout::VictoryClaims { gameid: GAMEID.to_string() };
+        storage.append(&Stored::Timeout { at: CLAIMED_AT + VICTORY_TIMEOUT, timeout }).unwrap();
+        storage.append(&Stored::Transaction(annotation)).unwrap();
+        drop(storage);
+
+        assert_eq!(shared.replication.restore(&shared), Ok(1));
+        assert!(!shared.games().contains_key(GAMEID));
+        let archive = shared.archived();
+        let game = archive.get(GAMEID).expect("the game is archived");
+        assert_eq!(game.winner.as_deref(), Some("alice"));
+        assert_eq!(game.ended_at, CLAIMED_AT + VICTORY_TIMEOUT);
+        assert_eq!(game.annotations.len(), 1);
+        assert_eq!(game.annotations[0].text, text);
+    }
+}
@@ -0,0 +1,70 @@
+// src/signing.rs
+//
+// Signed responses. Each node has its own long-lived response key (`response_key`, published on
+// `/pubkey`), distinct from the genesis key the replicas share to certify games. Every response is
+// signed over the request it answers, its status and its body (fleetcore::ResponseStatement), and
+// the signature is sent in the `x-chain-signature` header; events carry their own signature (see
+// events.rs). A host that knows the key can thus detect a forged "OK" from a man in the middle.
+// Streams (SSE and WebSocket upgrades) are not buffered, so only their events are signed.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use fleetcore::{sign_canonical, ResponseStatement, RESPONSE_DOMAIN, RESPONSE_NONCE_HEADER, RESPONSE_SIGNATURE_HEADER};
+use tracing::error;
+
+use crate::SharedData;
+
+// Handler returning the hex-encoded public key the node's responses and events are verified with
+pub async fn pubkey_handler(Extension(shared): Extension<SharedData>) -> String {
+    hex::encode(shared.responses.verifying_key().as_bytes())
+}
+
+fn streamed(response: &Response) -> bool {
+    response.status() == StatusCode::SWITCHING_PROTOCOLS
+        || response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+// Middleware signing the responses of the chain
+pub async fn sign_responses(Extension(shared): Extension<SharedData>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path_and_query().map_or_else(|| request.uri().path().to_string(), |p| p.to_string());
+    let nonce = request
+        .headers()
+        .get(RESPONSE_NONCE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    let response = next.run(request).await;
+    if streamed(&response) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Could not read the response to {} {}: {}", method, path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let statement = ResponseStatement::new(&method, &path, nonce.as_deref(), parts.status.as_u16(), &body);
+    match sign_canonical(&shared.responses, RESPONSE_DOMAIN, &statement) {
+        Ok(signature) => {
+            if let Ok(value) = HeaderValue::from_str(&hex::encode(signature)) {
+                parts.headers.insert(RESPONSE_SIGNATURE_HEADER, value);
+            }
+        }
+        Err(e) => error!("Could not sign the response to {} {}: {}", method, path, e),
+    }
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,187 @@
+// src/spectators.rs
+//
+// Spectators. Anyone can register as a spectator of a game with `POST /games/{id}/spectate`,
+// which returns a token for the game's spectator stream, `/games/{id}/spectate/stream?token=`.
+// The stream replays the public events of the game from its start (or from the browser's
+// Last-Event-ID when it reconnects), then follows them live: joins, shots, reports, claims and the
+// end of the game. Events only ever state what the chain verified, with boards identified by their
+// commitments, so the stream never carries the boards or the randomness behind them.
+//
+// Each game counts its connected spectator streams, shown in the game listings. Tokens are valid
+// for SPECTATOR_TOKEN_TTL_SECONDS, reconnections included.
+
+use axum::{
+    extract::{Path, Query},
+    http::HeaderMap,
+    response::{sse::Event, IntoResponse},
+    Extension, Json,
+};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use crate::error::ChainError;
+use crate::monitoring::{self, SseClient};
+use crate::{chain_event, now_secs, resync_event, SharedData};
+
+// Seconds a spectator token can be used to connect to the stream
+const SPECTATOR_TOKEN_TTL_SECONDS: u64 = 6 * 60 * 60;
+
+struct Ticket {
+    gameid: String,
+    expires_at: u64,
+}
+
+#[derive(Default)]
+struct SpectatorState {
+    tickets: HashMap<String, Ticket>,
+    // Spectator streams connected to each game
+    connected: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct Spectators {
+    state: Mutex<SpectatorState>,
+}
+
+impl Spectators {
+    fn state(&self) -> MutexGuard<'_, SpectatorState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Spectator streams connected to a game
+    pub fn count(&self, gameid: &str) -> usize {
+        self.state().connected.get(gameid).copied().unwrap_or(0)
+    }
+
+    // Drop the tokens of a game that is no longer kept
+    pub fn forget_game(&self, gameid: &str) {
+        self.state().tickets.retain(|_, ticket| ticket.gameid != gameid);
+    }
+}
+
+// Counts a spectator of a game for as long as its stream is alive
+struct Spectator {
+    spectators: Arc<Spectators>,
+    gameid: String,
+}
+
+impl Spectator {
+    fn connect(spectators: Arc<Spectators>, gameid: String) -> Self {
+        *spectators.state().connected.entry(gameid.clone()).or_default() += 1;
+        Spectator { spectators, gameid }
+    }
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        let mut state = self.spectators.state();
+        if let Some(count) = state.connected.get_mut(&self.gameid) {
+            *count -= 1;
+            if *count == 0 {
+                state.connected.remove(&self.gameid);
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SpectatorToken {
+    token: String,
+    // Path of the spectator stream, token included
+    stream: String,
+    expires_at: u64,
+}
+
+pub async fn spectate_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<SpectatorToken>, ChainError> {
+    // Only games the chain is running or keeps in its archive can be watched
+    if !shared.games().contains_key(&gameid) && !shared.archived().contains_key(&gameid) {
+        return Err(ChainError::NotFound("Game not found".to_string()));
+    }
+
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    let now = now_secs();
+    let expires_at = now + SPECTATOR_TOKEN_TTL_SECONDS;
+
+    let mut state = shared.spectators.state();
+    state.tickets.retain(|_, ticket| now < ticket.expires_at);
+    state.tickets.insert(token.clone(), Ticket { gameid: gameid.clone(), expires_at });
+    drop(state);
+
+    Ok(Json(SpectatorToken {
+        stream: format!("/games/{}/spectate/stream?token={}", gameid, token),
+        token,
+        expires_at,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    token: String,
+}
+
+// SSE stream of the public events of a game, for the holder of a spectator token
+pub async fn spectator_stream(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    Query(query): Query<StreamQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ChainError> {
+    let valid = shared.spectators
+        .state()
+        .tickets
+        .get(&query.token)
+        .is_some_and(|ticket| ticket.gameid == gameid && now_secs() < ticket.expires_at);
+    if !valid {
+        return Err(ChainError::Unauthorized("Invalid or expired spectator token".to_string()));
+    }
+
+    // Spectators catch up on the whole game, unless they resume after an event they saw
+    let last_seq = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok()?.parse().ok())
+        .unwrap_or(0);
+    let subscription = shared.events.subscribe(Some(last_seq), Some(&gameid));
+
+    let gap: Option<Result<Event, Box<dyn Error + Send + Sync>>> = subscription
+        .gap
+        .then(|| Ok(Event::default().event("gap").data("Some events were missed")));
+    let missed = subscription.missed.into_iter().map(|event| Ok(chain_event(&event)));
+    // Counted as connected until the stream is dropped
+    let client = (SseClient::connect(), Spectator::connect(shared.spectators.clone(), gameid.clone()));
+    let gameid = Some(gameid);
+    let live = BroadcastStream::new(subscription.live)
+        .scan(Some(subscription.last_seq), move |position, result| {
+            let _client = &client;
+            // The stream ends after asking the spectator to resync
+            let Some(last_seq) = *position else { return future::ready(None) };
+            let item = match result {
+                Ok(event) if event.gameid != gameid => None,
+                Ok(event) => {
+                    *position = Some(event.seq);
+                    Some(chain_event(&event))
+                }
+                // The spectator fell behind: tell it where it stopped, so it catches up from the
+                // game's history
+                Err(BroadcastStreamRecvError::Lagged(count)) => {
+                    monitoring::events_lagged(count);
+                    *position = None;
+                    Some(resync_event(&shared.events.resync(count, last_seq, gameid.as_deref())))
+                }
+            };
+            future::ready(Some(item))
+        })
+        .filter_map(|item| future::ready(item.map(Ok)));
+
+    Ok(axum::response::sse::Sse::new(stream::iter(gap).chain(stream::iter(missed)).chain(live)))
+}
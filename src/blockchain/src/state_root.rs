@@ -0,0 +1,54 @@
+// src/state_root.rs
+//
+// State roots. `GET /games/{gameid}/state-root` answers with the canonical state of a running game
+// (see fleetcore::StateSnapshot) and its digest. Every replica that applied the same transactions
+// to the game serves the same root, so comparing roots across replicas, or before and after a
+// reconnect, shows whether they diverged. The state leaves out what each node keeps on its own
+// clock, like the players' chess clocks and the game's last activity.
+
+use axum::{extract::Path, Extension, Json};
+use fleetcore::{PlayerSnapshot, StateRoot, StateSnapshot};
+
+use crate::error::ChainError;
+use crate::{Game, SharedData};
+
+fn snapshot(gameid: &str, game: &Game) -> StateSnapshot {
+    let mut players: Vec<PlayerSnapshot> = game.pmap
+        .values()
+        .map(|player| PlayerSnapshot {
+            fleet: player.name.clone(),
+            board: player.current_state,
+            ready: player.ready,
+            forfeited: player.forfeited,
+            claimed_victory: player.has_claimed_victory,
+            shots_fired: player.shots_fired,
+            hits: player.hits,
+            receipt_nonce: player.receipt_nonce,
+            team: player.team.clone(),
+        })
+        .collect();
+    players.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+
+    StateSnapshot {
+        gameid: gameid.to_string(),
+        started: game.started,
+        paused: game.paused,
+        next_player: game.next_player.clone(),
+        next_report: game.next_report.clone(),
+        victory_claim: game.first_victory_claim.clone(),
+        shots: game.shots,
+        players,
+    }
+}
+
+pub async fn state_root_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+) -> Result<Json<StateRoot>, ChainError> {
+    let gmap = shared.games();
+    // Read under the game lock: every transaction up to this number is reflected in the state
+    let seq = *shared.applied.borrow();
+    let game = gmap.get(&gameid).ok_or_else(|| ChainError::NotFound("Game not found".to_string()))?;
+    let state = snapshot(&gameid, game);
+    Ok(Json(StateRoot { seq, root: state.root(), state }))
+}
@@ -0,0 +1,110 @@
+// src/status.rs
+//
+// Remaining-fleet statistics. With the Status transaction a player proves how many ship squares
+// are left on its board, without revealing where they are, so spectators and opponents can follow
+// the progress of a game and spot a victory claim that cannot be right before its timeout. With
+// `status_every_shots`, new games require every player to send one after that many shots have
+// been fired since its last one, before it can fire again.
+//
+// Blinded games never remove hits from the board, so their status would tell nothing: Status is
+// only accepted in plain games.
+
+use fleetcore::{CommunicationData, StatusJournal};
+use tracing::warn;
+
+use crate::challenge::{check_challenge, redeem_challenge};
+use crate::error::ChainError;
+use crate::keys::verify_receipt;
+use crate::{charge_cycles, now_secs, record_player, Game, Player, SharedData};
+
+// Remaining fleet a player last proved
+#[derive(Clone, Copy)]
+pub struct FleetStatus {
+    pub remaining: u32,
+    // Shots fired in the game when it was proved
+    pub shot: u32,
+}
+
+// Check if a player must prove its remaining fleet before firing again
+pub fn status_due(game: &Game, player: &Player) -> bool {
+    let since = player.status.map_or(0, |status| status.shot);
+    game.status_every > 0 && !game.blinded && game.shots >= since + game.status_every
+}
+
+pub fn handle_status(shared: &SharedData, input_data: &CommunicationData) -> Result<String, ChainError> {
+    // Decode the journal
+    let data: StatusJournal = input_data.receipt.journal.decode()
+        .map_err(|_| ChainError::Malformed("Could not decode journal".to_string()))?;
+    record_player(&data.gameid, &data.fleet);
+    let mut gmap = shared.games();
+
+    // Check if the game exists
+    let game = match gmap.get_mut(&data.gameid) {
+        Some(game) => game,
+        None => {
+            warn!("Game {} not found", data.gameid);
+            return Err(ChainError::NotFound("Game not found".to_string()));
+        }
+    };
+
+    // Blinded games keep every square on the board
+    if game.blinded {
+        warn!("Game {} uses blinded reports, status rejected", data.gameid);
+        return Err(ChainError::Rejected("This game uses blinded reports".to_string()));
+    }
+    let shots = game.shots;
+
+    // Check if the player is in the game
+    let player = match game.pmap.get_mut(&data.fleet) {
+        Some(player) => player,
+        None => {
+            warn!("Player {} not found in game {}", data.fleet, data.gameid);
+            return Err(ChainError::NotFound("Player not found".to_string()));
+        }
+    };
+
+    // Verify the signature against the receipt data, the command and the game
+    if let Err(err) = verify_receipt(&shared.identities.player_key(&data.gameid, player), input_data, &data.gameid) {
+        warn!("Invalid signature in status request");
+        return Err(err);
+    }
+
+    // Check if the receipt is not a replay of an earlier one
+    if input_data.nonce <= player.receipt_nonce {
+        warn!("Stale receipt nonce from {} in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Receipt nonce must increase".to_string()));
+    }
+
+    // Check if the receipt answers a challenge the chain issued to the player
+    check_challenge(shared, &data.gameid, &data.fleet, &data.challenge)?;
+
+    // Check if player's board hash matches the current state (current saved board hash)
+    if player.current_state != data.board {
+        warn!("Player {}'s board hash does not match the current state in game {}", data.fleet, data.gameid);
+        return Err(ChainError::Rejected("Board hash mismatch".to_string()));
+    }
+
+    // Check if the player stays within the game's cycle budget
+    let cycles = match charge_cycles(game.cycle_budget, player.cycles_spent, input_data.cycles) {
+        Ok(cycles) => cycles,
+        Err(err) => {
+            warn!("Player {} cannot afford a status in game {}: {}", data.fleet, data.gameid, err);
+            return Err(err);
+        }
+    };
+
+    // Every check passed: use up the nonce and the challenge of the receipt
+    player.receipt_nonce = input_data.nonce;
+    redeem_challenge(shared, &data.gameid, &data.fleet, &data.challenge);
+    player.cycles_spent += cycles;
+    player.status = Some(FleetStatus { remaining: data.remaining, shot: shots });
+    game.last_activity = now_secs();
+
+    shared.game_event(&data.gameid, format!(
+        "{} has {} ship squares left in game {}",
+        data.fleet,
+        data.remaining,
+        data.gameid
+    ));
+    Ok("OK".to_string())
+}
@@ -0,0 +1,90 @@
+// src/storage.rs
+//
+// Transaction log on disk, written ahead of the games. The games are never written out: every
+// transaction this node is about to apply, whether it accepted it or a peer did, is appended to
+// `transaction_log` and fsynced before it is applied and answered, along with the actions of the
+// operator on games and the deadlines the chain acted on (see timeouts.rs). One that then fails to
+// apply is followed by a `rejected` mark. At startup the log is replayed through the same handlers
+// that applied it the first time (see Replication::restore), so the games rebuilt from storage are
+// the games that were served, and a move that was answered is never forgotten by a crash. The last
+// entry may not have been applied before a crash; it is replayed, and dropped if it does not apply.
+// Any other entry that no longer applies stops the chain from starting. Deadlines that passed while
+// the chain was down are acted on once the games are rebuilt.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::admin::OperatorAction;
+use crate::replication::Replicated;
+use crate::timeouts::Timeout;
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "stored", rename_all = "snake_case")]
+pub enum Stored {
+    Transaction(Replicated),
+    // Action of the operator on a game, with the clock of the node when it was taken
+    Operator { at: u64, action: OperatorAction },
+    // Deadline the chain acted on, with the clock of the node when it did
+    Timeout { at: u64, timeout: Timeout },
+    // The entry before it was written but did not apply
+    Rejected,
+}
+
+pub struct Storage {
+    path: PathBuf,
+    file: File,
+}
+
+impl Storage {
+    // Open the log kept by a previous run and read its entries. A last line cut short by a crash
+    // while it was written is dropped, any other unreadable line is an error.
+    pub fn open(path: PathBuf) -> Result<(Self, Vec<Stored>), String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+
+        let mut stored = Vec::new();
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        let mut valid = 0u64;
+        let mut number = 0;
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+            if read == 0 {
+                break;
+            }
+            number += 1;
+            match serde_json::from_str(line.trim_end()) {
+                Ok(entry) => stored.push(entry),
+                Err(_) if !line.ends_with('\n') => {
+                    warn!("Dropping the last line of {}, cut short", path.display());
+                    file.set_len(valid).map_err(|e| format!("Could not truncate {}: {}", path.display(), e))?;
+                    break;
+                }
+                Err(e) => return Err(format!("Line {} of {} is corrupt: {}", number, path.display(), e)),
+            }
+            valid += read as u64;
+        }
+        drop(reader);
+        Ok((Storage { path, file }, stored))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Append an entry and wait until it is on disk
+    pub fn append(&mut self, stored: &Stored) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(stored)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()
+    }
+}
@@ -0,0 +1,149 @@
+// src/submissions.rs
+//
+// Transaction IDs. Every transaction submitted on /chain (or SubmitReceipt) gets a unique ID,
+// returned in the `x-tx-id` header whether the transaction is applied or rejected, and
+// `GET /tx/{id}` reports what became of it: received, verified, applied (with its index in the
+// chain) or rejected (with the reason). The node keeps the latest 10000 submissions it received;
+// the IDs are local to it, its replicas only know the transactions by index.
+
+use axum::{extract::Path, Extension, Json};
+use fleetcore::Command;
+use rand::RngCore;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use utoipa::ToSchema;
+
+use crate::error::ChainError;
+use crate::SharedData;
+
+pub const TX_ID_HEADER: &str = "x-tx-id";
+// Submissions remembered, the oldest is forgotten beyond
+const MAX_SUBMISSIONS: usize = 10_000;
+
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TxStatus {
+    // Being verified
+    Received,
+    // The receipt verified, being applied to the game
+    Verified,
+    // Applied as the transaction `index` of the chain
+    Applied { index: u64, block: Option<u64> },
+    Rejected { reason: String },
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Submission {
+    id: String,
+    command: Command,
+    gameid: Option<String>,
+    fleet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+    received_at: u64,
+    #[serde(flatten)]
+    status: TxStatus,
+}
+
+#[derive(Default)]
+struct State {
+    submissions: HashMap<String, Submission>,
+    // IDs from the oldest submission
+    order: VecDeque<String>,
+}
+
+#[derive(Default)]
+pub struct Submissions {
+    state: Mutex<State>,
+}
+
+impl Submissions {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Give a new submission its ID
+    pub fn open(&self, command: Command, now: u64) -> String {
+        let mut bytes = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut bytes);
+        let id = hex::encode(bytes);
+
+        let mut state = self.state();
+        if state.order.len() == MAX_SUBMISSIONS {
+            if let Some(oldest) = state.order.pop_front() {
+                state.submissions.remove(&oldest);
+            }
+        }
+        state.order.push_back(id.clone());
+        let submission = Submission {
+            id: id.clone(),
+            command,
+            gameid: None,
+            fleet: None,
+            correlation_id: None,
+            received_at: now,
+            status: TxStatus::Received,
+        };
+        state.submissions.insert(id.clone(), submission);
+        id
+    }
+
+    // Record the game and fleet of a submission, once its journal is decoded
+    pub fn identify(&self, id: &str, gameid: &str, fleet: &str) {
+        if let Some(submission) = self.state().submissions.get_mut(id) {
+            submission.gameid = Some(gameid.to_string());
+            submission.fleet = Some(fleet.to_string());
+        }
+    }
+
+    // Record the correlation ID the host gave the action that sent a submission
+    pub fn correlate(&self, id: &str, correlation_id: &str) {
+        if let Some(submission) = self.state().submissions.get_mut(id) {
+            submission.correlation_id = Some(correlation_id.to_string());
+        }
+    }
+
+    pub fn update(&self, id: &str, status: TxStatus) {
+        if let Some(submission) = self.state().submissions.get_mut(id) {
+            submission.status = status;
+        }
+    }
+
+    // Forget a submission answered with the response of an earlier one
+    pub fn discard(&self, id: &str) {
+        let mut state = self.state();
+        state.submissions.remove(id);
+        state.order.retain(|other| other != id);
+    }
+
+    fn get(&self, id: &str) -> Option<Submission> {
+        self.state().submissions.get(id).cloned()
+    }
+}
+
+// Status of a transaction submitted to this node
+#[utoipa::path(
+    get,
+    path = "/tx/{id}",
+    tag = "chain",
+    params(("id" = String, Path, description = "Transaction ID returned in `x-tx-id`")),
+    responses(
+        (status = 200, description = "Status of the transaction", body = Submission),
+        (status = 404, description = "Unknown transaction ID", body = String),
+    ),
+)]
+pub async fn tx_handler(
+    Extension(shared): Extension<SharedData>,
+    Path(id): Path<String>,
+) -> Result<Json<Submission>, ChainError> {
+    let mut submission = shared
+        .submissions
+        .get(&id)
+        .ok_or_else(|| ChainError::NotFound("Unknown transaction ID".to_string()))?;
+    // The transaction may have been sealed in a block since it was applied
+    if let TxStatus::Applied { index, block } = &mut submission.status {
+        *block = shared.blocks.block_of(*index);
+    }
+    Ok(Json(submission))
+}
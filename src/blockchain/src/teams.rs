@@ -0,0 +1,73 @@
+// src/teams.rs
+//
+// Team games. A game whose creator joined with a team is played by teams: every player joins one,
+// nobody can fire at its own team, the turn always passes to a player of another team, and a
+// victory claim is made for the claimant's team, so teammates do not contest each other. The game
+// ends once a single team has players left, or when a team's claim stands. The winner is still
+// recorded as a fleet (a claimant, or a player left), and its whole team wins on the leaderboard.
+
+use std::collections::BTreeSet;
+
+use crate::Game;
+
+impl Game {
+    pub fn team_of(&self, fleet: &str) -> Option<&str> {
+        self.pmap.get(fleet).and_then(|player| player.team.as_deref())
+    }
+
+    // Side a player plays for: its team in a team game, itself otherwise
+    pub fn side<'a>(&'a self, fleet: &'a str) -> &'a str {
+        if self.teams {
+            self.team_of(fleet).unwrap_or(fleet)
+        } else {
+            fleet
+        }
+    }
+
+    // Check if two players play for different sides
+    pub fn opponents(&self, a: &str, b: &str) -> bool {
+        a != b && self.side(a) != self.side(b)
+    }
+
+    // Players of a team, forfeited ones included
+    pub fn members(&self, team: &str) -> BTreeSet<&str> {
+        self.pmap
+            .iter()
+            .filter(|(_, player)| player.team.as_deref() == Some(team))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    // Sides that still have players in the game
+    pub fn sides_left(&self) -> BTreeSet<&str> {
+        self.pmap
+            .iter()
+            .filter(|(_, player)| !player.forfeited)
+            .map(|(name, _)| self.side(name))
+            .collect()
+    }
+
+    // Name of the winning side in events
+    pub fn winner_label(&self, fleet: &str) -> String {
+        match self.team_of(fleet).filter(|_| self.teams) {
+            Some(team) => format!("Team {} ({})", team, fleet),
+            None => fleet.to_string(),
+        }
+    }
+
+    // Outcome of the victory claims once their timeout expired: the winning fleet (the first
+    // claimant by name) when every claim was made for the same side, all the claimants otherwise
+    pub fn settle_claims(&self) -> Result<String, Vec<String>> {
+        let mut claimants: Vec<&String> = self.pmap
+            .iter()
+            .filter(|(_, player)| player.has_claimed_victory)
+            .map(|(name, _)| name)
+            .collect();
+        claimants.sort();
+        let sides: BTreeSet<&str> = claimants.iter().map(|name| self.side(name)).collect();
+        match claimants.first() {
+            Some(winner) if sides.len() == 1 => Ok((*winner).clone()),
+            _ => Err(claimants.into_iter().cloned().collect()),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// src/timeline.rs
+//
+// Public "who's next" view of a running game for the dashboard and the spectator page. It is
+// built from the turn state the chain already keeps (next player, pending report, last turn of
+// each fleet, victory claim), so it never exposes anything a player has not published.
+
+use fleetconfig::TurnPolicy;
+use serde::Serialize;
+
+use crate::Game;
+
+#[derive(Serialize)]
+pub struct TimelineEntry {
+    fleet: String,
+    last_turn_timestamp: u64,
+    // Seconds since the fleet last fired or reported
+    idle_seconds: u64,
+    claimed_victory: bool,
+}
+
+// A shot that still has to be answered by its target
+#[derive(Serialize)]
+pub struct PendingReport {
+    from: Option<String>,
+    to: String,
+}
+
+#[derive(Serialize)]
+pub enum ClockState {
+    // Players are still joining, the game has not started
+    Lobby,
+    // Waiting for `next_player` to fire or wave
+    Turn,
+    // Waiting for the target of the last shot to report
+    Report,
+    // Someone claimed victory; other players can contest until the clock runs out at `deadline`
+    VictoryClaim { claimant: String, remaining_seconds: u64, deadline: u64 },
+}
+
+#[derive(Serialize)]
+pub struct Timeline {
+    gameid: String,
+    // Fleets in the order they are expected to play, starting with the one the game waits for
+    rotation: Vec<TimelineEntry>,
+    next_player: Option<String>,
+    pending_report: Option<PendingReport>,
+    turn_started_at: u64,
+    turn_elapsed_seconds: u64,
+    clock: ClockState,
+}
+
+pub fn timeline(gameid: &str, game: &Game, now: u64) -> Timeline {
+    // Under the default policy the reporter plays next once it has reported, and a wave passes
+    // the turn to the fleet that has not played for the longest time. Round-robin games follow
+    // the turn order, and random games have no predictable order.
+    let active = game.next_report.as_ref().or(game.next_player.as_ref());
+    let mut others: Vec<_> = game.pmap.values().filter(|p| Some(&p.name) != active).collect();
+    match game.turn_policy {
+        TurnPolicy::RoundRobin => {
+            let position = |name: &String| game.order.iter().position(|n| n == name).unwrap_or(0);
+            let start = active.map_or(0, position);
+            let len = game.order.len().max(1);
+            others.sort_by_key(|p| (position(&p.name) + len - start) % len);
+        }
+        _ => others.sort_by(|a, b| a.last_turn_timestamp.cmp(&b.last_turn_timestamp).then(a.name.cmp(&b.name))),
+    }
+    let rotation = active
+        .and_then(|name| game.pmap.get(name))
+        .into_iter()
+        .chain(others)
+        .map(|player| TimelineEntry {
+            fleet: player.name.clone(),
+            last_turn_timestamp: player.last_turn_timestamp,
+            idle_seconds: now.saturating_sub(player.last_turn_timestamp),
+            claimed_victory: player.has_claimed_victory,
+        })
+        .collect();
+
+    let pending_report = game.next_report.as_ref().map(|to| PendingReport {
+        from: game.last_shooter.clone(),
+        to: to.clone(),
+    });
+
+    let clock = match &game.first_victory_claim {
+        Some((claimant, claim_time)) => ClockState::VictoryClaim {
+            claimant: claimant.clone(),
+            remaining_seconds: game.victory_timeout_seconds.saturating_sub(now.saturating_sub(*claim_time)),
+            deadline: claim_time + game.victory_timeout_seconds,
+        },
+        None if !game.started => ClockState::Lobby,
+        None if game.next_report.is_some() => ClockState::Report,
+        None => ClockState::Turn,
+    };
+
+    Timeline {
+        gameid: gameid.to_string(),
+        rotation,
+        next_player: game.next_player.clone(),
+        pending_report,
+        turn_started_at: game.turn_started_at,
+        turn_elapsed_seconds: now.saturating_sub(game.turn_started_at),
+        clock,
+    }
+}
+
+// Renders timelines fetched from the chain into an element; shared by the dashboard and the
+// spectator page
+pub const TIMELINE_JS: &str = r#"
+function renderTimeline(container, t) {
+    const game = document.createElement('div');
+    game.className = 'timeline';
+    const title = document.createElement('h3');
+    title.textContent = 'Game ' + t.gameid;
+    game.appendChild(title);
+
+    const clock = document.createElement('p');
+    if (t.clock === 'Lobby') {
+        clock.textContent = 'Waiting for the players to get ready';
+    } else if (t.clock === 'Report') {
+        clock.textContent = 'Waiting for a report (' + t.turn_elapsed_seconds + 's)';
+    } else if (t.clock === 'Turn') {
+        clock.textContent = 'Turn of ' + t.next_player + ' (' + t.turn_elapsed_seconds + 's)';
+    } else if (t.clock.VictoryClaim) {
+        const claim = t.clock.VictoryClaim;
+        clock.textContent = claim.claimant + ' claims victory, ' + claim.remaining_seconds + 's left to contest';
+    }
+    game.appendChild(clock);
+
+    if (t.pending_report) {
+        const arrow = document.createElement('p');
+        arrow.textContent = (t.pending_report.from || '?') + ' → ' + t.pending_report.to + ' (report pending)';
+        game.appendChild(arrow);
+    }
+
+    const order = document.createElement('ol');
+    t.rotation.forEach((entry, i) => {
+        const item = document.createElement('li');
+        item.textContent = entry.fleet + ' — idle ' + entry.idle_seconds + 's' + (entry.claimed_victory ? ' — claimed victory' : '');
+        if (i === 0 && t.clock !== 'Lobby') {
+            item.style.fontWeight = 'bold';
+        }
+        order.appendChild(item);
+    });
+    game.appendChild(order);
+    container.appendChild(game);
+}
+
+function followTimelines(container, url) {
+    const refresh = async () => {
+        const res = await fetch(url);
+        container.textContent = '';
+        if (!res.ok) {
+            container.textContent = await res.text();
+            return;
+        }
+        const data = await res.json();
+        (Array.isArray(data) ? data : [data]).forEach(t => renderTimeline(container, t));
+    };
+    refresh();
+    setInterval(refresh, 1000);
+}
+"#;
@@ -0,0 +1,57 @@
+// src/timeouts.rs
+//
+// Deadlines the chain acts on by itself: victory claims that ran past their timeout, chess clocks
+// that ran out, games idle for longer than `game_ttl_seconds` and rematches nobody joined. They
+// are found every second, then taken through the transaction log like the actions of the
+// operator, with the clock of the node when they were taken. A restart replays them between the
+// transactions they happened between, so a game that timed out is archived again before the
+// annotations, reveals or rematches that followed it.
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::ChainError;
+use crate::{clocks, expire_game, rematch, time_out_claims, SharedData};
+
+// A deadline that passed in a game
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "timeout", rename_all = "snake_case")]
+pub enum Timeout {
+    // The victory claims of the game ran past their timeout
+    VictoryClaims { gameid: String },
+    // The running clock of the game ran out
+    Clock { gameid: String },
+    // Nobody played in the game for `ttl` seconds
+    Expiry { gameid: String, ttl: u64 },
+    // Nobody joined the rematch in time
+    Rematch { gameid: String },
+}
+
+impl Timeout {
+    fn gameid(&self) -> &str {
+        match self {
+            Timeout::VictoryClaims { gameid }
+            | Timeout::Clock { gameid }
+            | Timeout::Expiry { gameid, .. }
+            | Timeout::Rematch { gameid } => gameid,
+        }
+    }
+}
+
+// Act on a deadline. Each one checks again that it passed, at the time it is taken.
+pub fn act(shared: &SharedData, timeout: &Timeout) -> Result<String, ChainError> {
+    match timeout {
+        Timeout::VictoryClaims { gameid } => time_out_claims(shared, gameid),
+        Timeout::Clock { gameid } => clocks::run_out(shared, gameid),
+        Timeout::Expiry { gameid, ttl } => expire_game(shared, gameid, *ttl),
+        Timeout::Rematch { gameid } => rematch::lapse(shared, gameid),
+    }
+}
+
+// Take a deadline found by the checks through the log. A transaction may have beaten it to the game.
+pub fn commit(shared: &SharedData, timeout: Timeout) {
+    let gameid = timeout.gameid().to_string();
+    if let Err(err) = shared.replication.time_out(shared, timeout) {
+        warn!("Deadline of game {} not taken: {}", gameid, err);
+    }
+}
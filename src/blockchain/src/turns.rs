@@ -0,0 +1,64 @@
+// src/turns.rs
+//
+// Turn policies. The chain decides who plays after a report and after a player passes its turn
+// (by waving or forfeiting); players who forfeited are never picked. In a team game the turn always
+// goes to a player of another team.
+
+use fleetconfig::TurnPolicy;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::Game;
+
+// Opponent of `fleet` still in the game who hasn't had a turn in the longest time (the
+// first by name on a tie, so every replica picks the same one)
+fn longest_waiting(game: &Game, fleet: &str) -> Option<String> {
+    game.pmap
+        .iter()
+        .filter(|(name, player)| game.opponents(fleet, name) && !player.forfeited)
+        .min_by_key(|(name, player)| (player.last_turn_timestamp, name.as_str()))
+        .map(|(name, _)| name.clone())
+}
+
+// Opponent of `fleet` still in the game that comes after it in the turn order
+fn next_in_order(game: &Game, fleet: &str) -> Option<String> {
+    let start = game.order.iter().position(|name| name == fleet)?;
+    game.order
+        .iter()
+        .cycle()
+        .skip(start + 1)
+        .take(game.order.len() - 1)
+        .find(|name| game.opponents(fleet, name) && game.pmap.get(*name).is_some_and(|player| !player.forfeited))
+        .cloned()
+}
+
+// Random opponent of `fleet` still in the game. Candidates are sorted first, so replicas
+// applying the transaction with the same seed draw the same player.
+fn random_other(game: &Game, fleet: &str, rng: &mut impl Rng) -> Option<String> {
+    let mut candidates: Vec<&String> = game.pmap
+        .iter()
+        .filter(|(name, player)| game.opponents(fleet, name) && !player.forfeited)
+        .map(|(name, _)| name)
+        .collect();
+    candidates.sort();
+    candidates.choose(rng).map(|name| name.to_string())
+}
+
+// Who plays once `reporter` has answered the shot of `shooter`
+pub fn after_report(game: &Game, shooter: Option<&str>, reporter: &str, rng: &mut impl Rng) -> String {
+    let next = match (game.turn_policy, shooter) {
+        (TurnPolicy::ReactiveDefault, _) | (_, None) => None,
+        (TurnPolicy::RoundRobin, Some(shooter)) => next_in_order(game, shooter),
+        (TurnPolicy::Random, Some(shooter)) => random_other(game, shooter, rng),
+    };
+    next.unwrap_or_else(|| reporter.to_string())
+}
+
+// Who plays when `fleet` passes its turn, None when nobody else is left
+pub fn after_pass(game: &Game, fleet: &str, rng: &mut impl Rng) -> Option<String> {
+    match game.turn_policy {
+        TurnPolicy::ReactiveDefault => longest_waiting(game, fleet),
+        TurnPolicy::RoundRobin => next_in_order(game, fleet),
+        TurnPolicy::Random => random_other(game, fleet, rng),
+    }
+}
@@ -0,0 +1,311 @@
+// src/versions.rs
+//
+// Guest versions accepted by the chain. During a rolling upgrade, receipts proven by the previous
+// build of the guests (identified by their image IDs) keep being accepted until a cutoff time, so
+// hosts can be upgraded one at a time. Transactions are tagged with the guest version that
+// proved them and hosts still on the old version are warned until the cutoff is reached.
+//
+// The previous guests are listed in `legacy_guests` (fleet.toml), several versions at once if
+// needed, and `guest_version` labels the current ones. The older environment variables still add
+// one more previous version:
+// - LEGACY_GUEST_IDS: comma-separated `command=image_id` pairs, e.g. `join=3f2a...,fire=91bc...`
+// - LEGACY_GUEST_CUTOFF: UNIX time after which the previous guests are rejected
+// - LEGACY_GUEST_VERSION / GUEST_VERSION: labels of the previous and current guests
+//
+// Every accepted image ID is served on `/methods`, so hosts can check at startup that the chain
+// accepts the guests they were built with.
+
+use axum::{Extension, Json};
+use risc0_zkvm::{Digest, Receipt};
+use std::collections::HashMap;
+use tracing::warn;
+
+use fleetconfig::config;
+use fleetcore::{AcceptedMethod, AcceptedMethods, Command};
+use methods::{
+    AUDIT_ID, BLIND_REPORT_ID, BLIND_WIN_ID, CONTEST_ID, FIRE_ID, FORFEIT_ID, JOIN_ID, REGISTER_ID, REPORT_ID, STATUS_ID,
+    WAVE_ID, WIN_ID,
+};
+
+use crate::error::ChainError;
+use crate::{now_secs, SharedData};
+
+struct LegacyGuests {
+    version: String,
+    ids: HashMap<Command, Digest>,
+    cutoff: u64,
+}
+
+pub struct GuestVersions {
+    current: String,
+    legacy: Vec<LegacyGuests>,
+}
+
+// How a receipt was accepted
+pub enum Accepted {
+    Current(String),
+    // Proven by the previous guests, which stop being accepted at `cutoff`
+    Legacy { version: String, cutoff: u64 },
+}
+
+impl Accepted {
+    pub fn version(&self) -> &str {
+        match self {
+            Accepted::Current(version) => version,
+            Accepted::Legacy { version, .. } => version,
+        }
+    }
+
+    // Warning for the hosts still proving with previous guests
+    pub fn deprecation(&self) -> Option<String> {
+        match self {
+            Accepted::Current(_) => None,
+            Accepted::Legacy { version, cutoff } => {
+                Some(format!("guest version {} is deprecated and will be rejected after {}", version, cutoff))
+            }
+        }
+    }
+}
+
+fn current_id(cmd: Command) -> Digest {
+    match cmd {
+        Command::Join => JOIN_ID.into(),
+        Command::Fire => FIRE_ID.into(),
+        Command::Report => REPORT_ID.into(),
+        Command::Wave => WAVE_ID.into(),
+        Command::Win => WIN_ID.into(),
+        Command::BlindReport => BLIND_REPORT_ID.into(),
+        Command::BlindWin => BLIND_WIN_ID.into(),
+        Command::Forfeit => FORFEIT_ID.into(),
+        Command::Register => REGISTER_ID.into(),
+        Command::Status => STATUS_ID.into(),
+        Command::Audit => AUDIT_ID.into(),
+        Command::Contest => CONTEST_ID.into(),
+    }
+}
+
+fn parse_command(name: &str) -> Option<Command> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "join" => Some(Command::Join),
+        "fire" => Some(Command::Fire),
+        "report" => Some(Command::Report),
+        "wave" => Some(Command::Wave),
+        "win" => Some(Command::Win),
+        "blind_report" => Some(Command::BlindReport),
+        "blind_win" => Some(Command::BlindWin),
+        "forfeit" => Some(Command::Forfeit),
+        "register" => Some(Command::Register),
+        "status" => Some(Command::Status),
+        "audit" => Some(Command::Audit),
+        "contest" => Some(Command::Contest),
+        _ => None,
+    }
+}
+
+fn parse_id(name: &str, id: &str) -> Result<(Command, Digest), String> {
+    let cmd = parse_command(name).ok_or_else(|| format!("unknown command {}", name))?;
+    let id: Digest = id.trim().parse().map_err(|_| format!("invalid image ID for {}", name))?;
+    Ok((cmd, id))
+}
+
+// Parse `command=image_id` pairs
+fn parse_ids(list: &str) -> Result<HashMap<Command, Digest>, String> {
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|pair| {
+            let (name, id) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected command=image_id, got {}", pair))?;
+            parse_id(name, id)
+        })
+        .collect()
+}
+
+// Previous guests given in the environment, if any. An invalid configuration is reported and
+// ignored.
+fn legacy_from_env() -> Option<LegacyGuests> {
+    let ids = match std::env::var("LEGACY_GUEST_IDS") {
+        Ok(list) if !list.trim().is_empty() => list,
+        _ => return None,
+    };
+
+    let ids = match parse_ids(&ids) {
+        Ok(ids) => ids,
+        Err(e) => {
+            warn!("Ignoring LEGACY_GUEST_IDS: {}", e);
+            return None;
+        }
+    };
+    let cutoff = match std::env::var("LEGACY_GUEST_CUTOFF").ok().and_then(|s| s.parse().ok()) {
+        Some(cutoff) => cutoff,
+        None => {
+            warn!("Ignoring LEGACY_GUEST_IDS: LEGACY_GUEST_CUTOFF must be set to a UNIX time");
+            return None;
+        }
+    };
+    let version = std::env::var("LEGACY_GUEST_VERSION").unwrap_or_else(|_| "legacy".to_string());
+    Some(LegacyGuests { version, ids, cutoff })
+}
+
+impl GuestVersions {
+    // Read the accepted versions from the configuration and the environment. An invalid previous
+    // version is reported and ignored, the other ones are still accepted.
+    pub fn load() -> Self {
+        let chain = &config().chain;
+        let current = std::env::var("GUEST_VERSION").unwrap_or_else(|_| chain.guest_version.clone());
+
+        let mut legacy = Vec::new();
+        for guests in &chain.legacy_guests {
+            let ids: Result<HashMap<Command, Digest>, String> =
+                guests.ids.iter().map(|(name, id)| parse_id(name, id)).collect();
+            match ids {
+                Ok(ids) => legacy.push(LegacyGuests { version: guests.version.clone(), ids, cutoff: guests.cutoff }),
+                Err(e) => warn!("Ignoring legacy guests {}: {}", guests.version, e),
+            }
+        }
+        legacy.extend(legacy_from_env());
+        GuestVersions { current, legacy }
+    }
+
+    // Verify a receipt against the current guests, then against the previous ones while their
+    // window is open
+    pub fn verify(&self, cmd: Command, receipt: &Receipt, now: u64) -> Result<Accepted, ChainError> {
+        if receipt.verify(current_id(cmd)).is_ok() {
+            return Ok(Accepted::Current(self.current.clone()));
+        }
+
+        let legacy = self
+            .legacy
+            .iter()
+            .find(|legacy| legacy.ids.get(&cmd).is_some_and(|id| receipt.verify(*id).is_ok()));
+        match legacy {
+            Some(legacy) => self.within_window(legacy, now),
+            None => Err(ChainError::Unauthorized("Could not verify receipt".to_string())),
+        }
+    }
+
+    // Accept a receipt of previous guests while their upgrade window is open
+    fn within_window(&self, legacy: &LegacyGuests, now: u64) -> Result<Accepted, ChainError> {
+        if now >= legacy.cutoff {
+            return Err(ChainError::Rejected(format!(
+                "Guest version {} is no longer accepted, upgrade to {}",
+                legacy.version, self.current
+            )));
+        }
+        Ok(Accepted::Legacy { version: legacy.version.clone(), cutoff: legacy.cutoff })
+    }
+
+    // Check if an image ID is one of the accepted guests for a command, e.g. the join guest a
+    // fire receipt composed with
+    pub fn accepts(&self, cmd: Command, id: &Digest, now: u64) -> bool {
+        if *id == current_id(cmd) {
+            return true;
+        }
+        self.legacy
+            .iter()
+            .any(|legacy| now < legacy.cutoff && legacy.ids.get(&cmd) == Some(id))
+    }
+
+    // Image IDs accepted right now, the current ones first
+    pub fn accepted(&self, now: u64) -> AcceptedMethods {
+        let mut methods: Vec<AcceptedMethod> = Command::ALL
+            .iter()
+            .map(|&command| AcceptedMethod {
+                command,
+                version: self.current.clone(),
+                image_id: current_id(command).to_string(),
+                cutoff: None,
+            })
+            .collect();
+        for legacy in self.legacy.iter().filter(|legacy| now < legacy.cutoff) {
+            for &command in Command::ALL.iter() {
+                if let Some(id) = legacy.ids.get(&command) {
+                    methods.push(AcceptedMethod {
+                        command,
+                        version: legacy.version.clone(),
+                        image_id: id.to_string(),
+                        cutoff: Some(legacy.cutoff),
+                    });
+                }
+            }
+        }
+        AcceptedMethods { current: self.current.clone(), methods }
+    }
+}
+
+// Handler listing the accepted image IDs
+pub async fn methods_handler(Extension(shared): Extension<SharedData>) -> Json<AcceptedMethods> {
+    Json(shared.guests.accepted(now_secs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUTOFF: u64 = 1_000;
+
+    // A chain upgraded to "v2" that still accepts the join guest of "v1" until CUTOFF
+    fn rollover() -> (GuestVersions, Digest) {
+        let old_join = Digest::from([7u32; 8]);
+        let legacy = LegacyGuests {
+            version: "v1".to_string(),
+            ids: HashMap::from([(Command::Join, old_join)]),
+            cutoff: CUTOFF,
+        };
+        (GuestVersions { current: "v2".to_string(), legacy: vec![legacy] }, old_join)
+    }
+
+    fn served(guests: &GuestVersions, now: u64, version: &str) -> Vec<AcceptedMethod> {
+        guests.accepted(now).methods.into_iter().filter(|method| method.version == version).collect()
+    }
+
+    #[test]
+    fn previous_guests_are_listed_by_command() {
+        let id = Digest::from([7u32; 8]);
+        let ids = parse_ids(&format!("join={}, Fire={}", id, id)).unwrap();
+        assert_eq!(ids, HashMap::from([(Command::Join, id), (Command::Fire, id)]));
+        assert!(parse_ids("join").is_err());
+        assert!(parse_ids(&format!("shoot={}", id)).is_err());
+        assert!(parse_ids("join=nothex").is_err());
+    }
+
+    #[test]
+    fn both_versions_are_accepted_before_the_cutoff() {
+        let (guests, old_join) = rollover();
+        let now = CUTOFF - 10;
+        assert!(guests.accepts(Command::Join, &current_id(Command::Join), now));
+        assert!(guests.accepts(Command::Join, &old_join, now));
+        // The previous guests only cover the commands they list
+        assert!(!guests.accepts(Command::Fire, &old_join, now));
+
+        assert_eq!(served(&guests, now, "v2").len(), Command::ALL.len());
+        let legacy = served(&guests, now, "v1");
+        assert_eq!(legacy.len(), 1);
+        assert_eq!(legacy[0].image_id, old_join.to_string());
+        assert_eq!(legacy[0].cutoff, Some(CUTOFF));
+    }
+
+    #[test]
+    fn previous_guests_are_warned_about_until_the_cutoff() {
+        let (guests, _) = rollover();
+        let accepted = guests.within_window(&guests.legacy[0], CUTOFF - 1).unwrap();
+        assert_eq!(accepted.version(), "v1");
+        let warning = accepted.deprecation().unwrap();
+        assert!(warning.contains("v1") && warning.contains(&CUTOFF.to_string()));
+
+        assert!(Accepted::Current("v2".to_string()).deprecation().is_none());
+    }
+
+    #[test]
+    fn previous_guests_are_rejected_after_the_cutoff() {
+        let (guests, old_join) = rollover();
+        for now in [CUTOFF, CUTOFF + 1] {
+            assert!(matches!(guests.within_window(&guests.legacy[0], now), Err(ChainError::Rejected(_))));
+            assert!(!guests.accepts(Command::Join, &old_join, now));
+            assert!(served(&guests, now, "v1").is_empty());
+        }
+        // The current guests are still accepted
+        assert!(guests.accepts(Command::Join, &current_id(Command::Join), CUTOFF + 1));
+        assert_eq!(served(&guests, CUTOFF + 1, "v2").len(), Command::ALL.len());
+    }
+}
@@ -0,0 +1,498 @@
+// src/webhooks.rs
+//
+// Webhooks. A player (or a Discord/Slack bridge it runs) registers a callback URL for a running game
+// with `POST /games/{id}/hooks`, signed with its key, and the chain POSTs a JSON document to it for
+// each event of the game: joins, shots, reports, turn changes, victory claims, forfeits and the end
+// of the game. Each delivery is signed with HMAC-SHA256 under the hook's secret, returned once at
+// registration, in the `X-Fleet-Signature: sha256=<hex>` header, and retried with backoff when the
+// hook does not answer with a 2xx status. Deliveries to a hook are made in order, one at a time.
+//
+// The secret also authorizes the management routes of the hook (`GET` and `DELETE
+// /games/{id}/hooks/{hook}`, with `Authorization: Bearer <secret>`). Hooks are kept in memory by
+// the node they were registered on, which delivers the events of every transaction it applies,
+// its replicas' included. They are dropped once the end of their game is delivered.
+//
+// The chain only calls public addresses: the host of a callback URL is resolved at registration,
+// refused if it resolves to a loopback, private, link-local or otherwise reserved address, and
+// the deliveries go to the addresses checked then, without following redirects.
+
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
+};
+use fleetcore::{verify_canonical, BlindReportJournal, Command, FireJournal, HookStatement, ReportJournal, HOOK_DOMAIN};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::admin::token_matches;
+use crate::error::ChainError;
+use crate::ids::check_fleet;
+use crate::keys::parse_signature;
+use crate::limits::JournalHead;
+use crate::replication::Transaction;
+use crate::{now_secs, SharedData};
+
+// Hooks a game accepts, and hooks each of its players can register
+const MAX_HOOKS_PER_GAME: usize = 8;
+const MAX_HOOKS_PER_PLAYER: usize = 2;
+// Longest callback URL accepted
+const MAX_URL_LEN: usize = 2048;
+// Seconds to wait before each retry of a failed delivery
+const RETRY_DELAYS: [u64; 4] = [1, 5, 30, 120];
+// Seconds a hook has to answer a delivery
+const DELIVERY_TIMEOUT_SECONDS: u64 = 10;
+
+// Event of a game, as delivered to its hooks
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookEvent {
+    Joined { fleet: String },
+    ShotFired { fleet: String, target: String, pos: String },
+    // The outcome is only published in games without blinded reports
+    Reported { fleet: String, pos: String, outcome: Option<String> },
+    TurnChanged { next_player: Option<String>, next_report: Option<String> },
+    VictoryClaimed { fleet: String },
+    VictoryContested { fleet: String },
+    Forfeited { fleet: String },
+    GameEnded { winner: Option<String> },
+}
+
+impl HookEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            HookEvent::Joined { .. } => "joined",
+            HookEvent::ShotFired { .. } => "shot_fired",
+            HookEvent::Reported { .. } => "reported",
+            HookEvent::TurnChanged { .. } => "turn_changed",
+            HookEvent::VictoryClaimed { .. } => "victory_claimed",
+            HookEvent::VictoryContested { .. } => "victory_contested",
+            HookEvent::Forfeited { .. } => "forfeited",
+            HookEvent::GameEnded { .. } => "game_ended",
+        }
+    }
+}
+
+const EVENT_KINDS: [&str; 8] = [
+    "joined",
+    "shot_fired",
+    "reported",
+    "turn_changed",
+    "victory_claimed",
+    "victory_contested",
+    "forfeited",
+    "game_ended",
+];
+
+// Body of a delivery
+#[derive(Serialize)]
+struct Delivery<'a> {
+    hook: &'a str,
+    gameid: &'a str,
+    // Number of the delivery to this hook, from 1
+    seq: u64,
+    at: u64,
+    event: &'a HookEvent,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct HookStats {
+    delivered: u64,
+    // Deliveries given up after every retry failed
+    failed: u64,
+    last_error: Option<String>,
+}
+
+struct Hook {
+    gameid: String,
+    // Player who registered the hook
+    fleet: String,
+    url: String,
+    secret: String,
+    // Kinds of events delivered, all of them when empty
+    events: Vec<String>,
+    sent: u64,
+    queue: mpsc::UnboundedSender<(String, String)>,
+    stats: Arc<Mutex<HookStats>>,
+}
+
+#[derive(Default)]
+pub struct Webhooks {
+    hooks: Mutex<HashMap<String, Hook>>,
+    // Games that ended with their winner, delivered after the events of the transaction ending them
+    ended: Mutex<Vec<(String, Option<String>)>>,
+    // Nonce of the last registration of each player, by game and fleet
+    nonces: Mutex<HashMap<(String, String), u64>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn signature(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// Check if the chain may call an address: not loopback, private, link-local, shared (CGNAT),
+// multicast, documentation or unspecified
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || first == 0x2001 && ip.segments()[1] == 0x0db8)
+        }
+    }
+}
+
+// Resolve the host of a callback URL, which must be an http(s) URL to public addresses only
+async fn resolve_callback(url: &str) -> Result<(String, Vec<SocketAddr>), ChainError> {
+    let invalid = || ChainError::Malformed("The hook URL must be an http(s) URL".to_string());
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "http" | "https") || url.len() > MAX_URL_LEN {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().ok_or_else(invalid)?.trim_matches(|c| c == '[' || c == ']').to_string();
+    let port = parsed.port_or_known_default().ok_or_else(invalid)?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| ChainError::Malformed(format!("Cannot resolve the hook host {}", host)))?
+        .collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        warn!("Refused a hook to {}, which is not a public address", host);
+        return Err(ChainError::Rejected("The hook URL must point to a public address".to_string()));
+    }
+    Ok((host, addrs))
+}
+
+// Deliver the events queued for a hook in order, until the hook is dropped. The host of the URL
+// is pinned to the addresses checked at registration.
+async fn deliver(
+    url: String,
+    (host, addrs): (String, Vec<SocketAddr>),
+    secret: String,
+    mut queue: mpsc::UnboundedReceiver<(String, String)>,
+    stats: Arc<Mutex<HookStats>>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECONDS))
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(&host, &addrs)
+        .build()
+        .unwrap_or_default();
+    while let Some((kind, body)) = queue.recv().await {
+        let signature = signature(&secret, &body);
+        let mut error = None;
+        for delay in std::iter::once(0).chain(RETRY_DELAYS) {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            let result = client
+                .post(&url)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-fleet-event", &kind)
+                .header("x-fleet-signature", &signature)
+                .body(body.clone())
+                .send()
+                .await;
+            error = match result {
+                Ok(response) if response.status().is_success() => None,
+                Ok(response) => Some(format!("answered {}", response.status())),
+                Err(e) => Some(e.to_string()),
+            };
+            if error.is_none() {
+                break;
+            }
+        }
+        let mut stats = lock(&stats);
+        match error {
+            None => stats.delivered += 1,
+            Some(e) => {
+                warn!("Gave up delivering a {} event to {}: {}", kind, url, e);
+                stats.failed += 1;
+                stats.last_error = Some(e);
+            }
+        }
+    }
+}
+
+impl Webhooks {
+    fn hooks(&self) -> MutexGuard<'_, HashMap<String, Hook>> {
+        lock(&self.hooks)
+    }
+
+    // Check if a game has hooks, before working out its events
+    pub fn watching(&self, gameid: &str) -> bool {
+        self.hooks().values().any(|hook| hook.gameid == gameid)
+    }
+
+    // Queue an event of a game for its hooks
+    pub fn notify(&self, gameid: &str, event: HookEvent) {
+        let at = now_secs();
+        for (id, hook) in self.hooks().iter_mut().filter(|(_, hook)| hook.gameid == gameid) {
+            if !hook.events.is_empty() && !hook.events.iter().any(|kind| kind == event.kind()) {
+                continue;
+            }
+            hook.sent += 1;
+            let delivery = Delivery { hook: id, gameid, seq: hook.sent, at, event: &event };
+            if let Ok(body) = serde_json::to_string(&delivery) {
+                let _ = hook.queue.send((event.kind().to_string(), body));
+            }
+        }
+    }
+
+    pub fn game_ended(&self, gameid: &str, winner: Option<String>) {
+        if self.watching(gameid) {
+            lock(&self.ended).push((gameid.to_string(), winner));
+        }
+    }
+
+    // Deliver the end of the games that ended to their hooks, which are dropped once their queue
+    // is delivered
+    pub fn deliver_ends(&self) {
+        let ended = std::mem::take(&mut *lock(&self.ended));
+        for (gameid, winner) in ended {
+            self.notify(&gameid, HookEvent::GameEnded { winner });
+            self.forget_game(&gameid);
+        }
+    }
+
+    pub fn forget_game(&self, gameid: &str) {
+        self.hooks().retain(|_, hook| hook.gameid != gameid);
+        lock(&self.nonces).retain(|(game, _), _| game != gameid);
+    }
+}
+
+// Turn of a game, to tell which transactions change it
+pub type Turn = Option<(Option<String>, Option<String>)>;
+
+pub fn turn(shared: &SharedData, gameid: &str) -> Turn {
+    shared.games().get(gameid).map(|game| (game.next_player.clone(), game.next_report.clone()))
+}
+
+// Notify the hooks of a game of a transaction applied to it, given the turn before it
+pub fn applied(shared: &SharedData, gameid: &str, tx: &Transaction, before: Turn) {
+    if let Transaction::Chain(input_data) = tx {
+        let journal = &input_data.receipt.journal;
+        let fleet = || journal.decode::<JournalHead>().ok().map(|head| head.fleet);
+        let event = match input_data.cmd {
+            Command::Join => fleet().map(|fleet| HookEvent::Joined { fleet }),
+            Command::Fire => journal.decode::<FireJournal>().ok().map(|j| HookEvent::ShotFired {
+                fleet: j.fleet,
+                target: j.target,
+                pos: j.pos.to_string(),
+            }),
+            Command::Report => journal.decode::<ReportJournal>().ok().map(|j| HookEvent::Reported {
+                fleet: j.fleet,
+                pos: j.pos.to_string(),
+                outcome: Some(format!("{:?}", j.report)),
+            }),
+            Command::BlindReport => journal.decode::<BlindReportJournal>().ok().map(|j| HookEvent::Reported {
+                fleet: j.fleet,
+                pos: j.pos.to_string(),
+                outcome: None,
+            }),
+            Command::Win | Command::BlindWin => fleet().map(|fleet| HookEvent::VictoryClaimed { fleet }),
+            Command::Contest => fleet().map(|fleet| HookEvent::VictoryContested { fleet }),
+            Command::Forfeit => fleet().map(|fleet| HookEvent::Forfeited { fleet }),
+            Command::Wave | Command::Register | Command::Status | Command::Audit => None,
+        };
+        if let Some(event) = event {
+            shared.webhooks.notify(gameid, event);
+        }
+    }
+
+    // A game that ended has no turn left, its end is delivered when it is archived
+    let after = turn(shared, gameid);
+    if let Some((next_player, next_report)) = after.clone().filter(|_| after != before) {
+        shared.webhooks.notify(gameid, HookEvent::TurnChanged { next_player, next_report });
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HookRequest {
+    url: String,
+    // Kinds of events to deliver, all of them when absent
+    #[serde(default)]
+    events: Vec<String>,
+    // Player of the game registering the hook, and its signature of a HookStatement
+    fleet: String,
+    // Must be larger than the nonce of the player's previous registration, so it cannot be replayed
+    nonce: u64,
+    signature: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct RegisteredHook {
+    id: String,
+    // Signs the deliveries and authorizes the management of the hook; only returned here
+    secret: String,
+    events: Vec<String>,
+}
+
+pub async fn register_hook(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    Json(request): Json<HookRequest>,
+) -> Result<(StatusCode, Json<RegisteredHook>), ChainError> {
+    check_fleet(&request.fleet)?;
+    if let Some(kind) = request.events.iter().find(|kind| !EVENT_KINDS.contains(&kind.as_str())) {
+        return Err(ChainError::Malformed(format!("Unknown event {}", kind)));
+    }
+
+    // Only a player of the game registers hooks for it
+    parse_signature(&request.signature)?;
+    let statement = HookStatement {
+        gameid: &gameid,
+        fleet: &request.fleet,
+        url: &request.url,
+        events: &request.events,
+        nonce: request.nonce,
+    };
+    {
+        let gmap = shared.games();
+        let game = match gmap.get(&gameid) {
+            Some(game) => game,
+            None => return Err(ChainError::NotFound("Game not found".to_string())),
+        };
+        let player = match game.pmap.get(&request.fleet) {
+            Some(player) => player,
+            None => return Err(ChainError::NotFound("Player not found".to_string())),
+        };
+        if !verify_canonical(&shared.identities.player_key(&gameid, player), HOOK_DOMAIN, &statement, &request.signature) {
+            warn!("Invalid signature in hook registration for game {}", gameid);
+            return Err(ChainError::Unauthorized("Invalid signature".to_string()));
+        }
+    }
+
+    let target = resolve_callback(&request.url).await?;
+
+    let mut hooks = shared.webhooks.hooks();
+    if hooks.values().filter(|hook| hook.gameid == gameid).count() >= MAX_HOOKS_PER_GAME {
+        return Err(ChainError::Rejected(format!("Games accept at most {} hooks", MAX_HOOKS_PER_GAME)));
+    }
+    let registered = hooks.values().filter(|hook| hook.gameid == gameid && hook.fleet == request.fleet).count();
+    if registered >= MAX_HOOKS_PER_PLAYER {
+        return Err(ChainError::Rejected(format!("Players can register at most {} hooks per game", MAX_HOOKS_PER_PLAYER)));
+    }
+    // Check if the registration is not a replay of an earlier one
+    let mut nonces = lock(&shared.webhooks.nonces);
+    let nonce = nonces.entry((gameid.clone(), request.fleet.clone())).or_default();
+    if request.nonce <= *nonce {
+        warn!("Replayed hook registration of fleet {} in game {}", request.fleet, gameid);
+        return Err(ChainError::Rejected("Hook nonce must increase".to_string()));
+    }
+    *nonce = request.nonce;
+    drop(nonces);
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let id = hex::encode(bytes);
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let secret = hex::encode(bytes);
+
+    let (queue, receiver) = mpsc::unbounded_channel();
+    let stats = Arc::new(Mutex::new(HookStats::default()));
+    tokio::spawn(deliver(request.url.clone(), target, secret.clone(), receiver, stats.clone()));
+    hooks.insert(id.clone(), Hook {
+        gameid: gameid.clone(),
+        fleet: request.fleet.clone(),
+        url: request.url,
+        secret: secret.clone(),
+        events: request.events.clone(),
+        sent: 0,
+        queue,
+        stats,
+    });
+    drop(hooks);
+
+    shared.game_event(&gameid, format!("A webhook was registered for game {}", gameid));
+    Ok((StatusCode::CREATED, Json(RegisteredHook { id, secret, events: request.events })))
+}
+
+#[derive(Serialize)]
+pub struct HookView {
+    id: String,
+    gameid: String,
+    url: String,
+    events: Vec<String>,
+    // Events queued for the hook so far
+    sent: u64,
+    stats: HookStats,
+}
+
+// Find a hook of a game, for the holder of its secret
+fn authorized<'a>(
+    hooks: &'a HashMap<String, Hook>,
+    gameid: &str,
+    id: &str,
+    headers: &HeaderMap,
+) -> Result<&'a Hook, ChainError> {
+    let hook = hooks
+        .get(id)
+        .filter(|hook| hook.gameid == gameid)
+        .ok_or_else(|| ChainError::NotFound("Hook not found".to_string()))?;
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match given {
+        Some(given) if token_matches(given, &hook.secret) => Ok(hook),
+        _ => Err(ChainError::Unauthorized("Invalid hook secret".to_string())),
+    }
+}
+
+pub async fn hook_status(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<HookView>, ChainError> {
+    let hooks = shared.webhooks.hooks();
+    let hook = authorized(&hooks, &gameid, &id, &headers)?;
+    Ok(Json(HookView {
+        id,
+        gameid,
+        url: hook.url.clone(),
+        events: hook.events.clone(),
+        sent: hook.sent,
+        stats: lock(&hook.stats).clone(),
+    }))
+}
+
+pub async fn delete_hook(
+    Extension(shared): Extension<SharedData>,
+    Path((gameid, id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ChainError> {
+    let mut hooks = shared.webhooks.hooks();
+    authorized(&hooks, &gameid, &id, &headers)?;
+    hooks.remove(&id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,611 @@
+// Configuration shared by the host and the blockchain server.
+//
+// Settings are read from `fleet.toml` (or the file named by FLEET_CONFIG) in the working
+// directory; every setting can be overridden with an environment variable or a command-line
+// flag, and missing ones fall back to the defaults below. Example file:
+//
+//   log_level = "info"
+//   dev_mode = false
+//   storage_path = "/var/lib/fleet"
+//   wasm_pkg = "fleetwasm/pkg"
+//
+//   [chain]
+//   bind = "0.0.0.0"
+//   port = 3001
+//   grpc_port = 3002
+//   victory_timeout_seconds = 30
+//   game_ttl_seconds = 86400
+//   min_players = 2
+//   max_players = 8
+//   turn_policy = "reactive_default"
+//   blind_reports = false
+//   genesis_key = "fleet-genesis.key"
+//   response_key = "fleet-response.key"
+//   max_body_bytes = 8388608
+//   ip_requests_per_minute = 120
+//   fleet_requests_per_minute = 30
+//   admin_token = "change-me"
+//   host_tokens = { host0 = "change-me-three" }
+//   challenge_ttl_seconds = 900
+//   status_every_shots = 0
+//   clock_seconds = 0
+//   cycle_budget = 0
+//   audit_required = false
+//   guest_version = "current"
+//   node_id = "chain0"
+//   peers = ["http://chain1:3001"]
+//   sync_from = "http://chain1:3001"
+//   peer_token = "change-me-too"
+//   block_interval_seconds = 5
+//   block_max_transactions = 100
+//   leaderboard_file = "fleet-leaderboard.json"
+//   transaction_log = "fleet-transactions.log"
+//
+//   [[chain.legacy_guests]]
+//   version = "v1"
+//   cutoff = 1767225600
+//   ids = { join = "3f2a...", fire = "91bc..." }
+//
+//   [host]
+//   bind = "0.0.0.0"
+//   port = 3000
+//   chain_url = "http://chain0:3001"
+//   chain_token = "change-me-three"
+//   chain_key = "9c41..."
+//   chain_replicas = ["http://chain1:3001"]
+//   chain_replica_keys = { "http://chain1:3001" = "5e07..." }
+//   chain_timeout_seconds = 30
+//   submit_attempts = 3
+//   submit_backoff_ms = 1000
+//   proving_mode = "auto"
+//   tracker_journal = "fleet-tracker.journal"
+//   join_receipts = "fleet-joins"
+//   session_key = "fleet-session.key"
+//   session_store = "fleet-sessions.json"
+//   session_ttl_seconds = 604800
+//   keystore = "fleet-keystore.json"
+//   signer = "keystore" # "keystore", "agent" or "remote"
+//   signer_socket = "/tmp/fleet-signer.sock"
+//   signer_url = "http://localhost:3003"
+//   signer_token = "change-me"
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::error;
+
+// Configuration file used when FLEET_CONFIG is not set
+pub const DEFAULT_CONFIG_FILE: &str = "fleet.toml";
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvingMode {
+    // Use the fleet-proverd daemon when it is running, a local prover worker otherwise
+    Auto,
+    // Always prove in a local prover worker
+    Local,
+    // Always prove on the fleet-proverd daemon
+    Daemon,
+}
+
+impl FromStr for ProvingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(ProvingMode::Auto),
+            "local" => Ok(ProvingMode::Local),
+            "daemon" => Ok(ProvingMode::Daemon),
+            other => Err(format!("unknown proving mode {}", other)),
+        }
+    }
+}
+
+// Where the host signs the players' receipts and statements
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerKind {
+    // With the fleet's key, unlocked from the host's keystore
+    Keystore,
+    // With a signing agent listening on a local Unix socket
+    Agent,
+    // With a remote signing service
+    Remote,
+}
+
+impl FromStr for SignerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "keystore" => Ok(SignerKind::Keystore),
+            "agent" => Ok(SignerKind::Agent),
+            "remote" => Ok(SignerKind::Remote),
+            other => Err(format!("unknown signer {}", other)),
+        }
+    }
+}
+
+// Who plays next in a game
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnPolicy {
+    // The target of a shot plays once it has reported; a wave passes the turn to the player that
+    // has not played for the longest time
+    ReactiveDefault,
+    // Players take turns in the order drawn when the game starts
+    RoundRobin,
+    // The next player is drawn at random among the others
+    Random,
+}
+
+impl FromStr for TurnPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "reactive_default" | "reactive" => Ok(TurnPolicy::ReactiveDefault),
+            "round_robin" => Ok(TurnPolicy::RoundRobin),
+            "random" => Ok(TurnPolicy::Random),
+            other => Err(format!("unknown turn policy {}", other)),
+        }
+    }
+}
+
+// Guests of a previous build the chain still accepts, during a rolling upgrade
+#[derive(Clone, Debug, Deserialize)]
+pub struct LegacyGuests {
+    pub version: String,
+    // UNIX time after which these guests are rejected
+    pub cutoff: u64,
+    // Image ID of each guest, by command name ("join", "fire", "blind_report"...)
+    pub ids: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChainConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    // Port of the gRPC service, which is disabled when unset
+    pub grpc_port: Option<u16>,
+    pub victory_timeout_seconds: u64,
+    // Seconds without any transaction after which a running game is ended (0 to keep games forever)
+    pub game_ttl_seconds: u64,
+    // Players a new game needs before shots can be fired, and players it accepts at most
+    pub min_players: usize,
+    pub max_players: usize,
+    // Turn policy of new games
+    pub turn_policy: TurnPolicy,
+    // Start new games with blinded reports, hiding hits and misses from the chain
+    pub blind_reports: bool,
+    // Signing key of the chain, relative to the storage path; created on first start
+    pub genesis_key: String,
+    // Key of this node signing its responses and events, relative to the storage path; created on
+    // first start
+    pub response_key: String,
+    // Largest transaction accepted on /chain, in bytes
+    pub max_body_bytes: usize,
+    // Transactions accepted per minute from one IP address and for one fleet (0 for no limit)
+    pub ip_requests_per_minute: u32,
+    pub fleet_requests_per_minute: u32,
+    // Bearer token of the admin API, which is disabled when unset
+    pub admin_token: Option<String>,
+    // Bearer token of each host allowed to submit transactions, by host name; any client can
+    // submit them when empty
+    pub host_tokens: HashMap<String, String>,
+    // Seconds a challenge stays valid; it must outlast the proof it is issued for
+    pub challenge_ttl_seconds: u64,
+    // Shots fired in a game after which each player must prove its remaining fleet before firing
+    // again (0 to never require it)
+    pub status_every_shots: u32,
+    // Time each player of a new game has to play in total, run down while the game waits for it;
+    // a player whose clock runs out forfeits (0 for no clock)
+    pub clock_seconds: u64,
+    // Cycles each player of a new game can spend on its proofs in total (0 for no budget)
+    pub cycle_budget: u64,
+    // New games withhold their certificate until the winner audits its reports
+    pub audit_required: bool,
+    // Label of the guests the chain was built with
+    pub guest_version: String,
+    // Guests of previous builds still accepted, served with the current ones on /methods
+    pub legacy_guests: Vec<LegacyGuests>,
+    // Name of this node among the replicas of the chain, unique to each of them
+    pub node_id: String,
+    // Base URLs of the other replicas, which receive every transaction this node accepts
+    pub peers: Vec<String>,
+    // Replica a fresh node downloads the existing transactions from before serving
+    pub sync_from: Option<String>,
+    // Bearer token the replicas present to each other; replication is disabled when unset
+    pub peer_token: Option<String>,
+    // A block is sealed this long after its first transaction, or once it holds this many
+    pub block_interval_seconds: u64,
+    pub block_max_transactions: usize,
+    // Statistics of each fleet across games, relative to the storage path
+    pub leaderboard_file: String,
+    // Transactions applied by this node, relative to the storage path; replayed at startup to
+    // rebuild the games (empty to keep the games in memory only)
+    pub transaction_log: String,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        ChainConfig {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3001,
+            grpc_port: None,
+            victory_timeout_seconds: 30,
+            game_ttl_seconds: 24 * 60 * 60,
+            min_players: 2,
+            max_players: 8,
+            turn_policy: TurnPolicy::ReactiveDefault,
+            blind_reports: false,
+            genesis_key: "fleet-genesis.key".to_string(),
+            response_key: "fleet-response.key".to_string(),
+            max_body_bytes: 8 * 1024 * 1024,
+            ip_requests_per_minute: 120,
+            fleet_requests_per_minute: 30,
+            admin_token: None,
+            host_tokens: HashMap::new(),
+            challenge_ttl_seconds: 15 * 60,
+            status_every_shots: 0,
+            clock_seconds: 0,
+            cycle_budget: 0,
+            audit_required: false,
+            guest_version: "current".to_string(),
+            legacy_guests: Vec::new(),
+            node_id: "chain0".to_string(),
+            peers: Vec::new(),
+            sync_from: None,
+            peer_token: None,
+            block_interval_seconds: 5,
+            block_max_transactions: 100,
+            leaderboard_file: "fleet-leaderboard.json".to_string(),
+            transaction_log: "fleet-transactions.log".to_string(),
+        }
+    }
+}
+
+impl ChainConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    pub fn grpc_addr(&self) -> Option<SocketAddr> {
+        self.grpc_port.map(|port| SocketAddr::new(self.bind, port))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HostConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub chain_url: String,
+    // Bearer token this host presents to the chain when submitting transactions
+    pub chain_token: Option<String>,
+    // Hex-encoded response key of the chain node at `chain_url`, fetched from its /pubkey when
+    // unset
+    pub chain_key: Option<String>,
+    // Replicas of the chain node at `chain_url`, in the order the host fails over to them when it
+    // cannot reach the node it uses, and their response keys, fetched like `chain_key` when unset
+    pub chain_replicas: Vec<String>,
+    pub chain_replica_keys: HashMap<String, String>,
+    // Seconds to wait for the chain's answer to a request (0 to wait as long as it takes)
+    pub chain_timeout_seconds: u64,
+    // Attempts at sending a transaction that gets no answer, and the delay before the first retry,
+    // doubled at each retry
+    pub submit_attempts: u32,
+    pub submit_backoff_ms: u64,
+    pub proving_mode: ProvingMode,
+    // Append-only journal of the actions this host submitted, relative to the storage path
+    pub tracker_journal: String,
+    // Directory of the join receipts our shots are proven against, relative to the storage path
+    pub join_receipts: String,
+    // Key signing the session cookies, relative to the storage path; created on first start
+    pub session_key: String,
+    // Sessions of the browsers playing on this host, relative to the storage path
+    pub session_store: String,
+    // Sessions unused for this long are forgotten (0 to keep them forever)
+    pub session_ttl_seconds: u64,
+    // Encrypted signing keys of the fleets playing on this host, relative to the storage path
+    pub keystore: String,
+    pub signer: SignerKind,
+    // Unix socket of the signing agent
+    pub signer_socket: String,
+    // Base URL of the remote signing service, and the bearer token it expects
+    pub signer_url: String,
+    pub signer_token: Option<String>,
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        HostConfig {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            chain_url: "http://chain0:3001".to_string(),
+            chain_token: None,
+            chain_key: None,
+            chain_replicas: Vec::new(),
+            chain_replica_keys: HashMap::new(),
+            chain_timeout_seconds: 30,
+            submit_attempts: 3,
+            submit_backoff_ms: 1000,
+            proving_mode: ProvingMode::Auto,
+            tracker_journal: "fleet-tracker.journal".to_string(),
+            join_receipts: "fleet-joins".to_string(),
+            session_key: "fleet-session.key".to_string(),
+            session_store: "fleet-sessions.json".to_string(),
+            session_ttl_seconds: 7 * 24 * 60 * 60,
+            keystore: "fleet-keystore.json".to_string(),
+            signer: SignerKind::Keystore,
+            signer_socket: "/tmp/fleet-signer.sock".to_string(),
+            signer_url: "http://localhost:3003".to_string(),
+            signer_token: None,
+        }
+    }
+}
+
+impl HostConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Filter for log output, in the `RUST_LOG` syntax (e.g. "info" or "host=debug")
+    pub log_level: String,
+    // Accept fake receipts (RISC0_DEV_MODE) so games can be played without proving
+    pub dev_mode: bool,
+    // Directory holding the files a binary keeps (working directory when unset)
+    pub storage_path: Option<PathBuf>,
+    // fleetwasm package built by wasm-pack, served on /wasm for in-browser verification (not
+    // served when unset)
+    pub wasm_pkg: Option<PathBuf>,
+    pub chain: ChainConfig,
+    pub host: HostConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            log_level: "info".to_string(),
+            dev_mode: false,
+            storage_path: None,
+            wasm_pkg: None,
+            chain: ChainConfig::default(),
+            host: HostConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    // An environment variable override has an invalid value
+    Env(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "could not read {}: {}", path, e),
+            ConfigError::Parse(path, e) => write!(f, "invalid configuration in {}: {}", path, e),
+            ConfigError::Env(var, e) => write!(f, "invalid value for {}: {}", var, e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Override a setting with an environment variable, if it is set
+fn env_override<T: FromStr>(var: &'static str, value: &mut T) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    if let Ok(s) = std::env::var(var) {
+        *value = s.parse().map_err(|e: T::Err| ConfigError::Env(var, e.to_string()))?;
+    }
+    Ok(())
+}
+
+impl Config {
+    // Load the configuration file, if there is one, and apply the environment overrides
+    pub fn load() -> Result<Config, ConfigError> {
+        let explicit = std::env::var("FLEET_CONFIG").ok();
+        let path = explicit.clone().unwrap_or_else(|| DEFAULT_CONFIG_FILE.to_string());
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigError::Parse(path.clone(), e))?,
+            // Running without a configuration file is fine unless one was asked for
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && explicit.is_none() => Config::default(),
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        env_override("FLEET_LOG_LEVEL", &mut config.log_level)?;
+        env_override("FLEET_DEV_MODE", &mut config.dev_mode)?;
+        if let Ok(path) = std::env::var("FLEET_STORAGE_PATH") {
+            config.storage_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = std::env::var("FLEET_WASM_PKG") {
+            config.wasm_pkg = Some(PathBuf::from(path));
+        }
+        env_override("FLEET_CHAIN_BIND", &mut config.chain.bind)?;
+        env_override("FLEET_CHAIN_PORT", &mut config.chain.port)?;
+        if std::env::var("FLEET_GRPC_PORT").is_ok() {
+            let mut port = 0;
+            env_override("FLEET_GRPC_PORT", &mut port)?;
+            config.chain.grpc_port = Some(port);
+        }
+        env_override("FLEET_VICTORY_TIMEOUT", &mut config.chain.victory_timeout_seconds)?;
+        env_override("FLEET_GAME_TTL", &mut config.chain.game_ttl_seconds)?;
+        env_override("FLEET_MIN_PLAYERS", &mut config.chain.min_players)?;
+        env_override("FLEET_MAX_PLAYERS", &mut config.chain.max_players)?;
+        env_override("FLEET_TURN_POLICY", &mut config.chain.turn_policy)?;
+        env_override("FLEET_BLIND_REPORTS", &mut config.chain.blind_reports)?;
+        env_override("FLEET_GENESIS_KEY", &mut config.chain.genesis_key)?;
+        env_override("FLEET_RESPONSE_KEY", &mut config.chain.response_key)?;
+        env_override("FLEET_MAX_BODY_BYTES", &mut config.chain.max_body_bytes)?;
+        env_override("FLEET_IP_RATE_LIMIT", &mut config.chain.ip_requests_per_minute)?;
+        env_override("FLEET_FLEET_RATE_LIMIT", &mut config.chain.fleet_requests_per_minute)?;
+        env_override("FLEET_CHALLENGE_TTL", &mut config.chain.challenge_ttl_seconds)?;
+        env_override("FLEET_STATUS_EVERY", &mut config.chain.status_every_shots)?;
+        env_override("FLEET_CLOCK_SECONDS", &mut config.chain.clock_seconds)?;
+        env_override("FLEET_CYCLE_BUDGET", &mut config.chain.cycle_budget)?;
+        env_override("FLEET_AUDIT_REQUIRED", &mut config.chain.audit_required)?;
+        env_override("FLEET_GUEST_VERSION", &mut config.chain.guest_version)?;
+        if let Ok(token) = std::env::var("FLEET_ADMIN_TOKEN") {
+            config.chain.admin_token = Some(token);
+        }
+        // Comma-separated `name=token` pairs
+        if let Ok(tokens) = std::env::var("FLEET_HOST_TOKENS") {
+            config.chain.host_tokens = tokens
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, token)| (name.trim().to_string(), token.trim().to_string()))
+                .filter(|(name, token)| !name.is_empty() && !token.is_empty())
+                .collect();
+        }
+        env_override("FLEET_NODE_ID", &mut config.chain.node_id)?;
+        if let Ok(peers) = std::env::var("FLEET_CHAIN_PEERS") {
+            config.chain.peers = peers.split(',').map(str::trim).filter(|p| !p.is_empty()).map(String::from).collect();
+        }
+        if let Ok(url) = std::env::var("FLEET_SYNC_FROM") {
+            config.chain.sync_from = Some(url);
+        }
+        if let Ok(token) = std::env::var("FLEET_PEER_TOKEN") {
+            config.chain.peer_token = Some(token);
+        }
+        env_override("FLEET_BLOCK_INTERVAL", &mut config.chain.block_interval_seconds)?;
+        env_override("FLEET_BLOCK_SIZE", &mut config.chain.block_max_transactions)?;
+        env_override("FLEET_LEADERBOARD", &mut config.chain.leaderboard_file)?;
+        env_override("FLEET_TRANSACTION_LOG", &mut config.chain.transaction_log)?;
+        env_override("FLEET_HOST_BIND", &mut config.host.bind)?;
+        env_override("FLEET_HOST_PORT", &mut config.host.port)?;
+        env_override("FLEET_CHAIN_URL", &mut config.host.chain_url)?;
+        if let Ok(token) = std::env::var("FLEET_CHAIN_TOKEN") {
+            config.host.chain_token = Some(token);
+        }
+        if let Ok(key) = std::env::var("FLEET_CHAIN_KEY") {
+            config.host.chain_key = Some(key);
+        }
+        if let Ok(replicas) = std::env::var("FLEET_CHAIN_REPLICAS") {
+            config.host.chain_replicas = replicas.split(',').map(str::trim).filter(|r| !r.is_empty()).map(String::from).collect();
+        }
+        if let Ok(keys) = std::env::var("FLEET_CHAIN_REPLICA_KEYS") {
+            config.host.chain_replica_keys = keys
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(url, key)| (url.trim().to_string(), key.trim().to_string()))
+                .filter(|(url, key)| !url.is_empty() && !key.is_empty())
+                .collect();
+        }
+        env_override("FLEET_CHAIN_TIMEOUT", &mut config.host.chain_timeout_seconds)?;
+        env_override("FLEET_SUBMIT_ATTEMPTS", &mut config.host.submit_attempts)?;
+        env_override("FLEET_SUBMIT_BACKOFF", &mut config.host.submit_backoff_ms)?;
+        env_override("FLEET_PROVING_MODE", &mut config.host.proving_mode)?;
+        env_override("FLEET_TRACKER_JOURNAL", &mut config.host.tracker_journal)?;
+        env_override("FLEET_JOIN_RECEIPTS", &mut config.host.join_receipts)?;
+        env_override("FLEET_SESSION_KEY", &mut config.host.session_key)?;
+        env_override("FLEET_SESSION_STORE", &mut config.host.session_store)?;
+        env_override("FLEET_SESSION_TTL", &mut config.host.session_ttl_seconds)?;
+        env_override("FLEET_KEYSTORE", &mut config.host.keystore)?;
+        env_override("FLEET_SIGNER", &mut config.host.signer)?;
+        env_override("FLEET_SIGNER_SOCKET", &mut config.host.signer_socket)?;
+        env_override("FLEET_SIGNER_URL", &mut config.host.signer_url)?;
+        if let Ok(token) = std::env::var("FLEET_SIGNER_TOKEN") {
+            config.host.signer_token = Some(token);
+        }
+        Ok(config)
+    }
+
+    // Check that files can be written to the storage directory, creating it if needed
+    pub fn check_storage(&self) -> std::io::Result<()> {
+        let dir = self.storage_path.clone().unwrap_or_else(|| PathBuf::from("."));
+        std::fs::create_dir_all(&dir)?;
+        let probe = dir.join(format!(".fleet-probe-{}", std::process::id()));
+        std::fs::write(&probe, b"ok")?;
+        std::fs::remove_file(&probe)
+    }
+
+    // Path of a file kept in the storage directory
+    pub fn storage_file(&self, name: &str) -> PathBuf {
+        match &self.storage_path {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+
+    // Path and content type of a file of the fleetwasm package, when the package is served
+    pub fn wasm_file(&self, name: &str) -> Option<(PathBuf, &'static str)> {
+        let dir = self.wasm_pkg.as_ref()?;
+        if name.starts_with('.') || name.contains(['/', '\\']) {
+            return None;
+        }
+        let content_type = match name.rsplit_once('.')?.1 {
+            "js" => "text/javascript",
+            "wasm" => "application/wasm",
+            _ => return None,
+        };
+        Some((dir.join(name), content_type))
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn load_or_exit() -> Config {
+    match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            // Logging is set up from the configuration, so nothing would show the error yet
+            let _ = tracing_subscriber::fmt().with_writer(std::io::stderr).try_init();
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn finish(mut config: Config) -> Config {
+    // Accept the chain and signer URLs with or without a trailing slash
+    while config.host.chain_url.ends_with('/') {
+        config.host.chain_url.pop();
+    }
+    while config.host.signer_url.ends_with('/') {
+        config.host.signer_url.pop();
+    }
+    config.host.chain_replica_keys =
+        config.host.chain_replica_keys.drain().map(|(url, key)| (url.trim_end_matches('/').to_string(), key)).collect();
+    let peers = config.chain.peers.iter_mut().chain(config.chain.sync_from.iter_mut());
+    for url in peers.chain(config.host.chain_replicas.iter_mut()) {
+        while url.ends_with('/') {
+            url.pop();
+        }
+    }
+    // The prover and the verifier read dev mode from the environment, and prover workers inherit it
+    if config.dev_mode {
+        std::env::set_var("RISC0_DEV_MODE", "1");
+    }
+    config
+}
+
+// Load the configuration and apply command-line overrides. Call it at startup, before anything
+// reads the configuration.
+pub fn init(overrides: impl FnOnce(&mut Config)) -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let mut config = load_or_exit();
+        overrides(&mut config);
+        finish(config)
+    })
+}
+
+// Configuration of the running process, loaded on first use. Exits if it is invalid.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(|| finish(load_or_exit()))
+}
@@ -0,0 +1,135 @@
+// Blinded reports: in a blinded game the chain never learns whether a shot hit. Each report only
+// publishes a commitment to its outcome, and the player's hits are kept in a private ledger that
+// the report and win guests prove statements about. Boards are revealed after the game so the
+// outcomes can be checked against the commitments.
+//
+// All digests are salted with the player's random seed, which never leaves the host until the
+// reveal.
+
+use crate::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::{Board, Coord};
+
+// Domain tags of the blinded digests
+const LEDGER_DOMAIN: &[u8] = b"fleet/ledger/v1";
+const OUTCOME_DOMAIN: &[u8] = b"fleet/outcome/v1";
+
+fn update_str(hasher: &mut Sha256, s: &str) {
+    hasher.update((s.len() as u32).to_le_bytes());
+    hasher.update(s.as_bytes());
+}
+
+fn finish(hasher: Sha256) -> Digest {
+    Digest::from(<[u8; 32]>::from(hasher.finalize()))
+}
+
+// Hash of a board's bitboard with the player's random seed, as committed when joining
+pub fn board_digest(board: Board, random: &str) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(board.to_bytes());
+    hasher.update(random.as_bytes());
+    finish(hasher)
+}
+
+// Commitment to the positions of a player's board that were hit before report number `turn`.
+// The turn number makes the ledger change on every report, hit or miss.
+pub fn ledger_digest(hits: &[u8], random: &str, turn: u32) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(LEDGER_DOMAIN);
+    hasher.update(turn.to_le_bytes());
+    hasher.update((hits.len() as u32).to_le_bytes());
+    hasher.update(hits);
+    update_str(&mut hasher, random);
+    finish(hasher)
+}
+
+// Commitment to the outcome of report number `turn` of a player
+pub fn outcome_commitment(gameid: &str, fleet: &str, pos: Coord, hit: bool, random: &str, turn: u32) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(OUTCOME_DOMAIN);
+    update_str(&mut hasher, gameid);
+    update_str(&mut hasher, fleet);
+    hasher.update([pos.pos(), hit as u8]);
+    hasher.update(turn.to_le_bytes());
+    update_str(&mut hasher, random);
+    finish(hasher)
+}
+
+// Struct sent by the rust code for input on the method blind_report
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlindReportInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Board,
+    pub random: String,
+    pub pos: Coord,
+    pub report: String, // "Hit" or "Miss", never committed in the clear
+    pub hits: Vec<u8>,  // positions of the board hit so far (private ledger)
+    pub turn: u32,      // number of reports this player already made
+    pub game_next_report: Option<String>,
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
+}
+
+// Struct to specify the output journal for blind_report method
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct BlindReportJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub pos: Coord,
+    pub turn: u32,
+    pub ledger: Digest,
+    pub next_ledger: Digest,
+    pub outcome: Digest,
+    pub challenge: String,
+}
+
+// Struct sent by the rust code for input on the method blind_win
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlindWinInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Board,
+    pub random: String,
+    pub hits: Vec<u8>,
+    pub turn: u32,
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
+}
+
+// Struct to specify the output journal for blind_win method
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct BlindWinJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub turn: u32,
+    pub ledger: Digest,
+    pub challenge: String,
+}
+
+// Struct used to reveal a board at the end of a blinded game
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RevealData {
+    pub fleet: String,
+    pub board: Board,
+    pub random: String,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for reveal signatures
+pub const REVEAL_DOMAIN: &str = "fleet/reveal/v1";
+
+// Statement signed (in canonical form) by the player when revealing a board
+#[derive(Serialize)]
+pub struct RevealStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub board: Board,
+    pub random: &'a str,
+}
@@ -0,0 +1,110 @@
+// Blocks of the chain. The chain numbers every transaction it applies and batches them into
+// blocks, sealed every few seconds or once they hold enough transactions. Each block header links
+// to the hash of the previous one, so consumers can order transactions by their index and check
+// that the blocks they were served follow each other, instead of relying on the order events
+// arrive in.
+
+use crate::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+// Domain tags of the block digests
+const TRANSACTIONS_DOMAIN: &[u8] = b"fleet/block/transactions/v1";
+const HEADER_DOMAIN: &[u8] = b"fleet/block/header/v1";
+
+fn update_str(hasher: &mut Sha256, s: &str) {
+    hasher.update((s.len() as u32).to_le_bytes());
+    hasher.update(s.as_bytes());
+}
+
+fn finish(hasher: Sha256) -> Digest {
+    Digest::from(<[u8; 32]>::from(hasher.finalize()))
+}
+
+// A transaction as recorded in a block
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockTransaction {
+    // Position of the transaction among all the transactions applied by the chain, from 0
+    pub index: u64,
+    // Command of a receipt ("Join", "Fire", ...), or "Ready", "Annotation", "Chat" or "Reveal"
+    pub kind: String,
+    pub gameid: String,
+    pub fleet: String,
+    // Journal digest of a receipt, digest of the canonical message otherwise
+    pub digest: Digest,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockHeader {
+    // Number of the block, from 0
+    pub number: u64,
+    // Hash of the previous block, zero for the first one
+    pub prev_hash: Digest,
+    // Time the block was sealed
+    pub timestamp: u64,
+    // Index of the first transaction of the block, and how many it holds
+    pub first_index: u64,
+    pub count: u32,
+    // Digest of the transactions of the block
+    pub transactions: Digest,
+    pub hash: Digest,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<BlockTransaction>,
+}
+
+// Digest of the transactions of a block, in order
+pub fn transactions_digest(transactions: &[BlockTransaction]) -> Digest {
+    let mut hasher = Sha256::new_with_prefix(TRANSACTIONS_DOMAIN);
+    hasher.update((transactions.len() as u32).to_le_bytes());
+    for tx in transactions {
+        hasher.update(tx.index.to_le_bytes());
+        update_str(&mut hasher, &tx.kind);
+        update_str(&mut hasher, &tx.gameid);
+        update_str(&mut hasher, &tx.fleet);
+        hasher.update(tx.digest.as_bytes());
+    }
+    finish(hasher)
+}
+
+impl BlockHeader {
+    // Hash of every field of the header but the hash itself
+    pub fn compute_hash(&self) -> Digest {
+        let mut hasher = Sha256::new_with_prefix(HEADER_DOMAIN);
+        hasher.update(self.number.to_le_bytes());
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(self.timestamp.to_le_bytes());
+        hasher.update(self.first_index.to_le_bytes());
+        hasher.update(self.count.to_le_bytes());
+        hasher.update(self.transactions.as_bytes());
+        finish(hasher)
+    }
+
+    // Check if this header follows `prev`, or starts the chain when there is none
+    pub fn follows(&self, prev: Option<&BlockHeader>) -> bool {
+        if self.hash != self.compute_hash() {
+            return false;
+        }
+        match prev {
+            Some(prev) => {
+                self.number == prev.number + 1
+                    && self.prev_hash == prev.hash
+                    && self.first_index == prev.first_index + prev.count as u64
+            }
+            None => self.number == 0 && self.prev_hash == Digest::ZERO && self.first_index == 0,
+        }
+    }
+}
+
+impl Block {
+    // Check if the transactions are the ones the header commits to, numbered in order
+    pub fn verify(&self) -> bool {
+        self.header.hash == self.header.compute_hash()
+            && self.header.count as usize == self.transactions.len()
+            && self.header.transactions == transactions_digest(&self.transactions)
+            && self.transactions.iter().zip(self.header.first_index..).all(|(tx, index)| tx.index == index)
+    }
+}
@@ -0,0 +1,345 @@
+// Squares and boards shared by the host, the chain and the guests. A square is named by its
+// column ('A'..'J') and row ('0'..'9'), as in "B7", and numbered y * 10 + x on the wire, so
+// journals and inputs keep the raw positions they always had; deserializing an out-of-bounds
+// position fails instead of reaching the game rules.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+// Number of columns and rows of a board
+pub const BOARD_SIZE: u8 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoordError {
+    // Column outside of 'A'..'J', or missing
+    X(String),
+    // Row outside of '0'..'9', or missing
+    Y(String),
+    // Cell that is not a column followed by a row
+    Cell(String),
+    // Position number outside of the board
+    Position(u8),
+    // Entry of a board list that is not a number
+    Number(String),
+    // Position number given twice in a board list
+    Duplicate(u8),
+}
+
+impl fmt::Display for CoordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoordError::X(x) if x.is_empty() => write!(f, "Invalid X coordinate"),
+            CoordError::X(_) => write!(f, "X coordinate must be between A and J"),
+            CoordError::Y(y) if y.is_empty() => write!(f, "Invalid Y coordinate"),
+            CoordError::Y(_) => write!(f, "Y coordinate must be between 0 and 9"),
+            CoordError::Cell(cell) => write!(f, "Invalid cell '{}': expected a column A-J and a row 0-9, like B7", cell),
+            CoordError::Position(pos) => write!(f, "Position {} is outside of the board", pos),
+            CoordError::Number(s) => write!(f, "Invalid number '{}' in board", s),
+            CoordError::Duplicate(pos) => write!(f, "Position {} is given twice", pos),
+        }
+    }
+}
+
+impl std::error::Error for CoordError {}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Coord {
+    x: u8,
+    y: u8,
+}
+
+impl Coord {
+    pub fn new(x: u8, y: u8) -> Option<Coord> {
+        (x < BOARD_SIZE && y < BOARD_SIZE).then_some(Coord { x, y })
+    }
+
+    pub fn from_pos(pos: u8) -> Option<Coord> {
+        Coord::new(pos % BOARD_SIZE, pos / BOARD_SIZE)
+    }
+
+    pub fn x(self) -> u8 {
+        self.x
+    }
+
+    pub fn y(self) -> u8 {
+        self.y
+    }
+
+    // Position number of the square, y * 10 + x
+    pub fn pos(self) -> u8 {
+        self.y * BOARD_SIZE + self.x
+    }
+
+    // Square given by a column and a row entered separately; only their first character counts
+    pub fn from_axes(x: &str, y: &str) -> Result<Coord, CoordError> {
+        let col = match x.chars().next() {
+            Some(c @ 'A'..='J') => c as u8 - b'A',
+            _ => return Err(CoordError::X(x.to_string())),
+        };
+        let row = match y.chars().next() {
+            Some(c @ '0'..='9') => c as u8 - b'0',
+            _ => return Err(CoordError::Y(y.to_string())),
+        };
+        Ok(Coord { x: col, y: row })
+    }
+}
+
+impl TryFrom<u8> for Coord {
+    type Error = CoordError;
+
+    fn try_from(pos: u8) -> Result<Self, CoordError> {
+        Coord::from_pos(pos).ok_or(CoordError::Position(pos))
+    }
+}
+
+impl From<Coord> for u8 {
+    fn from(coord: Coord) -> u8 {
+        coord.pos()
+    }
+}
+
+// "B7", in either case
+impl FromStr for Coord {
+    type Err = CoordError;
+
+    fn from_str(s: &str) -> Result<Self, CoordError> {
+        let cell = s.trim().to_ascii_uppercase();
+        let mut chars = cell.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(x), Some(y), None) => Coord::from_axes(&x.to_string(), &y.to_string()),
+            _ => Err(CoordError::Cell(s.trim().to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'A' + self.x) as char, self.y)
+    }
+}
+
+// Squares of a player's ships, as a bitboard: bit y * 10 + x is set for each ship square. A square
+// is on the board or not, so a board never holds the same square twice. Boards travel as their
+// sorted position numbers in JSON, for the page and the chain's API, and as the bitboard itself
+// in binary encodings like the guests' inputs.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Board(u128);
+
+// Bits of the squares of a board
+const SQUARES: u128 = (1 << (BOARD_SIZE as u32 * BOARD_SIZE as u32)) - 1;
+
+impl Board {
+    pub fn new(coords: impl IntoIterator<Item = Coord>) -> Board {
+        Board(coords.into_iter().fold(0, |bits, coord| bits | 1 << coord.pos()))
+    }
+
+    // Board of the given bits, None if a bit lies outside of the board
+    pub fn from_bits(bits: u128) -> Option<Board> {
+        (bits & !SQUARES == 0).then_some(Board(bits))
+    }
+
+    pub fn bits(self) -> u128 {
+        self.0
+    }
+
+    // Bytes of the bitboard (little-endian), as hashed into the board digest
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    // Position numbers of the squares, in increasing order
+    pub fn squares(self) -> Vec<u8> {
+        self.coords().map(Coord::pos).collect()
+    }
+
+    // Squares in increasing position order
+    pub fn coords(self) -> impl Iterator<Item = Coord> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let pos = bits.trailing_zeros() as u8;
+            bits &= bits - 1;
+            Coord::from_pos(pos)
+        })
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, coord: Coord) -> bool {
+        self.contains_pos(coord.pos())
+    }
+
+    // Whether the square numbered `pos` is on the board; false for a number off the board
+    pub fn contains_pos(self, pos: u8) -> bool {
+        pos < 128 && self.0 & (1 << pos) & SQUARES != 0
+    }
+
+    // Whether every square of the board is on `other`
+    pub fn is_subset(self, other: Board) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    // The board once the ship square at `coord`, if any, has been sunk
+    pub fn without(self, coord: Coord) -> Board {
+        Board(self.0 & !(1 << coord.pos()))
+    }
+}
+
+// Position numbers; one off the board or given twice is an error
+impl TryFrom<Vec<u8>> for Board {
+    type Error = CoordError;
+
+    fn try_from(squares: Vec<u8>) -> Result<Self, CoordError> {
+        Board::try_from(squares.as_slice())
+    }
+}
+
+impl TryFrom<&[u8]> for Board {
+    type Error = CoordError;
+
+    fn try_from(squares: &[u8]) -> Result<Self, CoordError> {
+        let mut board = Board::default();
+        for &pos in squares {
+            let coord = Coord::from_pos(pos).ok_or(CoordError::Position(pos))?;
+            if board.contains(coord) {
+                return Err(CoordError::Duplicate(pos));
+            }
+            board = Board(board.0 | 1 << pos);
+        }
+        Ok(board)
+    }
+}
+
+impl From<Board> for Vec<u8> {
+    fn from(board: Board) -> Vec<u8> {
+        board.squares()
+    }
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            self.squares().serialize(serializer)
+        } else {
+            // Two words rather than one per square
+            (self.0 as u64, (self.0 >> 64) as u64).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let squares = Vec::<u8>::deserialize(deserializer)?;
+            Board::try_from(squares).map_err(D::Error::custom)
+        } else {
+            let (low, high) = <(u64, u64)>::deserialize(deserializer)?;
+            Board::from_bits(u128::from(high) << 64 | u128::from(low))
+                .ok_or_else(|| D::Error::custom("Bitboard has squares outside of the board"))
+        }
+    }
+}
+
+// Comma-separated position numbers, as sent by the page: "3,14,15"
+impl FromStr for Board {
+    type Err = CoordError;
+
+    fn from_str(s: &str) -> Result<Self, CoordError> {
+        if s.trim().is_empty() {
+            return Ok(Board::default());
+        }
+        let squares = s
+            .split(',')
+            .map(|n| n.trim().parse::<u8>().map_err(|_| CoordError::Number(n.to_string())))
+            .collect::<Result<Vec<u8>, CoordError>>()?;
+        Board::try_from(squares)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let squares: Vec<String> = self.squares().iter().map(u8::to_string).collect();
+        write!(f, "{}", squares.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_read_in_either_case() {
+        assert_eq!("B7".parse(), Ok(Coord { x: 1, y: 7 }));
+        assert_eq!("b7".parse(), Ok(Coord { x: 1, y: 7 }));
+        assert_eq!(" j9 ".parse::<Coord>().map(Coord::pos), Ok(99));
+        assert_eq!("A0".parse::<Coord>().map(Coord::pos), Ok(0));
+    }
+
+    #[test]
+    fn cells_off_the_board_are_refused() {
+        assert_eq!("K1".parse::<Coord>(), Err(CoordError::X("K".to_string())));
+        // Rows run from 0 to 9
+        assert_eq!("J10".parse::<Coord>(), Err(CoordError::Cell("J10".to_string())));
+        assert_eq!("A11".parse::<Coord>(), Err(CoordError::Cell("A11".to_string())));
+        assert_eq!("A".parse::<Coord>(), Err(CoordError::Cell("A".to_string())));
+        assert_eq!("AB".parse::<Coord>(), Err(CoordError::Y("B".to_string())));
+        assert_eq!(Coord::try_from(100), Err(CoordError::Position(100)));
+        assert_eq!(Coord::new(10, 0), None);
+    }
+
+    #[test]
+    fn cells_read_back_from_their_names() {
+        for pos in 0..100 {
+            let coord = Coord::from_pos(pos).unwrap();
+            assert_eq!(coord.to_string().parse(), Ok(coord));
+            assert_eq!(u8::from(coord), pos);
+        }
+    }
+
+    #[test]
+    fn squares_are_given_once_and_on_the_board() {
+        assert_eq!(Board::try_from(vec![3, 14, 3]), Err(CoordError::Duplicate(3)));
+        assert_eq!("3, 14, 14".parse::<Board>(), Err(CoordError::Duplicate(14)));
+        assert_eq!(Board::try_from(vec![3, 100]), Err(CoordError::Position(100)));
+        assert_eq!("3,x".parse::<Board>(), Err(CoordError::Number("x".to_string())));
+        assert!(serde_json::from_str::<Board>("[3,3]").is_err());
+        assert!(serde_json::from_str::<Board>("[100]").is_err());
+    }
+
+    #[test]
+    fn bits_off_the_board_are_refused() {
+        assert_eq!(Board::from_bits(1 << 100), None);
+        assert_eq!(Board::from_bits(1 << 99).map(Board::squares), Some(vec![99]));
+        assert!(!Board::default().contains_pos(200));
+        // Bit 104, in the high word
+        let encoded = bincode::serialize(&(0u64, 1u64 << 40)).unwrap();
+        assert!(bincode::deserialize::<Board>(&encoded).is_err());
+    }
+
+    #[test]
+    fn boards_read_back_from_their_encodings() {
+        let board: Board = "3,14,15,92,65".parse().unwrap();
+        assert_eq!(board.to_string(), "3,14,15,65,92");
+        assert_eq!(board.to_string().parse(), Ok(board));
+        assert_eq!("".parse(), Ok(Board::default()));
+
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, "[3,14,15,65,92]");
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+
+        let encoded = bincode::serialize(&board).unwrap();
+        assert_eq!(encoded.len(), 16);
+        assert_eq!(bincode::deserialize::<Board>(&encoded).unwrap(), board);
+    }
+}
@@ -0,0 +1,52 @@
+// Certificates issued by the chain when a game ends. A certificate is a compact, signed summary
+// of the game that players can keep and anyone can verify offline with the chain's genesis key,
+// e.g. to accept a victory on an external leaderboard.
+
+#[cfg(feature = "signatures")]
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "signatures")]
+use crate::verify_canonical;
+
+// Domain tag for certificate signatures
+pub const CERTIFICATE_DOMAIN: &str = "fleet/certificate/v1";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CertifiedPlayer {
+    pub fleet: String,
+    // Hex-encoded Ed25519 key the player signed its transactions with
+    pub public_key: String,
+    // Hex-encoded commitment to the player's board at the end of the game
+    pub final_board: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GameCertificate {
+    pub gameid: String,
+    // Hex-encoded SHA-256 of the canonical game settings (timeouts, budget, mode)
+    pub config_hash: String,
+    // Sorted by fleet name
+    pub players: Vec<CertifiedPlayer>,
+    pub winner: Option<String>,
+    // Sequence number of the last transaction the chain applied before the game ended
+    pub seq: u64,
+    pub ended_at: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SignedCertificate {
+    pub certificate: GameCertificate,
+    pub signature: Vec<u8>,
+}
+
+#[cfg(feature = "signatures")]
+impl SignedCertificate {
+    // Check the chain's signature, with a genesis key obtained from a trusted source
+    pub fn verify(&self, genesis_key: &VerifyingKey) -> bool {
+        verify_canonical(genesis_key, CERTIFICATE_DOMAIN, &self.certificate, &self.signature)
+    }
+}
@@ -0,0 +1,203 @@
+// Limits on the inputs of the guests. `env::read` deserializes whatever the host sends, and every
+// string a guest reads is hashed or committed to its journal, so an oversized game ID or nonce
+// costs cycles and ends up on the chain. Each guest checks its inputs against these limits before
+// doing anything else, and the chain applies the same limits to the journals it receives.
+//
+// Game IDs and fleet names are 1 to MAX_ID_LEN ASCII letters, digits, '-' or '_', the characters
+// the chain already keeps when a game ID ends up in a page. The board nonce follows the same rules
+// (the host draws it with nanoid, from that alphabet), and challenges are the hex strings the chain
+// issues.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::blind::{BlindReportInputs, BlindWinInputs};
+use crate::win::{fleet_squares, WinBasis, WinInputs};
+use crate::{AuditInputs, BaseInputs, FireInputs, RegisterInputs, ANY_GAME};
+
+// Longest game ID or fleet name
+pub const MAX_ID_LEN: usize = 64;
+// Longest board nonce
+pub const MAX_RANDOM_LEN: usize = 64;
+// Longest challenge, in hex digits: the chain issues 16 random bytes (32 digits) today
+pub const MAX_CHALLENGE_LEN: usize = 64;
+// Longest journal a guest takes as an assumption
+pub const MAX_JOURNAL_LEN: usize = 2048;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InputError {
+    Empty(&'static str),
+    TooLong { field: &'static str, len: usize, max: usize },
+    BadCharacter { field: &'static str, found: char },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Empty(field) => write!(f, "{} is empty", field),
+            InputError::TooLong { field, len, max } => write!(f, "{} is {} bytes long, at most {} are allowed", field, len, max),
+            InputError::BadCharacter { field, found } => write!(f, "{} holds the character {:?}", field, found),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+impl InputError {
+    // The input that broke the limits
+    pub fn field(&self) -> &'static str {
+        match self {
+            InputError::Empty(field) | InputError::TooLong { field, .. } | InputError::BadCharacter { field, .. } => field,
+        }
+    }
+}
+
+// Body of the chain's response to a request naming a game or fleet outside the limits
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InvalidInput {
+    pub field: String,
+    pub error: String,
+}
+
+impl From<&InputError> for InvalidInput {
+    fn from(err: &InputError) -> Self {
+        InvalidInput { field: err.field().to_string(), error: err.to_string() }
+    }
+}
+
+fn check_len(field: &'static str, len: usize, max: usize) -> Result<(), InputError> {
+    if len > max {
+        return Err(InputError::TooLong { field, len, max });
+    }
+    Ok(())
+}
+
+// A game ID, fleet name or nonce: 1 to `max` ASCII letters, digits, '-' or '_'
+fn check_token(field: &'static str, value: &str, max: usize) -> Result<(), InputError> {
+    if value.is_empty() {
+        return Err(InputError::Empty(field));
+    }
+    check_len(field, value.len(), max)?;
+    match value.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        Some(found) => Err(InputError::BadCharacter { field, found }),
+        None => Ok(()),
+    }
+}
+
+// A game ID or fleet name
+pub fn check_id(field: &'static str, id: &str) -> Result<(), InputError> {
+    check_token(field, id, MAX_ID_LEN)
+}
+
+// The game ID of a registration, which can also be ANY_GAME
+pub fn check_register_gameid(gameid: &str) -> Result<(), InputError> {
+    if gameid == ANY_GAME {
+        return Ok(());
+    }
+    check_id("game ID", gameid)
+}
+
+// The ID a host gives a player's action, which the chain copies into its logs and events
+pub fn check_correlation_id(id: &str) -> Result<(), InputError> {
+    check_token("correlation ID", id, MAX_ID_LEN)
+}
+
+pub fn check_random(random: &str) -> Result<(), InputError> {
+    check_token("random", random, MAX_RANDOM_LEN)
+}
+
+// A challenge issued by the chain, or none when the chain does not ask for one
+pub fn check_challenge(challenge: &str) -> Result<(), InputError> {
+    check_len("challenge", challenge.len(), MAX_CHALLENGE_LEN)?;
+    match challenge.chars().find(|c| !c.is_ascii_hexdigit()) {
+        Some(found) => Err(InputError::BadCharacter { field: "challenge", found }),
+        None => Ok(()),
+    }
+}
+
+fn check_turn(next_player: &Option<String>, next_report: &Option<String>) -> Result<(), InputError> {
+    if let Some(next_player) = next_player {
+        check_id("next player", next_player)?;
+    }
+    if let Some(next_report) = next_report {
+        check_id("next report", next_report)?;
+    }
+    Ok(())
+}
+
+impl BaseInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_random(&self.random)?;
+        check_challenge(&self.challenge)?;
+        check_turn(&self.game_next_player, &self.game_next_report)
+    }
+}
+
+impl FireInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_id("target", &self.target)?;
+        check_random(&self.random)?;
+        check_challenge(&self.challenge)?;
+        check_turn(&self.game_next_player, &self.game_next_report)?;
+        check_len("hits", self.hits.len(), fleet_squares())?;
+        check_len("join journal", self.join_journal.len(), MAX_JOURNAL_LEN)?;
+        check_len("fire journal", self.fire_journal.len(), MAX_JOURNAL_LEN)
+    }
+}
+
+impl BlindReportInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_random(&self.random)?;
+        check_challenge(&self.challenge)?;
+        check_turn(&None, &self.game_next_report)?;
+        check_len("report", self.report.len(), "Miss".len())?;
+        check_len("hits", self.hits.len(), fleet_squares())
+    }
+}
+
+impl BlindWinInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_random(&self.random)?;
+        check_challenge(&self.challenge)?;
+        check_len("hits", self.hits.len(), fleet_squares())
+    }
+}
+
+impl WinInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_random(&self.random)?;
+        check_challenge(&self.challenge)?;
+        match &self.basis {
+            WinBasis::Sunk { opponent } => check_id("opponent", opponent),
+            WinBasis::Hits { opponents } => opponents.iter().try_for_each(|(opponent, _)| check_id("opponent", opponent)),
+        }
+    }
+}
+
+impl RegisterInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_register_gameid(&self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_challenge(&self.challenge)
+    }
+}
+
+impl AuditInputs {
+    pub fn check(&self) -> Result<(), InputError> {
+        check_id("game ID", &self.gameid)?;
+        check_id("fleet", &self.fleet)?;
+        check_challenge(&self.challenge)?;
+        self.reports.iter().try_for_each(|journal| check_len("report journal", journal.len(), MAX_JOURNAL_LEN))
+    }
+}
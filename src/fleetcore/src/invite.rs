@@ -0,0 +1,61 @@
+// Invitations to private games. The creator of a private game signs invites for it, for one fleet
+// or for anyone holding the invite, and the chain only lets fleets join the game with an invite
+// signed by the creator's key. Invites are passed around as codes, the hex-encoded JSON of the
+// signed invite, so they can be pasted into a message.
+
+#[cfg(feature = "signatures")]
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "signatures")]
+use crate::verify_canonical;
+
+// Domain tag for invite signatures
+pub const INVITE_DOMAIN: &str = "fleet/invite/v1";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Invite {
+    pub gameid: String,
+    // Fleet invited, None when anyone holding the invite can join
+    pub fleet: Option<String>,
+    // Time after which the invite is refused
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignedInvite {
+    pub invite: Invite,
+    pub signature: Vec<u8>,
+}
+
+impl SignedInvite {
+    pub fn code(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        json.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.trim();
+        if code.len() % 2 != 0 || !code.is_ascii() {
+            return None;
+        }
+        let bytes: Option<Vec<u8>> = (0..code.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&code[i..i + 2], 16).ok())
+            .collect();
+        serde_json::from_slice(&bytes?).ok()
+    }
+
+    // Check the creator's signature
+    #[cfg(feature = "signatures")]
+    pub fn verify(&self, creator_key: &VerifyingKey) -> bool {
+        verify_canonical(creator_key, INVITE_DOMAIN, &self.invite, &self.signature)
+    }
+
+    // Check if the invite lets a fleet into a game at time `now`, signature aside
+    pub fn admits(&self, gameid: &str, fleet: &str, now: u64) -> bool {
+        self.invite.gameid == gameid
+            && self.invite.fleet.as_deref().map_or(true, |invited| invited == fleet)
+            && now < self.invite.expires_at
+    }
+}
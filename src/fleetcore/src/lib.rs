@@ -1,17 +1,63 @@
 use serde::{Deserialize, Serialize};
-use risc0_zkvm::{Receipt, Digest};
+#[cfg(feature = "zkvm")]
+use risc0_zkvm::Receipt;
 
-// Struct sent by the rust code for input on the methods join, wave and win
+mod blind;
+mod block;
+mod board;
+mod canonical;
+mod certificate;
+#[cfg(not(feature = "zkvm"))]
+mod digest;
+mod inputs;
+mod invite;
+mod outcome;
+mod placement;
+mod response;
+mod state_root;
+mod turn_order;
+mod win;
+pub use blind::{
+    board_digest, ledger_digest, outcome_commitment, BlindReportInputs, BlindReportJournal,
+    BlindWinInputs, BlindWinJournal, RevealData, RevealStatement, REVEAL_DOMAIN,
+};
+pub use block::{transactions_digest, Block, BlockHeader, BlockTransaction};
+pub use board::{Board, Coord, CoordError, BOARD_SIZE};
+pub use canonical::{canonical_json, signed_message, CanonicalError};
+#[cfg(feature = "signatures")]
+pub use canonical::{sign_canonical, verify_canonical};
+pub use certificate::{CertifiedPlayer, GameCertificate, SignedCertificate, CERTIFICATE_DOMAIN};
+pub use inputs::{
+    check_challenge, check_correlation_id, check_id, check_random, check_register_gameid, InputError, InvalidInput, MAX_CHALLENGE_LEN,
+    MAX_ID_LEN, MAX_JOURNAL_LEN, MAX_RANDOM_LEN,
+};
+pub use invite::{Invite, SignedInvite, INVITE_DOMAIN};
+pub use outcome::{report_outcome, ShotOutcome};
+pub use placement::{fits, ships, validate_fleet_placement, SHIP_SIZES};
+pub use response::{
+    EventStatement, ResponseStatement, EVENT_DOMAIN, RESPONSE_DOMAIN, RESPONSE_NONCE_HEADER, RESPONSE_SIGNATURE_HEADER,
+};
+pub use state_root::{PlayerSnapshot, StateRoot, StateSnapshot, STATE_ROOT_DOMAIN};
+pub use turn_order::{journal_digest, JoinDigest, TurnOrder};
+pub use win::{fleet_squares, WinBasis, WinInputs, WinJournal};
+#[cfg(feature = "zkvm")]
+pub use risc0_zkvm::Digest;
+#[cfg(not(feature = "zkvm"))]
+pub use digest::{Digest, DigestError};
+
+// Struct sent by the rust code for input on the methods join, wave, forfeit, status and contest
 // The struct is read by the zkvm code and the data is used to generate the output Journal
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseInputs {
     pub gameid: String,
     pub fleet: String,
-    pub board: Vec<u8>,
+    pub board: Board,
     pub random: String,
     // Add turn validation fields
     pub game_next_player: Option<String>,  // Who should fire next
     pub game_next_report: Option<String>,  // Who should report next
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
 }
 
 // If GameState isn't available from fleetcore, add this struct definition
@@ -19,6 +65,28 @@ pub struct BaseInputs {
 pub struct GameState {
     pub next_player: Option<String>,
     pub next_report: Option<String>,
+    // Sequence number of the last transaction the chain had applied when the state was read
+    #[serde(default)]
+    pub seq: u64,
+    // Set in games with blinded reports, with the number of reports this player already made
+    #[serde(default)]
+    pub blinded: bool,
+    #[serde(default)]
+    pub report_turn: u32,
+    // Set when the player must prove its remaining fleet before firing again
+    #[serde(default)]
+    pub status_due: bool,
+    // Seconds to contest a victory claim, and when the running claim's timeout expires
+    #[serde(default)]
+    pub victory_timeout_seconds: u64,
+    #[serde(default)]
+    pub victory_deadline: Option<u64>,
+    // Seconds left on the player's clock, in games with clocks
+    #[serde(default)]
+    pub clock_left: Option<u64>,
+    // Set while the game is paused by its players
+    #[serde(default)]
+    pub paused: bool,
 }
 
 // Struct sent by the rust code for input on the methods fire and report
@@ -27,53 +95,477 @@ pub struct GameState {
 pub struct FireInputs {
     pub gameid: String,
     pub fleet: String,
-    pub board: Vec<u8>,
+    pub board: Board,
     pub random: String,
     pub target: String,
-    pub pos: u8,
+    pub pos: Coord,
     // Add turn validation fields
     pub game_next_player: Option<String>,  // Who should fire next
     pub game_next_report: Option<String>,  // Who should report next
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
+    // Squares of our board hit by earlier reports, to tell when a hit sinks a ship (reports only)
+    pub hits: Vec<Coord>,
+    // Board as placed when joining, the image ID of the join guest and the journal of our join
+    // receipt, which the fire guest verifies as an assumption (shots only)
+    pub placement: Board,
+    pub join_id: Digest,
+    pub join_journal: Vec<u8>,
+    // Image ID of the fire guest and journal of the shot being answered, which the report guest
+    // verifies as an assumption (reports only)
+    pub fire_id: Digest,
+    pub fire_journal: Vec<u8>,
 }
 
+// Struct sent by the rust code for input on the audit method: the journals of every report a
+// player made in a game, in order, each verified by the guest as an assumption
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AuditInputs {
+    pub gameid: String,
+    pub fleet: String,
+    // Board digest committed when joining
+    pub initial: Digest,
+    pub report_id: Digest,
+    pub reports: Vec<Vec<u8>>,
+    pub challenge: String,
+}
+
+// Version of the transaction and journal formats. Every journal starts with the version it was
+// written in, so the chain can tell a journal in a format it does not know from a corrupt one.
+// Version 2 digests boards as bitboards rather than as the list of squares in placement order.
+pub const PROTOCOL_VERSION: u32 = 2;
+// Oldest version the chain still accepts: board digests of version 1 cannot be checked any more
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
 // Enum used to define the command that will be sent to the server by the host in the communication packet
-#[derive(Deserialize,Serialize)]
-pub enum Command {Join, Fire, Report, Wave, Win}
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Command {Join, Fire, Report, Wave, Win, BlindReport, BlindWin, Forfeit, Register, Status, Audit, Contest}
+
+impl Command {
+    pub const ALL: [Command; 12] = [
+        Command::Join,
+        Command::Fire,
+        Command::Report,
+        Command::Wave,
+        Command::Win,
+        Command::BlindReport,
+        Command::BlindWin,
+        Command::Forfeit,
+        Command::Register,
+        Command::Status,
+        Command::Audit,
+        Command::Contest,
+    ];
+}
+
+// Header carrying the ID of the player's action a request belongs to, from the host to the chain
+// and back, so the action can be followed in the logs and events of both
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
 
 // Struct used to specify the packet sent from the client to the blockchain server
-#[derive(Deserialize,Serialize)]
+#[cfg(feature = "zkvm")]
+#[derive(Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CommunicationData {
+    // PROTOCOL_VERSION of the host, 0 for hosts that predate versioning
+    #[serde(default)]
+    pub version: u32,
     pub cmd: Command,
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
     pub receipt: Receipt,
     pub signature: Vec<u8>,
     pub public_key: Option<Vec<u8>>,
+    // Must be larger than the nonce of the player's previous receipt, so receipts cannot be replayed
+    pub nonce: u64,
+    // Cycle count reported by the prover, absent from hosts that predate cycle accounting
+    #[serde(default)]
+    pub cycles: Option<u64>,
+    // Join only: the game created by the join is private
+    #[serde(default)]
+    pub private: bool,
+    // Join only: invite code to a private game, signed by its creator
+    #[serde(default)]
+    pub invite: Option<String>,
+    // Join only: team of the player, which makes the game a team game when the join creates it
+    #[serde(default)]
+    pub team: Option<String>,
+    // Join only: seconds to contest a victory claim in the game the join creates, instead of the
+    // chain's default
+    #[serde(default)]
+    pub victory_timeout: Option<u64>,
+    // ID of the player's action on the host, also sent as CORRELATION_HEADER; not signed
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+// Receipt transaction the chain applied to a game, as served on `/games/{id}/receipts` so third
+// parties can check the game on their own
+#[cfg(feature = "zkvm")]
+#[derive(Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppliedReceipt {
+    // Index of the transaction, and the sealed block holding it (None while its block is open)
+    pub index: u64,
+    pub block: Option<u64>,
+    // Time the chain accepted the transaction
+    pub at: u64,
+    pub data: CommunicationData,
+}
+
+// Body of the chain's response to a transaction or journal in a version it does not accept
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnsupportedVersion {
+    pub version: u32,
+    pub min_supported: u32,
+    pub max_supported: u32,
+}
+
+// Challenge the chain issues to a player before a proof, on `/challenge/{gameid}/{fleet}`. The
+// guest commits it to the journal and the chain accepts it once, until `expires_at`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Challenge {
+    pub challenge: String,
+    pub expires_at: u64,
+}
+
+// Request to be placed in an open game, on `/matchmake`. Rated requests are only matched with
+// fleets of the same rating band.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MatchRequest {
+    pub fleet: String,
+    #[serde(default)]
+    pub rated: bool,
+}
+
+// Game the chain assigned a fleet to, which the fleet then joins as usual
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MatchAssignment {
+    pub gameid: String,
+    // The game was opened for this request
+    pub created: bool,
+    // Fleets assigned to the game so far, this one included
+    pub fleets: usize,
+    // Rating band of a rated match
+    pub band: Option<u32>,
+}
+
+// Image ID the chain accepts for a command, from the guests it was built with or from a previous
+// build it still accepts until `cutoff`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AcceptedMethod {
+    pub command: Command,
+    pub version: String,
+    pub image_id: String,
+    pub cutoff: Option<u64>,
+}
+
+// Guests accepted by the chain, served on `/methods` so hosts can check they are compatible
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AcceptedMethods {
+    pub current: String,
+    pub methods: Vec<AcceptedMethod>,
+}
+
+// Domain tag for receipt signatures
+pub const RECEIPT_DOMAIN: &str = "fleet/receipt/v1";
+
+// Statement signed (in canonical form) by the player when sending a receipt to the chain. It binds
+// the journal to the command and the game it is sent for, so it cannot be replayed in another context.
+#[derive(Serialize)]
+pub struct ReceiptStatement<'a> {
+    pub cmd: Command,
+    pub gameid: &'a str,
+    pub nonce: u64,
+    pub journal: Digest,
+}
+
+impl<'a> ReceiptStatement<'a> {
+    pub fn new(cmd: Command, gameid: &'a str, nonce: u64, journal: &[u8]) -> Self {
+        ReceiptStatement { cmd, gameid, nonce, journal: journal_digest(journal) }
+    }
+}
+
+// Game ID of a registration that holds in every game
+pub const ANY_GAME: &str = "*";
+
+// Struct sent by the rust code for input on the method register
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RegisterInputs {
+    pub gameid: String, // game the key is registered for, or ANY_GAME
+    pub fleet: String,
+    pub public_key: Vec<u8>,
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
+}
+
+// Struct to specify the output journal for register method
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct RegisterJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub public_key: Vec<u8>,
+    pub challenge: String,
 }
 
-// Struct to specify the  output journal for join, wave and win methods
+// Struct to specify the  output journal for join, wave, win and forfeit methods
 #[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
 pub struct BaseJournal {
+    pub version: u32,
     pub gameid: String,
     pub fleet: String,
     pub board: Digest,
+    pub challenge: String,
 }
 
 // Struct to specify the  output journal for fire method
 #[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
 pub struct FireJournal {
+    pub version: u32,
     pub gameid: String,
     pub fleet: String,
     pub board: Digest,
     pub target: String,
-    pub pos: u8,
+    pub pos: Coord,
+    pub challenge: String,
+    // Join receipt the board was checked against: image ID of its guest and digest of its journal
+    pub join_id: Digest,
+    pub join_journal: Digest,
+}
+
+// Struct to specify the  output journal for status method: the number of ship squares left on the
+// player's board, without their positions
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct StatusJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub remaining: u32,
+    pub challenge: String,
+}
+
+// Struct to specify the output journal for audit method: the player's reports took the board
+// committed when joining to `board`, one after the other
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct AuditJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub initial: Digest,
+    pub board: Digest,
+    pub reports: u32,
+    pub report_id: Digest,
+    pub challenge: String,
 }
 
 // Struct to specify the  output journal for report method
 #[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
 pub struct ReportJournal {
+    pub version: u32,
     pub gameid: String,
     pub fleet: String,
-    pub report: String,
-    pub pos: u8,
+    pub report: ShotOutcome,
+    pub pos: Coord,
     pub board: Digest,
     pub next_board: Digest,
-}
\ No newline at end of file
+    pub challenge: String,
+    // Earlier hits the outcome was computed with, checked by the chain against its history
+    pub hits: Vec<Coord>,
+    // Fire receipt the report answers: image ID of its guest and digest of its journal
+    pub fire_id: Digest,
+    pub fire_journal: Digest,
+}
+
+// Kind of annotation a player can attach to a finished game
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AnnotationKind {Gg, Strategy}
+
+// Struct used to attach a signed annotation (gg message, strategy notes) to an archived game
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AnnotationData {
+    pub fleet: String,
+    pub kind: AnnotationKind,
+    pub text: String,
+    // Must be larger than the nonce of the player's previous annotation, so it cannot be replayed
+    pub nonce: u64,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for annotation signatures
+pub const ANNOTATION_DOMAIN: &str = "fleet/annotation/v1";
+
+// Statement signed (in canonical form) by the player when attaching an annotation to a game
+#[derive(Serialize)]
+pub struct AnnotationStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub kind: AnnotationKind,
+    pub text: &'a str,
+    pub nonce: u64,
+}
+
+// Struct used to mark a player ready in the lobby of a game. The creator of the game can also
+// start it without waiting for the other players.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ReadyData {
+    pub fleet: String,
+    pub start: bool,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for ready signatures
+pub const READY_DOMAIN: &str = "fleet/ready/v1";
+
+// Statement signed (in canonical form) by the player when getting ready or starting the game
+#[derive(Serialize)]
+pub struct ReadyStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub start: bool,
+}
+
+// Struct used to send a signed chat message to the other players of a running game
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChatData {
+    pub fleet: String,
+    pub text: String,
+    // Must be larger than the nonce of the player's previous message, so messages cannot be replayed
+    pub nonce: u64,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for chat signatures
+pub const CHAT_DOMAIN: &str = "fleet/chat/v1";
+
+// Statement signed (in canonical form) by the player when sending a chat message
+#[derive(Serialize)]
+pub struct ChatStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub text: &'a str,
+    pub nonce: u64,
+}
+
+// Struct used to vote for pausing a running game, or for resuming a paused one. The game switches
+// once every player still in the game voted for it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PauseData {
+    pub fleet: String,
+    // True to pause the game, false to resume it
+    pub pause: bool,
+    // Must be larger than the nonce of the player's previous vote, so votes cannot be replayed
+    pub nonce: u64,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for pause signatures
+pub const PAUSE_DOMAIN: &str = "fleet/pause/v1";
+
+// Statement signed (in canonical form) by the player when voting to pause or resume a game
+#[derive(Serialize)]
+pub struct PauseStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub pause: bool,
+    pub nonce: u64,
+}
+
+// Struct used by a player of a game that ended to ask for a rematch with the same players. The
+// chain answers with the ID of the new game, which the players then join with a new board.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RematchData {
+    pub fleet: String,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for rematch signatures
+pub const REMATCH_DOMAIN: &str = "fleet/rematch/v1";
+
+// Statement signed (in canonical form) by the player when asking for a rematch of a game
+#[derive(Serialize)]
+pub struct RematchStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+}
+
+// Domain tag for webhook registration signatures
+pub const HOOK_DOMAIN: &str = "fleet/hook/v1";
+
+// Statement signed (in canonical form) by a player registering a webhook for its game
+#[derive(Serialize)]
+pub struct HookStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub url: &'a str,
+    pub events: &'a [String],
+    pub nonce: u64,
+}
+
+// Content key of a team message, sealed for one member of the team
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SealedKey {
+    pub fleet: String,
+    // Content key encrypted with ChaCha20-Poly1305 under the key agreed by X25519 between the
+    // message's ephemeral key and the member's key
+    pub key: Vec<u8>,
+}
+
+// Struct used to send a message to the members of a player's team in a team game. The text is
+// encrypted with ChaCha20-Poly1305 under a random content key, sealed for every member of the team
+// (the sender included) with the X25519 form of the member's Ed25519 key. The chain checks the
+// signature and the recipients, and relays the message without being able to read it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TeamMessageData {
+    pub fleet: String,
+    // X25519 public key made for this message only
+    pub ephemeral_key: Vec<u8>,
+    // Nonce of the encrypted text
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub keys: Vec<SealedKey>,
+    // Must be larger than the nonce of the player's previous team message, so messages cannot be replayed
+    pub nonce: u64,
+    pub signature: Vec<u8>,
+}
+
+// Domain tag for team message signatures
+pub const TEAM_MESSAGE_DOMAIN: &str = "fleet/team-message/v1";
+
+// Statement signed (in canonical form) by the player when sending a team message
+#[derive(Serialize)]
+pub struct TeamMessageStatement<'a> {
+    pub gameid: &'a str,
+    pub fleet: &'a str,
+    pub ephemeral_key: &'a [u8],
+    pub iv: &'a [u8],
+    pub ciphertext: &'a [u8],
+    pub keys: &'a [SealedKey],
+    pub nonce: u64,
+}
+
+// Team message as relayed by the chain, numbered from 1 in its game
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TeamMessage {
+    pub seq: u64,
+    pub sent_at: u64,
+    #[serde(flatten)]
+    pub data: TeamMessageData,
+}
+
+// Member of a team and the key its messages are signed with, and sealed for
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TeamMember {
+    pub fleet: String,
+    pub public_key: Vec<u8>,
+}
+
+// Channel of a team, as served to one of its members
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TeamChannel {
+    pub team: String,
+    pub members: Vec<TeamMember>,
+    pub messages: Vec<TeamMessage>,
+}
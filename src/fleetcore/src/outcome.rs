@@ -0,0 +1,140 @@
+// Outcome of a shot, as proven by the report guest. Ships never touch (see placement.rs), so the
+// ship under a square is the group of squares connected to it on the board as placed: the squares
+// still afloat plus the ones hit by earlier reports. A hit sinks that ship when none of its other
+// squares is still afloat.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::board::{Board, Coord};
+use crate::placement::grow;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ShotOutcome {
+    Hit,
+    #[default]
+    Miss,
+    // The hit completed a ship of the given size
+    Sunk { size: u8 },
+}
+
+impl ShotOutcome {
+    pub fn is_hit(self) -> bool {
+        self != ShotOutcome::Miss
+    }
+}
+
+// "Hit", "Miss" or "Sunk(3)", as shown in the chain's events
+impl fmt::Display for ShotOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShotOutcome::Hit => write!(f, "Hit"),
+            ShotOutcome::Miss => write!(f, "Miss"),
+            ShotOutcome::Sunk { size } => write!(f, "Sunk({})", size),
+        }
+    }
+}
+
+impl FromStr for ShotOutcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "Hit" => Ok(ShotOutcome::Hit),
+            "Miss" => Ok(ShotOutcome::Miss),
+            _ => s
+                .strip_prefix("Sunk(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|size| size.parse().ok())
+                .map(|size| ShotOutcome::Sunk { size })
+                .ok_or_else(|| format!("unknown outcome {}", s)),
+        }
+    }
+}
+
+// Outcome of a shot at `pos` on `board`, given the squares of the board hit by earlier reports
+pub fn report_outcome(board: &Board, hits: &[Coord], pos: Coord) -> ShotOutcome {
+    if !board.contains(pos) {
+        return ShotOutcome::Miss;
+    }
+
+    // Squares of the board as placed
+    let placed = board.bits() | Board::new(hits.iter().copied()).bits();
+
+    // Ship under `pos`
+    let ship = grow(placed, Board::new([pos]).bits());
+
+    // Afloat while another square of the ship is still on the board
+    if ship & board.without(pos).bits() != 0 {
+        ShotOutcome::Hit
+    } else {
+        ShotOutcome::Sunk { size: ship.count_ones() as u8 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(cell: &str) -> Coord {
+        cell.parse().unwrap()
+    }
+
+    fn board(cells: &str) -> Board {
+        Board::new(cells.split_whitespace().map(coord))
+    }
+
+    // A carrier, a battleship, a destroyer, two cruisers and two submarines
+    const FLEET: &str = "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 C6";
+
+    #[test]
+    fn a_shot_beside_the_ships_misses() {
+        assert_eq!(report_outcome(&board(FLEET), &[], coord("F0")), ShotOutcome::Miss);
+        assert_eq!(report_outcome(&board(FLEET), &[], coord("J9")), ShotOutcome::Miss);
+    }
+
+    #[test]
+    fn a_hit_leaves_the_rest_of_the_ship_afloat() {
+        assert_eq!(report_outcome(&board(FLEET), &[], coord("C0")), ShotOutcome::Hit);
+        // Every square but one hit
+        let hits = [coord("A2"), coord("C2")];
+        let afloat = board(FLEET).without(hits[0]).without(hits[1]);
+        assert_eq!(report_outcome(&afloat, &hits, coord("E2")), ShotOutcome::Hit);
+    }
+
+    #[test]
+    fn a_submarine_sinks_with_its_only_square() {
+        assert_eq!(report_outcome(&board(FLEET), &[], coord("A6")), ShotOutcome::Sunk { size: 1 });
+    }
+
+    #[test]
+    fn the_carrier_sinks_with_its_last_square() {
+        let hits: Vec<Coord> = ["A0", "B0", "D0", "E0"].into_iter().map(coord).collect();
+        let afloat = hits.iter().fold(board(FLEET), |afloat, &hit| afloat.without(hit));
+        assert_eq!(report_outcome(&afloat, &hits, coord("C0")), ShotOutcome::Sunk { size: 5 });
+        // Hits on other ships do not count towards it
+        let hits = [coord("A2"), coord("B2")];
+        let afloat = board(FLEET).without(hits[0]).without(hits[1]);
+        assert_eq!(report_outcome(&afloat, &hits, coord("E0")), ShotOutcome::Hit);
+    }
+
+    #[test]
+    fn a_square_already_hit_misses() {
+        let hits = [coord("G0")];
+        let afloat = board(FLEET).without(hits[0]);
+        assert_eq!(report_outcome(&afloat, &hits, coord("G0")), ShotOutcome::Miss);
+        // Even when it was the last square of a submarine
+        let hits = [coord("C6")];
+        let afloat = board(FLEET).without(hits[0]);
+        assert_eq!(report_outcome(&afloat, &hits, coord("C6")), ShotOutcome::Miss);
+    }
+
+    #[test]
+    fn outcomes_read_back_from_their_names() {
+        for outcome in [ShotOutcome::Hit, ShotOutcome::Miss, ShotOutcome::Sunk { size: 3 }] {
+            assert_eq!(outcome.to_string().parse::<ShotOutcome>(), Ok(outcome));
+        }
+        assert!("Sunk(x)".parse::<ShotOutcome>().is_err());
+    }
+}
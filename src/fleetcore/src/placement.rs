@@ -0,0 +1,221 @@
+// Rules of a legal fleet placement, checked by the join guest before it commits to a board and
+// used by the host to generate random placements and to place ships one at a time.
+//
+// The join guest runs this inside the zkVM, where every allocation and hash costs cycles, so ships
+// are found, measured and compared with shifts and masks on the board's bitboard.
+
+use crate::Board;
+
+// Expected ship sizes: 1 carrier (size 5), 1 battleship (size 4), 1 destroyer (size 3),
+// 2 cruisers (size 2), 2 submarines (size 1)
+pub const SHIP_SIZES: &[usize] = &[5, 4, 3, 2, 2, 1, 1];
+
+// Squares of the 10x10 board
+const BOARD: u128 = (1 << 100) - 1;
+// Squares of the first and the last column
+const FIRST_COL: u128 = {
+    let mut mask = 0;
+    let mut row = 0;
+    while row < 10 {
+        mask |= 1 << (row * 10);
+        row += 1;
+    }
+    mask
+};
+const LAST_COL: u128 = FIRST_COL << 9;
+// Squares of the first row
+const FIRST_ROW: u128 = (1 << 10) - 1;
+// Most ships a board of the right size can hold
+const MAX_SHIPS: usize = 18;
+
+// Squares next to the squares of `mask`, up, down, left or right
+fn neighbors(mask: u128) -> u128 {
+    ((mask << 10) | (mask >> 10) | ((mask & !LAST_COL) << 1) | ((mask & !FIRST_COL) >> 1)) & BOARD
+}
+
+// Squares around the squares of `mask`, diagonals included
+fn surroundings(mask: u128) -> u128 {
+    let row = mask | ((mask & !LAST_COL) << 1) | ((mask & !FIRST_COL) >> 1);
+    (row | (row << 10) | (row >> 10)) & BOARD & !mask
+}
+
+// Ship of `grid` holding the squares of `seed`: grow it (up, down, left, right only) until it takes
+// in no other square
+pub(crate) fn grow(grid: u128, seed: u128) -> u128 {
+    let mut ship = seed;
+    loop {
+        let grown = ship | (neighbors(ship) & grid);
+        if grown == ship {
+            return ship;
+        }
+        ship = grown;
+    }
+}
+
+// Ships of a board, each as the board of its squares, in the order of their first square
+pub fn ships(board: Board) -> Vec<Board> {
+    let mut ships = Vec::new();
+    let mut left = board.bits();
+    while left != 0 {
+        let ship = grow(board.bits(), left & left.wrapping_neg());
+        ships.push(Board::from_bits(ship).unwrap_or_default());
+        left &= !ship;
+    }
+    ships
+}
+
+// Whether `ship` can join the ships of `board`: on free squares touching none of them, even
+// diagonally
+pub fn fits(board: Board, ship: Board) -> bool {
+    (ship.bits() | surroundings(ship.bits())) & board.bits() == 0
+}
+
+// IMPORTANT:This code follows the rules of the classical Battleship game.
+// Boats must be placed in a straight line (either horizontally or vertically), cannot touch each other either directly or diagonally, and must be of specific sizes.
+// The definition of classical Battleship comes from the internet, and disagrees with my childhood memories.
+// Not in the scope of this course, but important to note that the game has many variations, and this code implements one of them.
+// A board holds each square at most once and only squares within the grid, so neither needs checking.
+pub fn validate_fleet_placement(board: Board) -> Result<(), String> {
+    let total_squares = SHIP_SIZES.iter().sum::<usize>(); // Should be 18
+
+    // Check if board has the correct number of squares
+    if board.len() != total_squares {
+        return Err(format!("Invalid number of ship squares: expected {}, got {}",
+                         total_squares, board.len()));
+    }
+    let grid = board.bits();
+
+    // Find all ships by growing each one from a square until it takes in no neighbor
+    let mut ships = [0u128; MAX_SHIPS];
+    let mut count = 0;
+    let mut left = grid;
+    while left != 0 {
+        let ship = grow(grid, left & left.wrapping_neg());
+        ships[count] = ship;
+        count += 1;
+        left &= !ship;
+    }
+    let ships = &ships[..count];
+
+    // Validate ship counts, by size
+    let mut ship_counts = [0u8; MAX_SHIPS + 1];
+    for ship in ships {
+        ship_counts[ship.count_ones() as usize] += 1;
+    }
+    let mut expected_counts = [0u8; MAX_SHIPS + 1];
+    for &size in SHIP_SIZES {
+        expected_counts[size] += 1;
+    }
+    if ship_counts != expected_counts {
+        return Err(format!("Invalid ship configuration: expected sizes {:?}, got {:?}",
+                         sizes(&expected_counts), sizes(&ship_counts)));
+    }
+
+    // Validate ship shapes (must be straight lines)
+    if !ships.iter().all(|&ship| is_straight_line(ship)) {
+        return Err("Ships must be straight lines (no L-shapes allowed)".to_string());
+    }
+
+    // Check that ships don't touch each other (including diagonally)
+    if ships.iter().any(|&ship| surroundings(ship) & grid != 0) {
+        return Err("Ships cannot touch each other either directly or diagonally".to_string());
+    }
+
+    Ok(())
+}
+
+// A connected ship is a straight line when it fits in the row or the column of its first square
+fn is_straight_line(ship: u128) -> bool {
+    let first = ship.trailing_zeros();
+    let (row, col) = (first / 10, first % 10);
+    ship & (FIRST_ROW << (row * 10)) == ship || ship & (FIRST_COL << col) == ship
+}
+
+// Sizes of the ships counted by size, largest first
+fn sizes(counts: &[u8]) -> Vec<usize> {
+    (0..counts.len()).rev().flat_map(|size| std::iter::repeat(size).take(counts[size] as usize)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coord;
+
+    // Board of the squares named in `cells`, like "A0 B0 C0"
+    fn board(cells: &str) -> Board {
+        Board::new(cells.split_whitespace().map(|cell| cell.parse::<Coord>().unwrap()))
+    }
+
+    // A carrier and a battleship on the first row, a destroyer and two cruisers on the third and
+    // two submarines on the seventh
+    const FLEET: &str = "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 C6";
+
+    #[test]
+    fn fleets_are_checked_against_the_rules() {
+        let cases = [
+            ("a legal fleet", FLEET, None),
+            (
+                "a legal fleet standing up",
+                "A0 A1 A2 A3 A4 C0 C1 C2 C3 E0 E1 E2 G0 G1 I0 I1 J5 J9",
+                None,
+            ),
+            (
+                "an L-shaped destroyer",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 B3 E2 F2 H2 I2 A6 C6",
+                Some("Ships must be straight lines (no L-shapes allowed)"),
+            ),
+            (
+                "cruisers side by side",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 E3 F3 A6 C6",
+                Some("Invalid ship configuration: expected sizes [5, 4, 3, 2, 2, 1, 1], got [5, 4, 4, 3, 1, 1]"),
+            ),
+            (
+                "a submarine touching two ships diagonally",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 D3",
+                Some("Ships cannot touch each other either directly or diagonally"),
+            ),
+            (
+                "a third cruiser instead of the submarines",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 B6",
+                Some("Invalid ship configuration: expected sizes [5, 4, 3, 2, 2, 1, 1], got [5, 4, 3, 2, 2, 2]"),
+            ),
+            (
+                "a missing submarine",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6",
+                Some("Invalid number of ship squares: expected 18, got 17"),
+            ),
+            (
+                "an extra submarine",
+                "A0 B0 C0 D0 E0 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 C6 E6",
+                Some("Invalid number of ship squares: expected 18, got 19"),
+            ),
+            (
+                // H8 I8 J8 A9 B9 are consecutive positions, but not a ship
+                "a carrier wrapping from column J to column A",
+                "H8 I8 J8 A9 B9 G0 H0 I0 J0 A2 B2 C2 E2 F2 H2 I2 A6 C6",
+                Some("Invalid ship configuration: expected sizes [5, 4, 3, 2, 2, 1, 1], got [4, 3, 3, 2, 2, 2, 1, 1]"),
+            ),
+        ];
+        for (name, cells, expected) in cases {
+            assert_eq!(validate_fleet_placement(board(cells)).err().as_deref(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn ships_are_found_in_the_order_of_their_first_square() {
+        let found: Vec<usize> = ships(board(FLEET)).into_iter().map(Board::len).collect();
+        assert_eq!(found, [5, 4, 3, 2, 2, 1, 1]);
+        // The squares on both sides of the edge belong to different ships
+        assert_eq!(ships(board("J0 A1")).len(), 2);
+    }
+
+    #[test]
+    fn ships_fit_only_on_free_squares_away_from_the_others() {
+        let fleet = board("A0 B0 C0");
+        assert!(fits(fleet, board("E0 F0")));
+        assert!(fits(fleet, board("J0 J1")));
+        assert!(!fits(fleet, board("C0 C1")));
+        assert!(!fits(fleet, board("D0 E0")));
+        assert!(!fits(fleet, board("D1 E1")));
+    }
+}
@@ -0,0 +1,65 @@
+// Signed responses of the chain. Each node of the chain signs the responses it serves and the
+// events it publishes with its response key (published on `/pubkey`), so a host holding that key
+// can tell a response from the chain from one forged on the way, e.g. an "OK" for a transaction
+// the chain never saw. A response signature covers the request it answers (method, path and the
+// nonce the client sent in `x-fleet-nonce`) along with the status and body, so it cannot be
+// replayed as the answer to another request.
+
+#[cfg(feature = "signatures")]
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+use crate::journal_digest;
+#[cfg(feature = "signatures")]
+use crate::verify_canonical;
+
+// Domain tags for response and event signatures
+pub const RESPONSE_DOMAIN: &str = "fleet/response/v1";
+pub const EVENT_DOMAIN: &str = "fleet/event/v1";
+
+// Header carrying the hex-encoded signature of a response, and the header of the client's nonce
+pub const RESPONSE_SIGNATURE_HEADER: &str = "x-chain-signature";
+pub const RESPONSE_NONCE_HEADER: &str = "x-fleet-nonce";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ResponseStatement {
+    pub method: String,
+    // Path of the request, query included
+    pub path: String,
+    pub nonce: Option<String>,
+    pub status: u16,
+    // Hex-encoded SHA-256 of the body
+    pub body: String,
+}
+
+impl ResponseStatement {
+    pub fn new(method: &str, path: &str, nonce: Option<&str>, status: u16, body: &[u8]) -> Self {
+        ResponseStatement {
+            method: method.to_string(),
+            path: path.to_string(),
+            nonce: nonce.map(String::from),
+            status,
+            body: journal_digest(body).to_string(),
+        }
+    }
+
+    // Check the chain's signature, with a response key obtained from a trusted source
+    #[cfg(feature = "signatures")]
+    pub fn verify(&self, key: &VerifyingKey, signature: &[u8]) -> bool {
+        verify_canonical(key, RESPONSE_DOMAIN, self, signature)
+    }
+}
+
+// What an event signature covers: the number and message of the event, as sent on `/logs`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EventStatement {
+    pub seq: u64,
+    pub msg: String,
+}
+
+impl EventStatement {
+    #[cfg(feature = "signatures")]
+    pub fn verify(&self, key: &VerifyingKey, signature: &[u8]) -> bool {
+        verify_canonical(key, EVENT_DOMAIN, self, signature)
+    }
+}
@@ -0,0 +1,65 @@
+// State roots. The chain publishes the state of a game it serves in a canonical form, with a
+// digest of it, so hosts and auditors can compare the roots several replicas give for the same
+// game, or the roots before and after reconnecting, and notice when two states diverged. The state
+// only holds what every replica derives from the transactions; StateSnapshot::root recomputes the
+// root from the published state.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{journal_digest, signed_message, Digest};
+
+// Domain tag of the state roots
+pub const STATE_ROOT_DOMAIN: &str = "fleet/state-root/v1";
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PlayerSnapshot {
+    pub fleet: String,
+    // Digest of the player's current board
+    pub board: Digest,
+    pub ready: bool,
+    pub forfeited: bool,
+    pub claimed_victory: bool,
+    pub shots_fired: u32,
+    pub hits: u32,
+    // Nonce of the last receipt the player sent
+    pub receipt_nonce: u64,
+    pub team: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StateSnapshot {
+    pub gameid: String,
+    pub started: bool,
+    pub paused: bool,
+    pub next_player: Option<String>,
+    pub next_report: Option<String>,
+    // Player whose victory claim runs, and when it was made
+    pub victory_claim: Option<(String, u64)>,
+    // Shots fired so far
+    pub shots: u32,
+    // Players, by fleet
+    pub players: Vec<PlayerSnapshot>,
+}
+
+impl StateSnapshot {
+    // Digest of the canonical form of the state
+    pub fn root(&self) -> Digest {
+        journal_digest(&signed_message(STATE_ROOT_DOMAIN, self).unwrap_or_default())
+    }
+}
+
+// State root of a game, served by the chain with the state it digests
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StateRoot {
+    // Sequence number of the last transaction the chain had applied when the state was read
+    pub seq: u64,
+    pub root: Digest,
+    pub state: StateSnapshot,
+}
+
+impl StateRoot {
+    // Check that the root is the digest of the state
+    pub fn verify(&self) -> bool {
+        self.state.root() == self.root
+    }
+}
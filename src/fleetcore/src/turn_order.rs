@@ -0,0 +1,80 @@
+// Verifiable turn order: when a game starts, the chain derives the order in which players take
+// turns from the journals of their joins, which each player proved with its own random seed, so
+// neither the chain nor a single player can choose who goes first. Clients recompute the order
+// from the published join digests with TurnOrder::verify.
+
+use crate::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+// Domain tags of the turn order digests
+const SEED_DOMAIN: &[u8] = b"fleet/turn-order/seed/v1";
+const RANK_DOMAIN: &[u8] = b"fleet/turn-order/rank/v1";
+
+fn update_str(hasher: &mut Sha256, s: &str) {
+    hasher.update((s.len() as u32).to_le_bytes());
+    hasher.update(s.as_bytes());
+}
+
+fn finish(hasher: Sha256) -> Digest {
+    Digest::from(<[u8; 32]>::from(hasher.finalize()))
+}
+
+// Digest of the journal of a player's join receipt
+pub fn journal_digest(journal: &[u8]) -> Digest {
+    finish(Sha256::new_with_prefix(journal))
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct JoinDigest {
+    pub fleet: String,
+    pub digest: Digest,
+}
+
+// Turn order of a game with everything needed to recompute it
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TurnOrder {
+    pub gameid: String,
+    // Join digests of the players, sorted by fleet
+    pub joins: Vec<JoinDigest>,
+    // Hash of the game ID and every join digest
+    pub seed: Digest,
+    // Players ranked by the hash of the seed and their fleet
+    pub order: Vec<String>,
+}
+
+impl TurnOrder {
+    // Derive the turn order of a game from the join digests of its players
+    pub fn derive(gameid: &str, mut joins: Vec<JoinDigest>) -> Self {
+        joins.sort_by(|a, b| a.fleet.cmp(&b.fleet));
+
+        let mut hasher = Sha256::new();
+        hasher.update(SEED_DOMAIN);
+        update_str(&mut hasher, gameid);
+        hasher.update((joins.len() as u32).to_le_bytes());
+        for join in &joins {
+            update_str(&mut hasher, &join.fleet);
+            hasher.update(join.digest.as_bytes());
+        }
+        let seed = finish(hasher);
+
+        let rank = |fleet: &str| {
+            let mut hasher = Sha256::new();
+            hasher.update(RANK_DOMAIN);
+            hasher.update(seed.as_bytes());
+            update_str(&mut hasher, fleet);
+            finish(hasher)
+        };
+        let mut ranked: Vec<(Digest, String)> = joins.iter().map(|join| (rank(&join.fleet), join.fleet.clone())).collect();
+        ranked.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        let order = ranked.into_iter().map(|(_, fleet)| fleet).collect();
+
+        TurnOrder { gameid: gameid.to_string(), joins, seed, order }
+    }
+
+    // Check that the seed and the order follow from the join digests
+    pub fn verify(&self) -> bool {
+        let expected = TurnOrder::derive(&self.gameid, self.joins.clone());
+        expected.seed == self.seed && expected.order == self.order
+    }
+}
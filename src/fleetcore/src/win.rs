@@ -0,0 +1,78 @@
+// Plain victory claims. A claim has to say what it rests on: either one opponent's fleet was fully
+// destroyed, or the claimant hit every square of each opponent's fleet. The win guest proves the
+// claimant still has ships and that the basis is well formed; the chain then checks the basis
+// against the reports it applied, so a claim cannot be made just to stall the game.
+
+use crate::Digest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{Board, Coord, SHIP_SIZES};
+
+// Squares of a whole fleet
+pub fn fleet_squares() -> usize {
+    SHIP_SIZES.iter().sum()
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum WinBasis {
+    // Every square of the opponent's fleet was reported hit
+    Sunk { opponent: String },
+    // The squares of each opponent's fleet the claimant hit, all of them
+    Hits { opponents: Vec<(String, Vec<Coord>)> },
+}
+
+impl Default for WinBasis {
+    fn default() -> Self {
+        WinBasis::Hits { opponents: Vec::new() }
+    }
+}
+
+impl WinBasis {
+    // Check that the basis names other fleets and, for hits, whole fleets
+    pub fn check(&self, fleet: &str) -> Result<(), String> {
+        match self {
+            WinBasis::Sunk { opponent } if opponent == fleet => Err("A fleet is not its own opponent".to_string()),
+            WinBasis::Sunk { .. } => Ok(()),
+            WinBasis::Hits { opponents } => {
+                if opponents.is_empty() {
+                    return Err("No opponent was hit".to_string());
+                }
+                let mut named = HashSet::new();
+                for (opponent, hits) in opponents {
+                    if opponent == fleet || !named.insert(opponent) {
+                        return Err(format!("Opponent {} is named twice or is the claimant", opponent));
+                    }
+                    // A square named twice only counts once on a board
+                    if Board::new(hits.iter().copied()).len() != hits.len() || hits.len() != fleet_squares() {
+                        return Err(format!("The hits on {} are not a whole fleet", opponent));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Struct sent by the rust code for input on the method win
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WinInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Board,
+    pub random: String,
+    pub basis: WinBasis,
+    // Challenge issued by the chain for this proof, committed to the journal
+    pub challenge: String,
+}
+
+// Struct to specify the output journal for win method. It starts like a BaseJournal.
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct WinJournal {
+    pub version: u32,
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub challenge: String,
+    pub basis: WinBasis,
+}
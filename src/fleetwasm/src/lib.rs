@@ -0,0 +1,136 @@
+// In-browser verification of the chain's receipts. Built with
+// `wasm-pack build --target web --out-dir pkg fleetwasm`, this crate exposes to JavaScript the
+// journal decoding and receipt verification of fleetcore, so the chain's explorer and the host's
+// page can check a game's receipts, signatures and certificate on the client instead of trusting
+// the server that serves them.
+//
+// Values cross the boundary as JSON text, in the format the chain serves them: receipts and
+// AppliedReceipts from `/games/{id}/receipts`, AcceptedMethods from `/methods`, certificates from
+// `/games/{id}/certificate`. Keys and image IDs are hex strings.
+
+use ed25519_dalek::VerifyingKey;
+use fleetcore::{
+    journal_digest, verify_canonical, AcceptedMethods, AppliedReceipt, AuditJournal, BaseJournal, BlindReportJournal,
+    BlindWinJournal, Command, FireJournal, ReceiptStatement, RegisterJournal, ReportJournal, SignedCertificate,
+    StatusJournal, WinJournal, RECEIPT_DOMAIN,
+};
+use risc0_zkvm::{Digest, Receipt};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+fn parse<T: DeserializeOwned>(what: &str, json: &str) -> Result<T, JsError> {
+    serde_json::from_str(json).map_err(|e| JsError::new(&format!("invalid {}: {}", what, e)))
+}
+
+fn parse_key(hex_key: &str) -> Result<VerifyingKey, JsError> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| JsError::new("invalid public key"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| JsError::new("invalid public key"))
+}
+
+// Digests serialize as their eight words, shown as the usual hex string
+fn hex_digests(value: Value) -> Value {
+    match value {
+        Value::Array(words) if words.len() == 8 && words.iter().all(|w| w.as_u64().is_some_and(|w| w <= u32::MAX as u64)) => {
+            let bytes: Vec<u8> = words.iter().flat_map(|w| (w.as_u64().unwrap_or_default() as u32).to_le_bytes()).collect();
+            Value::String(hex::encode(bytes))
+        }
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(name, value)| (name, hex_digests(value))).collect()),
+        value => value,
+    }
+}
+
+fn journal_value<T: DeserializeOwned + Serialize>(receipt: &Receipt) -> Result<Value, JsError> {
+    let journal: T = receipt.journal.decode().map_err(|e| JsError::new(&format!("cannot decode journal: {}", e)))?;
+    serde_json::to_value(journal).map(hex_digests).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn journal(cmd: Command, receipt: &Receipt) -> Result<Value, JsError> {
+    match cmd {
+        Command::Join | Command::Wave | Command::Forfeit | Command::Contest => journal_value::<BaseJournal>(receipt),
+        Command::Fire => journal_value::<FireJournal>(receipt),
+        Command::Report => journal_value::<ReportJournal>(receipt),
+        Command::BlindReport => journal_value::<BlindReportJournal>(receipt),
+        Command::Win => journal_value::<WinJournal>(receipt),
+        Command::BlindWin => journal_value::<BlindWinJournal>(receipt),
+        Command::Register => journal_value::<RegisterJournal>(receipt),
+        Command::Status => journal_value::<StatusJournal>(receipt),
+        Command::Audit => journal_value::<AuditJournal>(receipt),
+    }
+}
+
+// Fields of the journal of a receipt for `cmd` ("Join", "Fire", ...), as JSON
+#[wasm_bindgen(js_name = decodeJournal)]
+pub fn decode_journal(cmd: &str, receipt: &str) -> Result<String, JsError> {
+    let cmd: Command = parse("command", &format!("\"{}\"", cmd))?;
+    let receipt: Receipt = parse("receipt", receipt)?;
+    Ok(journal(cmd, &receipt)?.to_string())
+}
+
+// Hex digest of a journal, as recorded in the chain's blocks
+#[wasm_bindgen(js_name = journalDigest)]
+pub fn journal_digest_hex(journal: &[u8]) -> String {
+    journal_digest(journal).to_string()
+}
+
+// Check a receipt against the hex image ID of its guest
+#[wasm_bindgen(js_name = verifyReceipt)]
+pub fn verify_receipt(receipt: &str, image_id: &str) -> Result<bool, JsError> {
+    let receipt: Receipt = parse("receipt", receipt)?;
+    let image_id: Digest = image_id.parse().map_err(|_| JsError::new("invalid image ID"))?;
+    Ok(receipt.verify(image_id).is_ok())
+}
+
+#[derive(Serialize)]
+struct Verification {
+    index: u64,
+    cmd: Command,
+    journal: Value,
+    // Published image ID the receipt verifies against, if any
+    image_id: Option<String>,
+    // Whether the player's key signed the receipt, when its key was given
+    signed: Option<bool>,
+}
+
+// Check a receipt served on `/games/{id}/receipts` against the image IDs the chain accepted for its
+// command when it was applied, and its signature against the player's key, from a JSON object of
+// hex keys by fleet. Returns the decoded journal with the results, as JSON.
+#[wasm_bindgen(js_name = verifyAppliedReceipt)]
+pub fn verify_applied_receipt(applied: &str, methods: &str, keys: &str) -> Result<String, JsError> {
+    let applied: AppliedReceipt = parse("receipt", applied)?;
+    let methods: AcceptedMethods = parse("methods", methods)?;
+    let keys: HashMap<String, String> = parse("keys", keys)?;
+    let data = &applied.data;
+
+    let image_id = methods
+        .methods
+        .iter()
+        .filter(|method| method.command == data.cmd && method.cutoff.map_or(true, |cutoff| applied.at < cutoff))
+        .find(|method| method.image_id.parse::<Digest>().is_ok_and(|id| data.receipt.verify(id).is_ok()))
+        .map(|method| method.image_id.clone());
+
+    let journal = journal(data.cmd, &data.receipt)?;
+    let field = |name: &str| journal.get(name).and_then(Value::as_str).unwrap_or_default();
+    let signed = match keys.get(field("fleet")) {
+        Some(key) => {
+            let key = parse_key(key)?;
+            let statement = ReceiptStatement::new(data.cmd, field("gameid"), data.nonce, &data.receipt.journal.bytes);
+            Some(verify_canonical(&key, RECEIPT_DOMAIN, &statement, &data.signature))
+        }
+        None => None,
+    };
+
+    let verification = Verification { index: applied.index, cmd: data.cmd, journal, image_id, signed };
+    serde_json::to_string(&verification).map_err(|e| JsError::new(&e.to_string()))
+}
+
+// Check a game certificate against the chain's hex genesis key
+#[wasm_bindgen(js_name = verifyCertificate)]
+pub fn verify_certificate(certificate: &str, genesis_key: &str) -> Result<bool, JsError> {
+    let certificate: SignedCertificate = parse("certificate", certificate)?;
+    Ok(certificate.verify(&parse_key(genesis_key)?))
+}
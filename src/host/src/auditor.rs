@@ -0,0 +1,449 @@
+// src/auditor.rs
+//
+// Independent audit of a game, run by the `fleet-audit` binary. The auditor trusts nothing the
+// chain states about the game: it downloads the receipts the chain applied (`/games/{id}/receipts`)
+// and checks that
+// - every receipt verifies against an image ID the chain publishes on `/methods` for its command,
+//   and was signed by its player with an increasing nonce;
+// - every receipt is recorded, with its journal digest, in a sealed block of an unbroken block chain;
+// - replaying the journals gives a consistent game: boards only change through reports, each shot
+//   is answered by its target with a report proven against it, and fires are composed with the
+//   shooter's own join;
+// - once the game is over, its winner claimed victory or outlasted its opponents, and its
+//   certificate is signed by the genesis key and agrees with the replay.
+//
+// Turns are not checked: the turn policies draw from seeds the chain does not publish.
+
+use ed25519_dalek::VerifyingKey;
+use fleetcore::{
+    journal_digest, verify_canonical, AcceptedMethods, AppliedReceipt, AuditJournal, BaseJournal, BlindReportJournal,
+    BlindWinJournal, Block, BlockHeader, Command, Coord, FireJournal, ReceiptStatement, RegisterJournal,
+    ReportJournal, SignedCertificate, StatusJournal, RECEIPT_DOMAIN,
+};
+use risc0_zkvm::{Digest, Receipt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::prover::image_id;
+
+// Outcome of the audit of a game
+pub struct AuditReport {
+    pub receipts: usize,
+    // Inconsistencies between what the chain serves and what its receipts prove
+    pub problems: Vec<String>,
+    // Checks that could not be made
+    pub warnings: Vec<String>,
+}
+
+// Archived game, as served on /games/{id}/archive
+#[derive(Deserialize)]
+struct ArchiveSummary {
+    players: Vec<String>,
+    winner: Option<String>,
+}
+
+struct PlayerState {
+    // Board committed when joining, and after the player's last report
+    initial: Digest,
+    board: Digest,
+    join_digest: Digest,
+    team: Option<String>,
+    nonce: u64,
+    forfeited: bool,
+    claimed: bool,
+}
+
+// Shot awaiting its report
+struct PendingShot {
+    shooter: String,
+    target: String,
+    pos: Coord,
+    journal: Digest,
+}
+
+#[derive(Default)]
+struct Replay {
+    players: HashMap<String, PlayerState>,
+    pending: Option<PendingShot>,
+    problems: Vec<String>,
+}
+
+fn decode<T: DeserializeOwned>(receipt: &Receipt) -> Option<T> {
+    receipt.journal.decode().ok()
+}
+
+impl Replay {
+    fn problem(&mut self, index: u64, msg: String) {
+        self.problems.push(format!("transaction {}: {}", index, msg));
+    }
+
+    // Check the board a player proved with against the one the replay reached
+    fn check_board(&mut self, index: u64, fleet: &str, board: &Digest) -> bool {
+        match self.players.get(fleet) {
+            Some(player) if player.board == *board => true,
+            Some(_) => {
+                self.problem(index, format!("{} proved with a board other than its current one", fleet));
+                false
+            }
+            None => {
+                self.problem(index, format!("{} acts in the game without having joined it", fleet));
+                false
+            }
+        }
+    }
+
+    // Apply one receipt, returning the fleet it was sent by
+    fn apply(&mut self, gameid: &str, applied: &AppliedReceipt) -> Option<String> {
+        let index = applied.index;
+        let data = &applied.data;
+        let receipt = &data.receipt;
+        let (journal_gameid, fleet) = match data.cmd {
+            Command::Join => {
+                let journal: BaseJournal = decode(receipt)?;
+                if self.players.contains_key(&journal.fleet) {
+                    self.problem(index, format!("{} joined twice", journal.fleet));
+                } else {
+                    self.players.insert(journal.fleet.clone(), PlayerState {
+                        initial: journal.board,
+                        board: journal.board,
+                        join_digest: journal_digest(&receipt.journal.bytes),
+                        team: data.team.clone(),
+                        nonce: 0,
+                        forfeited: false,
+                        claimed: false,
+                    });
+                }
+                (journal.gameid, journal.fleet)
+            }
+            Command::Fire => {
+                let journal: FireJournal = decode(receipt)?;
+                if self.check_board(index, &journal.fleet, &journal.board) {
+                    if self.players.get(&journal.fleet).is_some_and(|player| player.join_digest != journal.join_journal) {
+                        self.problem(index, format!("{} fired with a board checked against another join", journal.fleet));
+                    }
+                    if !self.players.contains_key(&journal.target) || journal.target == journal.fleet {
+                        self.problem(index, format!("{} fired at {}, who is not an opponent in the game", journal.fleet, journal.target));
+                    }
+                    if let Some(pending) = &self.pending {
+                        let msg = format!("{} fired while the shot of {} was not reported", journal.fleet, pending.shooter);
+                        self.problem(index, msg);
+                    }
+                }
+                self.pending = Some(PendingShot {
+                    shooter: journal.fleet.clone(),
+                    target: journal.target.clone(),
+                    pos: journal.pos,
+                    journal: journal_digest(&receipt.journal.bytes),
+                });
+                (journal.gameid, journal.fleet)
+            }
+            Command::Report => {
+                let journal: ReportJournal = decode(receipt)?;
+                self.check_report(index, &journal.fleet, journal.pos, Some(journal.fire_journal));
+                if self.check_board(index, &journal.fleet, &journal.board) {
+                    if let Some(player) = self.players.get_mut(&journal.fleet) {
+                        player.board = journal.next_board;
+                    }
+                }
+                (journal.gameid, journal.fleet)
+            }
+            Command::BlindReport => {
+                let journal: BlindReportJournal = decode(receipt)?;
+                self.check_report(index, &journal.fleet, journal.pos, None);
+                self.check_board(index, &journal.fleet, &journal.board);
+                (journal.gameid, journal.fleet)
+            }
+            Command::Wave | Command::Win | Command::Forfeit | Command::Contest => {
+                let journal: BaseJournal = decode(receipt)?;
+                if self.check_board(index, &journal.fleet, &journal.board) {
+                    if data.cmd == Command::Wave && self.pending.is_some() {
+                        self.problem(index, format!("{} waved while a shot was not reported", journal.fleet));
+                    }
+                    if let Some(player) = self.players.get_mut(&journal.fleet) {
+                        player.claimed |= data.cmd == Command::Win;
+                        player.forfeited |= data.cmd == Command::Forfeit;
+                    }
+                    // A pending report of a player who forfeits is dropped with the shot
+                    if data.cmd == Command::Forfeit && self.pending.as_ref().is_some_and(|shot| shot.target == journal.fleet) {
+                        self.pending = None;
+                    }
+                }
+                (journal.gameid, journal.fleet)
+            }
+            Command::BlindWin => {
+                let journal: BlindWinJournal = decode(receipt)?;
+                if self.check_board(index, &journal.fleet, &journal.board) {
+                    if let Some(player) = self.players.get_mut(&journal.fleet) {
+                        player.claimed = true;
+                    }
+                }
+                (journal.gameid, journal.fleet)
+            }
+            Command::Status => {
+                let journal: StatusJournal = decode(receipt)?;
+                self.check_board(index, &journal.fleet, &journal.board);
+                (journal.gameid, journal.fleet)
+            }
+            Command::Audit => {
+                let journal: AuditJournal = decode(receipt)?;
+                let consistent = self.players
+                    .get(&journal.fleet)
+                    .map(|player| player.initial == journal.initial && player.board == journal.board);
+                match consistent {
+                    Some(true) => {}
+                    Some(false) => self.problem(index, format!("{} audited its reports from or to another board", journal.fleet)),
+                    None => self.problem(index, format!("{} audited a game it did not join", journal.fleet)),
+                }
+                (journal.gameid, journal.fleet)
+            }
+            Command::Register => {
+                let journal: RegisterJournal = decode(receipt)?;
+                (journal.gameid, journal.fleet)
+            }
+        };
+        if journal_gameid != gameid {
+            self.problem(index, format!("journal is for game {}", journal_gameid));
+        }
+        Some(fleet)
+    }
+
+    // Check a report against the shot it answers
+    fn check_report(&mut self, index: u64, fleet: &str, pos: Coord, fire_journal: Option<Digest>) {
+        match self.pending.take() {
+            Some(shot) => {
+                if shot.target != fleet || shot.pos != pos {
+                    self.problem(index, format!("{} reported at {} a shot of {} at {} on {}", fleet, pos, shot.shooter, shot.pos, shot.target));
+                }
+                if fire_journal.is_some_and(|digest| digest != shot.journal) {
+                    self.problem(index, format!("report of {} was proven against another shot", fleet));
+                }
+            }
+            None => self.problem(index, format!("{} reported without a pending shot", fleet)),
+        }
+    }
+
+    // Check if a winner claimed victory, or outlasted every opponent
+    fn won_fairly(&self, winner: &str) -> bool {
+        let team = self.players.get(winner).and_then(|player| player.team.clone());
+        let opponent = |name: &String, player: &PlayerState| name != winner && (team.is_none() || player.team != team);
+        self.players.get(winner).is_some_and(|player| player.claimed)
+            || self.players.iter().all(|(name, player)| !opponent(name, player) || player.forfeited)
+    }
+}
+
+struct Chain {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Chain {
+    // GET a JSON document, None when the chain does not have it
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, String> {
+        let response = self.client
+            .get(format!("{}{}", self.url, path))
+            .send()
+            .await
+            .map_err(|e| format!("could not reach the chain: {}", e))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!("{} answered {}", path, response.status()));
+        }
+        response.json().await.map(Some).map_err(|e| format!("could not parse {}: {}", path, e))
+    }
+
+    // Every sealed block header, checking that each one follows the previous
+    async fn headers(&self, problems: &mut Vec<String>) -> Result<Vec<BlockHeader>, String> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        loop {
+            let page: Vec<BlockHeader> = self.get(&format!("/blocks?since={}", headers.len())).await?.unwrap_or_default();
+            if page.is_empty() {
+                return Ok(headers);
+            }
+            for header in page {
+                if !header.follows(headers.last()) {
+                    problems.push(format!("block {} does not follow the previous block", header.number));
+                }
+                headers.push(header);
+            }
+        }
+    }
+}
+
+// Image IDs published for each command, with the time they stop being accepted
+fn published_ids(methods: &AcceptedMethods, warnings: &mut Vec<String>) -> HashMap<Command, Vec<(Digest, Option<u64>)>> {
+    let mut ids: HashMap<Command, Vec<(Digest, Option<u64>)>> = HashMap::new();
+    for method in &methods.methods {
+        match method.image_id.parse::<Digest>() {
+            Ok(id) => ids.entry(method.command).or_default().push((id, method.cutoff)),
+            Err(_) => warnings.push(format!("invalid image ID published for {:?}: {}", method.command, method.image_id)),
+        }
+    }
+    // The guests the auditor was built with should be among them
+    for cmd in Command::ALL {
+        let local = Digest::from(image_id(cmd));
+        if !ids.get(&cmd).is_some_and(|ids| ids.iter().any(|(id, _)| *id == local)) {
+            warnings.push(format!("the chain does not publish the {:?} guest this auditor was built with", cmd));
+        }
+    }
+    ids
+}
+
+fn key_from_bytes(bytes: &[u8]) -> Option<VerifyingKey> {
+    VerifyingKey::from_bytes(bytes.try_into().ok()?).ok()
+}
+
+fn parse_key(hex_key: &str) -> Option<VerifyingKey> {
+    key_from_bytes(&hex::decode(hex_key.trim()).ok()?)
+}
+
+pub async fn audit_game(chain_url: &str, gameid: &str) -> Result<AuditReport, String> {
+    let chain = Chain { client: reqwest::Client::new(), url: chain_url.trim_end_matches('/').to_string() };
+    let mut problems = Vec::new();
+    let mut warnings = Vec::new();
+
+    let methods: AcceptedMethods = chain.get("/methods").await?.ok_or("the chain does not publish its image IDs")?;
+    let ids = published_ids(&methods, &mut warnings);
+    let receipts: Vec<AppliedReceipt> = chain
+        .get(&format!("/games/{}/receipts", gameid))
+        .await?
+        .ok_or_else(|| format!("the chain has no receipts for game {}", gameid))?;
+    let headers = chain.headers(&mut problems).await?;
+
+    // Keys the players signed with: the ones certified at the end of the game, else the ones
+    // registered or sent when joining
+    let archive: Option<ArchiveSummary> = chain.get(&format!("/games/{}/archive", gameid)).await?;
+    let certificate: Option<SignedCertificate> = chain.get(&format!("/games/{}/certificate", gameid)).await?;
+    let mut keys: HashMap<String, VerifyingKey> = certificate
+        .iter()
+        .flat_map(|signed| signed.certificate.players.iter())
+        .filter_map(|player| Some((player.fleet.clone(), parse_key(&player.public_key)?)))
+        .collect();
+
+    let mut replay = Replay::default();
+    let mut blocks: HashMap<u64, Block> = HashMap::new();
+    let mut last_index = None;
+    for applied in &receipts {
+        let index = applied.index;
+        let data = &applied.data;
+
+        if last_index.is_some_and(|last| index <= last) {
+            problems.push(format!("transaction {}: served out of order", index));
+        }
+        last_index = Some(index);
+
+        // The receipt must prove its journal with a guest the chain accepted at the time
+        let verified = ids.get(&data.cmd).is_some_and(|ids| {
+            ids.iter()
+                .any(|(id, cutoff)| cutoff.map_or(true, |cutoff| applied.at < cutoff) && data.receipt.verify(*id).is_ok())
+        });
+        if !verified {
+            problems.push(format!("transaction {}: {:?} receipt does not verify against a published image ID", index, data.cmd));
+        }
+
+        // The receipt must be recorded in a sealed block of the chain
+        match applied.block {
+            Some(number) => {
+                if let Entry::Vacant(slot) = blocks.entry(number) {
+                    if let Some(block) = chain.get::<Block>(&format!("/blocks/{}", number)).await? {
+                        let sealed = headers.get(number as usize).is_some_and(|header| *header == block.header);
+                        if !block.verify() || !sealed {
+                            problems.push(format!("block {} does not match its header in the block chain", number));
+                        }
+                        slot.insert(block);
+                    }
+                }
+                let recorded = blocks.get(&number).and_then(|block| block.transactions.iter().find(|tx| tx.index == index));
+                let matches = recorded.is_some_and(|tx| {
+                    tx.kind == format!("{:?}", data.cmd) && tx.gameid == gameid && tx.digest == journal_digest(&data.receipt.journal.bytes)
+                });
+                if !matches {
+                    problems.push(format!("transaction {}: not recorded as served in block {}", index, number));
+                }
+            }
+            None => warnings.push(format!("transaction {}: its block is not sealed yet", index)),
+        }
+
+        // Replay the journal
+        let fleet = match replay.apply(gameid, applied) {
+            Some(fleet) => fleet,
+            None => {
+                problems.push(format!("transaction {}: could not decode the {:?} journal", index, data.cmd));
+                continue;
+            }
+        };
+
+        // The player must have signed the receipt, with a nonce larger than its previous one
+        if data.cmd == Command::Register {
+            if let Some(key) = decode::<RegisterJournal>(&data.receipt).and_then(|journal| key_from_bytes(&journal.public_key)) {
+                keys.entry(fleet.clone()).or_insert(key);
+            }
+        }
+        if data.cmd == Command::Join {
+            if let Some(key) = data.public_key.as_deref().and_then(key_from_bytes) {
+                keys.entry(fleet.clone()).or_insert(key);
+            }
+        }
+        match keys.get(&fleet) {
+            Some(key) => {
+                let statement = ReceiptStatement::new(data.cmd, gameid, data.nonce, &data.receipt.journal.bytes);
+                if !verify_canonical(key, RECEIPT_DOMAIN, &statement, &data.signature) {
+                    problems.push(format!("transaction {}: {:?} of {} is not signed by its key", index, data.cmd, fleet));
+                }
+            }
+            None => warnings.push(format!("transaction {}: no known key to check the signature of {}", index, fleet)),
+        }
+        if let Some(player) = replay.players.get_mut(&fleet) {
+            if data.cmd != Command::Join && data.nonce <= player.nonce {
+                problems.push(format!("transaction {}: nonce of {} did not increase", index, fleet));
+            }
+            player.nonce = data.nonce;
+        }
+    }
+    problems.append(&mut replay.problems);
+
+    // The outcome must follow from the receipts
+    if let Some(archive) = &archive {
+        let mut players = archive.players.clone();
+        players.sort();
+        let mut joined: Vec<String> = replay.players.keys().cloned().collect();
+        joined.sort();
+        if players != joined {
+            problems.push(format!("archived players {:?} are not the ones who joined {:?}", players, joined));
+        }
+        if let Some(winner) = &archive.winner {
+            if !replay.won_fairly(winner) {
+                problems.push(format!("{} won without claiming victory or outlasting its opponents", winner));
+            }
+        }
+    }
+    if let Some(signed) = &certificate {
+        let genesis = chain.client
+            .get(format!("{}/genesis", chain.url))
+            .send()
+            .await
+            .map_err(|e| format!("could not reach the chain: {}", e))?
+            .text()
+            .await
+            .map_err(|e| format!("could not read the genesis key: {}", e))?;
+        match parse_key(&genesis) {
+            Some(genesis) if signed.verify(&genesis) => {}
+            Some(_) => problems.push("the certificate is not signed by the genesis key".to_string()),
+            None => problems.push("the chain serves an invalid genesis key".to_string()),
+        }
+        if archive.as_ref().is_some_and(|archive| archive.winner != signed.certificate.winner) {
+            problems.push("the certificate names another winner than the archive".to_string());
+        }
+        for player in &signed.certificate.players {
+            let board = replay.players.get(&player.fleet).map(|state| state.board.to_string());
+            if board.as_deref() != Some(player.final_board.as_str()) {
+                problems.push(format!("the certificate gives {} another final board than its reports", player.fleet));
+            }
+        }
+    }
+
+    Ok(AuditReport { receipts: receipts.len(), problems, warnings })
+}
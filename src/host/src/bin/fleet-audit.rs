@@ -0,0 +1,40 @@
+// Independent auditor: downloads the receipts the chain applied to a game, verifies them against
+// the published image IDs and the block chain, replays the game and reports any inconsistency.
+// Exits with status 1 when a problem is found.
+
+use fleetconfig::config;
+use host::auditor;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let gameid = match args.next() {
+        Some(gameid) => gameid,
+        None => {
+            eprintln!("usage: fleet-audit <gameid> [chain url]");
+            std::process::exit(2);
+        }
+    };
+    let chain_url = args.next().unwrap_or_else(|| config().host.chain_url.clone());
+    host::init_tracing();
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start tokio runtime");
+    let report = match runtime.block_on(auditor::audit_game(&chain_url, &gameid)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("fleet-audit could not audit game {}: {}", gameid, e);
+            std::process::exit(2);
+        }
+    };
+
+    println!("Audited {} receipts of game {} on {}", report.receipts, gameid, chain_url);
+    for warning in &report.warnings {
+        println!("warning: {}", warning);
+    }
+    for problem in &report.problems {
+        println!("problem: {}", problem);
+    }
+    if !report.problems.is_empty() {
+        std::process::exit(1);
+    }
+    println!("No inconsistency found");
+}
@@ -0,0 +1,64 @@
+// Cycle benchmark of the join guest: executes it (without proving) on random legal placements and
+// reports the cycles it ran. Exits with status 1 when a run goes over the budget, so a change to
+// the placement rules that makes joining more expensive is caught before it ships. The budget is
+// JOIN_CYCLE_BUDGET, or the first argument, with the number of placements as the second.
+
+use fleetcore::BaseInputs;
+use host::placement::random_placement;
+use methods::JOIN_ELF;
+use risc0_zkvm::{default_executor, ExecutorEnv};
+
+// Cycles a join may take when JOIN_CYCLE_BUDGET is not set
+const DEFAULT_JOIN_CYCLE_BUDGET: u64 = 1 << 18;
+// Placements executed when no count is given
+const DEFAULT_RUNS: usize = 10;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let budget = args
+        .next()
+        .or_else(|| std::env::var("JOIN_CYCLE_BUDGET").ok())
+        .map_or(Ok(DEFAULT_JOIN_CYCLE_BUDGET), |budget| budget.parse())
+        .unwrap_or_else(|e| {
+            eprintln!("usage: fleet-bench [cycle budget] [runs]: {}", e);
+            std::process::exit(2);
+        });
+    let runs = args.next().map_or(Ok(DEFAULT_RUNS), |runs| runs.parse()).unwrap_or_else(|e| {
+        eprintln!("usage: fleet-bench [cycle budget] [runs]: {}", e);
+        std::process::exit(2);
+    });
+
+    let executor = default_executor();
+    let mut rng = rand::thread_rng();
+    let mut max = 0;
+    for run in 1..=runs {
+        let inputs = BaseInputs {
+            gameid: "bench".to_string(),
+            fleet: "bench".to_string(),
+            board: random_placement(&mut rng),
+            random: "bench".to_string(),
+            game_next_player: None,
+            game_next_report: None,
+            challenge: String::new(),
+        };
+        let session = ExecutorEnv::builder()
+            .write(&inputs)
+            .and_then(|builder| builder.build())
+            .and_then(|env| executor.execute(env, JOIN_ELF));
+        let session = match session {
+            Ok(session) => session,
+            Err(e) => {
+                eprintln!("fleet-bench could not execute the join guest: {}", e);
+                std::process::exit(2);
+            }
+        };
+        let cycles = session.cycles();
+        println!("join {}: {} cycles in {} segments", run, cycles, session.segments.len());
+        max = max.max(cycles);
+    }
+
+    println!("Most cycles for a join: {} (budget {})", max, budget);
+    if max > budget {
+        std::process::exit(1);
+    }
+}
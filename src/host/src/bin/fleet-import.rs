@@ -0,0 +1,58 @@
+// Imports a game exported from a chain's /games/{gameid}/export into another chain, which replays
+// and checks every transaction of it. Needs the admin token of the importing chain, set as
+// `admin_token` in the configuration or in FLEET_ADMIN_TOKEN. Exits with status 1 when the chain
+// refuses the export.
+
+use fleetconfig::config;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: fleet-import <export.json> [chain url]");
+            std::process::exit(2);
+        }
+    };
+    let chain_url = args.next().unwrap_or_else(|| config().host.chain_url.clone());
+    let token = match config().chain.admin_token.as_deref() {
+        Some(token) if !token.is_empty() => token,
+        _ => {
+            eprintln!("fleet-import needs the admin token of the chain (FLEET_ADMIN_TOKEN)");
+            std::process::exit(2);
+        }
+    };
+
+    let export = match std::fs::read(&path) {
+        Ok(export) => export,
+        Err(e) => {
+            eprintln!("fleet-import could not read {}: {}", path, e);
+            std::process::exit(2);
+        }
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start tokio runtime");
+    let result = runtime.block_on(async {
+        let response = reqwest::Client::new()
+            .post(format!("{}/admin/import", chain_url))
+            .bearer_auth(token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(export)
+            .send()
+            .await?;
+        let status = response.status();
+        Ok::<_, reqwest::Error>((status, response.text().await?))
+    });
+
+    match result {
+        Ok((status, text)) if status.is_success() => println!("{}: {}", chain_url, text),
+        Ok((status, text)) => {
+            println!("{} refused the export ({}): {}", chain_url, status, text);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("fleet-import could not reach {}: {}", chain_url, e);
+            std::process::exit(2);
+        }
+    }
+}
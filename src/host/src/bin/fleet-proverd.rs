@@ -0,0 +1,19 @@
+// Standalone prover daemon: generates receipts for host processes connecting over a Unix socket,
+// so a prover crash or memory spike cannot take down the host's web server.
+
+use host::proverd;
+
+fn main() {
+    // The daemon's own prover workers are started from this binary as well
+    if std::env::args().nth(1).as_deref() == Some(host::WORKER_ARG) {
+        std::process::exit(host::run_worker());
+    }
+
+    host::init_tracing();
+    let path = std::env::args().nth(1).unwrap_or_else(proverd::socket_path);
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start tokio runtime");
+    if let Err(e) = runtime.block_on(proverd::serve(&path)) {
+        eprintln!("fleet-proverd stopped: {}", e);
+        std::process::exit(1);
+    }
+}
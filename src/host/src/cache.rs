@@ -0,0 +1,81 @@
+// src/cache.rs
+//
+// Content-addressed cache of receipts on disk. Re-joining or retrying an action after a network
+// hiccup proves exactly the same inputs again, so receipts are stored under the SHA-256 of the
+// guest's image ID and the serialized inputs and reused instead of being regenerated.
+
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::warn;
+
+use fleetconfig::config;
+
+use crate::prover::{Proof, ProofJob};
+
+// Directory used when RECEIPT_CACHE_DIR is not set, in the storage path or the temp directory
+const DEFAULT_CACHE_DIR: &str = "fleet-receipts";
+
+fn cache_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("RECEIPT_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    match &config().storage_path {
+        Some(dir) => dir.join(DEFAULT_CACHE_DIR),
+        None => std::env::temp_dir().join(DEFAULT_CACHE_DIR),
+    }
+}
+
+fn cache_path(job: &ProofJob, image_id: [u32; 8]) -> Option<PathBuf> {
+    let inputs = serde_json::to_vec(&job.inputs).ok()?;
+
+    let mut hasher = Sha256::new();
+    for word in image_id {
+        hasher.update(word.to_le_bytes());
+    }
+    hasher.update(&inputs);
+    let key = hex::encode(hasher.finalize());
+
+    Some(cache_dir().join(format!("{}.json", key)))
+}
+
+// Proof previously generated for the same guest and inputs, if its receipt is still valid
+pub fn load(job: &ProofJob, image_id: [u32; 8]) -> Option<Proof> {
+    let path = cache_path(job, image_id)?;
+    let bytes = std::fs::read(&path).ok()?;
+    let proof: Proof = match serde_json::from_slice(&bytes) {
+        Ok(proof) => proof,
+        Err(_) => {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+    };
+
+    // Drop entries that were corrupted or written for another build of the guest
+    if proof.receipt.verify(image_id).is_err() {
+        let _ = std::fs::remove_file(&path);
+        return None;
+    }
+    Some(proof)
+}
+
+// Store a proof for later reuse. A cache that cannot be written only costs a new proof later.
+pub fn store(job: &ProofJob, image_id: [u32; 8], proof: &Proof) {
+    let path = match cache_path(job, image_id) {
+        Some(path) => path,
+        None => return,
+    };
+    let bytes = match serde_json::to_vec(proof) {
+        Ok(bytes) => bytes,
+        Err(_) => return,
+    };
+
+    // Write to a temporary file first so a concurrent reader never sees a partial receipt
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let result = std::fs::create_dir_all(cache_dir())
+        .and_then(|_| std::fs::write(&tmp, bytes))
+        .and_then(|_| std::fs::rename(&tmp, &path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        warn!("Could not cache receipt in {}: {}", path.display(), e);
+    }
+}
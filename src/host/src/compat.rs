@@ -0,0 +1,90 @@
+// src/compat.rs
+//
+// Guest compatibility with the chain. The host proves with the guests it was built with, and the
+// chain only accepts the image IDs it lists on `/methods`: its own guests and the previous builds
+// it still accepts during an upgrade. The host checks them at startup, so a host and a chain built
+// from different guests are reported before a player spends minutes on a proof the chain rejects.
+
+use fleetcore::{AcceptedMethods, Command};
+use risc0_zkvm::Digest;
+use tracing::{error, info, warn};
+
+use crate::endpoints;
+use crate::prover::image_id;
+
+// How the chain accepts one of our guests
+pub enum Compatibility {
+    Accepted,
+    // Accepted as a previous version, until the cutoff time
+    Deprecated { version: String, cutoff: u64 },
+    Rejected,
+}
+
+// Compare our guests with the image IDs accepted by the chain
+pub fn compare(accepted: &AcceptedMethods) -> Vec<(Command, Compatibility)> {
+    Command::ALL
+        .iter()
+        .map(|&cmd| {
+            let id = Digest::from(image_id(cmd)).to_string();
+            let method = accepted.methods.iter().find(|method| method.command == cmd && method.image_id == id);
+            let compatibility = match method {
+                Some(method) => match method.cutoff {
+                    Some(cutoff) => Compatibility::Deprecated { version: method.version.clone(), cutoff },
+                    None => Compatibility::Accepted,
+                },
+                None => Compatibility::Rejected,
+            };
+            (cmd, compatibility)
+        })
+        .collect()
+}
+
+async fn fetch_methods() -> Result<AcceptedMethods, String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/methods", endpoints::current()))
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch accepted guests: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err("Failed to get accepted guests".to_string());
+    }
+
+    response.json().await
+        .map_err(|e| format!("Failed to parse accepted guests: {}", e))
+}
+
+// Check our guests against the chain and report the ones it does not accept (or not for long)
+pub async fn check_chain() {
+    let accepted = match fetch_methods().await {
+        Ok(accepted) => accepted,
+        Err(err) => {
+            warn!("Could not check guest compatibility with the chain: {}", err);
+            return;
+        }
+    };
+
+    let mut compatible = true;
+    for (cmd, compatibility) in compare(&accepted) {
+        match compatibility {
+            Compatibility::Accepted => {}
+            Compatibility::Deprecated { version, cutoff } => warn!(
+                "The chain accepts our {:?} guest as version {} until {}, upgrade to {}",
+                cmd, version, cutoff, accepted.current
+            ),
+            Compatibility::Rejected => {
+                compatible = false;
+                error!(
+                    "The chain does not accept our {:?} guest (image ID {}), its proofs will be rejected",
+                    cmd,
+                    Digest::from(image_id(cmd))
+                );
+            }
+        }
+    }
+    if compatible {
+        info!("Guests compatible with the chain (version {})", accepted.current);
+    }
+}
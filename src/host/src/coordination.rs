@@ -0,0 +1,135 @@
+// src/coordination.rs
+//
+// End-to-end encryption of the messages of a team (see `fleetcore::TeamMessageData`). A player's
+// Ed25519 key doubles as an X25519 key, in Montgomery form, so teammates need no other key than the
+// one the chain already knows them by. Each message is encrypted under a random content key, sealed
+// for every member of the team with a key agreed between an X25519 key pair made for the message and
+// the member's key. Reading a message needs the fleet's signing key, so it only works with keys held
+// in the keystore; sending works with any signer.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use fleetcore::{verify_canonical, SealedKey, TeamMember, TeamMessage, TeamMessageStatement, TEAM_MESSAGE_DOMAIN};
+
+// Context of the keys sealing the content keys
+const SEAL_CONTEXT: &[u8] = b"fleet/team-seal/v1";
+// Every sealing key comes from a key pair made for one message, so its nonce is never reused
+const SEAL_NONCE: [u8; 12] = [0; 12];
+const IV_LENGTH: usize = 12;
+
+// Text of a message encrypted for the members of a team
+pub struct Sealed {
+    pub ephemeral_key: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub keys: Vec<SealedKey>,
+}
+
+fn member_key(member: &TeamMember) -> Result<VerifyingKey, String> {
+    let bytes: [u8; PUBLIC_KEY_LENGTH] = member
+        .public_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("Invalid key for team member {}", member.fleet))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| format!("Invalid key for team member {}", member.fleet))
+}
+
+// X25519 form of an Ed25519 key
+fn exchange_key(key: &VerifyingKey) -> PublicKey {
+    PublicKey::from(key.to_montgomery().to_bytes())
+}
+
+// Cipher sealing the content key of a message for one member
+fn seal_cipher(shared: &[u8; 32], ephemeral: &PublicKey, member: &PublicKey) -> ChaCha20Poly1305 {
+    let key = Sha256::new()
+        .chain_update(SEAL_CONTEXT)
+        .chain_update(shared)
+        .chain_update(ephemeral.as_bytes())
+        .chain_update(member.as_bytes())
+        .finalize();
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+// The text is bound to its game and sender, so it cannot be passed off as another's
+fn associated_data(gameid: &str, fleet: &str) -> Vec<u8> {
+    format!("{}\n{}", gameid, fleet).into_bytes()
+}
+
+// Encrypt a message of `fleet` for the members of its team
+pub fn seal(gameid: &str, fleet: &str, text: &str, members: &[TeamMember]) -> Result<Sealed, String> {
+    let mut content_key = [0u8; 32];
+    OsRng.fill_bytes(&mut content_key);
+    let mut iv = [0u8; IV_LENGTH];
+    OsRng.fill_bytes(&mut iv);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&content_key))
+        .encrypt(Nonce::from_slice(&iv), Payload { msg: text.as_bytes(), aad: &associated_data(gameid, fleet) })
+        .map_err(|_| "Could not encrypt the message".to_string())?;
+
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_key = PublicKey::from(&ephemeral);
+    let keys = members
+        .iter()
+        .map(|member| {
+            let public = exchange_key(&member_key(member)?);
+            let shared = ephemeral.diffie_hellman(&public);
+            // A small-order key would let anyone read the message
+            if !shared.was_contributory() {
+                return Err(format!("Invalid key for team member {}", member.fleet));
+            }
+            let key = seal_cipher(shared.as_bytes(), &ephemeral_key, &public)
+                .encrypt(Nonce::from_slice(&SEAL_NONCE), content_key.as_slice())
+                .map_err(|_| "Could not seal the message key".to_string())?;
+            Ok(SealedKey { fleet: member.fleet.clone(), key })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Sealed { ephemeral_key: ephemeral_key.as_bytes().to_vec(), iv: iv.to_vec(), ciphertext, keys })
+}
+
+// Check that a message was signed by a member of the team, and decrypt it with the key of `fleet`
+pub fn open(gameid: &str, fleet: &str, signing_key: &SigningKey, members: &[TeamMember], message: &TeamMessage) -> Result<String, String> {
+    let data = &message.data;
+    let sender = members
+        .iter()
+        .find(|member| member.fleet == data.fleet)
+        .ok_or_else(|| format!("{} is not a member of the team", data.fleet))?;
+    let statement = TeamMessageStatement {
+        gameid,
+        fleet: &data.fleet,
+        ephemeral_key: &data.ephemeral_key,
+        iv: &data.iv,
+        ciphertext: &data.ciphertext,
+        keys: &data.keys,
+        nonce: data.nonce,
+    };
+    if !verify_canonical(&member_key(sender)?, TEAM_MESSAGE_DOMAIN, &statement, &data.signature) {
+        return Err("Invalid signature".to_string());
+    }
+
+    let sealed = data
+        .keys
+        .iter()
+        .find(|sealed| sealed.fleet == fleet)
+        .ok_or_else(|| "Not sealed for this fleet".to_string())?;
+    let ephemeral: [u8; 32] = data.ephemeral_key.as_slice().try_into().map_err(|_| "Invalid ephemeral key".to_string())?;
+    let ephemeral = PublicKey::from(ephemeral);
+    let shared = StaticSecret::from(signing_key.to_scalar_bytes()).diffie_hellman(&ephemeral);
+    let content_key = seal_cipher(shared.as_bytes(), &ephemeral, &exchange_key(&signing_key.verifying_key()))
+        .decrypt(Nonce::from_slice(&SEAL_NONCE), sealed.key.as_slice())
+        .map_err(|_| "Could not unseal the message key, was the fleet's key changed?".to_string())?;
+
+    if data.iv.len() != IV_LENGTH {
+        return Err("Invalid nonce".to_string());
+    }
+    let text = ChaCha20Poly1305::new_from_slice(&content_key)
+        .map_err(|_| "Invalid message key".to_string())?
+        .decrypt(Nonce::from_slice(&data.iv), Payload { msg: &data.ciphertext, aad: &associated_data(gameid, &data.fleet) })
+        .map_err(|_| "Could not decrypt the message".to_string())?;
+    String::from_utf8(text).map_err(|_| "The message is not text".to_string())
+}
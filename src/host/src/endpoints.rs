@@ -0,0 +1,127 @@
+// src/endpoints.rs
+//
+// Chain nodes the host talks to: the node at `chain_url`, then the replicas of `chain_replicas`,
+// in that order. Challenges and rate limits are handled by the node a player talks to, so the host
+// sticks to one node and only moves on when it cannot reach it: a request that gets no answer
+// marks its node down and switches the host to the next node not known to be down. A health check
+// probes the `/healthz` of every node every HEALTH_INTERVAL, so nodes that come back are tried
+// again, without switching back to them while the current node answers.
+
+use fleetconfig::config;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::Duration;
+use tracing::warn;
+
+// Time between two health checks of the nodes, and the time each check waits for an answer
+const HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct Endpoint {
+    url: String,
+    // Whether the node answered the last request or health check sent to it
+    up: bool,
+}
+
+struct Endpoints {
+    nodes: Vec<Endpoint>,
+    // Node the host talks to
+    current: usize,
+}
+
+static ENDPOINTS: LazyLock<Mutex<Endpoints>> = LazyLock::new(|| {
+    let host = &config().host;
+    let mut nodes: Vec<Endpoint> = Vec::new();
+    for url in std::iter::once(&host.chain_url).chain(&host.chain_replicas) {
+        if !nodes.iter().any(|node| &node.url == url) {
+            nodes.push(Endpoint { url: url.clone(), up: true });
+        }
+    }
+    Mutex::new(Endpoints { nodes, current: 0 })
+});
+
+fn endpoints() -> MutexGuard<'static, Endpoints> {
+    ENDPOINTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Endpoints {
+    // Move to the first node after the current one that is not known to be down, or to the next
+    // node when all of them are
+    fn switch(&mut self) {
+        let count = self.nodes.len();
+        let next = (1..count)
+            .map(|step| (self.current + step) % count)
+            .find(|&index| self.nodes[index].up)
+            .unwrap_or((self.current + 1) % count);
+        if next != self.current {
+            warn!("Cannot reach the chain at {}, switching to {}", self.nodes[self.current].url, self.nodes[next].url);
+            self.current = next;
+        }
+    }
+
+    fn mark(&mut self, url: &str, up: bool) {
+        if let Some(node) = self.nodes.iter_mut().find(|node| node.url == url) {
+            node.up = up;
+        }
+    }
+}
+
+// Base URL of the node the host talks to
+pub fn current() -> String {
+    let endpoints = endpoints();
+    endpoints.nodes[endpoints.current].url.clone()
+}
+
+// Base URLs of all the nodes, in failover order
+pub fn all() -> Vec<String> {
+    endpoints().nodes.iter().map(|node| node.url.clone()).collect()
+}
+
+// Node a request URL is sent to
+pub fn node_of(url: &str) -> Option<String> {
+    endpoints()
+        .nodes
+        .iter()
+        .filter(|node| url.starts_with(node.url.as_str()))
+        .max_by_key(|node| node.url.len())
+        .map(|node| node.url.clone())
+}
+
+// A request to `node` got no answer: fail over to another node if it is the current one
+pub fn failed(node: &str) {
+    let mut endpoints = endpoints();
+    endpoints.mark(node, false);
+    if endpoints.nodes[endpoints.current].url == node {
+        endpoints.switch();
+    }
+}
+
+// A request to `node` got an answer
+pub fn answered(node: &str) {
+    endpoints().mark(node, true);
+}
+
+// Probe every node's /healthz forever, failing over when the current node stops answering
+pub async fn check_health() {
+    let nodes = all();
+    if nodes.len() < 2 {
+        return;
+    }
+    let client = reqwest::Client::new();
+    loop {
+        tokio::time::sleep(HEALTH_INTERVAL).await;
+        for node in &nodes {
+            let healthy = client
+                .get(format!("{}/healthz", node))
+                .timeout(HEALTH_TIMEOUT)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .is_ok();
+            if healthy {
+                answered(node);
+            } else {
+                failed(node);
+            }
+        }
+    }
+}
@@ -1,42 +1,163 @@
 // src/game_actions.rs
 
-use fleetcore::{BaseInputs, Command, FireInputs, GameState};
-use methods::{FIRE_ELF, JOIN_ELF, REPORT_ELF, WAVE_ELF, WIN_ELF};
-use ed25519_dalek::Signer;
+use fleetcore::{
+    report_outcome, validate_fleet_placement, AnnotationData, AnnotationKind, AnnotationStatement, AuditInputs,
+    BaseInputs, BaseJournal, BlindReportInputs, BlindWinInputs, Board, Challenge, ChatData, ChatStatement, Command, Coord, FireInputs, GameState,
+    Invite, MatchAssignment, MatchRequest, PauseData, PauseStatement, ReadyData, ReadyStatement, RegisterInputs, RematchData,
+    RematchStatement, RevealData, RevealStatement, SignedInvite, TeamChannel, TeamMessageData, TeamMessageStatement, WinBasis,
+    WinInputs, ANNOTATION_DOMAIN, ANY_GAME, CHAT_DOMAIN, INVITE_DOMAIN, PAUSE_DOMAIN, READY_DOMAIN, REMATCH_DOMAIN, REVEAL_DOMAIN,
+    TEAM_MESSAGE_DOMAIN,
+};
+use fleetconfig::config;
+use methods::{FIRE_ID, JOIN_ID, REPORT_ID};
+use risc0_zkvm::{Digest, Receipt};
+use tracing::warn;
 
 use crate::{
-    generate_receipt_for_base_inputs, send_receipt, unmarshal_data, unmarshal_fire,
-    unmarshal_report, FormData, generate_receipt_for_fire_inputs, generate_keys_from_random,
+    chain_post, last_applied_seq, next_nonce, send_receipt, unmarshal_data, unmarshal_fire, unmarshal_form,
+    unmarshal_report, FormData, JoinTerms,
 };
+use crate::coordination;
+use crate::endpoints;
+use crate::joins::{self, JoinRecord};
+use crate::keystore;
+use crate::signer::{self, sign_canonical};
+use crate::jobs::{progress, JobState};
+use crate::prover::{prove, ProofInputs, ProofJob};
+use crate::responses;
+use crate::shots::ShotEvent;
+use crate::tracker;
+
+// Run the action of the button pressed on the page
+pub async fn dispatch(data: FormData) -> String {
+    match data.button.as_str() {
+        "Register" => register(data).await,
+        "Join" => join_game(data).await,
+        "Ready" => ready(data, false).await,
+        "Start" => ready(data, true).await,
+        "Fire" => fire(data).await,
+        "Report" => report(data).await,
+        "Wave" => wave(data).await,
+        "Win" => win(data).await,
+        "Contest" => contest(data).await,
+        "Forfeit" => forfeit(data).await,
+        "Status" => status(data).await,
+        "Audit" => audit(data).await,
+        "Annotate" => annotate(data).await,
+        "Chat" => chat(data).await,
+        "Team" => team_message(data).await,
+        "Read team" => read_team(data).await,
+        "Pause" => pause(data, true).await,
+        "Resume" => pause(data, false).await,
+        "Rematch" => rematch(data).await,
+        "Invite" => invite(data).await,
+        "Reveal" => reveal(data).await,
+        _ => "Unknown button pressed".to_string(),
+    }
+}
+
+// Register our key with the chain, for the game of the form or, when no game is given, for every
+// game. The key is created in the keystore if the fleet has none yet.
+pub async fn register(idata: FormData) -> String {
+    let fleetid = match idata.fleetid.as_ref().filter(|id| !id.is_empty()) {
+        Some(id) => id.clone(),
+        None => return "You must provide a Fleet ID".to_string(),
+    };
+    let passphrase = match idata.passphrase.as_ref().filter(|passphrase| !passphrase.is_empty()) {
+        Some(passphrase) => passphrase.clone(),
+        None => return "You must provide a Passphrase".to_string(),
+    };
+    let gameid = match idata.gameid.as_ref().filter(|id| !id.is_empty()) {
+        Some(id) => id.clone(),
+        None => ANY_GAME.to_string(),
+    };
+
+    let identity = match keystore::unlock_or_create(&fleetid, &passphrase) {
+        Ok(identity) => identity,
+        Err(err) => return err.to_string(),
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    let public_key = match signer.verifying_key().await {
+        Ok(key) => key.to_bytes().to_vec(),
+        Err(err) => return format!("Error getting verifying key: {}", err),
+    };
+    let inputs = RegisterInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        public_key,
+        challenge,
+    };
+
+    match prove(ProofJob { cmd: Command::Register, inputs: ProofInputs::Register(inputs) }).await {
+        Ok(proof) => send_receipt(Command::Register, &gameid, &fleetid, proof, &*signer, None).await,
+        Err(e) => format!("Error creating register receipt: {}.", e),
+    }
+}
 
 pub async fn join_game(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+    let (gameid, fleetid, board, passphrase) = match unmarshal_form(&idata) {
         Ok(values) => values,
         Err(err) => return err,
     };
 
+    // Joining creates the fleet's key in the keystore, unless the fleet already has one
+    let identity = match keystore::unlock_or_create(&fleetid, &passphrase) {
+        Ok(identity) => identity,
+        Err(err) => return err.to_string(),
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // Check the placement before spending minutes proving a join the guest would refuse
+    if let Err(err) = validate_fleet_placement(board) {
+        return format!("Invalid fleet placement: {}", err);
+    }
+
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
     let base_inputs = BaseInputs {
         gameid: gameid.clone(),
         fleet: fleetid.clone(),
-        board: board.clone(),
-        random: random.clone(),
+        board,
+        random: identity.random.clone(),
         game_next_player: None,
         game_next_report: None,
+        challenge,
     };
 
-    match generate_receipt_for_base_inputs(base_inputs, JOIN_ELF) {
-        Ok(receipt) => {
-            // Generate keys from the random string
-            let (signing_key, verifying_key) = generate_keys_from_random(&random);
-
-            // Sign the receipt with the generated key
-            let signature = signing_key.sign(&receipt.journal.bytes.as_slice()).to_bytes();
-            let public_key = verifying_key.to_bytes();
+    match prove(ProofJob { cmd: Command::Join, inputs: ProofInputs::Base(base_inputs) }).await {
+        Ok(proof) => {
+            let public_key = match signer.verifying_key().await {
+                Ok(key) => key.to_bytes(),
+                Err(err) => return format!("Error getting verifying key: {}", err),
+            };
 
-            // Send the receipt along with the command and keys
-            send_receipt(Command::Join, receipt, &signature, Some(&public_key)).await
+            // Send the receipt along with the command and keys, keeping it for our shots
+            let receipt = proof.receipt.clone();
+            let terms = JoinTerms {
+                public_key: &public_key,
+                private: idata.private.is_some(),
+                invite: idata.invite.clone().filter(|code| !code.trim().is_empty()),
+                team: idata.team.as_ref().map(|team| team.trim().to_string()).filter(|team| !team.is_empty()),
+                victory_timeout: idata.victory_timeout.as_ref().and_then(|secs| secs.trim().parse().ok()),
+            };
+            let response = send_receipt(Command::Join, &gameid, &fleetid, proof, &*signer, Some(terms)).await;
+            if response == "OK" {
+                if let Err(e) = joins::store(&gameid, &fleetid, &JoinRecord { placement: board, receipt, reports: Vec::new() }) {
+                    warn!("Could not keep the join receipt of {} in game {}: {}", fleetid, gameid, e);
+                }
+            }
+            response
         }
-        Err(e) => format!("Invalid fleet placement. Please check your fleet and try again. Must have 5 ships: 1x5, 2x4, 3x3, 4x2, 5x1 (number x size)."),
+        Err(e) => format!("Error creating join receipt: {}.", e),
     }
 }
 
@@ -44,167 +165,850 @@ pub async fn join_game(idata: FormData) -> String {
 async fn fetch_game_state(gameid: &str, fleet: &str) -> Result<GameState, String> {
     // Make HTTP request to blockchain's game state endpoint
     let client = reqwest::Client::new();
-    let response = client
-        .get(&format!("http://chain0:3001/gamestate/{}/{}", gameid, fleet))
-        .send()
-        .await
+    let request = client
+        .get(format!("{}/gamestate/{}/{}", endpoints::current(), gameid, fleet))
+        .query(&[("min_seq", last_applied_seq())]);
+    let response = responses::send(request).await
         .map_err(|e| format!("Failed to fetch game state: {}", e))?;
     
-    if !response.status().is_success() {
+    // The chain has not applied our previous action yet
+    if response.status == reqwest::StatusCode::CONFLICT {
+        return Err("The chain is still applying your last action, please try again".to_string());
+    }
+    if !response.status.is_success() {
         return Err("Failed to get game state".to_string());
     }
     
-    response.json().await
+    serde_json::from_str(&response.text)
         .map_err(|e| format!("Failed to parse game state: {}", e))
 }
 
+// Ask the chain for a fresh challenge to commit to the next proof
+async fn fetch_challenge(gameid: &str, fleet: &str) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/challenge/{}/{}", endpoints::current(), gameid, fleet))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch challenge: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err("Failed to get challenge".to_string());
+    }
+
+    let challenge: Challenge = response.json().await
+        .map_err(|e| format!("Failed to parse challenge: {}", e))?;
+    Ok(challenge.challenge)
+}
+
+// Ask the chain for the fire receipt we have to report on
+async fn fetch_shot(gameid: &str) -> Result<Receipt, String> {
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/games/{}/shot", endpoints::current(), gameid))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch shot: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err("No shot is awaiting a report".to_string());
+    }
+
+    response.json().await
+        .map_err(|e| format!("Failed to parse shot: {}", e))
+}
+
 pub async fn fire(idata: FormData) -> String {
-    let (gameid, fleetid, board, random, targetfleet, x, y) = match unmarshal_fire(&idata) {
+    let (gameid, fleetid, board, identity, targetfleet, pos) = match unmarshal_fire(&idata) {
         Ok(values) => values,
         Err(err) => return err,
     };
+    let signer = signer::for_fleet(&fleetid, &identity);
     
     // Fetch current game state for turn validation
     let game_state = match fetch_game_state(&gameid, &fleetid).await {
         Ok(state) => state,
         Err(err) => return format!("Error fetching game state: {}", err),
     };
-    
-    // Calculate the position from x and y (matches the reverse formula in xy_pos method in blockchain)
-    let pos = y * 10 + x;
+
+    // Do not prove a shot the chain would reject until we prove our remaining fleet
+    if game_state.status_due {
+        return "Prove your remaining fleet with Status before firing".to_string();
+    }
+
+    // Shots are proven against our own join receipt
+    let join = match joins::load(&gameid, &fleetid) {
+        Some(join) => join,
+        None => return "No join receipt for this fleet on this host, join the game from this host to fire".to_string(),
+    };
+
+    // Ask for a challenge right before proving, as it expires
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
 
     let fire_inputs = FireInputs {
         gameid: gameid.clone(),
         fleet: fleetid.clone(),
-        board: board.clone(),
-        random: random.clone(),
+        board,
+        random: identity.random.clone(),
         target: targetfleet.clone(),
-        pos: pos,
+        pos,
         // Include game state for turn validation
         game_next_player: game_state.next_player,
         game_next_report: game_state.next_report,
+        challenge,
+        hits: Vec::new(),
+        placement: join.placement,
+        join_id: JOIN_ID.into(),
+        join_journal: join.receipt.journal.bytes.clone(),
+        fire_id: Digest::ZERO,
+        fire_journal: Vec::new(),
     };
 
-    match generate_receipt_for_fire_inputs(fire_inputs, FIRE_ELF) {
-        Ok(receipt) => {
-            // Generate keys from the random string
-            let (signing_key, _verifying_key) = generate_keys_from_random(&random);
-
-            // Sign the receipt with the generated key
-            let signature = signing_key.sign(&receipt.journal.bytes.as_slice()).to_bytes();
-
+    let inputs = ProofInputs::ComposedFire(fire_inputs, Box::new(join.receipt));
+    match prove(ProofJob { cmd: Command::Fire, inputs }).await {
+        Ok(proof) => {
             // Send the receipt along with the command and keys
-            send_receipt(Command::Fire, receipt, &signature, None).await
+            let response = send_receipt(Command::Fire, &gameid, &fleetid, proof, &*signer, None).await;
+            if response == "OK" {
+                tracker::record_shot(&gameid, &fleetid, ShotEvent::Fired { target: targetfleet, pos });
+            }
+            response
         }
         Err(e) => format!("Error creating fire receipt: {}.", e),
     }
 }
 
+// Mark the shot we just reported on our shot board
+fn record_report(gameid: &str, fleet: &str, pos: Coord, report: &str) {
+    if let Ok(outcome) = report.parse() {
+        tracker::record_shot(gameid, fleet, ShotEvent::Reported { pos, outcome });
+    }
+}
+
 pub async fn report(idata: FormData) -> String {
-    let (gameid, fleetid, board, random, _report, x, y) = match unmarshal_report(&idata) {
+    let (gameid, fleetid, board, identity, _report, pos) = match unmarshal_report(&idata) {
         Ok(values) => values,
         Err(err) => return err,
     };
+    let signer = signer::for_fleet(&fleetid, &identity);
     
     // Fetch current game state for turn validation
     let game_state = match fetch_game_state(&gameid, &fleetid).await {
         Ok(state) => state,
         Err(err) => return format!("Error fetching game state: {}", err),
     };
-    
-    // Calculate the position from x and y (matches the reverse formula in xy_pos method in blockchain)
-    let pos = y * 10 + x;
+
+    // Ask for a challenge right before proving, as it expires
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    // In a blinded game the report only commits to its outcome
+    if game_state.blinded {
+        let inputs = BlindReportInputs {
+            gameid: gameid.clone(),
+            fleet: fleetid.clone(),
+            board,
+            random: identity.random.clone(),
+            pos,
+            report: _report.clone(),
+            hits: tracker::hits(&gameid, &fleetid),
+            turn: game_state.report_turn,
+            game_next_report: game_state.next_report,
+            challenge,
+        };
+        let proof = match prove(ProofJob { cmd: Command::BlindReport, inputs: ProofInputs::BlindReport(inputs) }).await {
+            Ok(proof) => proof,
+            Err(e) => return format!("Error creating report receipt: {}.", e),
+        };
+        let response = send_receipt(Command::BlindReport, &gameid, &fleetid, proof, &*signer, None).await;
+
+        // Keep our private ledger in step with the one the chain accepted
+        if response == "OK" {
+            if _report == "Hit" {
+                tracker::record_hit(&gameid, &fleetid, pos.pos());
+            }
+            record_report(&gameid, &fleetid, pos, &_report);
+        }
+        return response;
+    }
+
+    // The guest tells whether a hit sinks a ship from our earlier hits, as the chain recorded them
+    let hits: Vec<Coord> = tracker::hits(&gameid, &fleetid).into_iter().filter_map(Coord::from_pos).collect();
+    let outcome = report_outcome(&board, &hits, pos);
+
+    // The report is proven against the shot it answers
+    let shot = match fetch_shot(&gameid).await {
+        Ok(shot) => shot,
+        Err(err) => return format!("Error fetching the shot to report: {}", err),
+    };
 
     let report_inputs = FireInputs {
         gameid: gameid.clone(),
         fleet: fleetid.clone(),
-        board: board.clone(),
-        random: random.clone(),
+        board,
+        random: identity.random.clone(),
         target: _report.clone(),
-        pos: pos,
+        pos,
         // Include game state for turn validation
         game_next_player: game_state.next_player,
         game_next_report: game_state.next_report,
+        challenge,
+        hits,
+        placement: Board::default(),
+        join_id: Digest::ZERO,
+        join_journal: Vec::new(),
+        fire_id: FIRE_ID.into(),
+        fire_journal: shot.journal.bytes.clone(),
     };
 
-    match generate_receipt_for_fire_inputs(report_inputs, REPORT_ELF) {
-        Ok(receipt) => {
-            // Generate keys from the random string
-            let (signing_key, _verifying_key) = generate_keys_from_random(&random);
-
-            // Sign the receipt with the generated key
-            let signature = signing_key.sign(&receipt.journal.bytes.as_slice()).to_bytes();
-
-            // Send the receipt along with the command and keys
-            send_receipt(Command::Report, receipt, &signature, None).await
+    let inputs = ProofInputs::ComposedFire(report_inputs, Box::new(shot));
+    match prove(ProofJob { cmd: Command::Report, inputs }).await {
+        Ok(proof) => {
+            // Send the receipt along with the command and keys, keeping it for the audit
+            let receipt = proof.receipt.clone();
+            let response = send_receipt(Command::Report, &gameid, &fleetid, proof, &*signer, None).await;
+            if response == "OK" {
+                if let Err(e) = joins::record_report(&gameid, &fleetid, receipt) {
+                    warn!("Could not keep the report receipt of {} in game {}: {}", fleetid, gameid, e);
+                }
+                if outcome.is_hit() {
+                    tracker::record_hit(&gameid, &fleetid, pos.pos());
+                }
+                record_report(&gameid, &fleetid, pos, &outcome.to_string());
+            }
+            response
         }
         Err(e) => format!("Error creating report receipt: {}.", e),
     }
 }
 
 pub async fn wave(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
         Ok(values) => values,
         Err(err) => return err,
     };
+    let signer = signer::for_fleet(&fleetid, &identity);
 
     // Fetch current game state for turn validation
     let game_state = match fetch_game_state(&gameid, &fleetid).await {
         Ok(state) => state,
         Err(err) => return format!("Error fetching game state: {}", err),
     };
+
+    // Ask for a challenge right before proving, as it expires
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
     
     let base_inputs = BaseInputs {
         gameid: gameid.clone(),
         fleet: fleetid.clone(),
-        board: board.clone(),
-        random: random.clone(),
+        board,
+        random: identity.random.clone(),
         // Include game state for turn validation
         game_next_player: game_state.next_player,
         game_next_report: game_state.next_report,
+        challenge,
     };
 
-    match generate_receipt_for_base_inputs(base_inputs, WAVE_ELF) {
-        Ok(receipt) => {
-            // Generate keys from the random string
-            let (signing_key, _verifying_key) = generate_keys_from_random(&random);
-
-            // Sign the receipt with the generated key
-            let signature = signing_key.sign(&receipt.journal.bytes.as_slice()).to_bytes();
-
+    match prove(ProofJob { cmd: Command::Wave, inputs: ProofInputs::Base(base_inputs) }).await {
+        Ok(proof) => {
             // Send the receipt along with the command and keys
-            send_receipt(Command::Wave, receipt, &signature, None).await
+            send_receipt(Command::Wave, &gameid, &fleetid, proof, &*signer, None).await
         }
         Err(e) => format!("Error creating wave receipt: {}.", e),
     }
 }
 
 pub async fn win(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // Fetch current game state to know if the game uses blinded reports
+    let game_state = match fetch_game_state(&gameid, &fleetid).await {
+        Ok(state) => state,
+        Err(err) => return format!("Error fetching game state: {}", err),
+    };
+
+    // Ask for a challenge right before proving, as it expires
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    // In a blinded game the claim is proven against our private ledger of hits
+    if game_state.blinded {
+        let inputs = BlindWinInputs {
+            gameid: gameid.clone(),
+            fleet: fleetid.clone(),
+            board,
+            random: identity.random.clone(),
+            hits: tracker::hits(&gameid, &fleetid),
+            turn: game_state.report_turn,
+            challenge,
+        };
+        return match prove(ProofJob { cmd: Command::BlindWin, inputs: ProofInputs::BlindWin(inputs) }).await {
+            Ok(proof) => {
+                send_receipt(Command::BlindWin, &gameid, &fleetid, proof, &*signer, None).await
+            }
+            Err(e) => format!("Error creating win receipt: {}.", e),
+        };
+    }
+
+    // A plain claim rests on an opponent fleet we destroyed, which the chain checks
+    let opponent = match tracker::shot_tracker(&gameid, &fleetid).destroyed() {
+        Some(opponent) => opponent,
+        None => return "No opponent fleet was destroyed by your shots yet, you cannot claim victory.".to_string(),
+    };
+    let inputs = WinInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board,
+        random: identity.random.clone(),
+        basis: WinBasis::Sunk { opponent },
+        challenge,
+    };
+
+    match prove(ProofJob { cmd: Command::Win, inputs: ProofInputs::Win(inputs) }).await {
+        Ok(proof) => {
+            // Send the receipt along with the command and keys
+            send_receipt(Command::Win, &gameid, &fleetid, proof, &*signer, None).await
+        }
+        Err(e) => format!("Error creating win receipt: {}.", e),
+    }
+}
+
+// Contest an opponent's victory claim by proving we still have ships left
+pub async fn contest(idata: FormData) -> String {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
         Ok(values) => values,
         Err(err) => return err,
     };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // The chain checks that a claim is running, so no game state is needed
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
 
     let base_inputs = BaseInputs {
         gameid: gameid.clone(),
         fleet: fleetid.clone(),
-        board: board.clone(),
-        random: random.clone(),
+        board,
+        random: identity.random.clone(),
         game_next_player: None,
         game_next_report: None,
+        challenge,
     };
 
-    match generate_receipt_for_base_inputs(base_inputs, WIN_ELF) {
-        Ok(receipt) => {
-            // Generate keys from the random string
-            let (signing_key, _verifying_key) = generate_keys_from_random(&random);
+    match prove(ProofJob { cmd: Command::Contest, inputs: ProofInputs::Base(base_inputs) }).await {
+        Ok(proof) => {
+            send_receipt(Command::Contest, &gameid, &fleetid, proof, &*signer, None).await
+        }
+        Err(e) => format!("Error creating contest receipt: {}.", e),
+    }
+}
 
-            // Sign the receipt with the generated key
-            let signature = signing_key.sign(&receipt.journal.bytes.as_slice()).to_bytes();
+pub async fn forfeit(idata: FormData) -> String {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
 
-            // Send the receipt along with the command and keys
-            send_receipt(Command::Win, receipt, &signature, None).await
+    // Conceding is allowed at any time, so no game state is needed
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    let base_inputs = BaseInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board,
+        random: identity.random.clone(),
+        game_next_player: None,
+        game_next_report: None,
+        challenge,
+    };
+
+    match prove(ProofJob { cmd: Command::Forfeit, inputs: ProofInputs::Base(base_inputs) }).await {
+        Ok(proof) => {
+            send_receipt(Command::Forfeit, &gameid, &fleetid, proof, &*signer, None).await
         }
-        Err(e) => format!("Error creating win receipt: {}.", e),
+        Err(e) => format!("Error creating forfeit receipt: {}.", e),
+    }
+}
+
+// Prove how many ship squares we have left, without revealing where they are
+pub async fn status(idata: FormData) -> String {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // A status is accepted at any time, so no game state is needed
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    let base_inputs = BaseInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board,
+        random: identity.random.clone(),
+        game_next_player: None,
+        game_next_report: None,
+        challenge,
+    };
+
+    match prove(ProofJob { cmd: Command::Status, inputs: ProofInputs::Base(base_inputs) }).await {
+        Ok(proof) => {
+            send_receipt(Command::Status, &gameid, &fleetid, proof, &*signer, None).await
+        }
+        Err(e) => format!("Error creating status receipt: {}.", e),
+    }
+}
+
+// Prove, once the game is over, that all our reports were consistent with our committed board
+pub async fn audit(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // The audit verifies the report receipts this host kept
+    let join = match joins::load(&gameid, &fleetid) {
+        Some(join) => join,
+        None => return "No receipts for this fleet on this host, only the host it played from can audit it".to_string(),
+    };
+    let initial = match join.receipt.journal.decode::<BaseJournal>() {
+        Ok(journal) => journal.board,
+        Err(_) => return "Could not decode the join receipt".to_string(),
+    };
+
+    let challenge = match fetch_challenge(&gameid, &fleetid).await {
+        Ok(challenge) => challenge,
+        Err(err) => return format!("Error fetching challenge: {}", err),
+    };
+
+    let inputs = AuditInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        initial,
+        report_id: REPORT_ID.into(),
+        reports: join.reports.iter().map(|receipt| receipt.journal.bytes.clone()).collect(),
+        challenge,
+    };
+
+    match prove(ProofJob { cmd: Command::Audit, inputs: ProofInputs::Audit(inputs, join.reports) }).await {
+        Ok(proof) => send_receipt(Command::Audit, &gameid, &fleetid, proof, &*signer, None).await,
+        Err(e) => format!("Error creating audit receipt: {}.", e),
+    }
+}
+
+pub async fn annotate(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let kind = match idata.annotation_kind.as_deref() {
+        Some("Gg") => AnnotationKind::Gg,
+        Some("Strategy") => AnnotationKind::Strategy,
+        _ => return "Annotation kind must be either 'Gg' or 'Strategy'".to_string(),
+    };
+    let text = match idata.annotation.as_ref() {
+        Some(text) if !text.trim().is_empty() => text.trim().to_string(),
+        _ => return "You must provide an Annotation".to_string(),
+    };
+
+    // Sign the annotation with the same key used during the game
+    let nonce = next_nonce();
+    let statement = AnnotationStatement { gameid: &gameid, fleet: &fleetid, kind, text: &text, nonce };
+    let signature = match sign_canonical(&*signer, ANNOTATION_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing annotation: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/annotations", gameid))
+        .json(&AnnotationData {
+            fleet: fleetid,
+            kind,
+            text,
+            nonce,
+            signature,
+        });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending annotation: {}", e),
+    }
+}
+// Tell the chain we are ready to play, or start the game right away when `start` is set (only
+// the creator of the game can)
+pub async fn ready(idata: FormData, start: bool) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // Sign the statement with the same key used during the game
+    let statement = ReadyStatement { gameid: &gameid, fleet: &fleetid, start };
+    let signature = match sign_canonical(&*signer, READY_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing ready statement: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/ready", gameid))
+        .json(&ReadyData { fleet: fleetid, start, signature });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending ready statement: {}", e),
+    }
+}
+
+// Send a signed chat message to the other players of a running game
+pub async fn chat(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let text = match idata.chat.as_ref() {
+        Some(text) if !text.trim().is_empty() => text.trim().to_string(),
+        _ => return "You must provide a Message".to_string(),
+    };
+
+    let nonce = next_nonce();
+
+    // Sign the message with the same key used during the game
+    let statement = ChatStatement { gameid: &gameid, fleet: &fleetid, text: &text, nonce };
+    let signature = match sign_canonical(&*signer, CHAT_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing message: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/chat", gameid))
+        .json(&ChatData {
+            fleet: fleetid,
+            text,
+            nonce,
+            signature,
+        });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending message: {}", e),
+    }
+}
+
+// Members of the fleet's team with their keys, and the team messages sealed for the fleet after `after`
+async fn fetch_team_channel(gameid: &str, fleet: &str, after: u64) -> Result<TeamChannel, String> {
+    let client = reqwest::Client::new();
+    let request = client
+        .get(format!("{}/games/{}/team-messages/{}", endpoints::current(), gameid, fleet))
+        .query(&[("after", after)]);
+    let request = match &config().host.chain_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    let response = responses::send(request).await
+        .map_err(|e| format!("Failed to fetch the team channel: {}", e))?;
+    if !response.status.is_success() {
+        return Err(response.text);
+    }
+    serde_json::from_str(&response.text)
+        .map_err(|e| format!("Failed to parse the team channel: {}", e))
+}
+
+// Send a message to the members of the fleet's team, encrypted so that only they can read it
+pub async fn team_message(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let text = match idata.team_message.as_ref() {
+        Some(text) if !text.trim().is_empty() => text.trim().to_string(),
+        _ => return "You must provide a Message to your team".to_string(),
+    };
+
+    // Seal the message for the members the chain knows now, the chain refuses it if they changed
+    let channel = match fetch_team_channel(&gameid, &fleetid, u64::MAX).await {
+        Ok(channel) => channel,
+        Err(err) => return err,
+    };
+    let sealed = match coordination::seal(&gameid, &fleetid, &text, &channel.members) {
+        Ok(sealed) => sealed,
+        Err(err) => return err,
+    };
+
+    let nonce = next_nonce();
+    let statement = TeamMessageStatement {
+        gameid: &gameid,
+        fleet: &fleetid,
+        ephemeral_key: &sealed.ephemeral_key,
+        iv: &sealed.iv,
+        ciphertext: &sealed.ciphertext,
+        keys: &sealed.keys,
+        nonce,
+    };
+    let signature = match sign_canonical(&*signer, TEAM_MESSAGE_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing message: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/team-messages", gameid))
+        .json(&TeamMessageData {
+            fleet: fleetid,
+            ephemeral_key: sealed.ephemeral_key,
+            iv: sealed.iv,
+            ciphertext: sealed.ciphertext,
+            keys: sealed.keys,
+            nonce,
+            signature,
+        });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending message: {}", e),
     }
 }
+
+// Decrypt the messages the chain keeps for the fleet's team. Only keys held in the keystore can
+// read them: the signing agent and service only sign.
+pub async fn read_team(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let Some(signing_key) = identity.signing_key else {
+        return "Team messages can only be read with keys held in the keystore".to_string();
+    };
+    let channel = match fetch_team_channel(&gameid, &fleetid, 0).await {
+        Ok(channel) => channel,
+        Err(err) => return err,
+    };
+    let own_key = signing_key.verifying_key().to_bytes();
+    if !channel.members.iter().any(|member| member.fleet == fleetid && member.public_key == own_key) {
+        return "The chain knows this fleet by a key the keystore does not hold, team messages cannot be read".to_string();
+    }
+    if channel.messages.is_empty() {
+        return format!("No messages in team {}", channel.team);
+    }
+
+    let mut lines = vec![format!("Messages of team {}:", channel.team)];
+    for message in &channel.messages {
+        match coordination::open(&gameid, &fleetid, &signing_key, &channel.members, message) {
+            Ok(text) => lines.push(format!("{}: {}", message.data.fleet, text)),
+            Err(err) => lines.push(format!("{}: (unreadable message {}: {})", message.data.fleet, message.seq, err)),
+        }
+    }
+    lines.join("\n")
+}
+
+// Vote to pause a running game, or to resume a paused one. The game switches once every player
+// still in it voted the same.
+pub async fn pause(idata: FormData, pause: bool) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let nonce = next_nonce();
+
+    // Sign the vote with the same key used during the game
+    let statement = PauseStatement { gameid: &gameid, fleet: &fleetid, pause, nonce };
+    let signature = match sign_canonical(&*signer, PAUSE_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing pause vote: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/pause", gameid))
+        .json(&PauseData {
+            fleet: fleetid,
+            pause,
+            nonce,
+            signature,
+        });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending pause vote: {}", e),
+    }
+}
+
+// Ask for a rematch of a game that ended and join it right away with the board of the form. The
+// chain carries our key over to the rematch, so only the join proof is needed.
+pub async fn rematch(mut idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    // Sign the request with the same key used during the game
+    let statement = RematchStatement { gameid: &gameid, fleet: &fleetid };
+    let signature = match sign_canonical(&*signer, REMATCH_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing rematch request: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/rematch", gameid))
+        .json(&RematchData { fleet: fleetid, signature });
+
+    let rematch = match responses::send(request).await {
+        Ok(response) if response.status.is_success() => response.text,
+        Ok(response) => return response.text,
+        Err(e) => return format!("Error sending rematch request: {}", e),
+    };
+
+    idata.gameid = Some(rematch.clone());
+    format!("Rematch {}: {}", rematch, join_game(idata).await)
+}
+
+// Reveal our board after a blinded game, so the chain can open the outcomes of our reports
+pub async fn reveal(idata: FormData) -> String {
+    let (gameid, fleetid, board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let statement = RevealStatement { gameid: &gameid, fleet: &fleetid, board, random: &identity.random };
+    let signature = match sign_canonical(&*signer, REVEAL_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return format!("Error signing reveal: {}", e),
+    };
+
+    progress(JobState::Submitting);
+    let client = reqwest::Client::new();
+    let request = chain_post(&client, &format!("/games/{}/reveal", gameid))
+        .json(&RevealData {
+            fleet: fleetid,
+            board,
+            random: identity.random,
+            signature,
+        });
+
+    match responses::send(request).await {
+        Ok(response) => response.text,
+        Err(e) => format!("Error sending reveal: {}", e),
+    }
+}
+
+// Seconds an invite we write stays valid
+const INVITE_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+// Write an invite to the private game we created, for the fleet given or for anyone holding it
+pub async fn invite(idata: FormData) -> String {
+    let (gameid, fleetid, _board, identity) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let signer = signer::for_fleet(&fleetid, &identity);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let invite = Invite {
+        gameid,
+        fleet: idata.invitee.clone().filter(|fleet| !fleet.trim().is_empty()),
+        expires_at: now + INVITE_TTL_SECONDS,
+    };
+
+    // Sign the invite with the same key used during the game
+    match sign_canonical(&*signer, INVITE_DOMAIN, &invite).await {
+        Ok(signature) => format!("Invite code: {}", SignedInvite { invite, signature }.code()),
+        Err(e) => format!("Error signing invite: {}", e),
+    }
+}
+
+// Ask the chain to place our fleet in an open game, which we then join as usual
+pub async fn matchmake(fleet: &str, rated: bool) -> Result<MatchAssignment, String> {
+    if fleet.is_empty() {
+        return Err("You must provide a Fleet ID".to_string());
+    }
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/matchmake", endpoints::current()))
+        .json(&MatchRequest { fleet: fleet.to_string(), rated })
+        .send()
+        .await
+        .map_err(|e| format!("Failed to request a match: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(response.text().await.unwrap_or_else(|_| "Failed to get a match".to_string()));
+    }
+
+    response.json().await
+        .map_err(|e| format!("Failed to parse match: {}", e))
+}
+
+// What the page needs to verify a game's receipts in the browser, passed through as the chain
+// serves it: the image IDs the chain accepts, the receipts it applied to the game, and once the
+// game is over its certificate with the genesis key
+#[derive(serde::Serialize)]
+pub struct VerificationData {
+    methods: serde_json::Value,
+    receipts: serde_json::Value,
+    certificate: Option<serde_json::Value>,
+    genesis: Option<String>,
+}
+
+pub async fn verification_data(gameid: &str) -> Result<VerificationData, String> {
+    let client = reqwest::Client::new();
+    let get = |path: String| {
+        let request = client.get(format!("{}{}", endpoints::current(), path));
+        async move {
+            let response = request.send().await.map_err(|e| format!("Failed to reach the chain: {}", e))?;
+            if !response.status().is_success() {
+                return Ok(None);
+            }
+            response.text().await.map(Some).map_err(|e| format!("Failed to read {}: {}", path, e))
+        }
+    };
+    let json = |text: Option<String>| text.and_then(|text| serde_json::from_str(&text).ok());
+
+    let methods = json(get("/methods".to_string()).await?).ok_or("The chain does not publish its image IDs")?;
+    let receipts = json(get(format!("/games/{}/receipts", gameid)).await?).ok_or("The chain has no receipts for this game")?;
+    let certificate = json(get(format!("/games/{}/certificate", gameid)).await?);
+    let genesis = match certificate {
+        Some(_) => get("/genesis".to_string()).await?,
+        None => None,
+    };
+    Ok(VerificationData { methods, receipts, certificate, genesis })
+}
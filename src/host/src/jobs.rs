@@ -0,0 +1,119 @@
+// src/jobs.rs
+//
+// Background jobs for game actions. Proving takes minutes, so the form POST only starts a job
+// and returns its ID; the page then follows the job's progress on `/jobs/{id}` (polling) or
+// `/jobs/{id}/events` (SSE) until the chain's response is available.
+//
+// Each job also gets a correlation ID, sent to the chain with every request the action makes (see
+// responses.rs) and in its receipt transaction. The chain logs it and tags the events of the
+// transaction with it, so one click can be followed through proving, submission, verification
+// and the events it published.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::broadcast;
+
+// Finished jobs are forgotten after this many seconds
+const JOB_RETENTION_SECONDS: u64 = 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum JobState {
+    // Waiting for a free prover
+    Queued,
+    // Running the action: checking the inputs and fetching the game state
+    Executing,
+    // Generating the receipt
+    Proving,
+    // Sending the receipt to the chain
+    Submitting,
+    // Finished; `response` holds the result of the action
+    Done,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub correlation_id: String,
+    pub state: JobState,
+    pub response: Option<String>,
+    pub updated_at: u64,
+}
+
+static JOBS: LazyLock<Mutex<HashMap<String, JobStatus>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+static EVENTS: LazyLock<broadcast::Sender<JobStatus>> = LazyLock::new(|| broadcast::channel(100).0);
+
+// Job run by the current task
+#[derive(Clone)]
+struct CurrentJob {
+    id: String,
+    correlation_id: String,
+}
+
+tokio::task_local! {
+    // Used by `progress` and `correlation_id`
+    static CURRENT_JOB: CurrentJob;
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn update(job: &CurrentJob, state: JobState, response: Option<String>) {
+    let status = JobStatus {
+        id: job.id.clone(),
+        correlation_id: job.correlation_id.clone(),
+        state,
+        response,
+        updated_at: now_secs(),
+    };
+    JOBS.lock().unwrap_or_else(|e| e.into_inner()).insert(job.id.clone(), status.clone());
+    // Nobody following the job is not an error
+    let _ = EVENTS.send(status);
+}
+
+// Record the progress of the job run by the current task. Does nothing outside of a job.
+pub fn progress(state: JobState) {
+    let _ = CURRENT_JOB.try_with(|job| update(job, state, None));
+}
+
+// Correlation ID of the job run by the current task, None outside of a job
+pub fn correlation_id() -> Option<String> {
+    CURRENT_JOB.try_with(|job| job.correlation_id.clone()).ok()
+}
+
+// Run a game action in the background and return the ID of its job
+pub fn spawn_job<F>(action: F) -> String
+where
+    F: Future<Output = String> + Send + 'static,
+{
+    let id = nanoid::nanoid!(10);
+
+    // Forget old finished jobs
+    let cutoff = now_secs().saturating_sub(JOB_RETENTION_SECONDS);
+    JOBS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, job| job.state != JobState::Done || job.updated_at > cutoff);
+
+    let job = CurrentJob { id: id.clone(), correlation_id: nanoid::nanoid!(16) };
+    update(&job, JobState::Queued, None);
+    tokio::spawn(CURRENT_JOB.scope(job.clone(), async move {
+        update(&job, JobState::Executing, None);
+        let response = action.await;
+        update(&job, JobState::Done, Some(response));
+    }));
+    id
+}
+
+pub fn job_status(id: &str) -> Option<JobStatus> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner()).get(id).cloned()
+}
+
+// Receiver for the status changes of all jobs
+pub fn subscribe() -> broadcast::Receiver<JobStatus> {
+    EVENTS.subscribe()
+}
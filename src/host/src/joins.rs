@@ -0,0 +1,67 @@
+// src/joins.rs
+//
+// Join receipts of the fleets playing on this host. Every shot is proven against the player's own
+// join receipt, which the fire guest verifies as an assumption, so the receipt accepted by the
+// chain is kept together with the placement it validated, in one private file per game and fleet.
+// The report receipts the chain accepted are kept alongside, for the end-of-game audit.
+
+use fleetcore::Board;
+use risc0_zkvm::Receipt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::PathBuf;
+
+use fleetconfig::config;
+
+use crate::session::create_private;
+
+#[derive(Deserialize, Serialize)]
+pub struct JoinRecord {
+    // Board as placed when joining
+    pub placement: Board,
+    pub receipt: Receipt,
+    // Plain reports accepted by the chain, in order
+    #[serde(default)]
+    pub reports: Vec<Receipt>,
+}
+
+// Game and fleet IDs are chosen by players, so files are named after their digest
+fn record_path(gameid: &str, fleet: &str) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(gameid.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(fleet.as_bytes());
+    let config = config();
+    config
+        .storage_file(&config.host.join_receipts)
+        .join(format!("{}.json", hex::encode(hasher.finalize())))
+}
+
+// Keep the join receipt the chain accepted for a fleet
+pub fn store(gameid: &str, fleet: &str, record: &JoinRecord) -> std::io::Result<()> {
+    let path = record_path(gameid, fleet);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut file = create_private(&tmp)?;
+    file.write_all(&serde_json::to_vec(record)?)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, &path)
+}
+
+// Join receipt of a fleet, if it joined the game from this host
+pub fn load(gameid: &str, fleet: &str) -> Option<JoinRecord> {
+    let bytes = std::fs::read(record_path(gameid, fleet)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+// Keep a report receipt the chain accepted, after the join receipt of the fleet
+pub fn record_report(gameid: &str, fleet: &str, receipt: Receipt) -> std::io::Result<()> {
+    let mut record = load(gameid, fleet).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no join receipt for this fleet")
+    })?;
+    record.reports.push(receipt);
+    store(gameid, fleet, &record)
+}
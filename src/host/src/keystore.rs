@@ -0,0 +1,228 @@
+// src/keystore.rs
+//
+// Encrypted keystore of the players' identities on this host. Each fleet gets a random signing key
+// and the random salt of its board commitments when it first joins or registers. They are
+// encrypted with ChaCha20-Poly1305 under a key derived from the player's passphrase with Argon2id,
+// so the browser only sends the passphrase and nothing secret is kept in the page or the session.
+// When a signing agent or service holds the keys, only the salt is created and unlocked here.
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{SigningKey, SECRET_KEY_LENGTH};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use tracing::error;
+
+use fleetconfig::{config, SignerKind};
+
+use crate::session::create_private;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+// Shortest passphrase accepted for a new identity
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+
+// Encrypted identity of a fleet, hex-encoded
+#[derive(Clone, Deserialize, Serialize)]
+struct Entry {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Plaintext of an entry
+#[derive(Deserialize, Serialize)]
+struct Secrets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key: Option<String>,
+    random: String,
+}
+
+// Unlocked identity of a fleet
+pub struct Identity {
+    // None when the fleet's key is held by a signing agent or service
+    pub signing_key: Option<SigningKey>,
+    // Salt of the fleet's board commitments
+    pub random: String,
+}
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    // The fleet has no identity on this host yet
+    NotFound(String),
+    WrongPassphrase,
+    WeakPassphrase,
+    Corrupted(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeystoreError::NotFound(fleet) => write!(f, "No key for fleet {} on this host, join a game first", fleet),
+            KeystoreError::WrongPassphrase => write!(f, "Wrong passphrase"),
+            KeystoreError::WeakPassphrase => {
+                write!(f, "The passphrase must have at least {} characters", MIN_PASSPHRASE_LENGTH)
+            }
+            KeystoreError::Corrupted(e) => write!(f, "Corrupted keystore entry: {}", e),
+            KeystoreError::Io(e) => write!(f, "Could not write the keystore: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {}
+
+struct Keystore {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+static KEYSTORE: LazyLock<Mutex<Keystore>> = LazyLock::new(|| {
+    let config = config();
+    let path = config.storage_file(&config.host.keystore);
+    let entries = load_entries(&path);
+    Mutex::new(Keystore { path, entries })
+});
+
+fn load_entries(path: &Path) -> HashMap<String, Entry> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            // Never start over silently: the keys of the existing fleets would be lost
+            error!("Could not parse the keystore {}: {}", path.display(), e);
+            std::process::exit(1);
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => {
+            error!("Could not read the keystore {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+impl Keystore {
+    // Write the keystore to a temporary file first, so a crash never leaves a truncated keystore
+    fn persist(&self) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut file = create_private(&tmp)?;
+        file.write_all(&serde_json::to_vec(&self.entries)?)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+fn keystore() -> std::sync::MutexGuard<'static, Keystore> {
+    KEYSTORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Whether the fleets' signing keys are kept in the keystore
+fn holds_keys() -> bool {
+    config().host.signer == SignerKind::Keystore
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, KeystoreError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| KeystoreError::Corrupted(e.to_string()))?;
+    Ok(ChaCha20Poly1305::new(&key))
+}
+
+fn decode_hex(value: &str, what: &str) -> Result<Vec<u8>, KeystoreError> {
+    hex::decode(value).map_err(|_| KeystoreError::Corrupted(format!("invalid {}", what)))
+}
+
+fn seal(passphrase: &str, identity: &Identity) -> Result<Entry, KeystoreError> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let secrets = Secrets {
+        signing_key: identity.signing_key.as_ref().map(|key| hex::encode(key.to_bytes())),
+        random: identity.random.clone(),
+    };
+    let plaintext = serde_json::to_vec(&secrets).map_err(|e| KeystoreError::Corrupted(e.to_string()))?;
+    let ciphertext = cipher(passphrase, &salt)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| KeystoreError::Corrupted("encryption failed".to_string()))?;
+    Ok(Entry { salt: hex::encode(salt), nonce: hex::encode(nonce), ciphertext: hex::encode(ciphertext) })
+}
+
+fn open(passphrase: &str, entry: &Entry) -> Result<Identity, KeystoreError> {
+    let salt = decode_hex(&entry.salt, "salt")?;
+    let nonce = decode_hex(&entry.nonce, "nonce")?;
+    let ciphertext = decode_hex(&entry.ciphertext, "ciphertext")?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(KeystoreError::Corrupted("invalid nonce".to_string()));
+    }
+
+    // A wrong passphrase derives another key, which fails the authentication tag
+    let plaintext = cipher(passphrase, &salt)?
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| KeystoreError::WrongPassphrase)?;
+    let secrets: Secrets = serde_json::from_slice(&plaintext).map_err(|e| KeystoreError::Corrupted(e.to_string()))?;
+    if !holds_keys() {
+        return Ok(Identity { signing_key: None, random: secrets.random });
+    }
+    let signing_key = secrets.signing_key.ok_or_else(|| {
+        KeystoreError::Corrupted("no signing key, the fleet was created for a signing agent or service".to_string())
+    })?;
+    let seed: [u8; SECRET_KEY_LENGTH] = decode_hex(&signing_key, "signing key")?
+        .try_into()
+        .map_err(|_| KeystoreError::Corrupted("invalid signing key".to_string()))?;
+    Ok(Identity { signing_key: Some(SigningKey::from_bytes(&seed)), random: secrets.random })
+}
+
+// Unlock the identity of a fleet
+pub fn unlock(fleet: &str, passphrase: &str) -> Result<Identity, KeystoreError> {
+    let entry = keystore().entries.get(fleet).cloned();
+    match entry {
+        Some(entry) => open(passphrase, &entry),
+        None => Err(KeystoreError::NotFound(fleet.to_string())),
+    }
+}
+
+// Unlock the identity of a fleet, creating it on the fleet's first action on this host
+pub fn unlock_or_create(fleet: &str, passphrase: &str) -> Result<Identity, KeystoreError> {
+    match unlock(fleet, passphrase) {
+        Err(KeystoreError::NotFound(_)) => {}
+        result => return result,
+    }
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(KeystoreError::WeakPassphrase);
+    }
+
+    let signing_key = holds_keys().then(|| {
+        let mut seed = [0u8; SECRET_KEY_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        SigningKey::from_bytes(&seed)
+    });
+    let identity = Identity { signing_key, random: nanoid::nanoid!(21) };
+    let entry = seal(passphrase, &identity)?;
+
+    let mut keystore = keystore();
+    // Another request may have created the identity while the key was being derived
+    if let Some(existing) = keystore.entries.get(fleet).cloned() {
+        drop(keystore);
+        return open(passphrase, &existing);
+    }
+    keystore.entries.insert(fleet.to_string(), entry);
+    if let Err(e) = keystore.persist() {
+        keystore.entries.remove(fleet);
+        return Err(KeystoreError::Io(e));
+    }
+    Ok(identity)
+}
+
+// Fleets with an identity on this host, by name
+pub fn fleets() -> Vec<String> {
+    let mut fleets: Vec<String> = keystore().entries.keys().cloned().collect();
+    fleets.sort();
+    fleets
+}
@@ -1,80 +1,279 @@
-// Remove the following 3 lines to enable compiler checkings
-#![allow(unused_imports)]
-#![allow(unused_variables)]
-#![allow(dead_code)]
-
-use percent_encoding;
 use serde::{Deserialize, Serialize};
+pub mod auditor;
+mod cache;
+pub mod compat;
+mod coordination;
+pub mod endpoints;
 mod game_actions;
+pub mod jobs;
+pub mod joins;
+pub mod keystore;
+pub mod metrics;
+pub mod notify;
+pub mod tracker;
+pub mod placement;
+mod prover;
+pub mod proverd;
+mod responses;
+pub mod session;
+pub mod shots;
+pub mod signer;
+pub mod ws;
 
-use fleetcore::{BaseInputs, Command, CommunicationData, FireInputs};
-use risc0_zkvm::Receipt;
-use risc0_zkvm::{default_prover, ExecutorEnv};
+use fleetconfig::config;
+use fleetcore::{
+    check_id, Board, Command, CommunicationData, Coord, ReceiptStatement, UnsupportedVersion, PROTOCOL_VERSION,
+    RECEIPT_DOMAIN,
+};
+use risc0_zkvm::{default_prover, ExecutorEnv, Receipt};
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, warn};
 
-pub use game_actions::{fire, join_game, report, wave, win};
-
-use std::collections::{HashMap, HashSet, VecDeque};
-use ed25519_dalek::{SigningKey, Signer, VerifyingKey};
-use sha2::{Sha256, Digest};
-
-fn generate_keys_from_random(random: &str) -> (SigningKey, VerifyingKey) {
-    // Create a deterministic seed from the random string
-    let mut hasher = Sha256::new();
-    hasher.update(random.as_bytes());
-    let seed_hash = hasher.finalize();
-    
-    // Take first 32 bytes as seed for Ed25519
-    let mut seed = [0u8; 32];
-    seed.copy_from_slice(&seed_hash[..32]);
-    
-    let signing_key = SigningKey::from_bytes(&seed);
-    let verifying_key = signing_key.verifying_key();
-    
-    (signing_key, verifying_key)
-}
-
-fn generate_receipt_for_base_inputs(
-    base_inputs: BaseInputs,
-    elf: &[u8],
-) -> Result<Receipt, Box<dyn Error + Send + Sync>> {
-    let env = ExecutorEnv::builder()
-        .write(&base_inputs)?
-        .build()?;
+pub use game_actions::{
+    annotate, audit, chat, contest, dispatch, fire, forfeit, invite, join_game, matchmake, pause, ready, register, rematch,
+    report, reveal, status, verification_data, wave, win, VerificationData,
+};
+use prover::Proof;
+pub use prover::{run_worker, worker_health, ProofInputs, ProofJob, WORKER_ARG};
+
+use keystore::Identity;
+use signer::Signer;
 
+// Log to stderr at the configured level, leaving stdout to the output of the command-line tools
+pub fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(&config().log_level))
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+// Prove with the prover the environment selects (local, Bonsai or dev mode), keeping its statistics
+fn prove_env(env: ExecutorEnv<'_>, elf: &[u8]) -> Result<Proof, Box<dyn Error + Send + Sync>> {
     let prover = default_prover();
-    Ok(prover.prove(env, elf)?.receipt)
+    let prove_info = prover.prove(env, elf)?;
+    Ok(Proof {
+        receipt: prove_info.receipt,
+        cycles: prove_info.stats.total_cycles,
+        segments: prove_info.stats.segments as u64,
+        backend: prover.get_name(),
+    })
 }
 
-fn generate_receipt_for_fire_inputs(
-    fire_inputs: FireInputs,
+// Prove a guest on its inputs. A shot is proven against our join receipt, a report against the
+// shot it answers and an audit against the reports it covers, each added as an assumption.
+fn generate_receipt<T: Serialize>(
+    inputs: &T,
+    assumptions: impl IntoIterator<Item = Receipt>,
     elf: &[u8],
-) -> Result<Receipt, Box<dyn Error + Send + Sync>> {
-    let env = ExecutorEnv::builder()
-        .write(&fire_inputs)?
+) -> Result<Proof, Box<dyn Error + Send + Sync>> {
+    let mut builder = ExecutorEnv::builder();
+    for receipt in assumptions {
+        builder.add_assumption(receipt);
+    }
+    let env = builder
+        .write(inputs)?
         .build()?;
 
-    let prover = default_prover();
-    Ok(prover.prove(env, elf)?.receipt)
+    prove_env(env, elf)
+}
+
+// Highest transaction sequence number the chain reported applying for this host, so later game
+// state fetches can ask for a state that already includes our own actions
+static LAST_APPLIED_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn last_applied_seq() -> u64 {
+    LAST_APPLIED_SEQ.load(Ordering::SeqCst)
+}
+
+// Nonce for a signed message. The chain only accepts increasing nonces from a player; the current
+// time in milliseconds is one that survives a restart of the host.
+static LAST_NONCE: AtomicU64 = AtomicU64::new(0);
+
+fn next_nonce() -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    // Two messages signed within the same millisecond still get different nonces
+    let next = |last: u64| now.max(last + 1);
+    match LAST_NONCE.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(next(last))) {
+        Ok(last) | Err(last) => next(last),
+    }
+}
+
+// Longest wait between two attempts at sending a transaction, whatever the backoff or the chain asks
+const MAX_SUBMIT_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+// POST request adding a transaction to the chain, with this host's token when it has one
+fn chain_post(client: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+    let request = client.post(format!("{}{}", endpoints::current(), path));
+    match &config().host.chain_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+// Sent along with a join receipt: the player's key, and who else can join the game
+struct JoinTerms<'a> {
+    public_key: &'a [u8],
+    // Make the game private when the join creates it
+    private: bool,
+    // Invite to a private game
+    invite: Option<String>,
+    // Team to play in, making the game a team game when the join creates it
+    team: Option<String>,
+    // Seconds to contest a victory claim in the game the join creates
+    victory_timeout: Option<u64>,
+}
+
+// Why a transaction did not make it onto the chain
+#[derive(Debug)]
+enum SubmitError {
+    // The receipt could not be signed
+    Signing(String),
+    // No answer the host can trust, after every attempt: the chain may or may not have applied the
+    // transaction, and a retry with the same idempotency key will tell
+    Unanswered(responses::SendError),
+    // The chain answered and refused the transaction
+    Rejected { status: reqwest::StatusCode, message: String },
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Signing(e) => write!(f, "Error signing receipt: {}", e),
+            SubmitError::Unanswered(e) => write!(f, "Error sending receipt: {}", e),
+            // The page shows the chain's own explanation
+            SubmitError::Rejected { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl SubmitError {
+    // Refusals the chain may take back later: it is overloaded, restarting behind a proxy, or
+    // rate limiting the fleet
+    fn is_transient(&self) -> bool {
+        match self {
+            SubmitError::Signing(_) => false,
+            SubmitError::Unanswered(_) => true,
+            SubmitError::Rejected { status, .. } => matches!(
+                *status,
+                reqwest::StatusCode::TOO_MANY_REQUESTS
+                    | reqwest::StatusCode::BAD_GATEWAY
+                    | reqwest::StatusCode::SERVICE_UNAVAILABLE
+                    | reqwest::StatusCode::GATEWAY_TIMEOUT
+            ),
+        }
+    }
 }
 
+fn rejection(response: &responses::ChainResponse) -> SubmitError {
+    // A chain running another protocol version answers with the versions it accepts
+    let message = match serde_json::from_str::<UnsupportedVersion>(&response.text) {
+        Ok(unsupported) => format!(
+            "The chain does not accept protocol version {} (accepts {} to {}), upgrade the host or the chain",
+            unsupported.version, unsupported.min_supported, unsupported.max_supported
+        ),
+        Err(_) => response.text.clone(),
+    };
+    SubmitError::Rejected { status: response.status, message }
+}
 
-async fn send_receipt(action: Command, receipt: Receipt, signature: &[u8], public_key: Option<&[u8]>) -> String {
+// Delay before the retry following attempt `attempt`: the configured backoff, doubled at each
+// retry, unless the chain said when to come back
+fn retry_delay(attempt: u32, retry_after: Option<std::time::Duration>) -> std::time::Duration {
+    let backoff = std::time::Duration::from_millis(config().host.submit_backoff_ms)
+        .saturating_mul(1 << (attempt - 1).min(16));
+    retry_after.unwrap_or(backoff).min(MAX_SUBMIT_DELAY)
+}
+
+// Send a transaction to `/chain`, retrying while it gets no answer or a transient refusal. The
+// journal digest as idempotency key gets the chain to answer a retry of a transaction it already
+// applied instead of applying it twice.
+async fn submit(action: Command, digest: &str, data: &CommunicationData) -> Result<responses::ChainResponse, SubmitError> {
     let client = reqwest::Client::new();
-    let res = client
-        .post("http://chain0:3001/chain")
-        .json(&CommunicationData {
-            cmd: action,
-            receipt,
-            signature: signature.to_vec(),
-            public_key: public_key.map(|pk| pk.to_vec()),
-        })
-        .send()
-        .await;
-
-    match res {
-        Ok(response) => response.text().await.unwrap(),
-        Err(_) => "Error sending receipt".to_string(),
+    let attempts = config().host.submit_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let request = chain_post(&client, "/chain").header("idempotency-key", digest).json(data);
+        let (error, retry_after) = match responses::send(request).await {
+            Ok(response) if response.status.is_success() => return Ok(response),
+            Ok(response) => {
+                let retry_after = response
+                    .headers
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .map(std::time::Duration::from_secs);
+                (rejection(&response), retry_after)
+            }
+            Err(e) => (SubmitError::Unanswered(e), None),
+        };
+        if attempt >= attempts || !error.is_transient() {
+            return Err(error);
+        }
+        let delay = retry_delay(attempt, retry_after);
+        warn!("Retrying the {:?} transaction in {:?} after an error: {}", action, delay, error);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+async fn send_receipt(
+    action: Command,
+    gameid: &str,
+    fleet: &str,
+    proof: Proof,
+    signer: &dyn Signer,
+    join: Option<JoinTerms<'_>>,
+) -> String {
+    // Sign the journal together with the command, the game and a fresh nonce
+    let nonce = next_nonce();
+    let statement = ReceiptStatement::new(action, gameid, nonce, &proof.receipt.journal.bytes);
+    let signature = match signer::sign_canonical(signer, RECEIPT_DOMAIN, &statement).await {
+        Ok(signature) => signature,
+        Err(e) => return SubmitError::Signing(e).to_string(),
+    };
+
+    jobs::progress(jobs::JobState::Submitting);
+    // Keep a durable record of the proof before it leaves the host
+    let digest = tracker::record_submission(gameid, fleet, action, &proof.receipt.journal.bytes);
+    let data = CommunicationData {
+        version: PROTOCOL_VERSION,
+        cmd: action,
+        receipt: proof.receipt,
+        cycles: Some(proof.cycles),
+        signature,
+        public_key: join.as_ref().map(|join| join.public_key.to_vec()),
+        nonce,
+        private: join.as_ref().is_some_and(|join| join.private),
+        team: join.as_ref().and_then(|join| join.team.clone()),
+        victory_timeout: join.as_ref().and_then(|join| join.victory_timeout),
+        invite: join.and_then(|join| join.invite),
+        correlation_id: jobs::correlation_id(),
+    };
+
+    match submit(action, &digest, &data).await {
+        Ok(response) => {
+            // The chain still accepts receipts from the previous guests during an upgrade, but warns about them
+            if let Some(warning) = response.headers.get("x-guest-deprecated") {
+                warn!("Warning from the chain: {}", warning.to_str().unwrap_or("guest version is deprecated"));
+            }
+            let seq = response.headers.get("x-applied-seq").and_then(|v| v.to_str().ok()?.parse().ok());
+            if let Some(id) = &data.correlation_id {
+                let tx = response.headers.get("x-tx-id").and_then(|v| v.to_str().ok()).unwrap_or("-");
+                debug!("Action {}: {:?} transaction {} on {}: {}", id, action, tx, response.node, response.text);
+            }
+            if let Some(seq) = seq {
+                LAST_APPLIED_SEQ.fetch_max(seq, Ordering::SeqCst);
+            }
+            tracker::record_outcome(gameid, fleet, &digest, &response.text, seq, Some(&response.node));
+            response.text
+        }
+        Err(e) => {
+            let text = e.to_string();
+            tracker::record_outcome(gameid, fleet, &digest, &text, None, None);
+            text
+        }
     }
 }
 
@@ -86,15 +285,36 @@ pub struct FormData {
     pub targetfleet: Option<String>,
     pub x: Option<String>,
     pub y: Option<String>,
+    // Square fired at as a single cell like "B7", instead of `x` and `y`
+    pub cell: Option<String>,
     pub rx: Option<String>,
     pub ry: Option<String>,
+    // Square reported as a single cell, instead of `rx` and `ry`
+    pub rcell: Option<String>,
     pub report: Option<String>,
     pub board: Option<String>,
     pub shots: Option<String>,
-    pub random: Option<String>,
+    // Unlocks the fleet's identity in the host's keystore
+    pub passphrase: Option<String>,
+    pub annotation_kind: Option<String>,
+    pub annotation: Option<String>,
+    pub chat: Option<String>,
+    // Encrypted for the fleet's teammates
+    pub team_message: Option<String>,
+    // Set by the checkbox making the game created by a join private
+    pub private: Option<String>,
+    // Invite code to join a private game with
+    pub invite: Option<String>,
+    // Fleet to write an invite for, anyone when empty
+    pub invitee: Option<String>,
+    // Team to join a game in
+    pub team: Option<String>,
+    // Seconds to contest a victory claim in a game created by a join
+    pub victory_timeout: Option<String>,
 }
 
-pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Vec<u8>, String), String> {
+// Game, fleet, board and passphrase of a form, before the fleet's identity is unlocked
+pub fn unmarshal_form(idata: &FormData) -> Result<(String, String, Board, String), String> {
     let gameid = idata
         .gameid
         .clone()
@@ -117,10 +337,14 @@ pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Vec<u8>, Stri
                 Ok(id)
             }
         })?;
-    let random: String = idata
-        .random
+    // The guests and the chain only take IDs of letters, digits, '-' and '_'
+    check_id("game ID", &gameid).map_err(|e| format!("Invalid Game ID: {}", e))?;
+    check_id("fleet", &fleetid).map_err(|e| format!("Invalid Fleet ID: {}", e))?;
+    let passphrase = idata
+        .passphrase
         .clone()
-        .ok_or_else(|| "You must provide a Random Seed".to_string())?;
+        .filter(|passphrase| !passphrase.is_empty())
+        .ok_or_else(|| "You must provide a Passphrase".to_string())?;
 
     let board = idata
         .board
@@ -132,70 +356,49 @@ pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Vec<u8>, Stri
                 .map_err(|_| "Invalid Board Placement".to_string())
                 .map(|decoded| {
                     decoded
-                        .split(',')
-                        .map(|s| {
-                            s.parse::<u8>()
-                                .map_err(|_| "Invalid number in Board Placement".to_string())
-                        })
-                        .collect::<Result<Vec<u8>, String>>()
+                        .parse::<Board>()
+                        .map_err(|e| format!("Invalid Board Placement: {}", e))
                 })
         })??;
 
-    Ok((gameid, fleetid, board, random))
+    Ok((gameid, fleetid, board, passphrase))
 }
 
-fn get_coordinates(x: &Option<String>, y: &Option<String>) -> Result<(u8, u8), String> {
-    let x: u8 = x
-        .as_ref()
-        .ok_or_else(|| "You must provide an X coordinate".to_string())
-        .and_then(|id| {
-            if let Some(first_char) = id.chars().next() {
-                if ('A'..='J').contains(&first_char) {
-                    Ok(first_char as u8 - b'A')
-                } else {
-                    Err("X coordinate must be between A and J".to_string())
-                }
-            } else {
-                Err("Invalid X coordinate".to_string())
-            }
-        })?;
-
-    let y: u8 = y
-        .as_ref()
-        .ok_or_else(|| "You must provide a Y coordinate".to_string())
-        .and_then(|id| {
-            if let Some(first_char) = id.chars().next() {
-                if ('0'..='9').contains(&first_char) {
-                    Ok(first_char as u8 - b'0')
-                } else {
-                    Err("Y coordinate must be between 0 and 9".to_string())
-                }
-            } else {
-                Err("Invalid Y coordinate".to_string())
-            }
-        })?;
+pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Board, Identity), String> {
+    let (gameid, fleetid, board, passphrase) = unmarshal_form(idata)?;
+    let identity = keystore::unlock(&fleetid, &passphrase).map_err(|e| e.to_string())?;
+    Ok((gameid, fleetid, board, identity))
+}
 
-    Ok((x, y))
+fn get_coordinates(cell: &Option<String>, x: &Option<String>, y: &Option<String>) -> Result<Coord, String> {
+    // A cell like "B7" takes precedence over separate coordinates
+    if let Some(cell) = cell.as_ref().filter(|cell| !cell.trim().is_empty()) {
+        return cell.parse::<Coord>().map_err(|e| e.to_string());
+    }
+    let x = x.as_ref().ok_or_else(|| "You must provide an X coordinate".to_string())?;
+    let y = y.as_ref().ok_or_else(|| "You must provide a Y coordinate".to_string())?;
+    Coord::from_axes(x, y).map_err(|e| e.to_string())
 }
 
 pub fn unmarshal_fire(
     idata: &FormData,
-) -> Result<(String, String, Vec<u8>, String, String, u8, u8), String> {
-    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
-    let (x, y) = get_coordinates(&idata.x, &idata.y)?;
+) -> Result<(String, String, Board, Identity, String, Coord), String> {
+    let (gameid, fleetid, board, identity) = unmarshal_data(idata)?;
+    let pos = get_coordinates(&idata.cell, &idata.x, &idata.y)?;
     let targetfleet = idata
         .targetfleet
         .clone()
         .ok_or_else(|| "You must provide a Target Fleet ID".to_string())?;
+    check_id("target", &targetfleet).map_err(|e| format!("Invalid Target Fleet ID: {}", e))?;
 
-    Ok((gameid, fleetid, board, random, targetfleet, x, y))
+    Ok((gameid, fleetid, board, identity, targetfleet, pos))
 }
 
 pub fn unmarshal_report(
     idata: &FormData,
-) -> Result<(String, String, Vec<u8>, String, String, u8, u8), String> {
-    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
-    let (x, y) = get_coordinates(&idata.rx, &idata.ry)?;
+) -> Result<(String, String, Board, Identity, String, Coord), String> {
+    let (gameid, fleetid, board, identity) = unmarshal_data(idata)?;
+    let pos = get_coordinates(&idata.rcell, &idata.rx, &idata.ry)?;
     let report = idata
         .report
         .clone()
@@ -208,5 +411,5 @@ pub fn unmarshal_report(
             }
         })?;
 
-    Ok((gameid, fleetid, board, random, report, x, y))
+    Ok((gameid, fleetid, board, identity, report, pos))
 }
@@ -3,99 +3,527 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
+use askama::Template;
 use axum::{
-    extract::Form,
-    response::Html,
+    extract::{Form, Path},
+    http::{header, HeaderMap, StatusCode},
+    response::{sse::Event, AppendHeaders, Html, IntoResponse, Response, Sse},
     routing::{get, post},
-    Router,
+    Json, Router,
 };
+use futures::stream::{self, Stream, StreamExt};
 use tokio::signal;
-use nanoid::nanoid;
+use tokio_stream::wrappers::BroadcastStream;
 
-use host::{fire, join_game, report, wave, win, FormData};
-use std::net::SocketAddr;
+use fleetconfig::config;
+use fleetcore::{check_id, Board, Coord};
+use host::placement::{add_ship, random_placement, remove_ship, ships_left, PlacementView};
+use host::shots::ShotTracker;
+use host::tracker::{game_record, shot_tracker};
+use host::jobs::{job_status, spawn_job, subscribe, JobStatus};
+use host::endpoints;
+use host::keystore;
+use host::notify;
+use host::session;
+use host::ws::ws_handler;
+use host::{dispatch, matchmake, verification_data, worker_health, FormData};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use tracing::info;
 
-async fn index() -> Html<String> {
-    render_html(None, None, None, None, None, None)
-}
-
-fn process_input_data(input_data: FormData) -> FormData {
-    match &input_data.random {
-        Some(random) if !random.is_empty() => input_data,
-        _ => FormData {
-            random: Some(nanoid!(12)),
-            ..input_data
-        },
-    }
+// Render the page again from the browser's session, giving it one if it has none
+async fn index(headers: HeaderMap) -> impl IntoResponse {
+    let (id, cookie) = session::resume(&headers);
+    let page = match session::load(&id) {
+        Some(s) => {
+            let response = s.gameid.as_ref().filter(|id| !id.is_empty()).map(|_| "OK".to_string());
+            render_html(s.gameid, s.fleetid, s.board, s.shots, response, None)
+        }
+        None => render_html(None, None, None, None, None, None),
+    };
+    (AppendHeaders(cookie.map(|cookie| (header::SET_COOKIE, cookie))), page)
 }
 
 #[axum::debug_handler]
-async fn submit(Form(input_data): Form<FormData>) -> Html<String> {
-    let gameid = input_data.gameid.clone();
-    let fleetid = input_data.fleetid.clone();
-    let data = process_input_data(input_data);
-    let random = data.random.clone();
+async fn submit(headers: HeaderMap, Form(data): Form<FormData>) -> impl IntoResponse {
+    let gameid = data.gameid.clone();
+    let fleetid = data.fleetid.clone();
+    let (session_id, cookie) = session::resume(&headers);
+    session::save(&session_id, &data);
     let board = data.board.clone();
     let shots = data.shots.clone();
-    let response_text = match data.button.as_str() {
-        "Join" => join_game(data).await,
-        "Fire" => fire(data).await,
-        "Report" => report(data).await,
-        "Wave" => wave(data).await,
-        "Win" => win(data).await,
-        _ => "Unknown button pressed".to_string(),
+
+    // Proving takes minutes: run the action in the background and let the page follow the job
+    let job_id = spawn_job(dispatch(data));
+    let page = render_html(gameid, fleetid, board, shots, None, Some(job_id));
+    (AppendHeaders(cookie.map(|cookie| (header::SET_COOKIE, cookie))), page)
+}
+
+async fn tracker_handler(Path((gameid, fleet)): Path<(String, String)>) -> impl IntoResponse {
+    match game_record(&gameid, &fleet) {
+        Some(record) => Json(record).into_response(),
+        None => (StatusCode::NOT_FOUND, "No actions recorded for this game").into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct PlayerState {
+    gameid: String,
+    fleetid: String,
+    shots: ShotTracker,
+}
+
+// Game of the browser's session, with the shots exchanged so far
+async fn state_handler(headers: HeaderMap) -> impl IntoResponse {
+    let data = session::session_id(&headers).and_then(|id| session::load(&id));
+    let game = data.and_then(|data| {
+        let gameid = data.gameid.filter(|id| !id.is_empty())?;
+        let fleetid = data.fleetid.filter(|id| !id.is_empty())?;
+        Some((gameid, fleetid))
+    });
+    match game {
+        Some((gameid, fleetid)) => {
+            let shots = shot_tracker(&gameid, &fleetid);
+            Json(PlayerState { gameid, fleetid, shots }).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "Not in game").into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct FleetEntry {
+    fleet: String,
+    // Game the session last played with the fleet
+    gameid: Option<String>,
+    // Whether the fleet has an identity in the host's keystore
+    identity: bool,
+}
+
+#[derive(Serialize)]
+struct Fleets {
+    selected: Option<String>,
+    fleets: Vec<FleetEntry>,
+}
+
+// Fleets the browser can play with: the ones its session played with and the ones with an
+// identity on this host
+async fn fleets_handler(headers: HeaderMap) -> Json<Fleets> {
+    let data = session::session_id(&headers).and_then(|id| session::load(&id)).unwrap_or_default();
+    let identities = keystore::fleets();
+    let mut names: Vec<&String> = identities.iter().chain(data.fleets.keys()).collect();
+    names.sort();
+    names.dedup();
+    let fleets = names
+        .into_iter()
+        .map(|fleet| FleetEntry {
+            fleet: fleet.clone(),
+            gameid: data.fleets.get(fleet).and_then(|context| context.gameid.clone()),
+            identity: identities.contains(fleet),
+        })
+        .collect();
+    Json(Fleets { selected: data.fleetid, fleets })
+}
+
+#[derive(Deserialize)]
+struct SelectFleet {
+    fleet: String,
+}
+
+// Switch the browser's session to another fleet, which the page is then rendered for
+async fn select_fleet_handler(headers: HeaderMap, Json(request): Json<SelectFleet>) -> impl IntoResponse {
+    if let Err(e) = check_id("fleet", &request.fleet) {
+        return (StatusCode::BAD_REQUEST, format!("Invalid Fleet ID: {}", e)).into_response();
+    }
+    let (id, cookie) = session::resume(&headers);
+    let data = session::select(&id, &request.fleet);
+    let selected = FleetEntry {
+        identity: keystore::fleets().contains(&request.fleet),
+        fleet: request.fleet,
+        gameid: data.gameid,
     };
-    render_html(gameid, fleetid, random, board, shots, Some(response_text))
+    (AppendHeaders(cookie.map(|cookie| (header::SET_COOKIE, cookie))), Json(selected)).into_response()
+}
+
+#[derive(Serialize)]
+struct SessionGame {
+    gameid: String,
+    fleet: String,
+    // Whether the page plays this game and fleet
+    selected: bool,
+    notification: Option<notify::Notification>,
+}
+
+// Games the browser's session plays, with each fleet it plays them with and the notification
+// still current for it
+async fn games_handler(headers: HeaderMap) -> Json<Vec<SessionGame>> {
+    let data = session::session_id(&headers).and_then(|id| session::load(&id)).unwrap_or_default();
+    let games = data
+        .games()
+        .into_iter()
+        .map(|(gameid, fleet)| SessionGame {
+            selected: data.gameid.as_ref() == Some(&gameid) && data.fleetid.as_ref() == Some(&fleet),
+            notification: notify::latest(&gameid, &fleet),
+            gameid,
+            fleet,
+        })
+        .collect();
+    Json(games)
+}
+
+#[derive(Deserialize)]
+struct SelectGame {
+    gameid: String,
+    fleet: String,
+}
+
+// Switch the browser's session to another game of one of its fleets
+async fn select_game_handler(headers: HeaderMap, Json(request): Json<SelectGame>) -> impl IntoResponse {
+    if let Err(e) = check_id("game ID", &request.gameid).and_then(|_| check_id("fleet", &request.fleet)) {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+    let (id, cookie) = session::resume(&headers);
+    session::select_game(&id, &request.fleet, &request.gameid);
+    let selected = SessionGame {
+        notification: notify::latest(&request.gameid, &request.fleet),
+        gameid: request.gameid,
+        fleet: request.fleet,
+        selected: true,
+    };
+    (AppendHeaders(cookie.map(|cookie| (header::SET_COOKIE, cookie))), Json(selected)).into_response()
+}
+
+#[derive(Serialize)]
+struct Placement {
+    board: Board,
+}
+
+// Random legal fleet placement to fill the board with
+async fn placement_handler() -> Json<Placement> {
+    Json(Placement { board: random_placement(&mut rand::thread_rng()) })
+}
+
+#[derive(Deserialize)]
+struct AddShip {
+    board: Board,
+    pos: Coord,
+    size: usize,
+    #[serde(default)]
+    horizontal: bool,
+}
+
+#[derive(Deserialize)]
+struct RemoveShip {
+    board: Board,
+    pos: Coord,
+}
+
+#[derive(Deserialize)]
+struct CheckPlacement {
+    board: Board,
+}
+
+fn placement_response(board: Result<Board, String>) -> Response {
+    match board {
+        Ok(board) => Json(PlacementView::from(board)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+// Place one more ship on the board being edited
+async fn add_ship_handler(Json(request): Json<AddShip>) -> impl IntoResponse {
+    placement_response(add_ship(request.board, request.pos, request.size, request.horizontal))
+}
+
+// Take the ship on a square off the board being edited
+async fn remove_ship_handler(Json(request): Json<RemoveShip>) -> impl IntoResponse {
+    placement_response(remove_ship(request.board, request.pos))
+}
+
+// Check a board against the placement rules, with the ships it still lacks
+async fn validate_placement_handler(Json(request): Json<CheckPlacement>) -> impl IntoResponse {
+    placement_response(ships_left(request.board).map(|_| request.board))
+}
+
+async fn randomize_placement_handler() -> impl IntoResponse {
+    placement_response(Ok(random_placement(&mut rand::thread_rng())))
+}
+
+#[derive(Deserialize)]
+struct QuickMatch {
+    fleetid: String,
+    #[serde(default)]
+    rated: bool,
+}
+
+// Game the chain assigned the fleet to, for the page to join
+async fn matchmake_handler(Json(request): Json<QuickMatch>) -> impl IntoResponse {
+    match matchmake(&request.fleetid, request.rated).await {
+        Ok(assignment) => Json(assignment).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+// Chain data for verifying the receipts of a game in the page
+async fn verification_handler(Path(gameid): Path<String>) -> impl IntoResponse {
+    match verification_data(&gameid).await {
+        Ok(data) => Json(data).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+// Files of the fleetwasm package, loaded by the page
+async fn wasm_asset(Path(file): Path<String>) -> impl IntoResponse {
+    let file = match config().wasm_file(&file) {
+        Some((path, content_type)) => tokio::fs::read(path).await.ok().map(|bytes| (content_type, bytes)),
+        None => None,
+    };
+    match file {
+        Some((content_type, bytes)) => ([(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        None => (StatusCode::NOT_FOUND, "File not found").into_response(),
+    }
+}
+
+async fn job_handler(Path(id): Path<String>) -> impl IntoResponse {
+    match job_status(&id) {
+        Some(status) => Json(status).into_response(),
+        None => (StatusCode::NOT_FOUND, "Job not found").into_response(),
+    }
+}
+
+fn job_event(status: &JobStatus) -> Result<Event, Infallible> {
+    Ok(Event::default().json_data(status).unwrap_or_default())
+}
+
+// SSE stream with the current status of a job followed by its updates
+async fn job_events(Path(id): Path<String>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let updates = BroadcastStream::new(subscribe());
+    let current = job_status(&id).map(|status| job_event(&status));
+    let stream = stream::iter(current).chain(updates.filter_map(move |update| {
+        let event = match update {
+            Ok(status) if status.id == id => Some(job_event(&status)),
+            _ => None,
+        };
+        async move { event }
+    }));
+    Sse::new(stream)
+}
+
+// SSE stream of the turn notifications of a player, following the game on the chain
+async fn notification_events(
+    Path((gameid, fleet)): Path<(String, String)>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let updates = BroadcastStream::new(notify::subscribe());
+    notify::watch(&gameid, &fleet);
+    let current = notify::latest(&gameid, &fleet).map(|n| Ok(Event::default().json_data(&n).unwrap_or_default()));
+    let stream = stream::iter(current).chain(updates.filter_map(move |update| {
+        let event = match update {
+            Ok(n) if n.gameid == gameid && n.fleet == fleet => Some(Ok(Event::default().json_data(&n).unwrap_or_default())),
+            _ => None,
+        };
+        async move { event }
+    }));
+    Sse::new(stream)
+}
+
+// SSE stream of the turn notifications of every game and fleet the browser's session played
+async fn session_notifications(headers: HeaderMap) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let data = session::session_id(&headers).and_then(|id| session::load(&id)).unwrap_or_default();
+    let followed: HashSet<(String, String)> = data.games().into_iter().collect();
+    let updates = BroadcastStream::new(notify::subscribe());
+    let mut current = Vec::new();
+    for (gameid, fleet) in &followed {
+        notify::watch(gameid, fleet);
+        current.extend(notify::latest(gameid, fleet).map(|n| Ok(Event::default().json_data(&n).unwrap_or_default())));
+    }
+    let stream = stream::iter(current).chain(updates.filter_map(move |update| {
+        let event = match update {
+            Ok(n) if followed.contains(&(n.gameid.clone(), n.fleet.clone())) => {
+                Some(Ok(Event::default().json_data(&n).unwrap_or_default()))
+            }
+            _ => None,
+        };
+        async move { event }
+    }));
+    Sse::new(stream)
+}
+
+// The page of the host, compiled from src/page.html. Text is HTML-escaped where it is shown and
+// written as JSON where the page's scripts read it.
+#[derive(Template)]
+#[template(path = "page.html")]
+struct Page {
+    gameid: String,
+    fleetid: String,
+    board: String,
+    shots: String,
+    job_id: String,
+    // Whether the page is playing a game, and the error of the last action
+    playing: bool,
+    not_in_game: bool,
+    error: Option<String>,
 }
 
 fn render_html(
     gameid: Option<String>,
     fleetid: Option<String>,
-    random: Option<String>,
     board: Option<String>,
     shots: Option<String>,
     response: Option<String>,
+    job_id: Option<String>,
 ) -> Html<String> {
-    let fleetid = fleetid.unwrap_or("".to_string());
-    let gameid = gameid.unwrap_or("".to_string());
-    let response_html = if let Some(response) = response {
-        if response == "OK" {
-            if gameid != "" {
-                format!("Playing Game: <b>{}</b> with fleet's ID: <b>{}</b> ", gameid, fleetid)
-            } else {
-                "Not in game".to_string()
-            }
-        } else {
-            format!("<p style='color:red'>{}</p>", response)
-        }
+    let gameid = gameid.unwrap_or_default();
+    let ok = response.as_deref() == Some("OK");
+    let page = Page {
+        playing: ok && !gameid.is_empty(),
+        not_in_game: ok && gameid.is_empty(),
+        error: response.filter(|_| !ok),
+        gameid,
+        fleetid: fleetid.unwrap_or_default(),
+        board: board.unwrap_or_default(),
+        shots: shots.unwrap_or_default(),
+        job_id: job_id.unwrap_or_default(),
+    };
+    match page.render() {
+        Ok(html) => Html(html),
+        Err(e) => Html(format!("Could not render the page: {}", e)),
+    }
+}
+
+// Liveness endpoint reporting the health of the prover workers
+async fn healthz() -> impl IntoResponse {
+    let health = worker_health();
+    let status = if health.status == "ok" {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health))
+}
+
+// Prometheus metrics of the proof pipeline
+async fn metrics_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], host::metrics::render())
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    storage: String,
+    chain: String,
+    // Chain node the host talks to
+    chain_node: String,
+}
+
+// Readiness endpoint: the host can only serve players if it can keep its records and reach the chain
+async fn readyz() -> impl IntoResponse {
+    let config = config();
+    let storage = config.check_storage();
+
+    let chain_node = endpoints::current();
+    let client = reqwest::Client::new();
+    let chain = client
+        .get(format!("{}/healthz", chain_node))
+        .timeout(std::time::Duration::from_secs(2))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    let status = if storage.is_ok() && chain.is_ok() {
+        StatusCode::OK
     } else {
-        "".to_string()
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let readiness = Readiness {
+        storage: storage.map_or_else(|e| e.to_string(), |_| "ok".to_string()),
+        chain: chain.map_or_else(|e| e.to_string(), |_| "ok".to_string()),
+        chain_node,
     };
-    let random = random.unwrap_or("".to_string());
+    (status, Json(readiness))
+}
 
-    let board = board.unwrap_or("".to_string());
-    let shots = shots.unwrap_or("".to_string());
+#[derive(Parser)]
+#[command(about = "Fleet host: proves game actions and submits them to the chain")]
+struct Args {
+    #[arg(long, help = "Port to listen on [default: 3000]")]
+    port: Option<u16>,
+    #[arg(long, help = "Address to listen on [default: 0.0.0.0]")]
+    bind: Option<IpAddr>,
+    #[arg(long, help = "URL of the blockchain server [default: http://chain0:3001]")]
+    chain_url: Option<String>,
+    #[arg(long, help = "Generate fake receipts instead of proving (RISC0_DEV_MODE)")]
+    dev_mode: bool,
+    #[arg(long, help = "Directory for the tracker journal and receipt cache")]
+    storage_path: Option<PathBuf>,
+}
 
-    let path = "host/src/page.html";
-    let html = std::fs::read_to_string(path).unwrap();
-    let html = html.replace("{response_html}", &response_html);
-    let html = html.replace("{gameid}", &gameid);
-    let html = html.replace("{fleetid}", &fleetid);
-    let html = html.replace("{random}", &random);
-    let html = html.replace("{board}", &board);
-    let html = html.replace("{shots}", &shots);
+fn main() {
+    // The host binary doubles as the prover worker spawned by the watchdog
+    if std::env::args().nth(1).as_deref() == Some(host::WORKER_ARG) {
+        std::process::exit(host::run_worker());
+    }
 
-    Html(html)
+    // Command-line flags take precedence over fleet.toml and the environment
+    let args = Args::parse();
+    fleetconfig::init(|config| {
+        if let Some(port) = args.port {
+            config.host.port = port;
+        }
+        if let Some(bind) = args.bind {
+            config.host.bind = bind;
+        }
+        if let Some(chain_url) = args.chain_url {
+            config.host.chain_url = chain_url;
+        }
+        if args.dev_mode {
+            config.dev_mode = true;
+        }
+        if let Some(storage_path) = args.storage_path {
+            config.storage_path = Some(storage_path);
+        }
+    });
+    serve();
 }
 
 #[tokio::main]
-async fn main() {
+async fn serve() {
+    let config = config();
+    host::init_tracing();
+
     let app = Router::new()
         .route("/", get(index))
-        .route("/submit", post(submit));
+        .route("/submit", post(submit))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics_handler))
+        .route("/jobs/:id", get(job_handler))
+        .route("/jobs/:id/events", get(job_events))
+        .route("/notifications", get(session_notifications))
+        .route("/notifications/:gameid/:fleet", get(notification_events))
+        .route("/ws", get(ws_handler))
+        .route("/api/state", get(state_handler))
+        .route("/api/fleets", get(fleets_handler))
+        .route("/api/fleets/select", post(select_fleet_handler))
+        .route("/api/games", get(games_handler))
+        .route("/api/games/select", post(select_game_handler))
+        .route("/api/placement", get(placement_handler))
+        .route("/api/placement/add", post(add_ship_handler))
+        .route("/api/placement/remove", post(remove_ship_handler))
+        .route("/api/placement/validate", post(validate_placement_handler))
+        .route("/api/placement/randomize", post(randomize_placement_handler))
+        .route("/api/matchmake", post(matchmake_handler))
+        .route("/api/games/:gameid/verification", get(verification_handler))
+        .route("/wasm/:file", get(wasm_asset))
+        .route("/tracker/:gameid/:fleet", get(tracker_handler));
+
+    // The chain may still be starting, so the check does not hold up the host
+    tokio::spawn(host::compat::check_chain());
+    tokio::spawn(endpoints::check_health());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    println!("Listening on {}", addr);
+    let addr = config.host.addr();
+    info!("Listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
     axum::serve(listener, app)
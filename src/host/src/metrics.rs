@@ -0,0 +1,102 @@
+// src/metrics.rs
+//
+// Metrics of the proof pipeline, served on the host's `/metrics` in the Prometheus text format.
+// Every receipt the host generates is counted under its command and the prover that generated it,
+// with the cycles the guest ran, the segments the execution was split into, the time the proof
+// took (in a worker or on fleet-proverd, queueing included) and the size of the receipt sent to the
+// chain. The last proof of each command is kept too, so a slow Fire can be told apart from a slow
+// backend. Receipts reused from the cache are only counted as cache hits.
+
+use fleetcore::Command;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::prover::Proof;
+
+#[derive(Clone, Copy, Default)]
+struct ProofStats {
+    proofs: u64,
+    cycles: u64,
+    segments: u64,
+    seconds: f64,
+    receipt_bytes: u64,
+    // Statistics of the last proof
+    last_cycles: u64,
+    last_segments: u64,
+    last_seconds: f64,
+    last_receipt_bytes: u64,
+}
+
+struct Metrics {
+    // By command and backend
+    proofs: BTreeMap<(String, String), ProofStats>,
+    // By command
+    cache_hits: BTreeMap<String, u64>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics { proofs: BTreeMap::new(), cache_hits: BTreeMap::new() });
+
+fn metrics() -> std::sync::MutexGuard<'static, Metrics> {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Count a receipt generated for a command, with the time it took
+pub fn record_proof(cmd: Command, proof: &Proof, elapsed: Duration) {
+    // Receipts are sent to the chain as JSON
+    let receipt_bytes = serde_json::to_vec(&proof.receipt).map_or(0, |bytes| bytes.len() as u64);
+    let backend = if proof.backend.is_empty() { "unknown".to_string() } else { proof.backend.clone() };
+    let seconds = elapsed.as_secs_f64();
+
+    let mut metrics = metrics();
+    let stats = metrics.proofs.entry((format!("{:?}", cmd), backend)).or_default();
+    stats.proofs += 1;
+    stats.cycles += proof.cycles;
+    stats.segments += proof.segments;
+    stats.seconds += seconds;
+    stats.receipt_bytes += receipt_bytes;
+    stats.last_cycles = proof.cycles;
+    stats.last_segments = proof.segments;
+    stats.last_seconds = seconds;
+    stats.last_receipt_bytes = receipt_bytes;
+}
+
+// Count a receipt reused from the cache instead of being proven again
+pub fn record_cache_hit(cmd: Command) {
+    *metrics().cache_hits.entry(format!("{:?}", cmd)).or_default() += 1;
+}
+
+// A Prometheus series: name, type, help text and the statistic it reports
+type Series = (&'static str, &'static str, &'static str, fn(&ProofStats) -> f64);
+
+// Metrics in the Prometheus text format
+pub fn render() -> String {
+    let metrics = metrics();
+    let mut out = String::new();
+    let series: [Series; 9] = [
+        ("fleet_proofs_total", "counter", "Receipts generated", |s| s.proofs as f64),
+        ("fleet_proof_cycles_total", "counter", "Cycles run by the guests", |s| s.cycles as f64),
+        ("fleet_proof_segments_total", "counter", "Segments proven", |s| s.segments as f64),
+        ("fleet_proof_seconds_total", "counter", "Wall time spent proving", |s| s.seconds),
+        ("fleet_proof_receipt_bytes_total", "counter", "Size of the receipts generated", |s| s.receipt_bytes as f64),
+        ("fleet_proof_last_cycles", "gauge", "Cycles run by the guest for the last receipt", |s| s.last_cycles as f64),
+        ("fleet_proof_last_segments", "gauge", "Segments proven for the last receipt", |s| s.last_segments as f64),
+        ("fleet_proof_last_seconds", "gauge", "Wall time spent proving the last receipt", |s| s.last_seconds),
+        ("fleet_proof_last_receipt_bytes", "gauge", "Size of the last receipt", |s| s.last_receipt_bytes as f64),
+    ];
+    for (name, kind, help, value) in series {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for ((command, backend), stats) in &metrics.proofs {
+            let _ = writeln!(out, "{}{{command=\"{}\",backend=\"{}\"}} {}", name, command, backend, value(stats));
+        }
+    }
+
+    let _ = writeln!(out, "# HELP fleet_receipt_cache_hits_total Receipts reused from the cache");
+    let _ = writeln!(out, "# TYPE fleet_receipt_cache_hits_total counter");
+    for (command, hits) in &metrics.cache_hits {
+        let _ = writeln!(out, "fleet_receipt_cache_hits_total{{command=\"{}\"}} {}", command, hits);
+    }
+    out
+}
@@ -0,0 +1,279 @@
+// src/notify.rs
+//
+// Turn notifications. For every game a player follows, the host subscribes to the chain's event
+// stream and, whenever an event mentions the game, reads the player's game state. The browser
+// is told on `/notifications/{gameid}/{fleet}` (SSE) when it becomes the player's turn, when the
+// player was fired at and has to report, and when the game is over.
+
+use fleetcore::{GameState, StateRoot};
+
+use crate::endpoints;
+use crate::responses;
+use crate::shots;
+use crate::tracker;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+// Wait before reconnecting to the chain's event stream
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum NotificationKind {
+    // The player has to fire or wave
+    YourTurn,
+    // The player was fired at and has to report
+    FiredAt,
+    // The game is no longer running on the chain
+    GameOver,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub gameid: String,
+    pub fleet: String,
+    pub kind: NotificationKind,
+    pub message: String,
+}
+
+// Games (and fleets) the host is following
+static WATCHED: LazyLock<Mutex<HashSet<(String, String)>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+static EVENTS: LazyLock<broadcast::Sender<Notification>> = LazyLock::new(|| broadcast::channel(100).0);
+// Notification still current for each followed game, replayed to pages that (re)connect
+static LATEST: LazyLock<Mutex<HashMap<(String, String), Notification>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Receiver for the notifications of all followed games
+pub fn subscribe() -> broadcast::Receiver<Notification> {
+    EVENTS.subscribe()
+}
+
+// Notification still current for a player, if any
+pub fn latest(gameid: &str, fleet: &str) -> Option<Notification> {
+    let key = (gameid.to_string(), fleet.to_string());
+    LATEST.lock().unwrap_or_else(|e| e.into_inner()).get(&key).cloned()
+}
+
+// Start following a game for a player, unless the host already does
+pub fn watch(gameid: &str, fleet: &str) {
+    let key = (gameid.to_string(), fleet.to_string());
+    if !WATCHED.lock().unwrap_or_else(|e| e.into_inner()).insert(key.clone()) {
+        return;
+    }
+    tokio::spawn(async move {
+        follow(&key.0, &key.1).await;
+        WATCHED.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+    });
+}
+
+fn notify(gameid: &str, fleet: &str, kind: NotificationKind) {
+    let message = match kind {
+        NotificationKind::YourTurn => format!("Your turn in game {}", gameid),
+        NotificationKind::FiredAt => format!("You were fired at in game {}, report the shot", gameid),
+        NotificationKind::GameOver => format!("Game {} is over", gameid),
+    };
+    let notification = Notification { gameid: gameid.to_string(), fleet: fleet.to_string(), kind, message };
+    LATEST
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert((gameid.to_string(), fleet.to_string()), notification.clone());
+    // Nobody listening is not an error
+    let _ = EVENTS.send(notification);
+}
+
+// What the game waits for from the player, if anything
+fn expected(state: &GameState, fleet: &str) -> Option<NotificationKind> {
+    if state.next_report.as_deref() == Some(fleet) {
+        Some(NotificationKind::FiredAt)
+    } else if state.next_report.is_none() && state.next_player.as_deref() == Some(fleet) {
+        Some(NotificationKind::YourTurn)
+    } else {
+        None
+    }
+}
+
+struct Follower {
+    client: reqwest::Client,
+    gameid: String,
+    fleet: String,
+    // Last notification sent, so each turn is only announced once
+    notified: Option<NotificationKind>,
+    // Number of the last chain event seen, to resume the stream after a reconnection, and the
+    // node that numbered it
+    last_event_id: Option<String>,
+    node: String,
+}
+
+impl Follower {
+    // Read the player's game state and notify the player if the game now waits for it.
+    // Returns false once the game is no longer running.
+    async fn check(&mut self) -> Result<bool, String> {
+        let request = self
+            .client
+            .get(format!("{}/gamestate/{}/{}", endpoints::current(), self.gameid, self.fleet));
+        let response = responses::send(request).await?;
+        if response.status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        if !response.status.is_success() {
+            return Err(format!("the chain answered {}", response.status));
+        }
+        let state: GameState = serde_json::from_str(&response.text).map_err(|e| e.to_string())?;
+
+        let expected = expected(&state, &self.fleet);
+        if expected != self.notified {
+            match expected {
+                Some(kind) => notify(&self.gameid, &self.fleet, kind),
+                None => {
+                    let key = (self.gameid.clone(), self.fleet.clone());
+                    LATEST.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+                }
+            }
+            self.notified = expected;
+        }
+        Ok(true)
+    }
+
+    // Read the game's state root, checking that it is the digest of the state it comes with.
+    // Returns None once the game is no longer running.
+    async fn state_root(&self) -> Result<Option<StateRoot>, String> {
+        let request = self
+            .client
+            .get(format!("{}/games/{}/state-root", endpoints::current(), self.gameid));
+        let response = responses::send(request).await?;
+        if response.status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status.is_success() {
+            return Err(format!("the chain answered {}", response.status));
+        }
+        let root: StateRoot = serde_json::from_str(&response.text).map_err(|e| e.to_string())?;
+        if !root.verify() {
+            return Err("the state root is not the digest of the state".to_string());
+        }
+        Ok(Some(root))
+    }
+
+    // Warn when the state the chain serves after a reconnection does not follow the state it
+    // served before, as when the host was switched to a replica that diverged or fell behind
+    async fn check_root(&self, before: &StateRoot) {
+        match self.state_root().await {
+            Ok(Some(after)) if after.seq < before.seq => warn!(
+                "The chain went back from transaction {} to {} in game {} while reconnecting",
+                before.seq, after.seq, self.gameid
+            ),
+            Ok(Some(after)) if after.seq == before.seq && after.root != before.root => warn!(
+                "The state of game {} changed without a transaction while reconnecting, the chain diverged",
+                self.gameid
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("Could not read the state root of game {}: {}", self.gameid, e),
+        }
+    }
+
+    // Read the chain's event stream until it ends, checking the game state after every event
+    // that mentions the game, once its signature is checked. Returns false once the game is no
+    // longer running.
+    async fn stream(&mut self) -> Result<bool, String> {
+        // Events are numbered by each node, so a stream resumed on another node starts over
+        let node = endpoints::current();
+        if node != self.node {
+            self.last_event_id = None;
+            self.node = node.clone();
+        }
+        let mut request = self.client.get(format!("{}/logs", node));
+        if let Some(id) = &self.last_event_id {
+            request = request.header("last-event-id", id.as_str());
+        }
+        let mut response = match request.send().await {
+            Ok(response) => response.error_for_status().map_err(|e| e.to_string())?,
+            Err(e) => {
+                endpoints::failed(&node);
+                return Err(e.to_string());
+            }
+        };
+
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            buffer.extend_from_slice(&chunk);
+            // Events are separated by a blank line
+            while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                let block = String::from_utf8_lossy(&block);
+                let (mut id, mut signature, mut data) = (None, None, Vec::new());
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = Some(value.trim().to_string());
+                    } else if let Some(value) = line.strip_prefix(": sig ") {
+                        signature = Some(value.to_string());
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data.push(value.strip_prefix(' ').unwrap_or(value));
+                    }
+                }
+                // Only numbered events are signed (not the `gap` notice)
+                let seq = match id.as_deref().and_then(|id| id.parse().ok()) {
+                    Some(seq) => seq,
+                    None => continue,
+                };
+                let data = data.join("\n");
+                let signature = signature.unwrap_or_default();
+                if let Err(e) = responses::verify_event(&node, seq, &data, &signature).await {
+                    warn!("Ignoring an event of the chain for game {}: {}", self.gameid, e);
+                    continue;
+                }
+                self.last_event_id = id;
+
+                let relevant = data.contains(self.gameid.as_str());
+                // Keep the player's shot board in step with the game
+                if let Some(event) = shots::parse_event(&self.gameid, &self.fleet, data.trim()) {
+                    tracker::record_shot(&self.gameid, &self.fleet, event);
+                }
+                if relevant && !self.check().await? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+async fn follow(gameid: &str, fleet: &str) {
+    let mut follower = Follower {
+        client: reqwest::Client::new(),
+        gameid: gameid.to_string(),
+        fleet: fleet.to_string(),
+        notified: None,
+        last_event_id: None,
+        node: endpoints::current(),
+    };
+
+    // Only follow games the player is in
+    match follower.check().await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => warn!("Could not read game {} from the chain: {}", gameid, e),
+    }
+
+    loop {
+        match follower.stream().await {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(e) => warn!("Chain event stream for game {} dropped: {}", gameid, e),
+        }
+        // State of the game before reconnecting, if the chain still answers
+        let before = follower.state_root().await.ok().flatten();
+        tokio::time::sleep(RECONNECT_DELAY).await;
+        // Events may have been missed while reconnecting
+        match follower.check().await {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(e) => warn!("Could not read game {} from the chain: {}", gameid, e),
+        }
+        if let Some(before) = &before {
+            follower.check_root(before).await;
+        }
+    }
+    notify(gameid, fleet, NotificationKind::GameOver);
+}
@@ -0,0 +1,195 @@
+// src/placement.rs
+//
+// Fleet placements built by the host, so players do not have to type 18 squares that satisfy the
+// placement rules by hand. Random placements put the ships one after the other on free squares;
+// the page can also add and remove ships one at a time, each checked against the ships already
+// placed. Either way the result is checked with the same rules the join guest enforces.
+
+use fleetcore::{fits, ships, validate_fleet_placement, Board, Coord, SHIP_SIZES};
+use rand::Rng;
+use serde::Serialize;
+
+// Positions tried for a ship before starting the placement over
+const MAX_ATTEMPTS: usize = 1000;
+
+// Draw a legal placement
+pub fn random_placement<R: Rng>(rng: &mut R) -> Board {
+    loop {
+        if let Some(board) = try_placement(rng) {
+            if validate_fleet_placement(board).is_ok() {
+                return board;
+            }
+        }
+    }
+}
+
+fn try_placement<R: Rng>(rng: &mut R) -> Option<Board> {
+    // Squares taken by a ship or next to one, since ships cannot touch even diagonally
+    let mut blocked = [false; 100];
+    let mut board = Vec::new();
+
+    for &size in SHIP_SIZES {
+        let ship = (0..MAX_ATTEMPTS).find_map(|_| {
+            let horizontal = rng.gen_bool(0.5);
+            let (rows, cols) = if horizontal { (10, 11 - size) } else { (11 - size, 10) };
+            let (row, col) = (rng.gen_range(0..rows), rng.gen_range(0..cols));
+            let squares: Vec<u8> = (0..size)
+                .map(|i| if horizontal { row * 10 + col + i } else { (row + i) * 10 + col } as u8)
+                .collect();
+            squares.iter().all(|&sq| !blocked[sq as usize]).then_some(squares)
+        })?;
+
+        for &sq in &ship {
+            let (row, col) = ((sq / 10) as i32, (sq % 10) as i32);
+            for r in (row - 1).max(0)..=(row + 1).min(9) {
+                for c in (col - 1).max(0)..=(col + 1).min(9) {
+                    blocked[(r * 10 + c) as usize] = true;
+                }
+            }
+        }
+        board.extend(ship);
+    }
+
+    Board::try_from(board).ok()
+}
+
+// Ship of `size` squares starting at `pos` and going right, or down, on free squares touching no
+// ship of `board`
+pub fn add_ship(board: Board, pos: Coord, size: usize, horizontal: bool) -> Result<Board, String> {
+    if !ships_left(board)?.contains(&size) {
+        return Err(format!("No ship of size {} left to place", size));
+    }
+    let squares = (0..size as u8)
+        .map(|i| if horizontal { Coord::new(pos.x() + i, pos.y()) } else { Coord::new(pos.x(), pos.y() + i) })
+        .collect::<Option<Vec<Coord>>>()
+        .ok_or_else(|| format!("A ship of size {} at {} does not fit on the board", size, pos))?;
+    let ship = Board::new(squares);
+    if !fits(board, ship) {
+        return Err("Ships cannot overlap or touch each other, even diagonally".to_string());
+    }
+    Ok(Board::new(board.coords().chain(ship.coords())))
+}
+
+// The board without the ship on `pos`
+pub fn remove_ship(board: Board, pos: Coord) -> Result<Board, String> {
+    if !board.contains(pos) {
+        return Err(format!("There is no ship at {}", pos));
+    }
+    Ok(Board::new(ships(board).into_iter().filter(|ship| !ship.contains(pos)).flat_map(Board::coords)))
+}
+
+// Sizes of the ships still to place, largest first
+pub fn ships_left(board: Board) -> Result<Vec<usize>, String> {
+    let mut left = SHIP_SIZES.to_vec();
+    for ship in ships(board) {
+        match left.iter().position(|&size| size == ship.len()) {
+            Some(index) => {
+                left.remove(index);
+            }
+            None => return Err(format!("The board holds one ship of size {} too many", ship.len())),
+        }
+    }
+    Ok(left)
+}
+
+// A placement as the page edits it, with the text the join form sends for it
+#[derive(Serialize)]
+pub struct PlacementView {
+    pub board: Board,
+    pub canonical: String,
+    pub ships_left: Vec<usize>,
+    // Set once the placement is legal, or with the rule it breaks
+    pub complete: bool,
+    pub error: Option<String>,
+}
+
+impl From<Board> for PlacementView {
+    fn from(board: Board) -> Self {
+        let error = validate_fleet_placement(board).err();
+        PlacementView {
+            board,
+            canonical: board.to_string(),
+            ships_left: ships_left(board).unwrap_or_default(),
+            complete: error.is_none(),
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn coord(cell: &str) -> Coord {
+        cell.parse().unwrap()
+    }
+
+    #[test]
+    fn random_placements_are_legal() {
+        for seed in 0..500 {
+            let board = random_placement(&mut StdRng::seed_from_u64(seed));
+            assert_eq!(validate_fleet_placement(board), Ok(()), "seed {}", seed);
+            assert_eq!(ships_left(board), Ok(vec![]), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn tried_placements_are_legal_when_they_succeed() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..500 {
+            if let Some(board) = try_placement(&mut rng) {
+                assert_eq!(validate_fleet_placement(board), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn a_ship_off_the_board_is_refused() {
+        let error = add_ship(Board::default(), coord("G0"), 5, true).unwrap_err();
+        assert_eq!(error, "A ship of size 5 at G0 does not fit on the board");
+        assert!(add_ship(Board::default(), coord("A7"), 4, false).is_err());
+        assert!(add_ship(Board::default(), coord("F0"), 5, true).is_ok());
+        assert!(add_ship(Board::default(), coord("A6"), 4, false).is_ok());
+    }
+
+    #[test]
+    fn a_ship_touching_another_is_refused() {
+        let board = add_ship(Board::default(), coord("C3"), 3, true).unwrap();
+        let touching = ["C3", "F3", "C4", "B2", "F4"];
+        for cell in touching {
+            assert_eq!(
+                add_ship(board, coord(cell), 1, true).unwrap_err(),
+                "Ships cannot overlap or touch each other, even diagonally",
+                "{}",
+                cell
+            );
+        }
+        assert!(add_ship(board, coord("G3"), 1, true).is_ok());
+        assert!(add_ship(board, coord("C5"), 1, true).is_ok());
+    }
+
+    #[test]
+    fn ships_left_count_down_from_the_fleet() {
+        let mut board = Board::default();
+        assert_eq!(ships_left(board).unwrap(), SHIP_SIZES);
+        let placed = [("A0", 5), ("G0", 4), ("A2", 3), ("E2", 2), ("H2", 2), ("A6", 1), ("C6", 1)];
+        for (placed_so_far, (cell, size)) in placed.into_iter().enumerate() {
+            board = add_ship(board, coord(cell), size, true).unwrap();
+            assert_eq!(ships_left(board).unwrap(), SHIP_SIZES[placed_so_far + 1..]);
+        }
+        assert_eq!(validate_fleet_placement(board), Ok(()));
+        assert_eq!(add_ship(board, coord("J9"), 1, true).unwrap_err(), "No ship of size 1 left to place");
+    }
+
+    #[test]
+    fn removing_a_ship_puts_it_back_in_the_fleet() {
+        let board = add_ship(Board::default(), coord("A0"), 5, true).unwrap();
+        let board = add_ship(board, coord("A2"), 3, false).unwrap();
+        let board = remove_ship(board, coord("C0")).unwrap();
+        assert_eq!(board.to_string(), "20,30,40");
+        assert_eq!(ships_left(board).unwrap(), [5, 4, 2, 2, 1, 1]);
+        assert_eq!(remove_ship(board, coord("C0")).unwrap_err(), "There is no ship at C0");
+    }
+}
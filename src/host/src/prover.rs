@@ -0,0 +1,339 @@
+// src/prover.rs
+//
+// Proving runs in a child process (the host binary started with the `prove-worker` argument) so
+// that a prover crash or memory spike cannot take down the web server. A watchdog kills workers
+// that hang, retries jobs whose worker died, and keeps health counters for `/healthz`.
+// When a `fleet-proverd` daemon is running, jobs are sent to it instead.
+
+use fleetconfig::{config, ProvingMode};
+use fleetcore::{AuditInputs, BaseInputs, BlindReportInputs, BlindWinInputs, Command, FireInputs, RegisterInputs, WinInputs};
+use methods::{
+    AUDIT_ELF, AUDIT_ID, BLIND_REPORT_ELF, BLIND_REPORT_ID, BLIND_WIN_ELF, BLIND_WIN_ID, CONTEST_ELF, CONTEST_ID, FIRE_ELF,
+    FIRE_ID, FORFEIT_ELF, FORFEIT_ID, JOIN_ELF, JOIN_ID, REGISTER_ELF, REGISTER_ID, REPORT_ELF, REPORT_ID, STATUS_ELF, STATUS_ID, WAVE_ELF, WAVE_ID,
+    WIN_ELF, WIN_ID,
+};
+use risc0_zkvm::Receipt;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+use crate::cache;
+use crate::jobs::{progress, JobState};
+use crate::metrics;
+use crate::proverd::{prove_via_daemon, socket_path};
+use crate::generate_receipt;
+
+// Argument that makes the host binary run as a prover worker
+pub const WORKER_ARG: &str = "prove-worker";
+
+// Number of times a job is started before giving up on a hung or killed worker
+const MAX_ATTEMPTS: u32 = 3;
+// Default time a worker is given to produce a receipt before it is considered hung
+const DEFAULT_TIMEOUT_SECS: u64 = 900;
+// Consecutive worker failures after which /healthz reports the prover as degraded
+const DEGRADED_AFTER_FAILURES: u32 = 3;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub enum ProofInputs {
+    Base(BaseInputs),
+    Fire(FireInputs),
+    // A shot or a report, proven with our join receipt or the shot answered as an assumption
+    ComposedFire(FireInputs, Box<Receipt>),
+    BlindReport(BlindReportInputs),
+    BlindWin(BlindWinInputs),
+    Win(WinInputs),
+    Register(RegisterInputs),
+    // Every report receipt of a game, verified by the audit guest as assumptions
+    Audit(AuditInputs, Vec<Receipt>),
+}
+
+// Receipt generated for a job, with the statistics reported by the prover
+#[derive(Deserialize, Serialize)]
+pub struct Proof {
+    pub receipt: Receipt,
+    pub cycles: u64,
+    // Number of segments the execution was split into; proofs cached by older hosts have none
+    #[serde(default)]
+    pub segments: u64,
+    // Prover that generated the receipt ("local", "bonsai", "ipc" or "devmode")
+    #[serde(default)]
+    pub backend: String,
+}
+
+// Job sent to a prover worker on its stdin
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ProofJob {
+    pub cmd: Command,
+    pub inputs: ProofInputs,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct WorkerHealth {
+    pub status: &'static str,
+    pub active_workers: u32,
+    pub jobs_completed: u64,
+    pub jobs_failed: u64,
+    pub hung_workers: u64,
+    pub killed_workers: u64,
+    pub retries: u64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+static HEALTH: Mutex<WorkerHealth> = Mutex::new(WorkerHealth {
+    status: "ok",
+    active_workers: 0,
+    jobs_completed: 0,
+    jobs_failed: 0,
+    hung_workers: 0,
+    killed_workers: 0,
+    retries: 0,
+    consecutive_failures: 0,
+    last_error: None,
+});
+
+fn update_health(f: impl FnOnce(&mut WorkerHealth)) {
+    let mut health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut health);
+    health.status = if health.consecutive_failures >= DEGRADED_AFTER_FAILURES {
+        "degraded"
+    } else {
+        "ok"
+    };
+}
+
+// Snapshot of the prover workers' health
+pub fn worker_health() -> WorkerHealth {
+    HEALTH.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// How a single worker attempt ended
+enum AttemptError {
+    // The worker did not finish within the timeout and was killed
+    Hung,
+    // The worker was killed by a signal (e.g. by the OOM killer)
+    Killed(i32),
+    // The worker could not be started or talked to
+    Io(std::io::Error),
+    // The worker ran but the proof failed (guest panic, invalid inputs); retrying will not help
+    Failed(String),
+}
+
+fn prover_timeout() -> Duration {
+    let secs = std::env::var("PROVER_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+async fn run_attempt(job: &[u8]) -> Result<Proof, AttemptError> {
+    let exe = std::env::current_exe().map_err(AttemptError::Io)?;
+    let mut child = tokio::process::Command::new(exe)
+        .arg(WORKER_ARG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Dropping the child (e.g. when the watchdog times out) kills the worker
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(AttemptError::Io)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(job).await.map_err(AttemptError::Io)?;
+    }
+
+    let output = match tokio::time::timeout(prover_timeout(), child.wait_with_output()).await {
+        Ok(output) => output.map_err(AttemptError::Io)?,
+        Err(_) => return Err(AttemptError::Hung),
+    };
+
+    if let Some(signal) = exit_signal(&output.status) {
+        return Err(AttemptError::Killed(signal));
+    }
+    if !output.status.success() {
+        return Err(AttemptError::Failed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| AttemptError::Failed(format!("Invalid receipt from prover worker: {}", e)))
+}
+
+// Generate a receipt in a prover worker, restarting the worker if it hangs or gets killed
+pub async fn prove_in_worker(job: ProofJob) -> Result<Proof, Box<dyn Error + Send + Sync>> {
+    let job = serde_json::to_vec(&job)?;
+
+    update_health(|h| h.active_workers += 1);
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        if attempt > 1 {
+            update_health(|h| h.retries += 1);
+        }
+        match run_attempt(&job).await {
+            Ok(proof) => {
+                update_health(|h| {
+                    h.active_workers -= 1;
+                    h.jobs_completed += 1;
+                    h.consecutive_failures = 0;
+                });
+                return Ok(proof);
+            }
+            Err(AttemptError::Failed(err)) => {
+                update_health(|h| {
+                    h.active_workers -= 1;
+                    h.jobs_failed += 1;
+                });
+                return Err(err.into());
+            }
+            Err(AttemptError::Hung) => {
+                last_error = "prover worker hung and was killed".to_string();
+                update_health(|h| h.hung_workers += 1);
+            }
+            Err(AttemptError::Killed(signal)) => {
+                last_error = format!("prover worker was killed by signal {}", signal);
+                update_health(|h| h.killed_workers += 1);
+            }
+            Err(AttemptError::Io(err)) => {
+                last_error = format!("could not run prover worker: {}", err);
+            }
+        }
+        let err = last_error.clone();
+        update_health(|h| {
+            h.consecutive_failures += 1;
+            h.last_error = Some(err);
+        });
+    }
+
+    update_health(|h| {
+        h.active_workers -= 1;
+        h.jobs_failed += 1;
+    });
+    Err(format!("{} (gave up after {} attempts)", last_error, MAX_ATTEMPTS).into())
+}
+
+pub(crate) fn image_id(cmd: Command) -> [u32; 8] {
+    match cmd {
+        Command::Join => JOIN_ID,
+        Command::Fire => FIRE_ID,
+        Command::Report => REPORT_ID,
+        Command::Wave => WAVE_ID,
+        Command::Win => WIN_ID,
+        Command::BlindReport => BLIND_REPORT_ID,
+        Command::BlindWin => BLIND_WIN_ID,
+        Command::Forfeit => FORFEIT_ID,
+        Command::Register => REGISTER_ID,
+        Command::Status => STATUS_ID,
+        Command::Audit => AUDIT_ID,
+        Command::Contest => CONTEST_ID,
+    }
+}
+
+// Generate a receipt, reusing a cached one when the same inputs were already proven
+pub async fn prove(job: ProofJob) -> Result<Proof, Box<dyn Error + Send + Sync>> {
+    let image_id = image_id(job.cmd);
+    if let Some(proof) = cache::load(&job, image_id) {
+        metrics::record_cache_hit(job.cmd);
+        return Ok(proof);
+    }
+
+    let started = Instant::now();
+    let proof = prove_uncached(&job).await?;
+    metrics::record_proof(job.cmd, &proof, started.elapsed());
+    if let Some(id) = crate::jobs::correlation_id() {
+        debug!("Action {}: proved {:?} in {:?}", id, job.cmd, started.elapsed());
+    }
+    cache::store(&job, image_id, &proof);
+    Ok(proof)
+}
+
+// Generate a receipt on the fleet-proverd daemon or in a local worker, depending on the proving
+// mode. In auto mode the local worker is used when the daemon is not running.
+async fn prove_uncached(job: &ProofJob) -> Result<Proof, Box<dyn Error + Send + Sync>> {
+    let mode = config().host.proving_mode;
+    if mode != ProvingMode::Local {
+        match prove_via_daemon(&socket_path(), job).await {
+            Ok(result) => return result.map_err(Into::into),
+            Err(e) if mode == ProvingMode::Daemon => return Err(format!("fleet-proverd unavailable: {}", e).into()),
+            Err(e) => warn!("fleet-proverd unavailable ({}), proving in a local worker", e),
+        }
+    }
+    progress(JobState::Proving);
+    prove_in_worker(job.clone()).await
+}
+
+fn prove_job(job: ProofJob) -> Result<Proof, Box<dyn Error + Send + Sync>> {
+    let elf = match job.cmd {
+        Command::Join => JOIN_ELF,
+        Command::Fire => FIRE_ELF,
+        Command::Report => REPORT_ELF,
+        Command::Wave => WAVE_ELF,
+        Command::Win => WIN_ELF,
+        Command::BlindReport => BLIND_REPORT_ELF,
+        Command::BlindWin => BLIND_WIN_ELF,
+        Command::Forfeit => FORFEIT_ELF,
+        Command::Register => REGISTER_ELF,
+        Command::Status => STATUS_ELF,
+        Command::Audit => AUDIT_ELF,
+        Command::Contest => CONTEST_ELF,
+    };
+    match job.inputs {
+        ProofInputs::Base(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::Fire(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::ComposedFire(inputs, join) => generate_receipt(&inputs, Some(*join), elf),
+        ProofInputs::BlindReport(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::BlindWin(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::Win(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::Register(inputs) => generate_receipt(&inputs, None, elf),
+        ProofInputs::Audit(inputs, reports) => generate_receipt(&inputs, reports, elf),
+    }
+}
+
+// Entry point of a prover worker: reads a job from stdin and writes the proof to stdout.
+// Returns the process exit code. Errors go to stderr, which the host reads back as the failure.
+pub fn run_worker() -> i32 {
+    let mut input = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut input) {
+        eprintln!("Could not read job: {}", e);
+        return 1;
+    }
+    let job: ProofJob = match serde_json::from_slice(&input) {
+        Ok(job) => job,
+        Err(e) => {
+            eprintln!("Invalid job: {}", e);
+            return 1;
+        }
+    };
+    let proof = match prove_job(job) {
+        Ok(proof) => proof,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    match serde_json::to_writer(std::io::stdout().lock(), &proof) {
+        Ok(()) => {
+            let _ = std::io::stdout().flush();
+            0
+        }
+        Err(e) => {
+            eprintln!("Could not write receipt: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,366 @@
+// src/proverd.rs
+//
+// Protocol and server for `fleet-proverd`, a daemon that generates receipts on behalf of one or
+// more host processes. Hosts connect over a local Unix socket or, when PROVERD_LISTEN is set,
+// over TCP, send one newline-terminated JSON request per connection and read newline-terminated
+// JSON responses until the job is done.
+//
+// Jobs arriving over TCP must be signed by a client key listed in PROVERD_CLIENTS. Waiting jobs
+// are queued per client and served round-robin, so one busy host cannot starve the others.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::jobs::{progress, JobState};
+use crate::prover::{prove_in_worker, Proof, ProofJob};
+
+// Socket used when PROVERD_SOCKET is not set
+pub const DEFAULT_SOCKET: &str = "/tmp/fleet-proverd.sock";
+// Number of proofs the daemon generates at the same time when PROVERD_MAX_JOBS is not set
+const DEFAULT_MAX_JOBS: usize = 1;
+// Maximum age of a signed submission, in seconds
+const MAX_SUBMISSION_AGE: u64 = 300;
+// Client name used for jobs submitted over the local socket
+const LOCAL_CLIENT: &str = "local";
+
+#[derive(Deserialize, Serialize)]
+pub enum DaemonRequest {
+    // Unauthenticated job, only accepted on the local Unix socket
+    Submit(Box<ProofJob>),
+    // Job signed by a registered client key, required on the TCP listener
+    SubmitSigned(SignedSubmission),
+    // Ask for the daemon's capacity and queue lengths
+    Status,
+}
+
+// A SubmissionPayload serialized to JSON and signed by the client's key
+#[derive(Deserialize, Serialize)]
+pub struct SignedSubmission {
+    pub client_key: Vec<u8>,
+    pub payload: String,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SubmissionPayload {
+    pub job: ProofJob,
+    pub timestamp: u64,
+    // URL the final DaemonResponse is POSTed to, so the client does not have to stay connected
+    pub callback: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub enum DaemonResponse {
+    // The job is waiting for a free proving slot
+    Queued,
+    // A worker is generating the receipt
+    Proving,
+    Done(Box<Proof>),
+    Failed(String),
+    Status(CapacityReport),
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CapacityReport {
+    pub capacity: usize,
+    pub running: usize,
+    pub queued: BTreeMap<String, usize>,
+}
+
+pub fn socket_path() -> String {
+    std::env::var("PROVERD_SOCKET").unwrap_or_else(|_| DEFAULT_SOCKET.to_string())
+}
+
+fn max_jobs() -> usize {
+    std::env::var("PROVERD_MAX_JOBS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_JOBS)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn invalid_data(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+}
+
+async fn send_line<T: Serialize>(stream: &mut (impl AsyncWriteExt + Unpin), msg: &T) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(msg).map_err(invalid_data)?;
+    line.push(b'\n');
+    stream.write_all(&line).await
+}
+
+// Proving slots shared by all clients. When every slot is busy, jobs wait in a queue per client
+// and freed slots are handed to the clients in turn.
+struct Scheduler {
+    capacity: usize,
+    running: usize,
+    queues: BTreeMap<String, VecDeque<oneshot::Sender<()>>>,
+    last_served: Option<String>,
+}
+
+// Releases its proving slot when dropped
+struct Slot(Arc<Mutex<Scheduler>>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut scheduler = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        scheduler.running -= 1;
+        scheduler.wake_next();
+    }
+}
+
+impl Scheduler {
+    fn new(capacity: usize) -> Self {
+        Scheduler { capacity, running: 0, queues: BTreeMap::new(), last_served: None }
+    }
+
+    // Hand free slots to waiting jobs, taking clients round-robin after the last one served
+    fn wake_next(&mut self) {
+        while self.running < self.capacity {
+            let next = {
+                let after = self.last_served.clone().unwrap_or_default();
+                self.queues
+                    .keys()
+                    .find(|client| **client > after)
+                    .or_else(|| self.queues.keys().next())
+                    .cloned()
+            };
+            let client = match next {
+                Some(client) => client,
+                None => return,
+            };
+
+            let waiter = self.queues.get_mut(&client).and_then(|queue| queue.pop_front());
+            if self.queues.get(&client).is_some_and(|queue| queue.is_empty()) {
+                self.queues.remove(&client);
+            }
+            self.last_served = Some(client);
+
+            // A waiter whose client hung up is skipped
+            if let Some(waiter) = waiter {
+                if waiter.send(()).is_ok() {
+                    self.running += 1;
+                }
+            }
+        }
+    }
+
+    fn report(&self) -> CapacityReport {
+        CapacityReport {
+            capacity: self.capacity,
+            running: self.running,
+            queued: self.queues.iter().map(|(client, queue)| (client.clone(), queue.len())).collect(),
+        }
+    }
+}
+
+async fn acquire_slot(scheduler: &Arc<Mutex<Scheduler>>, client: &str) -> std::io::Result<Slot> {
+    let waiter = {
+        let mut guard = scheduler.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.running < guard.capacity && guard.queues.is_empty() {
+            guard.running += 1;
+            guard.last_served = Some(client.to_string());
+            return Ok(Slot(scheduler.clone()));
+        }
+        let (tx, rx) = oneshot::channel();
+        guard.queues.entry(client.to_string()).or_default().push_back(tx);
+        rx
+    };
+    waiter.await.map_err(invalid_data)?;
+    Ok(Slot(scheduler.clone()))
+}
+
+// Client keys allowed to submit over TCP, from a comma-separated list of hex public keys
+fn allowed_clients() -> Vec<VerifyingKey> {
+    std::env::var("PROVERD_CLIENTS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|s| {
+            let bytes: [u8; 32] = hex::decode(s.trim()).ok()?.try_into().ok()?;
+            VerifyingKey::from_bytes(&bytes).ok()
+        })
+        .collect()
+}
+
+// Check a signed submission and return the client name (hex key) and its payload
+fn authenticate(submission: &SignedSubmission, clients: &[VerifyingKey]) -> Result<(String, SubmissionPayload), String> {
+    let key = clients
+        .iter()
+        .find(|key| key.as_bytes().as_slice() == submission.client_key.as_slice())
+        .ok_or_else(|| "Unknown client key".to_string())?;
+    let signature = Signature::from_slice(&submission.signature).map_err(|_| "Malformed signature".to_string())?;
+    key.verify_strict(submission.payload.as_bytes(), &signature)
+        .map_err(|_| "Invalid signature".to_string())?;
+
+    let payload: SubmissionPayload = serde_json::from_str(&submission.payload).map_err(|e| e.to_string())?;
+    if now_secs().abs_diff(payload.timestamp) > MAX_SUBMISSION_AGE {
+        return Err("Submission expired".to_string());
+    }
+    Ok((hex::encode(key.as_bytes()), payload))
+}
+
+async fn deliver_callback(url: &str, response: &DaemonResponse) {
+    let client = reqwest::Client::new();
+    if let Err(e) = client.post(url).json(response).send().await {
+        warn!("Could not deliver receipt to {}: {}", url, e);
+    }
+}
+
+async fn handle_client(
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    scheduler: Arc<Mutex<Scheduler>>,
+    clients: Arc<Vec<VerifyingKey>>,
+    local: bool,
+) -> std::io::Result<()> {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+
+    let line = match lines.next_line().await? {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+    let (client, job, callback) = match serde_json::from_str(&line).map_err(invalid_data)? {
+        DaemonRequest::Status => {
+            let report = scheduler.lock().unwrap_or_else(|e| e.into_inner()).report();
+            return send_line(&mut write, &DaemonResponse::Status(report)).await;
+        }
+        DaemonRequest::Submit(job) if local => (LOCAL_CLIENT.to_string(), *job, None),
+        DaemonRequest::Submit(_) => {
+            let msg = "Unsigned jobs are only accepted on the local socket".to_string();
+            return send_line(&mut write, &DaemonResponse::Failed(msg)).await;
+        }
+        DaemonRequest::SubmitSigned(submission) => match authenticate(&submission, &clients) {
+            Ok((client, payload)) => (client, payload.job, payload.callback),
+            Err(msg) => return send_line(&mut write, &DaemonResponse::Failed(msg)).await,
+        },
+    };
+
+    // With a callback the client may hang up early; the job still runs and the result is POSTed
+    let connected = send_line(&mut write, &DaemonResponse::Queued).await.is_ok();
+    if !connected && callback.is_none() {
+        return Ok(());
+    }
+    let _slot = acquire_slot(&scheduler, &client).await?;
+    let connected = connected && send_line(&mut write, &DaemonResponse::Proving).await.is_ok();
+
+    let response = match prove_in_worker(job).await {
+        Ok(proof) => DaemonResponse::Done(Box::new(proof)),
+        Err(e) => DaemonResponse::Failed(e.to_string()),
+    };
+    if let Some(url) = &callback {
+        deliver_callback(url, &response).await;
+    }
+    if connected {
+        send_line(&mut write, &response).await?;
+    }
+    Ok(())
+}
+
+// Accept proving jobs on the Unix socket at `path`, and on PROVERD_LISTEN if it is set, until
+// the process is stopped
+pub async fn serve(path: &str) -> std::io::Result<()> {
+    let scheduler = Arc::new(Mutex::new(Scheduler::new(max_jobs())));
+    let clients = Arc::new(allowed_clients());
+
+    if let Ok(addr) = std::env::var("PROVERD_LISTEN") {
+        let listener = TcpListener::bind(&addr).await?;
+        info!("fleet-proverd accepting {} client keys on {}", clients.len(), addr);
+        let (scheduler, clients) = (scheduler.clone(), clients.clone());
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("TCP accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let (scheduler, clients) = (scheduler.clone(), clients.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(stream, scheduler, clients, false).await {
+                        warn!("Prover client error: {}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    // A socket file left behind by a previous run would make bind fail
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    info!("fleet-proverd listening on {}", path);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (scheduler, clients) = (scheduler.clone(), clients.clone());
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, scheduler, clients, true).await {
+                warn!("Prover client error: {}", e);
+            }
+        });
+    }
+}
+
+async fn read_result(read: impl AsyncRead + Unpin) -> std::io::Result<Result<Proof, String>> {
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str(&line).map_err(invalid_data)? {
+            DaemonResponse::Queued => progress(JobState::Queued),
+            DaemonResponse::Proving => progress(JobState::Proving),
+            DaemonResponse::Done(proof) => return Ok(Ok(*proof)),
+            DaemonResponse::Failed(err) => return Ok(Err(err)),
+            DaemonResponse::Status(_) => return Err(invalid_data("unexpected status response")),
+        }
+    }
+    Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "fleet-proverd closed the connection"))
+}
+
+// Submit a job to the daemon and wait for its receipt. The daemon is reached over TCP when
+// PROVERD_URL and PROVERD_CLIENT_KEY (hex signing key seed) are set, and over the local socket
+// otherwise. The outer error means the daemon could not be reached or hung up; the inner one is
+// a proving failure reported by the daemon.
+pub async fn prove_via_daemon(path: &str, job: &ProofJob) -> std::io::Result<Result<Proof, String>> {
+    if let (Ok(addr), Ok(key)) = (std::env::var("PROVERD_URL"), std::env::var("PROVERD_CLIENT_KEY")) {
+        let seed: [u8; 32] = hex::decode(key.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid_data("PROVERD_CLIENT_KEY must be 32 hex-encoded bytes"))?;
+        let signing_key = SigningKey::from_bytes(&seed);
+
+        let payload = serde_json::to_string(&SubmissionPayload {
+            job: job.clone(),
+            timestamp: now_secs(),
+            callback: None,
+        })
+        .map_err(invalid_data)?;
+        let request = DaemonRequest::SubmitSigned(SignedSubmission {
+            client_key: signing_key.verifying_key().to_bytes().to_vec(),
+            signature: signing_key.sign(payload.as_bytes()).to_bytes().to_vec(),
+            payload,
+        });
+
+        let stream = TcpStream::connect(&addr).await?;
+        let (read, mut write) = stream.into_split();
+        send_line(&mut write, &request).await?;
+        return read_result(read).await;
+    }
+
+    let stream = UnixStream::connect(path).await?;
+    let (read, mut write) = stream.into_split();
+    send_line(&mut write, &DaemonRequest::Submit(Box::new(job.clone()))).await?;
+    read_result(read).await
+}
@@ -0,0 +1,169 @@
+// src/responses.rs
+//
+// Verification of the chain's signed responses. The chain node signs every response over the
+// request it answers, including a fresh nonce the host sends with each request, and every event
+// of its stream. The host checks these signatures against the node's response key, pinned with
+// `chain_key` (and `chain_replica_keys` for the replicas), so a man in the middle cannot fake the
+// chain's answers. Without a pinned key the host fetches the node's key from its `/pubkey` on first
+// use, which only protects against someone who was not already in the middle at that point.
+//
+// Every request waits at most `chain_timeout_seconds` for the answer. A request that gets none, or
+// one whose signature does not check, fails with a `SendError` telling the two apart; an answer
+// refusing the request is still a response, left to the caller. A request that gets no answer
+// fails the host over to another node (see endpoints.rs). Requests made by a game action carry the
+// correlation ID of its job.
+
+use ed25519_dalek::VerifyingKey;
+use fleetconfig::config;
+use fleetcore::{EventStatement, ResponseStatement, CORRELATION_HEADER, RESPONSE_NONCE_HEADER, RESPONSE_SIGNATURE_HEADER};
+use rand::RngCore;
+use reqwest::{header::HeaderMap, RequestBuilder, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::endpoints;
+use crate::jobs;
+
+// Response key of each node, once pinned or fetched
+static KEYS: LazyLock<Mutex<HashMap<String, VerifyingKey>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn parse_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "The chain's response key must be 32 hex-encoded bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| "Invalid response key for the chain".to_string())
+}
+
+async fn fetch_key(node: &str) -> Result<VerifyingKey, String> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/pubkey", node))
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch the chain's response key: {}", e))?;
+    let text = response
+        .error_for_status()
+        .map_err(|e| format!("Failed to fetch the chain's response key: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the chain's response key: {}", e))?;
+    let key = parse_key(&text)?;
+    warn!(
+        "Trusting the response key {} served by the chain at {}, pin it in the host's configuration",
+        text.trim(),
+        node
+    );
+    Ok(key)
+}
+
+async fn key(node: &str) -> Result<VerifyingKey, String> {
+    if let Some(key) = KEYS.lock().unwrap_or_else(|e| e.into_inner()).get(node) {
+        return Ok(*key);
+    }
+    let host = &config().host;
+    let pinned = if node == host.chain_url { host.chain_key.as_ref() } else { host.chain_replica_keys.get(node) };
+    let key = match pinned {
+        Some(key) => parse_key(key)?,
+        None => fetch_key(node).await?,
+    };
+    KEYS.lock().unwrap_or_else(|e| e.into_inner()).insert(node.to_string(), key);
+    Ok(key)
+}
+
+// Response of the chain whose signature was checked
+pub struct ChainResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub text: String,
+    // Base URL of the node that answered
+    pub node: String,
+}
+
+// Why a request got no answer the host can trust
+#[derive(Debug)]
+pub enum SendError {
+    // The chain could not be reached or did not answer in time
+    Network(String),
+    // The chain answered, but the answer is not signed with its key
+    Unverified(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Network(e) => write!(f, "{}", e),
+            SendError::Unverified(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<SendError> for String {
+    fn from(err: SendError) -> Self {
+        err.to_string()
+    }
+}
+
+// Send a request to the chain and check the signature of its response
+pub async fn send(request: RequestBuilder) -> Result<ChainResponse, SendError> {
+    let mut nonce = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let nonce = hex::encode(nonce);
+    let request = match config().host.chain_timeout_seconds {
+        0 => request,
+        secs => request.timeout(Duration::from_secs(secs)),
+    };
+    // Requests made by a player's action carry its correlation ID
+    let request = match jobs::correlation_id() {
+        Some(id) => request.header(CORRELATION_HEADER, id),
+        None => request,
+    };
+    let (client, request) = request.header(RESPONSE_NONCE_HEADER, &nonce).build_split();
+    let request = request.map_err(|e| SendError::Network(e.to_string()))?;
+
+    // The chain signs the path it received, relative to its base URL
+    let method = request.method().to_string();
+    let url = request.url().as_str();
+    let node = endpoints::node_of(url).unwrap_or_else(|| config().host.chain_url.clone());
+    let path = url.strip_prefix(node.as_str()).unwrap_or(url).to_string();
+
+    let answer = match client.execute(request).await {
+        Ok(response) => {
+            let status = response.status();
+            let headers = response.headers().clone();
+            response.bytes().await.map(|body| (status, headers, body))
+        }
+        Err(e) => Err(e),
+    };
+    let (status, headers, body) = match answer {
+        Ok(answer) => answer,
+        Err(e) => {
+            endpoints::failed(&node);
+            return Err(SendError::Network(e.to_string()));
+        }
+    };
+    endpoints::answered(&node);
+
+    let signature = headers
+        .get(RESPONSE_SIGNATURE_HEADER)
+        .and_then(|v| hex::decode(v.as_bytes()).ok())
+        .ok_or_else(|| SendError::Unverified("Unsigned response from the chain".to_string()))?;
+    let statement = ResponseStatement::new(&method, &path, Some(&nonce), status.as_u16(), &body);
+    if !statement.verify(&key(&node).await.map_err(SendError::Unverified)?, &signature) {
+        return Err(SendError::Unverified("Invalid signature on the response of the chain".to_string()));
+    }
+    Ok(ChainResponse { status, headers, text: String::from_utf8_lossy(&body).into_owned(), node })
+}
+
+// Check the signature of an event of the stream of the chain node at `node`
+pub async fn verify_event(node: &str, seq: u64, msg: &str, signature: &str) -> Result<(), String> {
+    let signature = hex::decode(signature.trim()).map_err(|_| "Malformed event signature".to_string())?;
+    let statement = EventStatement { seq, msg: msg.to_string() };
+    if !statement.verify(&key(node).await?, &signature) {
+        return Err(format!("Invalid signature on chain event {}", seq));
+    }
+    Ok(())
+}
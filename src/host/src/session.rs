@@ -0,0 +1,258 @@
+// src/session.rs
+//
+// Server-side sessions holding the game context of each browser: game, fleet, board placement and
+// shots. The browser only keeps the session ID, in a cookie signed with the host's session key, so
+// a closed tab loses nothing and the page is rendered again from the session.
+// A session can drive several fleets, each in several games: it keeps the board and shots of each
+// (game, fleet) it played, and the page shows the game and fleet selected last, so one browser can
+// play several players of a game, or one fleet in several games, by switching between them.
+// Sessions live in memory and are written to the session store after every change.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use tracing::{error, warn};
+
+use fleetconfig::config;
+
+use crate::FormData;
+
+const COOKIE_NAME: &str = "fleet_session";
+const KEY_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Context of the selected game and fleet, with the context of every fleet of the session by name
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct SessionData {
+    pub gameid: Option<String>,
+    pub fleetid: Option<String>,
+    pub board: Option<String>,
+    pub shots: Option<String>,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub fleets: BTreeMap<String, FleetContext>,
+}
+
+// Game a fleet was played in last, with its board and shots, and the board and shots of every
+// game the fleet played in
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct FleetContext {
+    pub gameid: Option<String>,
+    pub board: Option<String>,
+    pub shots: Option<String>,
+    #[serde(default)]
+    pub games: BTreeMap<String, GameContext>,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct GameContext {
+    pub board: Option<String>,
+    pub shots: Option<String>,
+}
+
+impl SessionData {
+    // Every (game, fleet) the session played
+    pub fn games(&self) -> Vec<(String, String)> {
+        self.fleets
+            .iter()
+            .flat_map(|(fleet, context)| context.games.keys().map(move |gameid| (gameid.clone(), fleet.clone())))
+            .collect()
+    }
+}
+
+struct Store {
+    path: PathBuf,
+    key: [u8; KEY_LENGTH],
+    sessions: HashMap<String, SessionData>,
+}
+
+static STORE: LazyLock<Mutex<Store>> = LazyLock::new(|| {
+    let config = config();
+    let key_path = config.storage_file(&config.host.session_key);
+    let key = load_key(&key_path).unwrap_or_else(|e| {
+        // Sessions then only last until the host restarts
+        warn!("Could not load the session key {}: {}", key_path.display(), e);
+        let mut key = [0u8; KEY_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        key
+    });
+    let path = config.storage_file(&config.host.session_store);
+    let sessions = load_sessions(&path);
+    Mutex::new(Store { path, key, sessions })
+});
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Create a file only the host may read
+pub(crate) fn create_private(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+// Load the key signing the session cookies (hex-encoded), creating it on first start
+fn load_key(path: &Path) -> std::io::Result<[u8; KEY_LENGTH]> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => hex::decode(text.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "session key must be 32 hex-encoded bytes")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut key = [0u8; KEY_LENGTH];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            let mut file = create_private(path)?;
+            file.write_all(hex::encode(key).as_bytes())?;
+            file.sync_all()?;
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn expired(session: &SessionData, now: u64) -> bool {
+    let ttl = config().host.session_ttl_seconds;
+    ttl > 0 && now.saturating_sub(session.updated_at) > ttl
+}
+
+fn load_sessions(path: &Path) -> HashMap<String, SessionData> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            warn!("Could not read the session store {}: {}", path.display(), e);
+            return HashMap::new();
+        }
+    };
+    let mut sessions: HashMap<String, SessionData> = serde_json::from_str(&text).unwrap_or_else(|e| {
+        warn!("Ignoring the corrupted session store {}: {}", path.display(), e);
+        HashMap::new()
+    });
+    let now = now_secs();
+    sessions.retain(|_, session| !expired(session, now));
+    sessions
+}
+
+impl Store {
+    fn sign(&self, id: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(id.as_bytes());
+        mac
+    }
+
+    // Write the sessions to a temporary file first, so a crash never leaves a truncated store
+    fn persist(&self) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut file = create_private(&tmp)?;
+        file.write_all(&serde_json::to_vec(&self.sessions)?)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+fn store() -> std::sync::MutexGuard<'static, Store> {
+    STORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Session ID of the request's cookie, if its signature is valid
+pub fn session_id(headers: &HeaderMap) -> Option<String> {
+    let value = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(COOKIE_NAME)?.strip_prefix('='))?;
+    let (id, signature) = value.split_once('.')?;
+    let signature = hex::decode(signature).ok()?;
+    store().sign(id).verify_slice(&signature).ok()?;
+    Some(id.to_string())
+}
+
+// Session ID of the request, or a new one with the cookie to set
+pub fn resume(headers: &HeaderMap) -> (String, Option<HeaderValue>) {
+    if let Some(id) = session_id(headers) {
+        return (id, None);
+    }
+    let id = nanoid::nanoid!(21);
+    let signature = hex::encode(store().sign(&id).finalize().into_bytes());
+    let ttl = config().host.session_ttl_seconds;
+    let max_age = if ttl > 0 { format!("; Max-Age={}", ttl) } else { String::new() };
+    let cookie = format!("{}={}.{}; Path=/; HttpOnly; SameSite=Lax{}", COOKIE_NAME, id, signature, max_age);
+    (id, HeaderValue::from_str(&cookie).ok())
+}
+
+pub fn load(id: &str) -> Option<SessionData> {
+    let store = store();
+    let session = store.sessions.get(id)?;
+    if expired(session, now_secs()) {
+        return None;
+    }
+    Some(session.clone())
+}
+
+// Remember the game context of the action a player just sent, and select its game and fleet
+pub fn save(id: &str, data: &FormData) {
+    let game = GameContext { board: data.board.clone(), shots: data.shots.clone() };
+    update(id, data.fleetid.clone().filter(|fleet| !fleet.is_empty()), |context| {
+        context.gameid = data.gameid.clone();
+        if let Some(gameid) = data.gameid.as_ref().filter(|gameid| !gameid.is_empty()) {
+            context.games.insert(gameid.clone(), game.clone());
+        }
+        context.board = game.board;
+        context.shots = game.shots;
+    });
+}
+
+// Select another fleet of the session, in the game it was played in last; a fleet the session
+// never played with starts with an empty context
+pub fn select(id: &str, fleet: &str) -> SessionData {
+    update(id, Some(fleet.to_string()), |_| {})
+}
+
+// Select one of the games a fleet plays, with the board and shots it was last played with there
+pub fn select_game(id: &str, fleet: &str, gameid: &str) -> SessionData {
+    update(id, Some(fleet.to_string()), |context| {
+        let game = context.games.get(gameid).cloned().unwrap_or_default();
+        context.gameid = Some(gameid.to_string());
+        context.board = game.board;
+        context.shots = game.shots;
+    })
+}
+
+fn update(id: &str, fleet: Option<String>, change: impl FnOnce(&mut FleetContext)) -> SessionData {
+    let now = now_secs();
+    let mut store = store();
+    store.sessions.retain(|_, session| !expired(session, now));
+    let session = store.sessions.entry(id.to_string()).or_default();
+    // Actions sent without a fleet are remembered, but for no fleet in particular
+    let mut unnamed = FleetContext::default();
+    let context = match &fleet {
+        Some(fleet) => session.fleets.entry(fleet.clone()).or_default(),
+        None => &mut unnamed,
+    };
+    change(context);
+    let context = context.clone();
+    session.gameid = context.gameid;
+    session.fleetid = fleet;
+    session.board = context.board;
+    session.shots = context.shots;
+    session.updated_at = now;
+    let session = session.clone();
+    if let Err(e) = store.persist() {
+        error!("Could not write the session store {}: {}", store.path.display(), e);
+    }
+    session
+}
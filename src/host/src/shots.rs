@@ -0,0 +1,151 @@
+// src/shots.rs
+//
+// Board of the shots a player exchanged in a game, so nobody needs a paper grid: the shots the
+// player fired with the outcome their target reported, and the shots fired at the player with the
+// outcome the player reported. Our own actions are recorded when the chain accepts them; the
+// shots fired at us and the reports on our shots are read from the chain's event stream.
+
+use fleetcore::{fleet_squares, Coord, ShotOutcome};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Outcome {
+    Hit,
+    Miss,
+    // The hit sank a ship
+    Sunk,
+}
+
+impl From<ShotOutcome> for Outcome {
+    fn from(outcome: ShotOutcome) -> Self {
+        match outcome {
+            ShotOutcome::Hit => Outcome::Hit,
+            ShotOutcome::Miss => Outcome::Miss,
+            ShotOutcome::Sunk { .. } => Outcome::Sunk,
+        }
+    }
+}
+
+// "Hit", "Miss" or "Sunk(size)", as in the chain's events
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        s.parse::<ShotOutcome>().map(Outcome::from)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Shot {
+    // Fleet fired at, or fleet that fired at us when it is known
+    pub fleet: Option<String>,
+    pub pos: Coord,
+    // Missing until the shot is reported, and for the blinded reports of other fleets
+    pub outcome: Option<Outcome>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ShotTracker {
+    pub fired: Vec<Shot>,
+    pub incoming: Vec<Shot>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ShotEvent {
+    // We fired at `target`
+    Fired { target: String, pos: Coord },
+    // `target` reported the outcome of our shot
+    Answered { target: String, pos: Coord, outcome: Outcome },
+    // `shooter` fired at us
+    Incoming { shooter: String, pos: Coord },
+    // We reported the outcome of a shot at us
+    Reported { pos: Coord, outcome: Outcome },
+}
+
+impl ShotTracker {
+    // Apply an event to the board. Returns false if it changed nothing.
+    pub fn apply(&mut self, event: ShotEvent) -> bool {
+        match event {
+            ShotEvent::Fired { target, pos } => {
+                self.fired.push(Shot { fleet: Some(target), pos, outcome: None });
+                true
+            }
+            ShotEvent::Answered { target, pos, outcome } => {
+                let shot = self
+                    .fired
+                    .iter_mut()
+                    .rev()
+                    .find(|shot| shot.fleet.as_deref() == Some(target.as_str()) && shot.pos == pos && shot.outcome.is_none());
+                match shot {
+                    Some(shot) => {
+                        shot.outcome = Some(outcome);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            ShotEvent::Incoming { shooter, pos } => {
+                // The stream repeats nothing, but a shot still waiting for our report is only kept once
+                let pending = self.incoming.last().is_some_and(|shot| {
+                    shot.fleet.as_deref() == Some(shooter.as_str()) && shot.pos == pos && shot.outcome.is_none()
+                });
+                if !pending {
+                    self.incoming.push(Shot { fleet: Some(shooter), pos, outcome: None });
+                }
+                !pending
+            }
+            ShotEvent::Reported { pos, outcome } => {
+                match self.incoming.iter_mut().rev().find(|shot| shot.pos == pos && shot.outcome.is_none()) {
+                    Some(shot) => shot.outcome = Some(outcome),
+                    // The shot was fired before the host followed the game
+                    None => self.incoming.push(Shot { fleet: None, pos, outcome: Some(outcome) }),
+                }
+                true
+            }
+        }
+    }
+
+    // Opponent whose whole fleet our shots hit, which a victory claim can rest on
+    pub fn destroyed(&self) -> Option<String> {
+        let mut hits: HashMap<&str, HashSet<Coord>> = HashMap::new();
+        for shot in &self.fired {
+            if let (Some(fleet), Some(Outcome::Hit | Outcome::Sunk)) = (&shot.fleet, shot.outcome) {
+                hits.entry(fleet.as_str()).or_default().insert(shot.pos);
+            }
+        }
+        hits.into_iter()
+            .find(|(_, squares)| squares.len() == fleet_squares())
+            .map(|(fleet, _)| fleet.to_string())
+    }
+}
+
+// Read a shot exchanged by `fleet` in `gameid` from an event of the chain's stream
+pub fn parse_event(gameid: &str, fleet: &str, text: &str) -> Option<ShotEvent> {
+    // "{shooter} fired at {target} in game {gameid} at position {pos}"
+    if let Some((shooter, rest)) = text.split_once(" fired at ") {
+        let (target, rest) = rest.split_once(" in game ")?;
+        let (game, pos) = rest.split_once(" at position ")?;
+        if game != gameid || target != fleet {
+            return None;
+        }
+        return Some(ShotEvent::Incoming { shooter: shooter.to_string(), pos: pos.parse().ok()? });
+    }
+
+    // "{reporter} reported {outcome} at position {pos} in game {gameid}, {next} plays next"
+    if let Some((reporter, rest)) = text.split_once(" reported ") {
+        let (outcome, rest) = rest.split_once(" at position ")?;
+        let (pos, rest) = rest.split_once(" in game ")?;
+        let game = rest.split_once(", ").map_or(rest, |(game, _)| game);
+        if game != gameid || reporter == fleet {
+            return None;
+        }
+        return Some(ShotEvent::Answered {
+            target: reporter.to_string(),
+            pos: pos.parse().ok()?,
+            outcome: outcome.parse().ok()?,
+        });
+    }
+    None
+}
@@ -0,0 +1,189 @@
+// src/signer.rs
+//
+// Signers of the players' receipts and statements. By default the fleet's key is unlocked from
+// the keystore and signs in the host process. Players who want their keys to never touch the web
+// process can have them held by a local signing agent, reached over a Unix socket, or by a remote
+// signing service over HTTP, selected with the host's `signer` setting.
+//
+// The agent reads one newline-terminated JSON SignerRequest per connection and answers with one
+// newline-terminated JSON SignerResponse. The remote service takes the same request as the body of
+// `POST {signer_url}/sign` and answers with a SignerResponse, authenticated with `signer_token`
+// as a bearer token when it is set.
+
+use ed25519_dalek::{Signer as _, SigningKey, VerifyingKey, PUBLIC_KEY_LENGTH};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use fleetconfig::{config, SignerKind};
+use fleetcore::signed_message;
+
+use crate::keystore::Identity;
+
+pub type SignerFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + 'a>>;
+
+pub trait Signer: Send + Sync {
+    // Key the chain checks the signatures against
+    fn verifying_key(&self) -> SignerFuture<'_, VerifyingKey>;
+
+    // Ed25519 signature of a message
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Vec<u8>>;
+}
+
+#[derive(Deserialize, Serialize)]
+pub enum SignerRequest {
+    PublicKey { fleet: String },
+    // Message to sign, hex-encoded
+    Sign { fleet: String, message: String },
+}
+
+#[derive(Deserialize, Serialize)]
+pub enum SignerResponse {
+    // Hex-encoded verifying key
+    PublicKey(String),
+    // Hex-encoded signature
+    Signature(String),
+    Error(String),
+}
+
+// Key unlocked from the host's keystore
+pub struct KeySigner(Option<SigningKey>);
+
+impl KeySigner {
+    fn key(&self) -> Result<&SigningKey, String> {
+        self.0.as_ref().ok_or_else(|| "The keystore holds no signing key for this fleet".to_string())
+    }
+}
+
+impl Signer for KeySigner {
+    fn verifying_key(&self) -> SignerFuture<'_, VerifyingKey> {
+        Box::pin(async move { Ok(self.key()?.verifying_key()) })
+    }
+
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Vec<u8>> {
+        Box::pin(async move { Ok(self.key()?.sign(message).to_bytes().to_vec()) })
+    }
+}
+
+// Signing agent listening on a local Unix socket
+pub struct AgentSigner {
+    socket: String,
+    fleet: String,
+}
+
+impl AgentSigner {
+    async fn request(&self, request: SignerRequest) -> Result<SignerResponse, String> {
+        let stream = UnixStream::connect(&self.socket)
+            .await
+            .map_err(|e| format!("Signing agent unavailable at {}: {}", self.socket, e))?;
+        let (read, mut write) = stream.into_split();
+        let mut line = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        write.write_all(&line).await.map_err(|e| format!("Could not reach the signing agent: {}", e))?;
+
+        let mut answer = String::new();
+        BufReader::new(read)
+            .read_line(&mut answer)
+            .await
+            .map_err(|e| format!("No answer from the signing agent: {}", e))?;
+        serde_json::from_str(&answer).map_err(|e| format!("Invalid answer from the signing agent: {}", e))
+    }
+}
+
+impl Signer for AgentSigner {
+    fn verifying_key(&self) -> SignerFuture<'_, VerifyingKey> {
+        Box::pin(async move { public_key(self.request(SignerRequest::PublicKey { fleet: self.fleet.clone() }).await?) })
+    }
+
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let request = SignerRequest::Sign { fleet: self.fleet.clone(), message: hex::encode(message) };
+            signature(self.request(request).await?)
+        })
+    }
+}
+
+// Connections to the signing service, shared by the fleets' signers
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+// Remote signing service
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    fleet: String,
+}
+
+impl RemoteSigner {
+    async fn request(&self, request: SignerRequest) -> Result<SignerResponse, String> {
+        let mut builder = self.client.post(format!("{}/sign", self.url)).json(&request);
+        if let Some(token) = &self.token {
+            builder = builder.bearer_auth(token);
+        }
+        let response = builder.send().await.map_err(|e| format!("Signing service unavailable: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Signing service answered {}", response.status()));
+        }
+        response.json().await.map_err(|e| format!("Invalid answer from the signing service: {}", e))
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn verifying_key(&self) -> SignerFuture<'_, VerifyingKey> {
+        Box::pin(async move { public_key(self.request(SignerRequest::PublicKey { fleet: self.fleet.clone() }).await?) })
+    }
+
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignerFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let request = SignerRequest::Sign { fleet: self.fleet.clone(), message: hex::encode(message) };
+            signature(self.request(request).await?)
+        })
+    }
+}
+
+fn public_key(response: SignerResponse) -> Result<VerifyingKey, String> {
+    let key = match response {
+        SignerResponse::PublicKey(key) => key,
+        SignerResponse::Error(e) => return Err(format!("Signer refused: {}", e)),
+        SignerResponse::Signature(_) => return Err("Signer answered with a signature".to_string()),
+    };
+    let bytes: [u8; PUBLIC_KEY_LENGTH] = hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Signer sent an invalid verifying key".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| "Signer sent an invalid verifying key".to_string())
+}
+
+fn signature(response: SignerResponse) -> Result<Vec<u8>, String> {
+    match response {
+        SignerResponse::Signature(signature) => {
+            hex::decode(signature).map_err(|_| "Signer sent an invalid signature".to_string())
+        }
+        SignerResponse::Error(e) => Err(format!("Signer refused: {}", e)),
+        SignerResponse::PublicKey(_) => Err("Signer answered with a public key".to_string()),
+    }
+}
+
+// Signer of a fleet, as configured for this host
+pub fn for_fleet(fleet: &str, identity: &Identity) -> Box<dyn Signer> {
+    let host = &config().host;
+    match host.signer {
+        SignerKind::Keystore => Box::new(KeySigner(identity.signing_key.clone())),
+        SignerKind::Agent => Box::new(AgentSigner { socket: host.signer_socket.clone(), fleet: fleet.to_string() }),
+        SignerKind::Remote => Box::new(RemoteSigner {
+            client: CLIENT.clone(),
+            url: host.signer_url.clone(),
+            token: host.signer_token.clone(),
+            fleet: fleet.to_string(),
+        }),
+    }
+}
+
+// Sign the canonical encoding of `value` under the given domain tag (see fleetcore::sign_canonical)
+pub async fn sign_canonical<T: Serialize>(signer: &dyn Signer, domain: &str, value: &T) -> Result<Vec<u8>, String> {
+    let message = signed_message(domain, value).map_err(|e| e.to_string())?;
+    signer.sign(&message).await
+}
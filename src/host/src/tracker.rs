@@ -0,0 +1,265 @@
+// src/tracker.rs
+//
+// Record of the actions this host submitted to the chain, per game and fleet. Every change is
+// appended to a journal file and fsynced before the action goes out, so a crash or power loss
+// never forgets a proof that was already submitted. The journal is compacted into one snapshot
+// line per game every COMPACT_EVERY entries.
+
+use fleetcore::Command;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use tracing::error;
+
+use fleetconfig::config;
+
+use crate::shots::{ShotEvent, ShotTracker};
+
+// Number of journal entries appended between two compactions
+const COMPACT_EVERY: usize = 256;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TrackedAction {
+    pub cmd: Command,
+    // SHA-256 of the receipt's journal, identifying the submitted proof
+    pub journal_digest: String,
+    pub submitted_at: u64,
+    // Response of the chain, missing if the host stopped before getting one
+    pub outcome: Option<String>,
+    pub seq: Option<u64>,
+    // Base URL of the chain node that applied the action
+    #[serde(default)]
+    pub node: Option<String>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct GameRecord {
+    pub gameid: String,
+    pub fleet: String,
+    pub actions: Vec<TrackedAction>,
+    // Positions of our board hit so far, which only this host knows in a blinded game
+    #[serde(default)]
+    pub hits: Vec<u8>,
+    // Shots fired by and at this fleet, with their outcomes
+    #[serde(default)]
+    pub shots: ShotTracker,
+}
+
+#[derive(Deserialize, Serialize)]
+enum JournalEntry {
+    Snapshot(GameRecord),
+    Submitting { gameid: String, fleet: String, action: TrackedAction },
+    Outcome {
+        gameid: String,
+        fleet: String,
+        journal_digest: String,
+        outcome: String,
+        seq: Option<u64>,
+        #[serde(default)]
+        node: Option<String>,
+    },
+    Hit { gameid: String, fleet: String, pos: u8 },
+    Shot { gameid: String, fleet: String, event: ShotEvent },
+}
+
+struct Tracker {
+    path: PathBuf,
+    file: File,
+    games: HashMap<(String, String), GameRecord>,
+    appended: usize,
+}
+
+static TRACKER: LazyLock<Mutex<Option<Tracker>>> = LazyLock::new(|| {
+    let config = config();
+    Mutex::new(open(config.storage_file(&config.host.tracker_journal)))
+});
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn apply(games: &mut HashMap<(String, String), GameRecord>, entry: JournalEntry) {
+    match entry {
+        JournalEntry::Snapshot(record) => {
+            games.insert((record.gameid.clone(), record.fleet.clone()), record);
+        }
+        JournalEntry::Submitting { gameid, fleet, action } => {
+            games
+                .entry((gameid.clone(), fleet.clone()))
+                .or_insert_with(|| GameRecord { gameid, fleet, actions: Vec::new(), hits: Vec::new(), shots: ShotTracker::default() })
+                .actions
+                .push(action);
+        }
+        JournalEntry::Outcome { gameid, fleet, journal_digest, outcome, seq, node } => {
+            let action = games
+                .get_mut(&(gameid, fleet))
+                .and_then(|record| record.actions.iter_mut().rev().find(|a| a.journal_digest == journal_digest));
+            if let Some(action) = action {
+                action.outcome = Some(outcome);
+                action.seq = seq;
+                action.node = node;
+            }
+        }
+        JournalEntry::Hit { gameid, fleet, pos } => {
+            let record = games
+                .entry((gameid.clone(), fleet.clone()))
+                .or_insert_with(|| GameRecord { gameid, fleet, actions: Vec::new(), hits: Vec::new(), shots: ShotTracker::default() });
+            if !record.hits.contains(&pos) {
+                record.hits.push(pos);
+            }
+        }
+        JournalEntry::Shot { gameid, fleet, event } => {
+            games
+                .entry((gameid.clone(), fleet.clone()))
+                .or_insert_with(|| GameRecord { gameid, fleet, actions: Vec::new(), hits: Vec::new(), shots: ShotTracker::default() })
+                .shots
+                .apply(event);
+        }
+    }
+}
+
+// Replay the journal at `path`, creating it if needed. Returns None (and tracks nothing) if the
+// journal cannot be opened.
+fn open(path: PathBuf) -> Option<Tracker> {
+    let mut games = HashMap::new();
+
+    // A line cut short by a crash while it was written is skipped
+    if let Ok(file) = File::open(&path) {
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(entry) = serde_json::from_str(&line) {
+                apply(&mut games, entry);
+            }
+        }
+    }
+
+    match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => Some(Tracker { path, file, games, appended: 0 }),
+        Err(e) => {
+            error!("Could not open tracker journal {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn sync_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+impl Tracker {
+    // Append an entry and wait until it is on disk
+    fn append(&mut self, entry: JournalEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+
+        apply(&mut self.games, entry);
+        self.appended += 1;
+        if self.appended >= COMPACT_EVERY {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    // Replace the journal with one snapshot entry per game
+    fn compact(&mut self) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("compact");
+        let mut file = File::create(&tmp)?;
+        for record in self.games.values() {
+            let mut line = serde_json::to_vec(&JournalEntry::Snapshot(record.clone()))?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        sync_dir(&self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.appended = 0;
+        Ok(())
+    }
+}
+
+fn with_tracker(f: impl FnOnce(&mut Tracker) -> std::io::Result<()>) {
+    let mut tracker = TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(tracker) = tracker.as_mut() {
+        if let Err(e) = f(tracker) {
+            error!("Could not write tracker journal {}: {}", tracker.path.display(), e);
+        }
+    }
+}
+
+// Record a proof about to be submitted and return its journal digest
+pub fn record_submission(gameid: &str, fleet: &str, cmd: Command, journal: &[u8]) -> String {
+    let journal_digest = hex::encode(Sha256::digest(journal));
+    let action = TrackedAction {
+        cmd,
+        journal_digest: journal_digest.clone(),
+        submitted_at: now_secs(),
+        outcome: None,
+        seq: None,
+        node: None,
+    };
+    with_tracker(|tracker| {
+        tracker.append(JournalEntry::Submitting { gameid: gameid.to_string(), fleet: fleet.to_string(), action })
+    });
+    journal_digest
+}
+
+// Record the chain's response to a submitted proof
+pub fn record_outcome(gameid: &str, fleet: &str, journal_digest: &str, outcome: &str, seq: Option<u64>, node: Option<&str>) {
+    with_tracker(|tracker| {
+        tracker.append(JournalEntry::Outcome {
+            gameid: gameid.to_string(),
+            fleet: fleet.to_string(),
+            journal_digest: journal_digest.to_string(),
+            outcome: outcome.to_string(),
+            seq,
+            node: node.map(String::from),
+        })
+    });
+}
+
+// Record a hit on our board, accepted by the chain in a blinded game
+pub fn record_hit(gameid: &str, fleet: &str, pos: u8) {
+    with_tracker(|tracker| {
+        tracker.append(JournalEntry::Hit { gameid: gameid.to_string(), fleet: fleet.to_string(), pos })
+    });
+}
+
+// Record a shot exchanged in a game, unless it changes nothing on the board
+pub fn record_shot(gameid: &str, fleet: &str, event: ShotEvent) {
+    with_tracker(|tracker| {
+        let key = (gameid.to_string(), fleet.to_string());
+        let mut shots = tracker.games.get(&key).map(|record| record.shots.clone()).unwrap_or_default();
+        if !shots.apply(event.clone()) {
+            return Ok(());
+        }
+        tracker.append(JournalEntry::Shot { gameid: key.0, fleet: key.1, event })
+    });
+}
+
+// Shots fired by and at a fleet so far
+pub fn shot_tracker(gameid: &str, fleet: &str) -> ShotTracker {
+    game_record(gameid, fleet).map(|record| record.shots).unwrap_or_default()
+}
+
+// Positions of our board hit so far in a blinded game
+pub fn hits(gameid: &str, fleet: &str) -> Vec<u8> {
+    game_record(gameid, fleet).map(|record| record.hits).unwrap_or_default()
+}
+
+pub fn game_record(gameid: &str, fleet: &str) -> Option<GameRecord> {
+    let tracker = TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    tracker.as_ref()?.games.get(&(gameid.to_string(), fleet.to_string())).cloned()
+}
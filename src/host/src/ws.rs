@@ -0,0 +1,179 @@
+// src/ws.rs
+//
+// WebSocket API for interactive play on `/ws`. The browser sends the fields of the page's form as
+// JSON, `button` naming the action, and the host answers on the same socket with the progress of
+// the action's job, the chain's response and the turn notifications of the game, so the page
+// never has to reload. A `Follow` action only subscribes the socket to a game's notifications.
+// A socket forwards the notifications of every (game, fleet) it follows, starting with the ones of
+// the browser's session, so a fleet playing several games hears about all of them on one socket.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use serde::Serialize;
+use std::collections::HashSet;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::jobs::{self, job_status, spawn_job, JobState, JobStatus};
+use crate::notify::{self, Notification};
+use crate::session;
+use crate::{dispatch, FormData};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    // Game and fleet the page now plays with
+    Session { gameid: Option<String>, fleetid: Option<String> },
+    Job(JobStatus),
+    Notification(Notification),
+    Error { message: String },
+}
+
+pub async fn ws_handler(headers: HeaderMap, ws: WebSocketUpgrade) -> Response {
+    // The page got its session cookie when it was loaded
+    let session_id = session::session_id(&headers);
+    ws.on_upgrade(move |socket| play(socket, session_id))
+}
+
+#[derive(Default)]
+struct Session {
+    // Server-side session the game context of each action is saved to
+    session_id: Option<String>,
+    // Game and fleet the page plays with
+    game: Option<(String, String)>,
+    // Games and fleets whose notifications are forwarded, the page's included
+    followed: HashSet<(String, String)>,
+    // Jobs started from this socket that are not done yet
+    running: HashSet<String>,
+}
+
+impl Session {
+    fn follow(&mut self, gameid: Option<String>, fleetid: Option<String>) -> Vec<ServerMessage> {
+        let (gameid, fleetid) = match (gameid, fleetid) {
+            (Some(gameid), Some(fleetid)) if !gameid.is_empty() && !fleetid.is_empty() => (gameid, fleetid),
+            _ => return Vec::new(),
+        };
+        notify::watch(&gameid, &fleetid);
+        self.followed.insert((gameid.clone(), fleetid.clone()));
+        self.game = Some((gameid, fleetid));
+        self.latest()
+    }
+
+    // Follow every game and fleet of the browser's session
+    fn follow_session(&mut self) -> Vec<ServerMessage> {
+        let data = self.session_id.as_deref().and_then(session::load).unwrap_or_default();
+        for (gameid, fleetid) in data.games() {
+            notify::watch(&gameid, &fleetid);
+            self.followed.insert((gameid, fleetid));
+        }
+        self.latest()
+    }
+
+    // Notifications still current for the followed games
+    fn latest(&self) -> Vec<ServerMessage> {
+        let mut followed: Vec<&(String, String)> = self.followed.iter().collect();
+        followed.sort();
+        followed
+            .into_iter()
+            .filter_map(|(gameid, fleetid)| notify::latest(gameid, fleetid))
+            .map(ServerMessage::Notification)
+            .collect()
+    }
+
+    fn action(&mut self, text: &str) -> Vec<ServerMessage> {
+        let data: FormData = match serde_json::from_str(text) {
+            Ok(data) => data,
+            Err(e) => return vec![ServerMessage::Error { message: format!("Invalid action: {}", e) }],
+        };
+        if data.button == "Follow" {
+            return self.follow(data.gameid, data.fleetid);
+        }
+
+        if let Some(id) = &self.session_id {
+            session::save(id, &data);
+        }
+        let mut replies = vec![ServerMessage::Session {
+            gameid: data.gameid.clone(),
+            fleetid: data.fleetid.clone(),
+        }];
+        replies.extend(self.follow(data.gameid.clone(), data.fleetid.clone()));
+
+        // Proving takes minutes: run the action in the background and forward its progress
+        let id = spawn_job(dispatch(data));
+        self.running.insert(id.clone());
+        replies.extend(job_status(&id).map(ServerMessage::Job));
+        replies
+    }
+
+    fn job_update(&mut self, status: JobStatus) -> Vec<ServerMessage> {
+        if !self.running.contains(&status.id) {
+            return Vec::new();
+        }
+        if status.state == JobState::Done {
+            self.running.remove(&status.id);
+            // A player is only followed once the chain knows them, which a join just changed
+            if status.response.as_deref() == Some("OK") {
+                for (gameid, fleetid) in &self.followed {
+                    notify::watch(gameid, fleetid);
+                }
+            }
+        }
+        vec![ServerMessage::Job(status)]
+    }
+
+    // Updates were missed: send the current status of the running jobs instead
+    fn jobs_lagged(&mut self) -> Vec<ServerMessage> {
+        let statuses: Vec<JobStatus> = self.running.iter().filter_map(|id| job_status(id)).collect();
+        statuses.into_iter().flat_map(|status| self.job_update(status)).collect()
+    }
+
+    fn notification(&self, notification: Notification) -> Vec<ServerMessage> {
+        let key = (notification.gameid.clone(), notification.fleet.clone());
+        if !self.followed.contains(&key) {
+            return Vec::new();
+        }
+        vec![ServerMessage::Notification(notification)]
+    }
+}
+
+async fn play(mut socket: WebSocket, session_id: Option<String>) {
+    let mut job_updates = jobs::subscribe();
+    let mut notifications = notify::subscribe();
+    let mut session = Session { session_id, ..Session::default() };
+    // Replies to send before waiting for the next message or update
+    let mut pending = session.follow_session();
+
+    loop {
+        for reply in pending.drain(..) {
+            let text = match serde_json::to_string(&reply) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("Could not encode WebSocket message: {}", e);
+                    continue;
+                }
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+        }
+
+        pending = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => session.action(&text),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => Vec::new(),
+            },
+            update = job_updates.recv() => match update {
+                Ok(status) => session.job_update(status),
+                Err(RecvError::Lagged(_)) => session.jobs_lagged(),
+                Err(RecvError::Closed) => break,
+            },
+            update = notifications.recv() => match update {
+                Ok(notification) => session.notification(notification),
+                Err(RecvError::Lagged(_)) => session.latest(),
+                Err(RecvError::Closed) => break,
+            },
+        };
+    }
+}
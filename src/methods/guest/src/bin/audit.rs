@@ -0,0 +1,39 @@
+use fleetcore::{AuditInputs, AuditJournal, ReportJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // read the input
+    let input: AuditInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // Follow the board from the one committed when joining through every report, each taken as
+    // an assumption: a report must start from the board the previous one left
+    let mut board = input.initial;
+    for (index, journal) in input.reports.iter().enumerate() {
+        env::verify(input.report_id, journal).expect("Invalid report receipt");
+        let report: ReportJournal = risc0_zkvm::serde::from_slice(journal).expect("Invalid report journal");
+        if report.gameid != input.gameid || report.fleet != input.fleet {
+            panic!("Report {} is for another game or fleet", index);
+        }
+        if report.board != board {
+            panic!("Report {} does not start from the board left by the previous one", index);
+        }
+        board = report.next_board;
+    }
+
+    let output = AuditJournal {
+        version: PROTOCOL_VERSION,
+        gameid: input.gameid,
+        fleet: input.fleet,
+        initial: input.initial,
+        board,
+        reports: input.reports.len() as u32,
+        report_id: input.report_id,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
@@ -0,0 +1,56 @@
+use fleetcore::{board_digest, ledger_digest, outcome_commitment, BlindReportInputs, BlindReportJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    let input: BlindReportInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // Validate it's this player's turn to report
+    if input.game_next_report.as_ref() != Some(&input.fleet) {
+        panic!("Not your turn to report");
+    }
+
+    // Validate that the report ("Hit" or "Miss") is accurate
+    let is_hit = input.board.contains(input.pos);
+    let is_valid_report = match input.report.as_str() {
+        "Hit" => is_hit,
+        "Miss" => !is_hit,
+        _ => panic!("Report must be 'Hit' or 'Miss'"),
+    };
+    if !is_valid_report {
+        panic!("Report does not match the actual board state");
+    }
+
+    // The ledger starts empty and can only hold positions of the board
+    if input.turn == 0 && !input.hits.is_empty() {
+        panic!("The ledger must be empty before the first report");
+    }
+    if input.hits.iter().any(|&pos| !input.board.contains_pos(pos)) {
+        panic!("The ledger holds a position that is not on the board");
+    }
+
+    // Add the position to the ledger if it was hit
+    let mut next_hits = input.hits.clone();
+    if is_hit && !next_hits.contains(&input.pos.pos()) {
+        next_hits.push(input.pos.pos());
+    }
+
+    // Commit to the outcome instead of publishing it
+    let output = BlindReportJournal {
+        version: PROTOCOL_VERSION,
+        board: board_digest(input.board, &input.random),
+        pos: input.pos,
+        turn: input.turn,
+        ledger: ledger_digest(&input.hits, &input.random, input.turn),
+        next_ledger: ledger_digest(&next_hits, &input.random, input.turn + 1),
+        outcome: outcome_commitment(&input.gameid, &input.fleet, input.pos, is_hit, &input.random, input.turn),
+        gameid: input.gameid,
+        fleet: input.fleet,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
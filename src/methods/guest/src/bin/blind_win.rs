@@ -0,0 +1,35 @@
+use fleetcore::{board_digest, ledger_digest, BlindWinInputs, BlindWinJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    let input: BlindWinInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // The ledger starts empty and can only hold positions of the board
+    if input.turn == 0 && !input.hits.is_empty() {
+        panic!("The ledger must be empty before the first report");
+    }
+    if input.hits.iter().any(|&pos| !input.board.contains_pos(pos)) {
+        panic!("The ledger holds a position that is not on the board");
+    }
+
+    // Prove there are still ships on the board that were not hit
+    if input.board.coords().all(|coord| input.hits.contains(&coord.pos())) {
+        panic!("Your fleet is already sunk. You cannot win.");
+    }
+
+    let output = BlindWinJournal {
+        version: PROTOCOL_VERSION,
+        board: board_digest(input.board, &input.random),
+        turn: input.turn,
+        ledger: ledger_digest(&input.hits, &input.random, input.turn),
+        gameid: input.gameid,
+        fleet: input.fleet,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
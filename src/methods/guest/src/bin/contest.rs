@@ -0,0 +1,30 @@
+use fleetcore::{board_digest, BaseInputs, BaseJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // read the input
+    let input: BaseInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // Prove there is still at least one ship square on the board, without revealing where
+    if input.board.is_empty() {
+        panic!("Your fleet is already sunk. You cannot contest.");
+    }
+
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(input.board, &input.random);
+
+    // create the output
+    let output = BaseJournal {
+        version: PROTOCOL_VERSION,
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: committed_board_hash,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
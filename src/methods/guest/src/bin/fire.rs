@@ -1,9 +1,11 @@
-use fleetcore::{FireInputs, FireJournal};
+use fleetcore::{board_digest, journal_digest, BaseJournal, FireInputs, FireJournal, PROTOCOL_VERSION};
 use risc0_zkvm::guest::env;
-use sha2::{Digest as _, Sha256};
 
 fn main() {
     let input: FireInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
     
     // Validate it's this player's turn to fire
     if input.game_next_player.as_ref() != Some(&input.fleet) {
@@ -16,42 +18,50 @@ fn main() {
     }
 
     let fleet = input.fleet.clone();
-    let board = input.board.clone();
+    let board = input.board;
     let random = input.random.clone();
     let target = input.target.clone();
-    let pos = input.pos.clone();
 
     // Validate that target is not himself
     if fleet == target {
         panic!("Cannot fire at yourself");
     }
 
-    // Validate that the position is within the board
-    if pos > 99 {
-        panic!("Position out of bounds");
-    }
+    // The position was checked to be within the board when the input was read
 
     // Validate that your fleet is not already sunk
-    if board.len() < 1 {
+    if board.is_empty() {
         panic!("Your fleet is already sunk");
     }
 
-    // Create the SHA256 hash of the board
-    let mut hasher = Sha256::new();
-    hasher.update(&board);
-    hasher.update(random.as_bytes());
-    let sha2_digest_output = hasher.finalize();
+    // Take our own join receipt as an assumption: the placement it validated is the one we
+    // committed to, and the board we fire with only has squares of that placement left
+    env::verify(input.join_id, &input.join_journal).expect("Invalid join receipt");
+    let join: BaseJournal = risc0_zkvm::serde::from_slice(&input.join_journal).expect("Invalid join journal");
+    if join.gameid != input.gameid || join.fleet != fleet {
+        panic!("The join receipt is for another game or fleet");
+    }
+    if board_digest(input.placement, &random) != join.board {
+        panic!("The placement does not match the join receipt");
+    }
+    if !board.is_subset(input.placement) {
+        panic!("The board has squares outside of the joined placement");
+    }
 
-    // Convert the SHA256 hash to a risc0_zkvm::Digest
-    let committed_board_hash = risc0_zkvm::Digest::from(<[u8; 32]>::from(sha2_digest_output));
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(board, &random);
     
     // create the output
     let output = FireJournal {
+        version: PROTOCOL_VERSION,
         gameid: input.gameid,
         fleet: input.fleet,
         board: committed_board_hash,
         target: input.target,
         pos: input.pos,
+        challenge: input.challenge,
+        join_id: input.join_id,
+        join_journal: journal_digest(&input.join_journal),
     };
 
     // write public output to the journal
@@ -0,0 +1,28 @@
+use fleetcore::{board_digest, BaseInputs, BaseJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // read the input
+    let input: BaseInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // A player can concede at any time, so there is no turn to validate: the proof only shows
+    // that the player knows the board committed when joining
+
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(input.board, &input.random);
+
+    // create the output
+    let output = BaseJournal {
+        version: PROTOCOL_VERSION,
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: committed_board_hash,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
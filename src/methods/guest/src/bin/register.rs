@@ -0,0 +1,32 @@
+use fleetcore::{RegisterInputs, RegisterJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+// Length of an Ed25519 verifying key
+const PUBLIC_KEY_LENGTH: usize = 32;
+
+fn main() {
+    // read the input
+    let input: RegisterInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // The proof binds the key to the fleet and to a fresh challenge; the chain checks that the
+    // player holds the key from the signature of the receipt
+
+    if input.public_key.len() != PUBLIC_KEY_LENGTH {
+        panic!("Verifying key must be {} bytes", PUBLIC_KEY_LENGTH);
+    }
+
+    // create the output
+    let output = RegisterJournal {
+        version: PROTOCOL_VERSION,
+        gameid: input.gameid,
+        fleet: input.fleet,
+        public_key: input.public_key,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
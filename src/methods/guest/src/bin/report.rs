@@ -1,27 +1,36 @@
-use fleetcore::{FireInputs, ReportJournal};
+use fleetcore::{board_digest, journal_digest, report_outcome, FireInputs, FireJournal, ReportJournal, PROTOCOL_VERSION};
 use risc0_zkvm::guest::env;
-use sha2::{Digest as _, Sha256};
 
 fn main() {
     let input: FireInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
     
     // Validate it's this player's turn to report
     if input.game_next_report.as_ref() != Some(&input.fleet) {
         panic!("Not your turn to report");
     }
     
-    let board = input.board.clone();
+    let board = input.board;
     let random = input.random.clone();
     let report = input.target.clone();
     let pos = input.pos;
+
+    // Take the shot being answered as an assumption: the report is for the square that was fired
+    // at, in this game, at this fleet
+    env::verify(input.fire_id, &input.fire_journal).expect("Invalid fire receipt");
+    let shot: FireJournal = risc0_zkvm::serde::from_slice(&input.fire_journal).expect("Invalid fire journal");
+    if shot.gameid != input.gameid || shot.target != input.fleet {
+        panic!("The fire receipt is for another game or target");
+    }
+    if shot.pos != pos {
+        panic!("The report is not for the position that was fired at");
+    }
     
-    // Parse the board from the input
-    // The board is expected to be a Vec<u8> with the positions of ships
     // Validate that the report ("Hit" or "Miss") is accurate
-    let board_vec = board.iter().map(|&b| b as u8).collect::<Vec<u8>>();
-    
     // Check if the position is in the board (ship positions)
-    let is_hit = board_vec.contains(&pos);
+    let is_hit = board.contains(pos);
     
     // Validate that the report matches the actual state
     let is_valid_report = match report.as_str() {
@@ -33,40 +42,35 @@ fn main() {
     if !is_valid_report {
         panic!("Report does not match the actual board state");
     }
-    
-    // Create the SHA256 hash of the board
-    let mut hasher = Sha256::new();
-    hasher.update(&board);
-    hasher.update(random.as_bytes());
-    let sha2_digest_output = hasher.finalize();
-
-    // Convert the SHA256 hash to a risc0_zkvm::Digest
-    let committed_board_hash = risc0_zkvm::Digest::from(<[u8; 32]>::from(sha2_digest_output));
 
-    // If player was hit, remove the position from the board and create a new board hash
-    let mut new_board = board_vec.clone();
-    if is_hit {
-        // Remove the position from the board
-        new_board.retain(|&x| x != pos);
+    // Earlier hits were removed from the board when they were reported
+    if input.hits.iter().any(|&hit| board.contains(hit)) {
+        panic!("An earlier hit is still on the board");
     }
 
-    // Create a new SHA256 hash for the updated board
-    let mut new_hasher = Sha256::new();
-    new_hasher.update(&new_board);
-    new_hasher.update(random.as_bytes());
-    let new_sha2_digest_output = new_hasher.finalize();
+    // Tell whether the hit sinks a ship, from the ship's squares on the board as placed
+    let outcome = report_outcome(&board, &input.hits, pos);
+    
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(board, &random);
 
-    // Convert the new SHA256 hash to a risc0_zkvm::Digest
-    let committed_new_board_hash = risc0_zkvm::Digest::from(<[u8; 32]>::from(new_sha2_digest_output));
+    // If player was hit, remove the position from the board and create a new board hash
+    let new_board = if is_hit { board.without(pos) } else { board };
+    let committed_new_board_hash = board_digest(new_board, &random);
     
     // Create the output journal with the validated report
     let output = ReportJournal {
+        version: PROTOCOL_VERSION,
         gameid: input.gameid,
         fleet: input.fleet,
         board: committed_board_hash, // Use the committed hash instead of raw board
-        report: input.target, // "Hit" or "Miss"
+        report: outcome, // Hit, Miss or Sunk
         pos: input.pos,
         next_board: committed_new_board_hash,
+        challenge: input.challenge,
+        hits: input.hits,
+        fire_id: input.fire_id,
+        fire_journal: journal_digest(&input.fire_journal),
     };
     
     // write public output to the journal
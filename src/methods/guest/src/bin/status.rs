@@ -0,0 +1,29 @@
+use fleetcore::{board_digest, BaseInputs, StatusJournal, PROTOCOL_VERSION};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // read the input
+    let input: BaseInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
+    // A player can prove its remaining fleet at any time, so there is no turn to validate: the
+    // proof shows that the board committed to still has this many ship squares
+
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(input.board, &input.random);
+
+    // Only the number of squares is committed, never their positions
+    let output = StatusJournal {
+        version: PROTOCOL_VERSION,
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: committed_board_hash,
+        remaining: input.board.len() as u32,
+        challenge: input.challenge,
+    };
+
+    // write public output to the journal
+    env::commit(&output);
+}
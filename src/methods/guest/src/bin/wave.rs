@@ -1,10 +1,12 @@
-use fleetcore::{BaseInputs, BaseJournal};
+use fleetcore::{board_digest, BaseInputs, BaseJournal, PROTOCOL_VERSION};
 use risc0_zkvm::guest::env;
-use sha2::{Digest as _, Sha256};
 
 fn main() {
     // read the input
     let input: BaseInputs = env::read();
+    if let Err(err) = input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
     
     // Validate it's this player's turn to wave (same logic as fire)
     if input.game_next_player.as_ref() != Some(&input.fleet) {
@@ -18,23 +20,19 @@ fn main() {
     
     let gameid = input.gameid.clone();
     let fleet = input.fleet.clone();
-    let board = input.board.clone();
+    let board = input.board;
     let random = input.random.clone();
 
-    // Encrypt the fleet position by hashing the board with a nonce (random)
-    let mut hasher = Sha256::new();
-    hasher.update(&board);
-    hasher.update(random.as_bytes());
-    let sha2_digest_output = hasher.finalize();
-
-    // Convert the SHA256 hash to a risc0_zkvm::Digest
-    let committed_board_hash = risc0_zkvm::Digest::from(<[u8; 32]>::from(sha2_digest_output));
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(board, &random);
 
     // create the output
     let output = BaseJournal {
+        version: PROTOCOL_VERSION,
         gameid: gameid,
         fleet: fleet,
         board: committed_board_hash,
+        challenge: input.challenge,
     };
 
     // write public output to the journal
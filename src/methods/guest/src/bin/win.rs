@@ -1,34 +1,39 @@
-use fleetcore::{BaseInputs, BaseJournal};
+use fleetcore::{board_digest, WinInputs, WinJournal, PROTOCOL_VERSION};
 use risc0_zkvm::guest::env;
-use sha2::{Digest as _, Sha256};
 
 fn main() {
     // read the input
-    let _input: BaseInputs = env::read();
+    let _input: WinInputs = env::read();
+    if let Err(err) = _input.check() {
+        panic!("Invalid inputs: {}", err);
+    }
+
     let gameid = _input.gameid.clone();
     let fleet = _input.fleet.clone();
-    let board = _input.board.clone();
+    let board = _input.board;
     let random = _input.random.clone();
 
     // Prove there is still ships on the board
-    if board.len() < 1 {
+    if board.is_empty() {
         panic!("Your fleet is already sunk. You cannot win.");
     }
-    
-    // Encrypt the fleet position by hashing the board with a nonce (random)
-    let mut hasher = Sha256::new();
-    hasher.update(&board);
-    hasher.update(random.as_bytes());
-    let sha2_digest_output = hasher.finalize();
 
-    // Convert the SHA256 hash to a risc0_zkvm::Digest
-    let committed_board_hash = risc0_zkvm::Digest::from(<[u8; 32]>::from(sha2_digest_output));
+    // Prove the claim names what it rests on, the chain checks it against the game
+    if let Err(err) = _input.basis.check(&fleet) {
+        panic!("Invalid victory claim: {}", err);
+    }
+    
+    // Commit to the board by hashing its bitboard with a nonce (random)
+    let committed_board_hash = board_digest(board, &random);
 
     // create the output
-    let output = BaseJournal {
+    let output = WinJournal {
+        version: PROTOCOL_VERSION,
         gameid: gameid,
         fleet: fleet,
         board: committed_board_hash,
+        challenge: _input.challenge,
+        basis: _input.basis,
     };
     
     // write public output to the journal